@@ -0,0 +1,110 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::Error;
+use launch::{
+    config::{
+        launch_params::{LocalFileParams, DEFAULT_TRANSFER_DURATION},
+        InputDataType, TransfersParams,
+    },
+    loki::PositiveDuration,
+    read::{build_transit_data, read_model},
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "loki_memory_usage",
+    about = "Build the data from ntfs/gtfs files and print the memory used by each of its components.",
+    rename_all = "snake_case"
+)]
+struct Options {
+    /// directory (or zip archive) containing ntfs/gtfs/netex files to load
+    #[structopt(long, parse(from_os_str))]
+    input_data_path: PathBuf,
+
+    /// type of input data given (ntfs/gtfs/netex)
+    #[structopt(long, default_value = "ntfs")]
+    input_data_type: InputDataType,
+
+    /// path to the passengers loads file
+    #[structopt(long, parse(from_os_str))]
+    loads_data_path: Option<PathBuf>,
+
+    /// the transfer duration between a stop point and itself
+    #[structopt(long, default_value = DEFAULT_TRANSFER_DURATION)]
+    default_transfer_duration: PositiveDuration,
+
+    /// print the report as json
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() {
+    launch::logger::init_logger();
+    if let Err(err) = run() {
+        eprintln!("{:?}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options = Options::from_args();
+    let data_files = LocalFileParams {
+        input_data_path: options.input_data_path,
+        loads_data_path: options.loads_data_path,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let base_model = read_model(
+        &data_files,
+        options.input_data_type,
+        options.default_transfer_duration,
+        &TransfersParams::default(),
+    )?;
+    let data = build_transit_data(&base_model);
+
+    let memory_usages = [base_model.memory_usage(), data.memory_usage()];
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&memory_usages)?);
+    } else {
+        for memory_usage in &memory_usages {
+            print!("{}", memory_usage);
+        }
+    }
+    Ok(())
+}
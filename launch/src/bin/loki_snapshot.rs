@@ -0,0 +1,122 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::Error;
+use launch::{
+    config::{
+        launch_params::{LocalFileParams, DEFAULT_TRANSFER_DURATION},
+        transfers_params::DEFAULT_TRANSFERS_WAITING_TIME,
+        InputDataType, TransfersParams,
+    },
+    loki::{transit_model, PositiveDuration},
+};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "loki_snapshot",
+    about = "Build the data from ntfs/gtfs files and write it in a snapshot file that loki_server can load.",
+    rename_all = "snake_case"
+)]
+struct Options {
+    /// directory (or zip archive) containing ntfs/gtfs/netex files to load
+    #[structopt(long, parse(from_os_str))]
+    input_data_path: PathBuf,
+
+    /// type of input data given (ntfs/gtfs/netex)
+    #[structopt(long, default_value = "ntfs")]
+    input_data_type: InputDataType,
+
+    /// path to the passengers loads file
+    #[structopt(long, parse(from_os_str))]
+    loads_data_path: Option<PathBuf>,
+
+    /// the transfer duration between a stop point and itself
+    #[structopt(long, default_value = DEFAULT_TRANSFER_DURATION)]
+    default_transfer_duration: PositiveDuration,
+
+    /// maximum distance, in meters, between two stop points linked by a generated transfer
+    #[structopt(long, default_value = transit_model::TRANSFER_MAX_DISTANCE)]
+    transfers_max_distance: f64,
+
+    /// walking speed, in meters per second, used to compute the duration of a generated transfer
+    #[structopt(long, default_value = transit_model::TRANSFER_WALKING_SPEED)]
+    transfers_walking_speed: f64,
+
+    /// waiting time added to the walking duration of a generated transfer
+    #[structopt(long, default_value = DEFAULT_TRANSFERS_WAITING_TIME)]
+    transfers_waiting_time: PositiveDuration,
+
+    /// path to a csv file with rules that override the transfers
+    #[structopt(long, parse(from_os_str))]
+    transfer_rules_path: Option<PathBuf>,
+
+    /// path of the snapshot file to write
+    #[structopt(long, parse(from_os_str))]
+    output: PathBuf,
+}
+
+fn main() {
+    launch::logger::init_logger();
+    if let Err(err) = run() {
+        eprintln!("{:?}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Error> {
+    let options = Options::from_args();
+    let data_files = LocalFileParams {
+        input_data_path: options.input_data_path,
+        loads_data_path: options.loads_data_path,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let transfers_params = TransfersParams {
+        max_distance: options.transfers_max_distance,
+        walking_speed: options.transfers_walking_speed,
+        waiting_time: options.transfers_waiting_time,
+        rules_path: options.transfer_rules_path,
+    };
+    launch::snapshot::write_snapshot(
+        &data_files,
+        options.input_data_type,
+        options.default_transfer_duration,
+        &transfers_params,
+        &options.output,
+    )
+}
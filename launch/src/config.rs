@@ -38,8 +38,10 @@ pub mod comparator_type;
 pub mod input_data_type;
 pub mod launch_params;
 pub mod request_params;
+pub mod transfers_params;
 
 pub use comparator_type::ComparatorType;
 pub use input_data_type::InputDataType;
 pub use launch_params::LaunchParams;
 pub use request_params::RequestParams;
+pub use transfers_params::TransfersParams;
@@ -41,6 +41,7 @@ use serde::{Deserialize, Serialize};
 pub enum InputDataType {
     Gtfs,
     Ntfs,
+    Netex,
 }
 
 impl Default for InputDataType {
@@ -56,6 +57,7 @@ impl std::str::FromStr for InputDataType {
         let result = match s {
             "ntfs" => InputDataType::Ntfs,
             "gtfs" => InputDataType::Gtfs,
+            "netex" => InputDataType::Netex,
             _ => {
                 return Err(InputDataTypeConfigError {
                     input_type_name: s.to_string(),
@@ -71,6 +73,7 @@ impl std::fmt::Display for InputDataType {
         match self {
             InputDataType::Gtfs => write!(f, "gtfs"),
             InputDataType::Ntfs => write!(f, "ntfs"),
+            InputDataType::Netex => write!(f, "netex"),
         }
     }
 }
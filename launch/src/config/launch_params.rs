@@ -34,7 +34,7 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use super::InputDataType;
+use super::{InputDataType, TransfersParams};
 use loki::PositiveDuration;
 
 use serde::{Deserialize, Serialize};
@@ -42,10 +42,10 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct LaunchParams {
-    /// directory containing ntfs/gtfs files to load
+    /// directory containing ntfs/gtfs/netex files to load
     pub input_data_path: std::path::PathBuf,
 
-    /// type of input data given (ntfs/gtfs)
+    /// type of input data given (ntfs/gtfs/netex)
     #[serde(default)]
     pub input_data_type: InputDataType,
 
@@ -55,6 +55,15 @@ pub struct LaunchParams {
     /// the transfer duration between a stop point and itself
     #[serde(default = "default_transfer_duration")]
     pub default_transfer_duration: PositiveDuration,
+
+    /// other datasets merged with the one at `input_data_path`.
+    /// Defaults to no other dataset.
+    #[serde(default)]
+    pub merged_inputs: Vec<InputParams>,
+
+    /// parameters of the transfers generated between nearby stop points
+    #[serde(default)]
+    pub transfers: TransfersParams,
 }
 
 pub const DEFAULT_TRANSFER_DURATION: &str = "00:01:00";
@@ -71,13 +80,45 @@ impl LaunchParams {
             input_data_type: InputDataType::Ntfs,
             default_transfer_duration: default_transfer_duration(),
             loads_data_path: None,
+            merged_inputs: Vec::new(),
+            transfers: TransfersParams::default(),
         }
     }
 }
 
+/// A dataset merged with the main one.
+///
+/// Transfers are generated between the nearby stop points of different datasets.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InputParams {
+    /// directory containing ntfs/gtfs/netex files to load
+    pub input_data_path: std::path::PathBuf,
+
+    /// type of input data given (ntfs/gtfs/netex)
+    #[serde(default)]
+    pub input_data_type: InputDataType,
+
+    /// prefix added, with a ':' separator, to the identifiers of the objects of this dataset,
+    /// so that they do not collide with the identifiers of the other datasets.
+    /// Defaults to None.
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct LocalFileParams {
     pub input_data_path: std::path::PathBuf,
     pub loads_data_path: Option<std::path::PathBuf>,
+    /// path to a snapshot built from the files above with `loki_snapshot`.
+    /// If the snapshot can be used, the data is read from it instead
+    /// of being built from the files.
+    /// Defaults to None.
+    #[serde(default)]
+    pub snapshot_path: Option<std::path::PathBuf>,
+    /// other datasets merged with the one at `input_data_path`.
+    /// Defaults to no other dataset.
+    #[serde(default)]
+    pub merged_inputs: Vec<InputParams>,
 }
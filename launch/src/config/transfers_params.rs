@@ -0,0 +1,98 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use loki::{transit_model, PositiveDuration};
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Parameters of the transfers generated between nearby stop points,
+/// for the input data types that may not provide them all (gtfs/netex),
+/// and when several datasets are merged.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TransfersParams {
+    /// maximum distance, in meters, between two stop points linked by a generated transfer
+    #[serde(default = "default_transfers_max_distance")]
+    pub max_distance: f64,
+
+    /// walking speed, in meters per second, used to compute the duration of a generated transfer
+    #[serde(default = "default_transfers_walking_speed")]
+    pub walking_speed: f64,
+
+    /// waiting time added to the walking duration of a generated transfer
+    #[serde(default = "default_transfers_waiting_time")]
+    pub waiting_time: PositiveDuration,
+
+    /// path to a csv file with rules that override the transfers
+    /// of the input data and the generated ones.
+    /// Each line has the columns `rule,stop_area_id,from_stop_id,to_stop_id,duration`,
+    /// with `rule` being one of :
+    ///  - `extra_waiting_time` : `duration` seconds are added to the generated transfers
+    ///    towards the stop points of the stop area `stop_area_id`,
+    ///  - `forbidden` : the transfer from `from_stop_id` to `to_stop_id` is removed,
+    ///  - `forced` : the transfer from `from_stop_id` to `to_stop_id` takes `duration` seconds.
+    ///
+    /// Defaults to None.
+    #[serde(default)]
+    pub rules_path: Option<std::path::PathBuf>,
+}
+
+pub fn default_transfers_max_distance() -> f64 {
+    f64::from_str(transit_model::TRANSFER_MAX_DISTANCE).unwrap()
+}
+
+pub fn default_transfers_walking_speed() -> f64 {
+    f64::from_str(transit_model::TRANSFER_WALKING_SPEED).unwrap()
+}
+
+// same as transit_model::TRANSFER_WAITING_TIME, which is given in seconds
+pub const DEFAULT_TRANSFERS_WAITING_TIME: &str = "00:01:00";
+
+pub fn default_transfers_waiting_time() -> PositiveDuration {
+    PositiveDuration::from_str(DEFAULT_TRANSFERS_WAITING_TIME).unwrap()
+}
+
+impl Default for TransfersParams {
+    fn default() -> Self {
+        Self {
+            max_distance: default_transfers_max_distance(),
+            walking_speed: default_transfers_walking_speed(),
+            waiting_time: default_transfers_waiting_time(),
+            rules_path: None,
+        }
+    }
+}
@@ -1,9 +1,13 @@
 pub mod config;
 pub mod datetime;
 pub mod logger;
+pub mod merge;
+pub mod netex;
 pub mod read;
+pub mod snapshot;
 pub mod solver;
 pub mod stop_areas;
+pub mod transfers;
 
 pub use loki;
 
@@ -0,0 +1,183 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::{
+    config::TransfersParams,
+    transfers::{generate_transfers, TransferRules},
+};
+use anyhow::{format_err, Context, Error};
+use loki::{
+    tracing::info,
+    transit_model::{
+        model::{Collections, Model},
+        AddPrefix, PrefixConfiguration,
+    },
+    typed_index_collection::{CollectionWithId, Id},
+};
+use std::collections::HashMap;
+
+/// Merges several models into one.
+///
+/// When a prefix is given along a model, it is added to the identifiers of the objects of this model.
+/// Objects of different models must have different identifiers, except for
+/// commercial modes, physical modes and addresses which are kept only once.
+///
+/// Transfers are generated, according to `transfers_params` and `transfer_rules`,
+/// between the nearby stop points of different models.
+pub fn merge_models(
+    models: Vec<(Model, Option<String>)>,
+    transfers_params: &TransfersParams,
+    transfer_rules: &TransferRules,
+) -> Result<Model, Error> {
+    let mut merged = Collections::default();
+    // for each stop point id, the index of the model it comes from
+    let mut stop_point_origins = HashMap::new();
+    for (model_idx, (model, prefix)) in models.into_iter().enumerate() {
+        let mut collections = model.into_collections();
+        if let Some(prefix) = &prefix {
+            let mut prefix_conf = PrefixConfiguration::default();
+            prefix_conf.set_data_prefix(prefix);
+            collections.prefix(&prefix_conf);
+        }
+        for stop_point in collections.stop_points.values() {
+            stop_point_origins.insert(stop_point.id.clone(), model_idx);
+        }
+        merge_collections(&mut merged, collections).with_context(|| {
+            format!(
+                "Could not merge dataset number {} with prefix {:?}",
+                model_idx, prefix
+            )
+        })?;
+    }
+    let model = Model::new(merged)?;
+    info!("Datasets merged");
+
+    let model = generate_transfers(
+        model,
+        transfers_params,
+        transfer_rules,
+        Some(Box::new(|model, from_idx, to_idx| {
+            let from_origin = stop_point_origins.get(&model.stop_points[from_idx].id);
+            let to_origin = stop_point_origins.get(&model.stop_points[to_idx].id);
+            from_origin != to_origin
+        })),
+    )?;
+    Ok(model)
+}
+
+fn merge_collections(merged: &mut Collections, other: Collections) -> Result<(), Error> {
+    try_merge(&mut merged.contributors, other.contributors)?;
+    try_merge(&mut merged.datasets, other.datasets)?;
+    try_merge(&mut merged.networks, other.networks)?;
+    merged.commercial_modes.merge(other.commercial_modes);
+    try_merge(&mut merged.lines, other.lines)?;
+    try_merge(&mut merged.routes, other.routes)?;
+    merged.frequencies.merge(other.frequencies);
+    merged.physical_modes.merge(other.physical_modes);
+    try_merge(&mut merged.stop_areas, other.stop_areas)?;
+    // the stop times of the vehicle journeys refer to the stop points by their index,
+    // which changes once the stop points are merged
+    let stop_point_ids: Vec<String> = other
+        .stop_points
+        .values()
+        .map(|stop_point| stop_point.id.clone())
+        .collect();
+    try_merge(&mut merged.stop_points, other.stop_points)?;
+    let mut vehicle_journeys = other.vehicle_journeys;
+    for vehicle_journey_idx in vehicle_journeys.indexes() {
+        let mut vehicle_journey = vehicle_journeys.index_mut(vehicle_journey_idx);
+        for stop_time in vehicle_journey.stop_times.iter_mut() {
+            let stop_point_id = &stop_point_ids[stop_time.stop_point_idx.get()];
+            stop_time.stop_point_idx = merged
+                .stop_points
+                .get_idx(stop_point_id)
+                .ok_or_else(|| format_err!("Stop point {} not found", stop_point_id))?;
+        }
+    }
+    try_merge(&mut merged.vehicle_journeys, vehicle_journeys)?;
+    try_merge(&mut merged.stop_locations, other.stop_locations)?;
+    for (key, value) in other.feed_infos {
+        merged.feed_infos.entry(key).or_insert(value);
+    }
+    try_merge(&mut merged.calendars, other.calendars)?;
+    try_merge(&mut merged.companies, other.companies)?;
+    try_merge(&mut merged.comments, other.comments)?;
+    try_merge(&mut merged.equipments, other.equipments)?;
+    merged.transfers.merge(other.transfers);
+    try_merge(&mut merged.trip_properties, other.trip_properties)?;
+    try_merge(&mut merged.geometries, other.geometries)?;
+    merged.admin_stations.merge(other.admin_stations);
+    merged.stop_time_headsigns.extend(other.stop_time_headsigns);
+    merged.stop_time_ids.extend(other.stop_time_ids);
+    merged.stop_time_comments.extend(other.stop_time_comments);
+    merged.prices_v1.merge(other.prices_v1);
+    merged.od_fares_v1.merge(other.od_fares_v1);
+    merged.fares_v1.merge(other.fares_v1);
+    try_merge(&mut merged.tickets, other.tickets)?;
+    try_merge(&mut merged.ticket_uses, other.ticket_uses)?;
+    merged.ticket_prices.merge(other.ticket_prices);
+    merged
+        .ticket_use_perimeters
+        .merge(other.ticket_use_perimeters);
+    merged
+        .ticket_use_restrictions
+        .merge(other.ticket_use_restrictions);
+    try_merge(&mut merged.pathways, other.pathways)?;
+    try_merge(&mut merged.levels, other.levels)?;
+    try_merge(&mut merged.grid_calendars, other.grid_calendars)?;
+    merged
+        .grid_exception_dates
+        .merge(other.grid_exception_dates);
+    merged.grid_periods.merge(other.grid_periods);
+    merged
+        .grid_rel_calendar_line
+        .merge(other.grid_rel_calendar_line);
+    merged.addresses.merge(other.addresses);
+    Ok(())
+}
+
+fn try_merge<T: Id<T>>(
+    collection: &mut CollectionWithId<T>,
+    other: CollectionWithId<T>,
+) -> Result<(), Error> {
+    collection.try_merge(other).map_err(|err| {
+        format_err!(
+            "{} {}. Use a prefix to distinguish the objects of each dataset.",
+            std::any::type_name::<T>(),
+            err
+        )
+    })
+}
@@ -0,0 +1,327 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! Reads NeTEx archives following the French profile (NeTEx France)
+//! into the transit model collections.
+//!
+//! The archive may contain any number of xml files, the objects
+//! being read from all of them :
+//!  - `StopPlace` and `Quay` give the stop areas and stop points,
+//!  - `DayType`, `DayTypeAssignment` and `(Uic)OperatingPeriod` give the calendars,
+//!  - `Network`, `Operator`, `Line`, `Route`, `ServiceJourneyPattern`,
+//!    `PassengerStopAssignment` and `ServiceJourney` give the vehicle journeys,
+//!  - `ServiceJourneyInterchange` and `SiteConnection` give the transfers.
+
+mod calendars;
+mod interchanges;
+mod offer;
+mod stops;
+
+use anyhow::{bail, format_err, Context, Error};
+use loki::{
+    chrono_tz::{self, Tz},
+    tracing::{info, warn},
+    transit_model::{
+        model::{Collections, Model},
+        objects::{Contributor, Dataset},
+        validity_period::compute_dataset_validity_period,
+    },
+};
+use roxmltree::{Document, Node};
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+/// Reads the NeTEx files in `path`, which is either a directory or a zip archive.
+pub fn read(path: &Path) -> Result<Model, Error> {
+    let files = if path.is_dir() {
+        let mut files = Vec::new();
+        read_directory(path, &mut files)?;
+        files
+    } else {
+        let file = File::open(path).with_context(|| format!("Could not open file {:?}", path))?;
+        read_zip(file).with_context(|| format!("Could not read NeTEx archive {:?}", path))?
+    };
+    read_files(&files)
+}
+
+/// Reads the NeTEx files in the zip archive read by `reader`.
+pub fn from_zip_reader<R>(reader: R, source: &str) -> Result<Model, Error>
+where
+    R: Seek + Read,
+{
+    let files =
+        read_zip(reader).with_context(|| format!("Could not read NeTEx archive {}", source))?;
+    read_files(&files)
+}
+
+// Returns the name and content of the xml files in `directory` and its sub-directories.
+fn read_directory(directory: &Path, files: &mut Vec<(String, String)>) -> Result<(), Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("Could not read directory {:?}", directory))?
+    {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            read_directory(&path, files)?;
+        } else if is_xml_file(&path.to_string_lossy()) {
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Could not read file {:?}", path))?;
+            files.push((path.to_string_lossy().to_string(), content));
+        }
+    }
+    Ok(())
+}
+
+// Returns the name and content of the xml files in the zip archive.
+fn read_zip<R: Seek + Read>(reader: R) -> Result<Vec<(String, String)>, Error> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut files = Vec::new();
+    for idx in 0..archive.len() {
+        let mut file = archive.by_index(idx)?;
+        if file.is_file() && is_xml_file(file.name()) {
+            let name = file.name().to_string();
+            let mut content = String::new();
+            file.read_to_string(&mut content)
+                .with_context(|| format!("Could not read file {}", name))?;
+            files.push((name, content));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn is_xml_file(name: &str) -> bool {
+    name.to_lowercase().ends_with(".xml")
+}
+
+fn read_files(files: &[(String, String)]) -> Result<Model, Error> {
+    info!("Reading {} NeTEx files", files.len());
+    let mut documents = Vec::with_capacity(files.len());
+    for (name, content) in files {
+        let document = Document::parse(content)
+            .with_context(|| format!("Could not parse NeTEx file {}", name))?;
+        documents.push(document);
+    }
+
+    let frame_defaults = FrameDefaults::new(&documents);
+    let mut stops = stops::Stops::default();
+    let mut day_types = calendars::DayTypes::default();
+    let mut offer = offer::Offer::default();
+    let mut interchanges = interchanges::Interchanges::default();
+    for document in &documents {
+        for node in document.descendants().filter(Node::is_element) {
+            let result = match node.tag_name().name() {
+                "StopPlace" => stops.read_stop_place(node, &frame_defaults),
+                "Quay" => stops.read_quay(node, &frame_defaults),
+                "DayType" => day_types.read_day_type(node),
+                "DayTypeAssignment" => day_types.read_day_type_assignment(node),
+                "OperatingPeriod" | "UicOperatingPeriod" => day_types.read_operating_period(node),
+                "Network" => offer.read_network(node),
+                "Operator" => offer.read_operator(node),
+                "Line" => offer.read_line(node),
+                "Route" => offer.read_route(node),
+                "PassengerStopAssignment" => offer.read_passenger_stop_assignment(node),
+                "ServiceJourneyPattern" => offer.read_journey_pattern(node),
+                "ServiceJourney" => offer.read_service_journey(node),
+                "ServiceJourneyInterchange" => interchanges.read_interchange(node),
+                "SiteConnection" => interchanges.read_site_connection(node),
+                _ => Ok(()),
+            };
+            if let Err(err) = result {
+                warn!(
+                    "Could not read a NeTEx {}. I'll skip it. {:?}",
+                    node.tag_name().name(),
+                    err
+                );
+            }
+        }
+    }
+
+    let mut collections = Collections::default();
+    let mut dataset = Dataset::default();
+    stops.fill(&mut collections);
+    let calendar_dates = day_types.calendar_dates();
+    offer.fill(
+        &mut collections,
+        &calendar_dates,
+        frame_defaults.timezone,
+        &dataset.id,
+    );
+    interchanges.fill(&mut collections, &stops, &offer);
+    compute_dataset_validity_period(&mut dataset, &collections.calendars)?;
+    collections.contributors.push(Contributor::default())?;
+    collections.datasets.push(dataset)?;
+    let model = Model::new(collections)?;
+    info!("NeTEx files read");
+    Ok(model)
+}
+
+/// The values given in the `FrameDefaults` of the NeTEx files,
+/// which apply to all objects.
+struct FrameDefaults {
+    timezone: Tz,
+    location_system: Option<String>,
+}
+
+impl FrameDefaults {
+    fn new(documents: &[Document]) -> Self {
+        let frame_defaults = || {
+            documents
+                .iter()
+                .flat_map(|document| document.descendants())
+                .filter(|node| is_element(node, "FrameDefaults"))
+        };
+        let timezone = frame_defaults()
+            .filter_map(|node| child(node, "DefaultLocale"))
+            .find_map(|node| read_text(node, "TimeZone"))
+            .and_then(|timezone| {
+                timezone
+                    .parse()
+                    .map_err(|err| warn!("Bad timezone {} in NeTEx files. {}", timezone, err))
+                    .ok()
+            })
+            // the timezone of the french profile
+            .unwrap_or(chrono_tz::Europe::Paris);
+        let location_system =
+            frame_defaults().find_map(|node| read_text(node, "DefaultLocationSystem"));
+        Self {
+            timezone,
+            location_system,
+        }
+    }
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is_element(child, name))
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| is_element(child, name))
+}
+
+// children named `name` of the children named `parent_name`,
+// as in `<dayTypes><DayTypeRef/><DayTypeRef/></dayTypes>`
+fn grand_children<'a, 'input>(
+    node: Node<'a, 'input>,
+    parent_name: &'a str,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    children(node, parent_name).flat_map(move |parent| children(parent, name))
+}
+
+fn read_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(ToString::to_string)
+}
+
+fn read_bool(node: Node, name: &str, default: bool) -> bool {
+    match read_text(node, name).as_deref() {
+        Some("true") => true,
+        Some("false") => false,
+        _ => default,
+    }
+}
+
+fn read_id(node: Node) -> Result<String, Error> {
+    node.attribute("id")
+        .map(ToString::to_string)
+        .ok_or_else(|| format_err!("{} has no id.", node.tag_name().name()))
+}
+
+// the `ref` attribute of the child named `name`, as in `<LineRef ref="line:1"/>`
+fn read_ref(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|child| child.attribute("ref"))
+        .map(ToString::to_string)
+}
+
+// a xsd:duration, like `PT5M` or `PT1H30M15S`, in seconds
+fn read_duration(node: Node, name: &str) -> Result<Option<u32>, Error> {
+    read_text(node, name)
+        .map(|text| {
+            parse_duration(&text)
+                .with_context(|| format!("Could not parse {} {} as a duration.", name, text))
+        })
+        .transpose()
+}
+
+fn parse_duration(text: &str) -> Result<u32, Error> {
+    let duration = text
+        .strip_prefix('P')
+        .ok_or_else(|| format_err!("A duration should start with P"))?;
+    let (days, time) = duration.split_once('T').unwrap_or((duration, ""));
+    let mut seconds = 0;
+    for (part, units) in [
+        (days, &[('D', 86_400)][..]),
+        (time, &[('H', 3600), ('M', 60), ('S', 1)][..]),
+    ] {
+        let mut number = String::new();
+        for character in part.chars() {
+            if character.is_ascii_digit() {
+                number.push(character);
+            } else {
+                let unit = units
+                    .iter()
+                    .find(|(designator, _)| *designator == character)
+                    .map(|(_, unit)| unit)
+                    .ok_or_else(|| format_err!("Unexpected {} in duration", character))?;
+                let value: u32 = number.parse()?;
+                seconds += value * unit;
+                number.clear();
+            }
+        }
+        if !number.is_empty() {
+            bail!("A duration should end with a designator");
+        }
+    }
+    Ok(seconds)
+}
@@ -0,0 +1,229 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use super::{grand_children, read_bool, read_id, read_ref, read_text};
+use anyhow::{bail, format_err, Context, Error};
+use loki::{
+    chrono::{Datelike, NaiveDate, Weekday},
+    tracing::warn,
+};
+use roxmltree::Node;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The `DayType`s of the NeTEx files, along with their `DayTypeAssignment`s
+/// and the `OperatingPeriod`s and `UicOperatingPeriod`s they refer to.
+#[derive(Default)]
+pub(super) struct DayTypes {
+    // the days of week of each day type, None when it applies to all days
+    days_of_week: HashMap<String, Option<HashSet<Weekday>>>,
+    assignments: Vec<DayTypeAssignment>,
+    operating_periods: HashMap<String, OperatingPeriod>,
+}
+
+struct DayTypeAssignment {
+    day_type_id: String,
+    assigned_days: AssignedDays,
+    is_available: bool,
+}
+
+enum AssignedDays {
+    Date(NaiveDate),
+    OperatingPeriod(String),
+}
+
+struct OperatingPeriod {
+    from_date: NaiveDate,
+    to_date: NaiveDate,
+    // for an `UicOperatingPeriod`, the i-th bit tells whether
+    // the i-th day from `from_date` is in the period
+    valid_day_bits: Option<Vec<bool>>,
+}
+
+impl DayTypes {
+    pub(super) fn read_day_type(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let mut days_of_week: Option<HashSet<Weekday>> = None;
+        for days in grand_children(node, "properties", "PropertyOfDay")
+            .filter_map(|property| read_text(property, "DaysOfWeek"))
+        {
+            days_of_week
+                .get_or_insert_with(HashSet::new)
+                .extend(parse_days_of_week(&days));
+        }
+        self.days_of_week.insert(id, days_of_week);
+        Ok(())
+    }
+
+    pub(super) fn read_day_type_assignment(&mut self, node: Node) -> Result<(), Error> {
+        let day_type_id = read_ref(node, "DayTypeRef")
+            .ok_or_else(|| format_err!("DayTypeAssignment has no DayTypeRef."))?;
+        let assigned_days = if let Some(date) = read_text(node, "Date") {
+            AssignedDays::Date(parse_date(&date)?)
+        } else if let Some(operating_period_id) =
+            read_ref(node, "OperatingPeriodRef").or_else(|| read_ref(node, "UicOperatingPeriodRef"))
+        {
+            AssignedDays::OperatingPeriod(operating_period_id)
+        } else {
+            bail!("DayTypeAssignment has no Date nor OperatingPeriodRef.");
+        };
+        self.assignments.push(DayTypeAssignment {
+            day_type_id,
+            assigned_days,
+            is_available: read_bool(node, "isAvailable", true),
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_operating_period(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let read_date = |name: &str| -> Result<NaiveDate, Error> {
+            let text = read_text(node, name)
+                .ok_or_else(|| format_err!("OperatingPeriod {} has no {}.", id, name))?;
+            parse_date(&text)
+        };
+        let from_date = read_date("FromDate")?;
+        let to_date = read_date("ToDate")?;
+        let valid_day_bits = read_text(node, "ValidDayBits")
+            .map(|bits| bits.chars().map(|bit| bit == '1').collect());
+        self.operating_periods.insert(
+            id,
+            OperatingPeriod {
+                from_date,
+                to_date,
+                valid_day_bits,
+            },
+        );
+        Ok(())
+    }
+
+    /// The dates of each day type.
+    ///
+    /// The dates of the assignments with `isAvailable` set to false
+    /// are removed from those of the other assignments.
+    pub(super) fn calendar_dates(&self) -> HashMap<String, BTreeSet<NaiveDate>> {
+        let mut calendar_dates: HashMap<String, BTreeSet<NaiveDate>> = self
+            .days_of_week
+            .keys()
+            .map(|id| (id.clone(), BTreeSet::new()))
+            .collect();
+        let (available, unavailable): (Vec<_>, Vec<_>) = self
+            .assignments
+            .iter()
+            .partition(|assignment| assignment.is_available);
+        for assignment in available.into_iter().chain(unavailable) {
+            let dates = match self.assigned_dates(assignment) {
+                Ok(dates) => dates,
+                Err(err) => {
+                    warn!(
+                        "Could not read an assignment of DayType {}. I'll skip it. {:?}",
+                        assignment.day_type_id, err
+                    );
+                    continue;
+                }
+            };
+            let day_type_dates = calendar_dates
+                .entry(assignment.day_type_id.clone())
+                .or_default();
+            if assignment.is_available {
+                day_type_dates.extend(dates);
+            } else {
+                for date in dates {
+                    day_type_dates.remove(&date);
+                }
+            }
+        }
+        calendar_dates
+    }
+
+    fn assigned_dates(&self, assignment: &DayTypeAssignment) -> Result<Vec<NaiveDate>, Error> {
+        let operating_period_id = match &assignment.assigned_days {
+            AssignedDays::Date(date) => return Ok(vec![*date]),
+            AssignedDays::OperatingPeriod(id) => id,
+        };
+        let operating_period = self
+            .operating_periods
+            .get(operating_period_id)
+            .ok_or_else(|| format_err!("OperatingPeriod {} not found.", operating_period_id))?;
+        let days_of_week = self
+            .days_of_week
+            .get(&assignment.day_type_id)
+            .and_then(Option::as_ref);
+        let dates = operating_period
+            .from_date
+            .iter_days()
+            .take_while(|date| *date <= operating_period.to_date)
+            .enumerate()
+            .filter(|(idx, _)| match &operating_period.valid_day_bits {
+                Some(bits) => bits.get(*idx).copied().unwrap_or(false),
+                None => true,
+            })
+            .map(|(_, date)| date)
+            .filter(|date| match days_of_week {
+                Some(days_of_week) => days_of_week.contains(&date.weekday()),
+                None => true,
+            })
+            .collect();
+        Ok(dates)
+    }
+}
+
+fn parse_days_of_week(text: &str) -> Vec<Weekday> {
+    use Weekday::*;
+    let mut days_of_week = Vec::new();
+    for day in text.split_whitespace() {
+        match day {
+            "Monday" => days_of_week.push(Mon),
+            "Tuesday" => days_of_week.push(Tue),
+            "Wednesday" => days_of_week.push(Wed),
+            "Thursday" => days_of_week.push(Thu),
+            "Friday" => days_of_week.push(Fri),
+            "Saturday" => days_of_week.push(Sat),
+            "Sunday" => days_of_week.push(Sun),
+            "Weekdays" => days_of_week.extend([Mon, Tue, Wed, Thu, Fri]),
+            "Weekend" => days_of_week.extend([Sat, Sun]),
+            "Everyday" => days_of_week.extend([Mon, Tue, Wed, Thu, Fri, Sat, Sun]),
+            _ => warn!("Unknown day of week {} in NeTEx DayType.", day),
+        }
+    }
+    days_of_week
+}
+
+// a xsd:date or xsd:dateTime, of which only the date is kept
+fn parse_date(text: &str) -> Result<NaiveDate, Error> {
+    let date = text.get(..10).unwrap_or(text);
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .with_context(|| format!("Could not parse {} as a date.", text))
+}
@@ -0,0 +1,228 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use super::{child, offer::Offer, read_bool, read_duration, read_ref, stops::Stops};
+use anyhow::{format_err, Error};
+use loki::{
+    tracing::warn,
+    transit_model::{model::Collections, objects::Transfer},
+    typed_index_collection::Collection,
+};
+use roxmltree::Node;
+use std::collections::BTreeMap;
+
+/// The `ServiceJourneyInterchange`s and `SiteConnection`s of the NeTEx files.
+///
+/// Transfers in loki are between stop points, so an interchange between two journeys
+/// gives a transfer between its stop points, used by all the journeys that stop there.
+/// An interchange where the passengers stay seated gives the same block id to its journeys.
+#[derive(Default)]
+pub(super) struct Interchanges {
+    interchanges: Vec<Interchange>,
+    site_connections: Vec<SiteConnection>,
+}
+
+struct Interchange {
+    from_scheduled_stop_point_id: String,
+    to_scheduled_stop_point_id: String,
+    from_journey_id: Option<String>,
+    to_journey_id: Option<String>,
+    minimum_transfer_time: Option<u32>,
+    stay_seated: bool,
+}
+
+struct SiteConnection {
+    from: ConnectionEnd,
+    to: ConnectionEnd,
+    duration: Option<u32>,
+}
+
+// a `QuayRef`, or else a `StopPlaceRef`
+enum ConnectionEnd {
+    Quay(String),
+    StopPlace(String),
+}
+
+impl Interchanges {
+    pub(super) fn read_interchange(&mut self, node: Node) -> Result<(), Error> {
+        let from_scheduled_stop_point_id = read_ref(node, "FromPointRef")
+            .ok_or_else(|| format_err!("ServiceJourneyInterchange has no FromPointRef."))?;
+        let to_scheduled_stop_point_id = read_ref(node, "ToPointRef")
+            .ok_or_else(|| format_err!("ServiceJourneyInterchange has no ToPointRef."))?;
+        self.interchanges.push(Interchange {
+            from_scheduled_stop_point_id,
+            to_scheduled_stop_point_id,
+            from_journey_id: read_ref(node, "FromJourneyRef"),
+            to_journey_id: read_ref(node, "ToJourneyRef"),
+            minimum_transfer_time: read_duration(node, "MinimumTransferTime")?,
+            stay_seated: read_bool(node, "StaySeated", false),
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_site_connection(&mut self, node: Node) -> Result<(), Error> {
+        let read_end = |name: &str| -> Result<ConnectionEnd, Error> {
+            let end =
+                child(node, name).ok_or_else(|| format_err!("SiteConnection has no {}.", name))?;
+            read_ref(end, "QuayRef")
+                .map(ConnectionEnd::Quay)
+                .or_else(|| read_ref(end, "StopPlaceRef").map(ConnectionEnd::StopPlace))
+                .ok_or_else(|| {
+                    format_err!(
+                        "The {} of a SiteConnection has no QuayRef nor StopPlaceRef.",
+                        name
+                    )
+                })
+        };
+        let duration = match child(node, "WalkTransferDuration") {
+            Some(durations) => read_duration(durations, "DefaultDuration")?,
+            None => None,
+        };
+        self.site_connections.push(SiteConnection {
+            from: read_end("From")?,
+            to: read_end("To")?,
+            duration,
+        });
+        Ok(())
+    }
+
+    /// Fills the transfers of `collections`, and the block ids
+    /// of the vehicle journeys in stay seated interchanges.
+    pub(super) fn fill(&self, collections: &mut Collections, stops: &Stops, offer: &Offer) {
+        // when several interchanges or connections link the same stop points,
+        // the longest duration is kept
+        let mut transfers: BTreeMap<(String, String), Option<u32>> = BTreeMap::new();
+        let mut add_transfer = |from: &str, to: &str, duration: Option<u32>| {
+            if !collections.stop_points.contains_id(from)
+                || !collections.stop_points.contains_id(to)
+            {
+                warn!(
+                    "Transfer between unknown quays {} and {}. I'll skip it.",
+                    from, to
+                );
+                return;
+            }
+            let transfer_duration = transfers
+                .entry((from.to_string(), to.to_string()))
+                .or_insert(duration);
+            *transfer_duration = (*transfer_duration).max(duration);
+        };
+
+        for site_connection in &self.site_connections {
+            let from_quays = quays(&site_connection.from, stops);
+            let to_quays = quays(&site_connection.to, stops);
+            for from in from_quays {
+                for to in to_quays {
+                    add_transfer(from, to, site_connection.duration);
+                }
+            }
+        }
+
+        for interchange in &self.interchanges {
+            if interchange.stay_seated {
+                continue;
+            }
+            let from = offer.quay_of(&interchange.from_scheduled_stop_point_id);
+            let to = offer.quay_of(&interchange.to_scheduled_stop_point_id);
+            match (from, to) {
+                (Some(from), Some(to)) => {
+                    add_transfer(from, to, interchange.minimum_transfer_time);
+                }
+                _ => warn!(
+                    "No quay assigned to ScheduledStopPoint {} or {}. I'll skip this interchange.",
+                    interchange.from_scheduled_stop_point_id,
+                    interchange.to_scheduled_stop_point_id
+                ),
+            }
+        }
+
+        collections.transfers = Collection::new(
+            transfers
+                .into_iter()
+                .map(|((from_stop_id, to_stop_id), duration)| Transfer {
+                    from_stop_id,
+                    to_stop_id,
+                    min_transfer_time: duration,
+                    real_min_transfer_time: duration,
+                    equipment_id: None,
+                })
+                .collect(),
+        );
+
+        for interchange in self
+            .interchanges
+            .iter()
+            .filter(|interchange| interchange.stay_seated)
+        {
+            let (from_journey_id, to_journey_id) = match (
+                &interchange.from_journey_id,
+                &interchange.to_journey_id,
+            ) {
+                (Some(from), Some(to)) => (from, to),
+                _ => {
+                    warn!("Stay seated interchange without FromJourneyRef or ToJourneyRef. I'll skip it.");
+                    continue;
+                }
+            };
+            let block_id = match collections.vehicle_journeys.get(from_journey_id) {
+                Some(vehicle_journey) => vehicle_journey
+                    .block_id
+                    .clone()
+                    .unwrap_or_else(|| vehicle_journey.id.clone()),
+                None => {
+                    warn!(
+                        "ServiceJourney {} not found. I'll skip this interchange.",
+                        from_journey_id
+                    );
+                    continue;
+                }
+            };
+            for journey_id in [from_journey_id, to_journey_id] {
+                if let Some(mut vehicle_journey) = collections.vehicle_journeys.get_mut(journey_id)
+                {
+                    vehicle_journey.block_id = Some(block_id.clone());
+                }
+            }
+        }
+    }
+}
+
+fn quays<'a>(end: &'a ConnectionEnd, stops: &'a Stops) -> &'a [String] {
+    match end {
+        ConnectionEnd::Quay(quay_id) => std::slice::from_ref(quay_id),
+        ConnectionEnd::StopPlace(stop_place_id) => stops.quays_of(stop_place_id),
+    }
+}
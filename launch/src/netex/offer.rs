@@ -0,0 +1,530 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use super::{child, grand_children, read_bool, read_id, read_ref, read_text};
+use anyhow::{format_err, Context, Error};
+use loki::{
+    chrono::NaiveDate,
+    chrono_tz::Tz,
+    tracing::warn,
+    transit_model::{
+        model::Collections,
+        objects::{
+            Calendar, CommercialMode, Company, Line, Network, PhysicalMode, Route, StopTime, Time,
+            VehicleJourney,
+        },
+    },
+    typed_index_collection::{CollectionWithId, Id, WithId},
+};
+use roxmltree::Node;
+use std::collections::{BTreeSet, HashMap};
+
+const DEFAULT_NETWORK_ID: &str = "default_network";
+const DEFAULT_COMPANY_ID: &str = "default_company";
+
+/// The objects of the NeTEx files that describe the lines
+/// and the vehicle journeys running on them.
+#[derive(Default)]
+pub(super) struct Offer {
+    networks: Vec<NetexNetwork>,
+    operators: Vec<Operator>,
+    lines: Vec<NetexLine>,
+    routes: Vec<NetexRoute>,
+    // the quay assigned to each scheduled stop point
+    stop_assignments: HashMap<String, String>,
+    journey_patterns: HashMap<String, JourneyPattern>,
+    service_journeys: Vec<ServiceJourney>,
+}
+
+struct NetexNetwork {
+    id: String,
+    name: String,
+    line_ids: Vec<String>,
+}
+
+struct Operator {
+    id: String,
+    name: String,
+    url: Option<String>,
+    phone: Option<String>,
+}
+
+struct NetexLine {
+    id: String,
+    name: String,
+    public_code: Option<String>,
+    transport_mode: Option<String>,
+    operator_id: Option<String>,
+    group_id: Option<String>,
+    color: Option<String>,
+    text_color: Option<String>,
+}
+
+struct NetexRoute {
+    id: String,
+    name: Option<String>,
+    line_id: String,
+    direction_type: Option<String>,
+}
+
+struct JourneyPattern {
+    route_id: String,
+    points: Vec<StopPointInJourneyPattern>,
+}
+
+struct StopPointInJourneyPattern {
+    id: String,
+    order: u32,
+    scheduled_stop_point_id: String,
+    for_boarding: bool,
+    for_alighting: bool,
+}
+
+struct ServiceJourney {
+    id: String,
+    name: Option<String>,
+    day_type_ids: Vec<String>,
+    journey_pattern_id: String,
+    operator_id: Option<String>,
+    passing_times: Vec<PassingTime>,
+}
+
+struct PassingTime {
+    point_in_journey_pattern_id: Option<String>,
+    arrival_time: Option<Time>,
+    departure_time: Option<Time>,
+}
+
+impl Offer {
+    pub(super) fn read_network(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        self.networks.push(NetexNetwork {
+            name: read_text(node, "Name").unwrap_or_else(|| id.clone()),
+            line_ids: grand_children(node, "members", "LineRef")
+                .filter_map(|line_ref| line_ref.attribute("ref"))
+                .map(ToString::to_string)
+                .collect(),
+            id,
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_operator(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let contact_details = child(node, "ContactDetails");
+        self.operators.push(Operator {
+            name: read_text(node, "Name").unwrap_or_else(|| id.clone()),
+            url: contact_details.and_then(|contact| read_text(contact, "Url")),
+            phone: contact_details.and_then(|contact| read_text(contact, "Phone")),
+            id,
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_line(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let presentation = child(node, "Presentation");
+        self.lines.push(NetexLine {
+            name: read_text(node, "Name").unwrap_or_else(|| id.clone()),
+            public_code: read_text(node, "PublicCode"),
+            transport_mode: read_text(node, "TransportMode"),
+            operator_id: read_ref(node, "OperatorRef"),
+            group_id: read_ref(node, "RepresentedByGroupRef"),
+            color: presentation.and_then(|presentation| read_text(presentation, "Colour")),
+            text_color: presentation.and_then(|presentation| read_text(presentation, "TextColour")),
+            id,
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_route(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let line_id =
+            read_ref(node, "LineRef").ok_or_else(|| format_err!("Route {} has no LineRef.", id))?;
+        self.routes.push(NetexRoute {
+            name: read_text(node, "Name"),
+            line_id,
+            direction_type: read_text(node, "DirectionType"),
+            id,
+        });
+        Ok(())
+    }
+
+    pub(super) fn read_passenger_stop_assignment(&mut self, node: Node) -> Result<(), Error> {
+        let scheduled_stop_point_id = read_ref(node, "ScheduledStopPointRef")
+            .ok_or_else(|| format_err!("PassengerStopAssignment has no ScheduledStopPointRef."))?;
+        let quay_id = read_ref(node, "QuayRef")
+            .ok_or_else(|| format_err!("PassengerStopAssignment has no QuayRef."))?;
+        self.stop_assignments
+            .insert(scheduled_stop_point_id, quay_id);
+        Ok(())
+    }
+
+    pub(super) fn read_journey_pattern(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let route_id = read_ref(node, "RouteRef")
+            .ok_or_else(|| format_err!("ServiceJourneyPattern {} has no RouteRef.", id))?;
+        let mut points = Vec::new();
+        for point in grand_children(node, "pointsInSequence", "StopPointInJourneyPattern") {
+            let point_id = read_id(point)?;
+            let order = point
+                .attribute("order")
+                .ok_or_else(|| format_err!("StopPointInJourneyPattern {} has no order.", point_id))?
+                .parse()
+                .with_context(|| {
+                    format!("StopPointInJourneyPattern {} has a bad order.", point_id)
+                })?;
+            let scheduled_stop_point_id =
+                read_ref(point, "ScheduledStopPointRef").ok_or_else(|| {
+                    format_err!(
+                        "StopPointInJourneyPattern {} has no ScheduledStopPointRef.",
+                        point_id
+                    )
+                })?;
+            points.push(StopPointInJourneyPattern {
+                id: point_id,
+                order,
+                scheduled_stop_point_id,
+                for_boarding: read_bool(point, "ForBoarding", true),
+                for_alighting: read_bool(point, "ForAlighting", true),
+            });
+        }
+        points.sort_by_key(|point| point.order);
+        self.journey_patterns
+            .insert(id, JourneyPattern { route_id, points });
+        Ok(())
+    }
+
+    pub(super) fn read_service_journey(&mut self, node: Node) -> Result<(), Error> {
+        let id = read_id(node)?;
+        let journey_pattern_id = read_ref(node, "ServiceJourneyPatternRef")
+            .or_else(|| read_ref(node, "JourneyPatternRef"))
+            .ok_or_else(|| format_err!("ServiceJourney {} has no JourneyPatternRef.", id))?;
+        let mut passing_times = Vec::new();
+        for passing_time in grand_children(node, "passingTimes", "TimetabledPassingTime") {
+            passing_times.push(PassingTime {
+                point_in_journey_pattern_id: read_ref(passing_time, "StopPointInJourneyPatternRef"),
+                arrival_time: read_time(passing_time, "ArrivalTime", "ArrivalDayOffset")?,
+                departure_time: read_time(passing_time, "DepartureTime", "DepartureDayOffset")?,
+            });
+        }
+        self.service_journeys.push(ServiceJourney {
+            name: read_text(node, "Name"),
+            day_type_ids: grand_children(node, "dayTypes", "DayTypeRef")
+                .filter_map(|day_type_ref| day_type_ref.attribute("ref"))
+                .map(ToString::to_string)
+                .collect(),
+            journey_pattern_id,
+            operator_id: read_ref(node, "OperatorRef"),
+            passing_times,
+            id,
+        });
+        Ok(())
+    }
+
+    /// The quay assigned to the scheduled stop point `scheduled_stop_point_id`.
+    pub(super) fn quay_of(&self, scheduled_stop_point_id: &str) -> Option<&str> {
+        self.stop_assignments
+            .get(scheduled_stop_point_id)
+            .map(String::as_str)
+    }
+
+    /// Fills the networks, companies, modes, lines, routes, calendars
+    /// and vehicle journeys of `collections`, whose stop points are already filled.
+    pub(super) fn fill(
+        &self,
+        collections: &mut Collections,
+        calendar_dates: &HashMap<String, BTreeSet<NaiveDate>>,
+        timezone: Tz,
+        dataset_id: &str,
+    ) {
+        let mut line_to_network = HashMap::new();
+        for network in &self.networks {
+            for line_id in &network.line_ids {
+                line_to_network.insert(line_id.as_str(), network.id.as_str());
+            }
+            let mut new_network = Network::with_id(&network.id);
+            new_network.name = network.name.clone();
+            new_network.timezone = Some(timezone);
+            push_or_warn(&mut collections.networks, new_network);
+        }
+
+        for operator in &self.operators {
+            let mut company = Company::with_id(&operator.id);
+            company.name = operator.name.clone();
+            company.url = operator.url.clone();
+            company.phone = operator.phone.clone();
+            push_or_warn(&mut collections.companies, company);
+        }
+
+        for line in &self.lines {
+            let network_id = line_to_network
+                .get(line.id.as_str())
+                .copied()
+                .or_else(|| {
+                    line.group_id
+                        .as_deref()
+                        .filter(|group_id| collections.networks.contains_id(group_id))
+                })
+                .unwrap_or_else(|| {
+                    collections
+                        .networks
+                        .get_or_create_with(DEFAULT_NETWORK_ID, || {
+                            let mut network = Network::with_id(DEFAULT_NETWORK_ID);
+                            network.timezone = Some(timezone);
+                            network
+                        });
+                    DEFAULT_NETWORK_ID
+                })
+                .to_string();
+            let (mode_id, mode_name) = mode(line.transport_mode.as_deref());
+            collections
+                .commercial_modes
+                .get_or_create_with(mode_id, || CommercialMode {
+                    id: mode_id.to_string(),
+                    name: mode_name.to_string(),
+                });
+            let mut new_line = Line::with_id(&line.id);
+            new_line.name = line.name.clone();
+            new_line.code = line.public_code.clone();
+            new_line.network_id = network_id;
+            new_line.commercial_mode_id = mode_id.to_string();
+            new_line.color = line.color.as_deref().and_then(|color| color.parse().ok());
+            new_line.text_color = line
+                .text_color
+                .as_deref()
+                .and_then(|color| color.parse().ok());
+            push_or_warn(&mut collections.lines, new_line);
+        }
+
+        for route in &self.routes {
+            if !collections.lines.contains_id(&route.line_id) {
+                warn!(
+                    "Line {} of Route {} not found. I'll skip the route.",
+                    route.line_id, route.id
+                );
+                continue;
+            }
+            let mut new_route = Route::with_id(&route.id);
+            new_route.name = route.name.clone().unwrap_or_default();
+            new_route.line_id = route.line_id.clone();
+            new_route.direction_type = route.direction_type.clone();
+            push_or_warn(&mut collections.routes, new_route);
+        }
+
+        let lines: HashMap<&str, &NetexLine> = self
+            .lines
+            .iter()
+            .map(|line| (line.id.as_str(), line))
+            .collect();
+        for service_journey in &self.service_journeys {
+            match self.make_vehicle_journey(service_journey, &lines, dataset_id, collections) {
+                Ok(vehicle_journey) => {
+                    let calendar_id = service_journey.day_type_ids.join("+");
+                    collections.calendars.get_or_create_with(&calendar_id, || {
+                        let mut calendar = Calendar::new(calendar_id.clone());
+                        for day_type_id in &service_journey.day_type_ids {
+                            match calendar_dates.get(day_type_id) {
+                                Some(dates) => calendar.dates.extend(dates),
+                                None => warn!("DayType {} not found.", day_type_id),
+                            }
+                        }
+                        calendar
+                    });
+                    let mut vehicle_journey = vehicle_journey;
+                    vehicle_journey.service_id = calendar_id;
+                    push_or_warn(&mut collections.vehicle_journeys, vehicle_journey);
+                }
+                Err(err) => {
+                    warn!(
+                        "Could not read ServiceJourney {}. I'll skip it. {:?}",
+                        service_journey.id, err
+                    );
+                }
+            }
+        }
+    }
+
+    fn make_vehicle_journey(
+        &self,
+        service_journey: &ServiceJourney,
+        lines: &HashMap<&str, &NetexLine>,
+        dataset_id: &str,
+        collections: &mut Collections,
+    ) -> Result<VehicleJourney, Error> {
+        let journey_pattern = self
+            .journey_patterns
+            .get(&service_journey.journey_pattern_id)
+            .ok_or_else(|| {
+                format_err!(
+                    "ServiceJourneyPattern {} not found.",
+                    service_journey.journey_pattern_id
+                )
+            })?;
+        let route = collections
+            .routes
+            .get(&journey_pattern.route_id)
+            .ok_or_else(|| format_err!("Route {} not found.", journey_pattern.route_id))?;
+        let line = lines
+            .get(route.line_id.as_str())
+            .ok_or_else(|| format_err!("Line {} not found.", route.line_id))?;
+        if service_journey.day_type_ids.is_empty() {
+            return Err(format_err!("No DayTypeRef."));
+        }
+
+        let (mode_id, mode_name) = mode(line.transport_mode.as_deref());
+        collections
+            .physical_modes
+            .get_or_create_with(mode_id, || PhysicalMode {
+                id: mode_id.to_string(),
+                name: mode_name.to_string(),
+                co2_emission: None,
+            });
+
+        let company_id = service_journey
+            .operator_id
+            .as_ref()
+            .or(line.operator_id.as_ref())
+            .filter(|operator_id| collections.companies.contains_id(operator_id))
+            .cloned()
+            .unwrap_or_else(|| {
+                collections.companies.get_or_create(DEFAULT_COMPANY_ID);
+                DEFAULT_COMPANY_ID.to_string()
+            });
+
+        let mut stop_times = Vec::with_capacity(service_journey.passing_times.len());
+        for (position, passing_time) in service_journey.passing_times.iter().enumerate() {
+            let point = match &passing_time.point_in_journey_pattern_id {
+                Some(point_id) => journey_pattern
+                    .points
+                    .iter()
+                    .find(|point| point.id == *point_id),
+                None => journey_pattern.points.get(position),
+            }
+            .ok_or_else(|| {
+                format_err!(
+                    "No StopPointInJourneyPattern for the {}-th passing time.",
+                    position
+                )
+            })?;
+            let quay_id = self
+                .quay_of(&point.scheduled_stop_point_id)
+                .ok_or_else(|| {
+                    format_err!(
+                        "No quay assigned to ScheduledStopPoint {}.",
+                        point.scheduled_stop_point_id
+                    )
+                })?;
+            let stop_point_idx = collections
+                .stop_points
+                .get_idx(quay_id)
+                .ok_or_else(|| format_err!("Quay {} not found.", quay_id))?;
+            let arrival_time = passing_time
+                .arrival_time
+                .or(passing_time.departure_time)
+                .ok_or_else(|| format_err!("The {}-th passing time has no time.", position))?;
+            let departure_time = passing_time.departure_time.unwrap_or(arrival_time);
+            stop_times.push(StopTime {
+                stop_point_idx,
+                sequence: point.order,
+                arrival_time,
+                departure_time,
+                boarding_duration: 0,
+                alighting_duration: 0,
+                pickup_type: if point.for_boarding { 0 } else { 1 },
+                drop_off_type: if point.for_alighting { 0 } else { 1 },
+                local_zone_id: None,
+                precision: None,
+            });
+        }
+
+        Ok(VehicleJourney {
+            id: service_journey.id.clone(),
+            route_id: route.id.clone(),
+            physical_mode_id: mode_id.to_string(),
+            dataset_id: dataset_id.to_string(),
+            company_id,
+            headsign: service_journey.name.clone(),
+            stop_times,
+            ..Default::default()
+        })
+    }
+}
+
+fn push_or_warn<T>(collection: &mut CollectionWithId<T>, object: T)
+where
+    T: Id<T>,
+{
+    if let Err(err) = collection.push(object) {
+        warn!("Could not add an object. I'll skip it. {}", err);
+    }
+}
+
+// an `ArrivalTime` or `DepartureTime`, on the day given by the `DayOffset`
+fn read_time(node: Node, name: &str, day_offset_name: &str) -> Result<Option<Time>, Error> {
+    let text = match read_text(node, name) {
+        Some(text) => text,
+        None => return Ok(None),
+    };
+    let day_offset: u32 = read_text(node, day_offset_name)
+        .map(|day_offset| day_offset.parse())
+        .transpose()
+        .with_context(|| format!("Bad {}.", day_offset_name))?
+        .unwrap_or(0);
+    let mut parts = text.split(':').map(|part| part.parse::<u32>());
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(hours)), Some(Ok(minutes)), Some(Ok(seconds)), None) => {
+            Ok(Some(Time::new(hours + 24 * day_offset, minutes, seconds)))
+        }
+        _ => Err(format_err!("Could not parse {} {} as a time.", name, text)),
+    }
+}
+
+// the id and name of the physical and commercial modes of a NeTEx `TransportMode`
+fn mode(transport_mode: Option<&str>) -> (&'static str, &'static str) {
+    match transport_mode {
+        Some("air") => ("Air", "Avion"),
+        Some("coach") => ("Coach", "Autocar"),
+        Some("ferry") | Some("water") => ("Ferry", "Ferry"),
+        Some("funicular") => ("Funicular", "Funiculaire"),
+        Some("cableway") => ("SuspendedCableCar", "Téléphérique"),
+        Some("metro") => ("Metro", "Métro"),
+        Some("rail") => ("Train", "Train"),
+        Some("taxi") => ("Taxi", "Taxi"),
+        Some("tram") => ("Tramway", "Tramway"),
+        _ => ("Bus", "Bus"),
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use super::{child, children, read_id, read_text, FrameDefaults};
+use anyhow::{bail, format_err, Error};
+use loki::{
+    tracing::warn,
+    transit_model::{
+        model::Collections,
+        objects::{Coord, StopArea, StopPoint},
+    },
+};
+use roxmltree::Node;
+use std::collections::HashMap;
+
+/// The `StopPlace`s and `Quay`s of the NeTEx files.
+///
+/// A `StopPlace` gives a stop area, and a `Quay` a stop point.
+#[derive(Default)]
+pub(super) struct Stops {
+    stop_places: Vec<NetexStop>,
+    quays: Vec<NetexStop>,
+    // the quays of each stop place
+    stop_place_quays: HashMap<String, Vec<String>>,
+}
+
+struct NetexStop {
+    id: String,
+    name: String,
+    public_code: Option<String>,
+    coord: Option<Coord>,
+}
+
+impl Stops {
+    pub(super) fn read_stop_place(
+        &mut self,
+        node: Node,
+        frame_defaults: &FrameDefaults,
+    ) -> Result<(), Error> {
+        let stop_place = read_stop(node, frame_defaults)?;
+        // the quays are either described in the stop place, or referenced
+        let quays = children(node, "quays")
+            .flat_map(|quays| quays.children())
+            .filter_map(|quay| match quay.tag_name().name() {
+                "Quay" => quay.attribute("id"),
+                "QuayRef" => quay.attribute("ref"),
+                _ => None,
+            })
+            .map(ToString::to_string)
+            .collect();
+        self.stop_place_quays.insert(stop_place.id.clone(), quays);
+        self.stop_places.push(stop_place);
+        Ok(())
+    }
+
+    pub(super) fn read_quay(
+        &mut self,
+        node: Node,
+        frame_defaults: &FrameDefaults,
+    ) -> Result<(), Error> {
+        let quay = read_stop(node, frame_defaults)?;
+        if quay.coord.is_none() {
+            warn!("Quay {} has no coordinates.", quay.id);
+        }
+        self.quays.push(quay);
+        Ok(())
+    }
+
+    /// The quays of the stop place `stop_place_id`.
+    pub(super) fn quays_of(&self, stop_place_id: &str) -> &[String] {
+        self.stop_place_quays
+            .get(stop_place_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub(super) fn fill(&self, collections: &mut Collections) {
+        let quays_coord: HashMap<&str, Coord> = self
+            .quays
+            .iter()
+            .filter_map(|quay| quay.coord.map(|coord| (quay.id.as_str(), coord)))
+            .collect();
+        let mut quay_to_stop_place = HashMap::new();
+        for stop_place in &self.stop_places {
+            let quays = self.quays_of(&stop_place.id);
+            // stop places without quays, like multimodal stop places, are not kept
+            if quays.is_empty() {
+                continue;
+            }
+            for quay in quays {
+                quay_to_stop_place.insert(quay.as_str(), stop_place.id.as_str());
+            }
+            // without coordinates, the stop area is placed at the barycenter of its quays
+            let coord = stop_place.coord.unwrap_or_else(|| {
+                let coords: Vec<Coord> = quays
+                    .iter()
+                    .filter_map(|quay| quays_coord.get(quay.as_str()))
+                    .copied()
+                    .collect();
+                barycenter(&coords)
+            });
+            let stop_area = StopArea {
+                id: stop_place.id.clone(),
+                name: stop_place.name.clone(),
+                visible: true,
+                coord,
+                ..Default::default()
+            };
+            if let Err(err) = collections.stop_areas.push(stop_area) {
+                warn!("Could not add a stop area. I'll skip it. {}", err);
+            }
+        }
+
+        for quay in &self.quays {
+            let mut stop_point = StopPoint {
+                id: quay.id.clone(),
+                name: quay.name.clone(),
+                visible: true,
+                coord: quay.coord.unwrap_or_default(),
+                platform_code: quay.public_code.clone(),
+                ..Default::default()
+            };
+            match quay_to_stop_place.get(quay.id.as_str()) {
+                Some(stop_place_id) => {
+                    stop_point.stop_area_id = stop_place_id.to_string();
+                }
+                None => {
+                    let stop_area = StopArea::from(stop_point.clone());
+                    stop_point.stop_area_id = stop_area.id.clone();
+                    if let Err(err) = collections.stop_areas.push(stop_area) {
+                        warn!("Could not add a stop area. I'll skip it. {}", err);
+                    }
+                }
+            }
+            if let Err(err) = collections.stop_points.push(stop_point) {
+                warn!("Could not add a stop point. I'll skip it. {}", err);
+            }
+        }
+    }
+}
+
+fn read_stop(node: Node, frame_defaults: &FrameDefaults) -> Result<NetexStop, Error> {
+    let id = read_id(node)?;
+    let coord = read_coord(node, frame_defaults)?;
+    Ok(NetexStop {
+        name: read_text(node, "Name").unwrap_or_else(|| id.clone()),
+        public_code: read_text(node, "PublicCode"),
+        coord,
+        id,
+    })
+}
+
+// the location of the `Centroid`, given either as `Longitude` and `Latitude`
+// or as a `gml:pos` in WGS84 or Lambert 93
+fn read_coord(node: Node, frame_defaults: &FrameDefaults) -> Result<Option<Coord>, Error> {
+    let location = match child(node, "Centroid").and_then(|centroid| child(centroid, "Location")) {
+        Some(location) => location,
+        None => return Ok(None),
+    };
+    if let (Some(lon), Some(lat)) = (
+        read_text(location, "Longitude"),
+        read_text(location, "Latitude"),
+    ) {
+        return Ok(Some(Coord {
+            lon: lon.parse()?,
+            lat: lat.parse()?,
+        }));
+    }
+    let pos = match child(location, "pos") {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let location_system = pos
+        .attribute("srsName")
+        .or_else(|| location.attribute("srsName"))
+        .or(frame_defaults.location_system.as_deref())
+        .unwrap_or("EPSG:4326");
+    let values = pos
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<Vec<f64>, _>>()?;
+    let (first, second) = match values.as_slice() {
+        [first, second] => (*first, *second),
+        _ => bail!("Bad position {:?}", pos.text()),
+    };
+    if location_system.ends_with("4326") {
+        // gml positions in WGS84 are given as latitude then longitude
+        Ok(Some(Coord {
+            lon: second,
+            lat: first,
+        }))
+    } else if location_system.ends_with("2154") {
+        Ok(Some(lambert_93_to_wgs84(first, second)))
+    } else {
+        Err(format_err!(
+            "Unsupported location system {}",
+            location_system
+        ))
+    }
+}
+
+fn barycenter(coords: &[Coord]) -> Coord {
+    if coords.is_empty() {
+        return Coord::default();
+    }
+    let nb_of_coords = coords.len() as f64;
+    Coord {
+        lon: coords.iter().map(|coord| coord.lon).sum::<f64>() / nb_of_coords,
+        lat: coords.iter().map(|coord| coord.lat).sum::<f64>() / nb_of_coords,
+    }
+}
+
+// Inverse of the Lambert conformal conic projection used by Lambert 93 (EPSG:2154),
+// as described in the IGN note NTG_71, algorithms ALG0004 and ALG0001.
+// Lambert 93 is defined on RGF93, which we consider identical to WGS84.
+fn lambert_93_to_wgs84(x: f64, y: f64) -> Coord {
+    const N: f64 = 0.725_607_765_053_267;
+    const C: f64 = 11_754_255.426_096;
+    const XS: f64 = 700_000.0;
+    const YS: f64 = 12_655_612.049_876;
+    const E: f64 = 0.081_819_191_042_815_8;
+    let lon_0 = 3f64.to_radians();
+
+    let dx = x - XS;
+    let dy = YS - y;
+    let radius = (dx * dx + dy * dy).sqrt();
+    let gamma = dx.atan2(dy);
+    let lon = lon_0 + gamma / N;
+    let isometric_lat = -(radius / C).ln() / N;
+
+    let mut lat = 2.0 * isometric_lat.exp().atan() - std::f64::consts::FRAC_PI_2;
+    loop {
+        let e_sin_lat = E * lat.sin();
+        let next_lat = 2.0
+            * (((1.0 + e_sin_lat) / (1.0 - e_sin_lat)).powf(E / 2.0) * isometric_lat.exp()).atan()
+            - std::f64::consts::FRAC_PI_2;
+        if (next_lat - lat).abs() < 1e-11 {
+            lat = next_lat;
+            break;
+        }
+        lat = next_lat;
+    }
+
+    Coord {
+        lon: lon.to_degrees(),
+        lat: lat.to_degrees(),
+    }
+}
@@ -35,23 +35,35 @@
 // www.navitia.io
 
 use super::config;
-use crate::{config::launch_params::LocalFileParams, loki::TransitData};
+use crate::{
+    config::{launch_params::LocalFileParams, TransfersParams},
+    loki::TransitData,
+    merge::merge_models,
+    netex,
+    transfers::{apply_rules, generate_transfers, TransferRules},
+};
 use anyhow::{format_err, Error};
 use loki::{
     models::base_model::{self, BaseModel},
     tracing::{info, warn},
     transit_model, DataTrait, LoadsData, PositiveDuration,
 };
-use std::{path::PathBuf, str::FromStr, time::SystemTime};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
 pub fn read(launch_params: &config::LaunchParams) -> Result<(TransitData, BaseModel), Error> {
     let base_model = read_model(
         &LocalFileParams {
             input_data_path: launch_params.input_data_path.clone(),
             loads_data_path: launch_params.loads_data_path.clone(),
+            snapshot_path: None,
+            merged_inputs: launch_params.merged_inputs.clone(),
         },
         launch_params.input_data_type.clone(),
         launch_params.default_transfer_duration,
+        &launch_params.transfers,
     )?;
 
     let data = build_transit_data(&base_model);
@@ -65,32 +77,29 @@ pub fn read_model_from_zip_reader<R>(
     source: &str,
     input_data_type: config::InputDataType,
     default_transfer_duration: PositiveDuration,
+    transfers_params: &TransfersParams,
 ) -> Result<BaseModel, Error>
 where
     R: std::io::Seek + std::io::Read,
 {
+    let transfer_rules = TransferRules::new(transfers_params)?;
     let model = match input_data_type {
         config::InputDataType::Ntfs => {
             transit_model::ntfs::from_zip_reader(input_data_reader, source)?
         }
         config::InputDataType::Gtfs => {
             let configuration = transit_model::gtfs::Configuration::default();
-            let max_distance = f64::from_str(transit_model::TRANSFER_MAX_DISTANCE)?;
-            let walking_speed = f64::from_str(transit_model::TRANSFER_WALKING_SPEED)?;
-            let waiting_time = u32::from_str(transit_model::TRANSFER_WAITING_TIME)?;
 
             let model = transit_model::gtfs::Reader::new(configuration)
                 .parse_zip_reader(input_data_reader, source)?;
-
-            transit_model::transfers::generates_transfers(
-                model,
-                max_distance,
-                walking_speed,
-                waiting_time,
-                None,
-            )?
+            generate_transfers(model, transfers_params, &transfer_rules, None)?
+        }
+        config::InputDataType::Netex => {
+            let model = netex::from_zip_reader(input_data_reader, source)?;
+            generate_transfers(model, transfers_params, &transfer_rules, None)?
         }
     };
+    let model = apply_rules(model, &transfer_rules)?;
     info!("Transit model loaded");
     let loads_data = read_loads_data_from_zip_reader(loads_data_reader, &model);
     BaseModel::new(model, loads_data, default_transfer_duration)
@@ -101,34 +110,64 @@ pub fn read_model(
     data_files: &LocalFileParams,
     input_data_type: config::InputDataType,
     default_transfer_duration: PositiveDuration,
+    transfers_params: &TransfersParams,
 ) -> Result<BaseModel, Error> {
+    let transfer_rules = TransferRules::new(transfers_params)?;
+    let model = read_input_model(
+        &data_files.input_data_path,
+        &input_data_type,
+        transfers_params,
+        &transfer_rules,
+    )?;
+    info!("Transit model loaded");
+    let model = if data_files.merged_inputs.is_empty() {
+        model
+    } else {
+        let mut models = vec![(model, None)];
+        for merged_input in &data_files.merged_inputs {
+            let merged_model = read_input_model(
+                &merged_input.input_data_path,
+                &merged_input.input_data_type,
+                transfers_params,
+                &transfer_rules,
+            )?;
+            info!(
+                "Transit model loaded from {:?}",
+                merged_input.input_data_path
+            );
+            models.push((merged_model, merged_input.prefix.clone()));
+        }
+        merge_models(models, transfers_params, &transfer_rules)?
+    };
+    let model = apply_rules(model, &transfer_rules)?;
+    let loads_data = read_loads_data(&data_files.loads_data_path, &model);
+    BaseModel::new(model, loads_data, default_transfer_duration)
+        .map_err(|err| format_err!("Could not create base model {:?}", err))
+}
+
+fn read_input_model(
+    input_data_path: &Path,
+    input_data_type: &config::InputDataType,
+    transfers_params: &TransfersParams,
+    transfer_rules: &TransferRules,
+) -> Result<base_model::Model, Error> {
     let model = match input_data_type {
-        config::InputDataType::Ntfs => transit_model::ntfs::read(&data_files.input_data_path)?,
+        config::InputDataType::Ntfs => transit_model::ntfs::read(input_data_path)?,
         config::InputDataType::Gtfs => {
             let configuration = transit_model::gtfs::Configuration::default();
-            let max_distance = f64::from_str(transit_model::TRANSFER_MAX_DISTANCE)?;
-            let walking_speed = f64::from_str(transit_model::TRANSFER_WALKING_SPEED)?;
-            let waiting_time = u32::from_str(transit_model::TRANSFER_WAITING_TIME)?;
-
-            let model = transit_model::gtfs::Reader::new(configuration)
-                .parse(&data_files.input_data_path)?;
 
-            transit_model::transfers::generates_transfers(
-                model,
-                max_distance,
-                walking_speed,
-                waiting_time,
-                None,
-            )?
+            let model = transit_model::gtfs::Reader::new(configuration).parse(input_data_path)?;
+            generate_transfers(model, transfers_params, transfer_rules, None)?
+        }
+        config::InputDataType::Netex => {
+            let model = netex::read(input_data_path)?;
+            generate_transfers(model, transfers_params, transfer_rules, None)?
         }
     };
-    info!("Transit model loaded");
-    let loads_data = read_loads_data(&data_files.loads_data_path, &model);
-    BaseModel::new(model, loads_data, default_transfer_duration)
-        .map_err(|err| format_err!("Could not create base model {:?}", err))
+    Ok(model)
 }
 
-fn read_loads_data_from_zip_reader<R: std::io::Read>(
+pub fn read_loads_data_from_zip_reader<R: std::io::Read>(
     reader: Option<R>,
     model: &base_model::Model,
 ) -> LoadsData {
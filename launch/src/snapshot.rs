@@ -0,0 +1,414 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! A snapshot stores a [`BaseModel`] and the [`TransitData`] built from it in a single file,
+//! so that a server can start without building the [`TransitData`] again.
+//!
+//! A snapshot file contains, in order :
+//!  - a header, with the versions used to write the snapshot and a hash of the input files,
+//!  - the model, as a ntfs zip archive, along with the passengers loads file
+//!    and the durations of the walks through pathways,
+//!  - the [`TransitData`].
+//!
+//! A snapshot is refused when the header does not match the current version of loki,
+//! or when the input files changed since the snapshot was written.
+//!
+//! Reading a snapshot saves the reading of the input files in their original format,
+//! the merge of datasets, the generation of transfers, the computation of the walks
+//! through pathways, and the build of the [`TransitData`], which takes most of the time
+//! on large datasets.
+//! The model itself is still parsed from the ntfs archive : the collections of `transit_model`
+//! are serialized for csv files, and cannot be stored in a binary format.
+//! The time taken by each step is logged when a snapshot is read.
+
+use crate::{
+    config::{launch_params::LocalFileParams, InputDataType, TransfersParams},
+    read::{build_transit_data, read_loads_data_from_zip_reader, read_model},
+};
+use anyhow::{bail, format_err, Context, Error};
+use loki::{
+    chrono::{DateTime, FixedOffset, TimeZone, Utc},
+    models::{
+        base_model::BaseModel,
+        pathways::{PathwayDurations, StopPointsPathwayDurations},
+    },
+    snapshot::with_base_indexes_of,
+    tracing::info,
+    transit_model, PositiveDuration, TransitData,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Cursor, Read, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+// To be incremented each time the layout of the snapshot changes,
+// or when the serialized types of loki change.
+const FORMAT_VERSION: u32 = 3;
+
+const MAGIC: &[u8; 8] = b"LOKISNAP";
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SnapshotHeader {
+    format_version: u32,
+    loki_version: String,
+    vehicle_loads: bool,
+    source_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotModel {
+    ntfs_archive: Vec<u8>,
+    loads_data: Option<Vec<u8>>,
+    default_transfer_duration: PositiveDuration,
+    // used to check that the model read back from the ntfs archive
+    // has its objects in the same order as the model the data was built from
+    vehicle_journey_ids: Vec<String>,
+    stop_point_ids: Vec<String>,
+    transfer_stop_ids: Vec<(String, String)>,
+    // the stop points are given by their index in the model
+    pathway_durations: Vec<(usize, usize, PathwayDurations)>,
+}
+
+impl SnapshotHeader {
+    fn new(source_hash: String) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            loki_version: loki::snapshot::LOKI_VERSION.to_string(),
+            vehicle_loads: cfg!(feature = "vehicle_loads"),
+            source_hash,
+        }
+    }
+}
+
+impl SnapshotModel {
+    fn object_ids(base_model: &BaseModel) -> (Vec<String>, Vec<String>, Vec<(String, String)>) {
+        let model = base_model.transit_model();
+        let vehicle_journey_ids = model
+            .vehicle_journeys
+            .values()
+            .map(|vehicle_journey| vehicle_journey.id.clone())
+            .collect();
+        let stop_point_ids = model
+            .stop_points
+            .values()
+            .map(|stop_point| stop_point.id.clone())
+            .collect();
+        let transfer_stop_ids = model
+            .transfers
+            .values()
+            .map(|transfer| (transfer.from_stop_id.clone(), transfer.to_stop_id.clone()))
+            .collect();
+        (vehicle_journey_ids, stop_point_ids, transfer_stop_ids)
+    }
+
+    fn pathway_durations(base_model: &BaseModel) -> Vec<(usize, usize, PathwayDurations)> {
+        let mut pathway_durations: Vec<_> = base_model
+            .pathway_durations()
+            .iter()
+            .map(|((from_stop, to_stop), durations)| (from_stop.get(), to_stop.get(), *durations))
+            .collect();
+        pathway_durations.sort_by_key(|(from_stop, to_stop, _)| (*from_stop, *to_stop));
+        pathway_durations
+    }
+
+    fn stop_points_pathway_durations(
+        pathway_durations: &[(usize, usize, PathwayDurations)],
+        model: &transit_model::Model,
+    ) -> Result<StopPointsPathwayDurations, Error> {
+        let stop_points: Vec<_> = model.stop_points.iter().map(|(idx, _)| idx).collect();
+        pathway_durations
+            .iter()
+            .map(|(from_stop, to_stop, durations)| {
+                match (stop_points.get(*from_stop), stop_points.get(*to_stop)) {
+                    (Some(from_stop), Some(to_stop)) => Ok(((*from_stop, *to_stop), *durations)),
+                    _ => Err(format_err!(
+                        "There is no stop point with index {} or {} in the model",
+                        from_stop,
+                        to_stop
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Hash of the input files and of the parameters used to read them.
+pub fn source_hash(
+    data_files: &LocalFileParams,
+    input_data_type: &InputDataType,
+    default_transfer_duration: PositiveDuration,
+    transfers_params: &TransfersParams,
+) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(input_data_type.to_string().as_bytes());
+    hasher.update(default_transfer_duration.to_string().as_bytes());
+    hasher.update(transfers_params.max_distance.to_string().as_bytes());
+    hasher.update(transfers_params.walking_speed.to_string().as_bytes());
+    hasher.update(transfers_params.waiting_time.to_string().as_bytes());
+    if let Some(rules_path) = &transfers_params.rules_path {
+        hasher.update(b"transfer_rules");
+        hash_file(&mut hasher, rules_path)?;
+    }
+
+    hash_input_data(&mut hasher, &data_files.input_data_path)?;
+
+    for merged_input in &data_files.merged_inputs {
+        hasher.update(b"merged_input");
+        hasher.update(merged_input.input_data_type.to_string().as_bytes());
+        if let Some(prefix) = &merged_input.prefix {
+            hasher.update(prefix.as_bytes());
+        }
+        hash_input_data(&mut hasher, &merged_input.input_data_path)?;
+    }
+
+    if let Some(loads_data_path) = &data_files.loads_data_path {
+        hasher.update(b"loads_data");
+        hash_file(&mut hasher, loads_data_path)?;
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn hash_input_data(hasher: &mut Sha256, input_data_path: &Path) -> Result<(), Error> {
+    if input_data_path.is_dir() {
+        hash_directory(hasher, input_data_path, input_data_path)?;
+    } else {
+        hash_file(hasher, input_data_path)?;
+    }
+    Ok(())
+}
+
+// Hashes the files of `directory` and of its sub-directories, as they are all read
+// for netex inputs, along with their paths relative to `root`, so that the hash
+// does not change when the input data is moved.
+fn hash_directory(hasher: &mut Sha256, root: &Path, directory: &Path) -> Result<(), Error> {
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("Could not read directory {:?}", directory))?
+    {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            hash_directory(hasher, root, &path)?;
+        } else if path.is_file() {
+            let relative_path = path.strip_prefix(root).unwrap_or(&path);
+            for component in relative_path.components() {
+                hasher.update(component.as_os_str().to_string_lossy().as_bytes());
+                hasher.update(b"/");
+            }
+            hash_file(hasher, &path)?;
+        }
+    }
+    Ok(())
+}
+
+fn hash_file(hasher: &mut Sha256, path: &Path) -> Result<(), Error> {
+    let mut file = File::open(path).with_context(|| format!("Could not open file {:?}", path))?;
+    std::io::copy(&mut file, hasher).with_context(|| format!("Could not read file {:?}", path))?;
+    Ok(())
+}
+
+/// Reads the input files, builds the data and writes both in a snapshot file at `output_path`.
+pub fn write_snapshot(
+    data_files: &LocalFileParams,
+    input_data_type: InputDataType,
+    default_transfer_duration: PositiveDuration,
+    transfers_params: &TransfersParams,
+    output_path: &Path,
+) -> Result<(), Error> {
+    let source_hash = source_hash(
+        data_files,
+        &input_data_type,
+        default_transfer_duration,
+        transfers_params,
+    )?;
+    let base_model = read_model(
+        data_files,
+        input_data_type,
+        default_transfer_duration,
+        transfers_params,
+    )?;
+    let data = build_transit_data(&base_model);
+
+    let ntfs_archive = ntfs_archive(&base_model, output_path)?;
+    let loads_data = data_files
+        .loads_data_path
+        .as_ref()
+        .map(|path| {
+            std::fs::read(path).with_context(|| format!("Could not read loads file {:?}", path))
+        })
+        .transpose()?;
+    let (vehicle_journey_ids, stop_point_ids, transfer_stop_ids) =
+        SnapshotModel::object_ids(&base_model);
+    let snapshot_model = SnapshotModel {
+        ntfs_archive,
+        loads_data,
+        default_transfer_duration,
+        vehicle_journey_ids,
+        stop_point_ids,
+        transfer_stop_ids,
+        pathway_durations: SnapshotModel::pathway_durations(&base_model),
+    };
+
+    let file = File::create(output_path)
+        .with_context(|| format!("Could not create snapshot file {:?}", output_path))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut writer, &SnapshotHeader::new(source_hash))?;
+    bincode::serialize_into(&mut writer, &snapshot_model)?;
+    bincode::serialize_into(&mut writer, &data)?;
+    writer.flush()?;
+    info!("Snapshot written to {:?}", output_path);
+    Ok(())
+}
+
+// The model is written as a ntfs archive in a temporary file next to `output_path`.
+fn ntfs_archive(base_model: &BaseModel, output_path: &Path) -> Result<Vec<u8>, Error> {
+    // keep the creation datetime of the dataset, which is overwritten when writing a ntfs
+    let current_datetime: DateTime<FixedOffset> = base_model
+        .dataset_created_at()
+        .map(|datetime| FixedOffset::east(0).from_utc_datetime(&datetime))
+        .unwrap_or_else(|| Utc::now().into());
+    let archive_path = output_path.with_extension("ntfs.zip.tmp");
+    transit_model::ntfs::write_to_zip(base_model.transit_model(), &archive_path, current_datetime)?;
+    let ntfs_archive = std::fs::read(&archive_path)
+        .with_context(|| format!("Could not read ntfs archive {:?}", archive_path));
+    std::fs::remove_file(&archive_path)
+        .with_context(|| format!("Could not remove ntfs archive {:?}", archive_path))?;
+    ntfs_archive
+}
+
+/// Reads the model and data stored in the snapshot file at `snapshot_path`.
+///
+/// Returns an error if the snapshot was written by another version of loki,
+/// or from input files that differ from `data_files`.
+pub fn read_snapshot(
+    snapshot_path: &Path,
+    data_files: &LocalFileParams,
+    input_data_type: &InputDataType,
+    default_transfer_duration: PositiveDuration,
+    transfers_params: &TransfersParams,
+) -> Result<(TransitData, BaseModel), Error> {
+    let timer = SystemTime::now();
+    let file = File::open(snapshot_path)
+        .with_context(|| format!("Could not open snapshot file {:?}", snapshot_path))?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        bail!("{:?} is not a snapshot file", snapshot_path);
+    }
+    let header: SnapshotHeader = bincode::deserialize_from(&mut reader)?;
+    let source_hash = source_hash(
+        data_files,
+        input_data_type,
+        default_transfer_duration,
+        transfers_params,
+    )?;
+    let expected_header = SnapshotHeader::new(source_hash);
+    if header != expected_header {
+        bail!(
+            "Snapshot {:?} does not match the current version or input data. \
+            Snapshot header : {:?}. Expected : {:?}",
+            snapshot_path,
+            header,
+            expected_header
+        );
+    }
+
+    let snapshot_model: SnapshotModel = bincode::deserialize_from(&mut reader)?;
+    let step_timer = SystemTime::now();
+    let model = transit_model::ntfs::from_zip_reader(
+        Cursor::new(snapshot_model.ntfs_archive),
+        &snapshot_path.to_string_lossy(),
+    )?;
+    info!(
+        "Model of snapshot {:?} parsed in {} ms",
+        snapshot_path,
+        step_timer.elapsed().unwrap().as_millis()
+    );
+    let step_timer = SystemTime::now();
+    let pathway_durations =
+        SnapshotModel::stop_points_pathway_durations(&snapshot_model.pathway_durations, &model)?;
+    let loads_data =
+        read_loads_data_from_zip_reader(snapshot_model.loads_data.map(Cursor::new), &model);
+    let base_model = BaseModel::with_pathway_durations(
+        model,
+        loads_data,
+        snapshot_model.default_transfer_duration,
+        pathway_durations,
+    )
+    .map_err(|err| format_err!("Could not create base model {:?}", err))?;
+    info!(
+        "Base model of snapshot {:?} built in {} ms",
+        snapshot_path,
+        step_timer.elapsed().unwrap().as_millis()
+    );
+    let (vehicle_journey_ids, stop_point_ids, transfer_stop_ids) =
+        SnapshotModel::object_ids(&base_model);
+    if vehicle_journey_ids != snapshot_model.vehicle_journey_ids
+        || stop_point_ids != snapshot_model.stop_point_ids
+        || transfer_stop_ids != snapshot_model.transfer_stop_ids
+    {
+        bail!(
+            "The model stored in snapshot {:?} does not match the data built from it",
+            snapshot_path
+        );
+    }
+
+    let step_timer = SystemTime::now();
+    let data: TransitData =
+        with_base_indexes_of(&base_model, || bincode::deserialize_from(&mut reader))?;
+    info!(
+        "Data of snapshot {:?} deserialized in {} ms",
+        snapshot_path,
+        step_timer.elapsed().unwrap().as_millis()
+    );
+
+    info!(
+        "Snapshot {:?} read in {} ms",
+        snapshot_path,
+        timer.elapsed().unwrap().as_millis()
+    );
+    Ok((data, base_model))
+}
@@ -53,7 +53,7 @@ use loki::{
 use super::config;
 use crate::{
     datetime::DateTimeRepresent,
-    loki::{DataTrait, TransitData},
+    loki::{DataTrait, DataWithIters, NaiveDateTime, TransitData},
 };
 use loki::{
     places_nearby::{BadPlacesNearby, PlacesNearbyIter},
@@ -92,85 +92,74 @@ impl Solver {
     where
         Self: Sized,
     {
-        use crate::datetime::DateTimeRepresent::{Arrival, Departure};
-        use config::ComparatorType::{Basic, Loads};
-
         if let Some(filters) = has_filters {
             self.fill_allowed_stops_and_vehicles(model, &filters);
 
             let data = TransitDataFiltered::new(data, &self.filter_memory);
+            solve_journeys_request_on_data(
+                &mut self.engine,
+                model,
+                &data,
+                request_input,
+                comparator_type,
+                datetime_represent,
+            )
+        } else {
+            solve_journeys_request_on_data(
+                &mut self.engine,
+                model,
+                data,
+                request_input,
+                comparator_type,
+                datetime_represent,
+            )
+        }
+    }
 
-            let responses = match (datetime_represent, comparator_type) {
-                (Arrival, Loads) => {
-                    let request = request::arrive_before::loads_comparator::Request::new(
-                        model,
-                        &data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, &data)
-                }
-                (Departure, Loads) => {
-                    let request = request::depart_after::loads_comparator::Request::new(
-                        model,
-                        &data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, &data)
-                }
-                (Arrival, Basic) => {
-                    let request = request::arrive_before::basic_comparator::Request::new(
-                        model,
-                        &data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, &data)
-                }
-                (Departure, Basic) => {
-                    let request = request::depart_after::basic_comparator::Request::new(
-                        model,
-                        &data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, &data)
-                }
-            };
-            Ok(responses)
+    /// Solve the journey request once for each of `datetimes`.
+    ///
+    /// Filters are computed once, and the engine is reused for all datetimes.
+    /// A journey found for several datetimes (same vehicles, boarded and debarked at the same stops)
+    /// is returned only once, along with the first datetime it was found for.
+    /// Journeys are sorted by departure datetime, then by arrival datetime.
+    ///
+    /// Fails only if the request could not be solved for any of the datetimes.
+    pub fn solve_journey_request_for_datetimes(
+        &mut self,
+        data: &TransitData,
+        model: &ModelRefs<'_>,
+        request_input: &RequestInput,
+        datetimes: &[NaiveDateTime],
+        has_filters: Option<Filters>,
+        comparator_type: &config::ComparatorType,
+        datetime_represent: &DateTimeRepresent,
+    ) -> Result<Vec<(NaiveDateTime, response::Response)>, BadRequest>
+    where
+        Self: Sized,
+    {
+        if let Some(filters) = has_filters {
+            self.fill_allowed_stops_and_vehicles(model, &filters);
+
+            let data = TransitDataFiltered::new(data, &self.filter_memory);
+            solve_journeys_request_for_datetimes(
+                &mut self.engine,
+                model,
+                &data,
+                request_input,
+                datetimes,
+                comparator_type,
+                datetime_represent,
+            )
         } else {
-            let responses = match (datetime_represent, comparator_type) {
-                (Arrival, Loads) => {
-                    let request = request::arrive_before::loads_comparator::Request::new(
-                        model,
-                        data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, data)
-                }
-                (Departure, Loads) => {
-                    let request = request::depart_after::loads_comparator::Request::new(
-                        model,
-                        data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, data)
-                }
-                (Arrival, Basic) => {
-                    let request = request::arrive_before::basic_comparator::Request::new(
-                        model,
-                        data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, data)
-                }
-                (Departure, Basic) => {
-                    let request = request::depart_after::basic_comparator::Request::new(
-                        model,
-                        data,
-                        request_input,
-                    )?;
-                    solve_journeys_request_inner(&mut self.engine, &request, data)
-                }
-            };
-            Ok(responses)
+            solve_journeys_request_for_datetimes(
+                &mut self.engine,
+                model,
+                data,
+                request_input,
+                datetimes,
+                comparator_type,
+                datetime_represent,
+            )
         }
     }
 
@@ -204,6 +193,118 @@ impl Solver {
     }
 }
 
+fn solve_journeys_request_for_datetimes<Data>(
+    engine: &mut MultiCriteriaRaptor<RequestTypes>,
+    model: &ModelRefs<'_>,
+    data: &Data,
+    request_input: &RequestInput,
+    datetimes: &[NaiveDateTime],
+    comparator_type: &config::ComparatorType,
+    datetime_represent: &DateTimeRepresent,
+) -> Result<Vec<(NaiveDateTime, response::Response)>, BadRequest>
+where
+    Data: DataWithIters<
+        Position = generic_request::Position,
+        Mission = generic_request::Mission,
+        Stop = generic_request::Stop,
+        Trip = generic_request::Trip,
+        Transfer = generic_request::Transfer,
+    >,
+{
+    let mut request_input = request_input.clone();
+    let mut journeys: Vec<(NaiveDateTime, response::Response)> = Vec::new();
+    let mut first_error = None;
+    let mut nb_of_solved_datetimes = 0;
+    for datetime in datetimes {
+        request_input.datetime = *datetime;
+        let result = solve_journeys_request_on_data(
+            engine,
+            model,
+            data,
+            &request_input,
+            comparator_type,
+            datetime_represent,
+        );
+        match result {
+            Ok(responses) => {
+                nb_of_solved_datetimes += 1;
+                for response in responses {
+                    let is_duplicate = journeys
+                        .iter()
+                        .any(|(_, journey)| journey.has_same_vehicle_sections(&response));
+                    if !is_duplicate {
+                        journeys.push((*datetime, response));
+                    }
+                }
+            }
+            Err(err) => {
+                debug!(
+                    "Could not solve request for datetime {}. {:?}",
+                    datetime, err
+                );
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    if let (0, Some(err)) = (nb_of_solved_datetimes, first_error) {
+        return Err(err);
+    }
+
+    journeys.sort_by_key(|(_, journey)| {
+        (
+            journey.first_vehicle_board_datetime(),
+            journey.last_vehicle_debark_datetime(),
+        )
+    });
+    Ok(journeys)
+}
+
+fn solve_journeys_request_on_data<Data>(
+    engine: &mut MultiCriteriaRaptor<RequestTypes>,
+    model: &ModelRefs<'_>,
+    data: &Data,
+    request_input: &RequestInput,
+    comparator_type: &config::ComparatorType,
+    datetime_represent: &DateTimeRepresent,
+) -> Result<Vec<response::Response>, BadRequest>
+where
+    Data: DataWithIters<
+        Position = generic_request::Position,
+        Mission = generic_request::Mission,
+        Stop = generic_request::Stop,
+        Trip = generic_request::Trip,
+        Transfer = generic_request::Transfer,
+    >,
+{
+    use crate::datetime::DateTimeRepresent::{Arrival, Departure};
+    use config::ComparatorType::{Basic, Loads};
+
+    let responses = match (datetime_represent, comparator_type) {
+        (Arrival, Loads) => {
+            let request =
+                request::arrive_before::loads_comparator::Request::new(model, data, request_input)?;
+            solve_journeys_request_inner(engine, &request, data)
+        }
+        (Departure, Loads) => {
+            let request =
+                request::depart_after::loads_comparator::Request::new(model, data, request_input)?;
+            solve_journeys_request_inner(engine, &request, data)
+        }
+        (Arrival, Basic) => {
+            let request =
+                request::arrive_before::basic_comparator::Request::new(model, data, request_input)?;
+            solve_journeys_request_inner(engine, &request, data)
+        }
+        (Departure, Basic) => {
+            let request =
+                request::depart_after::basic_comparator::Request::new(model, data, request_input)?;
+            solve_journeys_request_inner(engine, &request, data)
+        }
+    };
+    Ok(responses)
+}
+
 fn solve_journeys_request_inner<'data, 'model, Data, Request>(
     engine: &mut MultiCriteriaRaptor<RequestTypes>,
     request: &Request,
@@ -0,0 +1,321 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::config::TransfersParams;
+use anyhow::{bail, format_err, Context, Error};
+use loki::{
+    models::pathways::compute_pathway_durations,
+    tracing::{info, warn},
+    transit_model::{
+        model::Model,
+        objects::Transfer,
+        transfers::{generates_transfers, NeedTransfer},
+    },
+};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    path::Path,
+};
+
+/// Rules that override the transfers of a model,
+/// read from the csv file at `TransfersParams::rules_path`.
+#[derive(Debug, Default, Clone)]
+pub struct TransferRules {
+    // extra waiting time, in seconds, of the generated transfers towards each stop area
+    extra_waiting_times: HashMap<String, u32>,
+    // (from_stop_id, to_stop_id) of the transfers to remove
+    forbidden: HashSet<(String, String)>,
+    // duration, in seconds, of the transfers between (from_stop_id, to_stop_id)
+    forced: HashMap<(String, String), u32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum RuleType {
+    ExtraWaitingTime,
+    Forbidden,
+    Forced,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleRecord {
+    rule: RuleType,
+    stop_area_id: Option<String>,
+    from_stop_id: Option<String>,
+    to_stop_id: Option<String>,
+    duration: Option<u32>,
+}
+
+impl TransferRules {
+    /// Reads the rules file given in `params`, if any.
+    pub fn new(params: &TransfersParams) -> Result<Self, Error> {
+        match &params.rules_path {
+            Some(rules_path) => Self::read(rules_path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn read(rules_path: &Path) -> Result<Self, Error> {
+        let file = std::fs::File::open(rules_path)
+            .with_context(|| format!("Could not open transfer rules file {:?}", rules_path))?;
+        Self::from_reader(file)
+            .with_context(|| format!("Could not read transfer rules file {:?}", rules_path))
+    }
+
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Self, Error> {
+        let mut rules = Self::default();
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        for record in reader.deserialize() {
+            let record: RuleRecord = record?;
+            rules.add(record)?;
+        }
+        Ok(rules)
+    }
+
+    fn add(&mut self, record: RuleRecord) -> Result<(), Error> {
+        match record.rule {
+            RuleType::ExtraWaitingTime => {
+                let stop_area_id = non_empty(record.stop_area_id, "stop_area_id", record.rule)?;
+                let duration = record
+                    .duration
+                    .ok_or_else(|| format_err!("Missing duration for rule {:?}", record.rule))?;
+                self.extra_waiting_times.insert(stop_area_id, duration);
+            }
+            RuleType::Forbidden => {
+                let from_stop_id = non_empty(record.from_stop_id, "from_stop_id", record.rule)?;
+                let to_stop_id = non_empty(record.to_stop_id, "to_stop_id", record.rule)?;
+                self.forbidden.insert((from_stop_id, to_stop_id));
+            }
+            RuleType::Forced => {
+                let from_stop_id = non_empty(record.from_stop_id, "from_stop_id", record.rule)?;
+                let to_stop_id = non_empty(record.to_stop_id, "to_stop_id", record.rule)?;
+                let duration = record
+                    .duration
+                    .ok_or_else(|| format_err!("Missing duration for rule {:?}", record.rule))?;
+                self.forced.insert((from_stop_id, to_stop_id), duration);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn non_empty(value: Option<String>, column: &str, rule: RuleType) -> Result<String, Error> {
+    match value {
+        Some(value) if !value.is_empty() => Ok(value),
+        _ => bail!("Missing {} for rule {:?}", column, rule),
+    }
+}
+
+/// Adds transfers between the nearby stop points of `model`, according to `params`,
+/// and between the stop points linked by the pathways of a station.
+///
+/// The transfers already in `model` are kept.
+/// When its stop points are linked by pathways, a generated transfer takes the duration
+/// of the shortest walk through these pathways instead of the walk in straight line.
+/// The extra waiting times of `rules` are added to the generated transfers,
+/// while the other rules are applied by [`apply_rules`].
+pub fn generate_transfers(
+    model: Model,
+    params: &TransfersParams,
+    rules: &TransferRules,
+    need_transfer: Option<NeedTransfer>,
+) -> Result<Model, Error> {
+    if params.walking_speed.is_nan() || params.walking_speed <= 0.0 {
+        bail!(
+            "The walking speed of generated transfers must be positive, got {}",
+            params.walking_speed
+        );
+    }
+    let waiting_time = u32::try_from(params.waiting_time.total_seconds())?;
+    let existing_transfers: HashSet<(String, String)> = model
+        .transfers
+        .values()
+        .map(|transfer| (transfer.from_stop_id.clone(), transfer.to_stop_id.clone()))
+        .collect();
+
+    let model = generates_transfers(
+        model,
+        params.max_distance,
+        params.walking_speed,
+        waiting_time,
+        need_transfer,
+    )?;
+
+    let nb_generated = model.transfers.len() - existing_transfers.len();
+    info!(
+        "{} transfers read from the input data, {} transfers generated",
+        existing_transfers.len(),
+        nb_generated
+    );
+
+    // duration, in seconds, of the walk through pathways between (from_stop_id, to_stop_id)
+    let mut pathway_seconds = HashMap::new();
+    for ((from_stop, to_stop), pathway_durations) in compute_pathway_durations(&model) {
+        let pair = (
+            model.stop_points[from_stop].id.clone(),
+            model.stop_points[to_stop].id.clone(),
+        );
+        let seconds = u32::try_from(pathway_durations.duration.total_seconds())?;
+        pathway_seconds.insert(pair, seconds);
+    }
+
+    if pathway_seconds.is_empty() && (rules.extra_waiting_times.is_empty() || nb_generated == 0) {
+        return Ok(model);
+    }
+    let mut collections = model.into_collections();
+    let stop_points = &collections.stop_points;
+    let extra_waiting_time = |to_stop_id: &str| {
+        stop_points
+            .get(to_stop_id)
+            .and_then(|stop_point| rules.extra_waiting_times.get(&stop_point.stop_area_id))
+            .copied()
+            .unwrap_or(0)
+    };
+    for transfer in collections.transfers.values_mut() {
+        let pair = (transfer.from_stop_id.clone(), transfer.to_stop_id.clone());
+        let walking_seconds = pathway_seconds.remove(&pair);
+        let is_generated = !existing_transfers.contains(&pair);
+        if !is_generated {
+            continue;
+        }
+        if let Some(walking_seconds) = walking_seconds {
+            transfer.min_transfer_time = Some(walking_seconds);
+            transfer.real_min_transfer_time = Some(walking_seconds + waiting_time);
+        }
+        let extra_waiting_time = extra_waiting_time(&transfer.to_stop_id);
+        transfer.real_min_transfer_time = transfer
+            .real_min_transfer_time
+            .map(|duration| duration + extra_waiting_time);
+    }
+    // the stop points linked by pathways but too far away from each other
+    // to get a transfer generated
+    let mut pathway_seconds: Vec<_> = pathway_seconds.into_iter().collect();
+    pathway_seconds.sort();
+    let nb_through_pathways = pathway_seconds.len();
+    for ((from_stop_id, to_stop_id), walking_seconds) in pathway_seconds {
+        let real_min_transfer_time =
+            walking_seconds + waiting_time + extra_waiting_time(&to_stop_id);
+        collections.transfers.push(Transfer {
+            from_stop_id,
+            to_stop_id,
+            min_transfer_time: Some(walking_seconds),
+            real_min_transfer_time: Some(real_min_transfer_time),
+            equipment_id: None,
+        });
+    }
+    if nb_through_pathways > 0 {
+        info!(
+            "{} transfers generated through pathways",
+            nb_through_pathways
+        );
+    }
+    Model::new(collections)
+}
+
+/// Removes the forbidden transfers of `rules` from `model`,
+/// and adds or updates its forced transfers.
+///
+/// Rules on unknown stops are ignored.
+pub fn apply_rules(model: Model, rules: &TransferRules) -> Result<Model, Error> {
+    for stop_area_id in rules.extra_waiting_times.keys() {
+        if !model.stop_areas.contains_id(stop_area_id) {
+            warn!(
+                "Transfer rules : unknown stop area {}, its extra waiting time is ignored.",
+                stop_area_id
+            );
+        }
+    }
+    if rules.forbidden.is_empty() && rules.forced.is_empty() {
+        return Ok(model);
+    }
+    let is_known = |(from_stop_id, to_stop_id): &(String, String)| {
+        let is_known = model.stop_points.contains_id(from_stop_id)
+            && model.stop_points.contains_id(to_stop_id);
+        if !is_known {
+            warn!(
+                "Transfer rules : unknown stop point in the transfer from {} to {}, the rule is ignored.",
+                from_stop_id, to_stop_id
+            );
+        }
+        is_known
+    };
+    let forbidden: HashSet<(String, String)> = rules
+        .forbidden
+        .iter()
+        .filter(|pair| is_known(pair))
+        .cloned()
+        .collect();
+    let mut forced: HashMap<(String, String), u32> = rules
+        .forced
+        .iter()
+        .filter(|(pair, _)| is_known(pair))
+        .map(|(pair, duration)| (pair.clone(), *duration))
+        .collect();
+
+    let mut collections = model.into_collections();
+    let nb_transfers = collections.transfers.len();
+    collections.transfers.retain(|transfer| {
+        !forbidden.contains(&(transfer.from_stop_id.clone(), transfer.to_stop_id.clone()))
+    });
+    let nb_forbidden = nb_transfers - collections.transfers.len();
+
+    let nb_forced = forced.len();
+    for transfer in collections.transfers.values_mut() {
+        let pair = (transfer.from_stop_id.clone(), transfer.to_stop_id.clone());
+        if let Some(duration) = forced.remove(&pair) {
+            transfer.min_transfer_time = Some(duration);
+            transfer.real_min_transfer_time = Some(duration);
+        }
+    }
+    for ((from_stop_id, to_stop_id), duration) in forced {
+        collections.transfers.push(Transfer {
+            from_stop_id,
+            to_stop_id,
+            min_transfer_time: Some(duration),
+            real_min_transfer_time: Some(duration),
+            equipment_id: None,
+        });
+    }
+    info!(
+        "Transfer rules : {} transfers removed, {} transfers forced",
+        nb_forbidden, nb_forced
+    );
+    Model::new(collections)
+}
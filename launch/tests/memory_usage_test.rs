@@ -0,0 +1,147 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+
+use anyhow::Error;
+use loki::{
+    chrono_tz::UTC,
+    models::{
+        base_model::BaseModel,
+        real_time_model::{RealTimeModel, TripVersion},
+        VehicleJourneyIdx,
+    },
+    PositiveDuration,
+};
+use utils::{
+    disruption_builder::StopTimesBuilder,
+    model_builder::{AsDate, ModelBuilder},
+};
+
+#[test]
+fn test_memory_usage_report() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("first", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .st("C", "10:10:00");
+        })
+        .vj("second", |vj_builder| {
+            vj_builder
+                .st("A", "11:00:00")
+                .st("B", "11:05:00")
+                .st("C", "11:10:00");
+        })
+        .build();
+    let base_model =
+        BaseModel::from_transit_model(model, loki::LoadsData::empty(), PositiveDuration::zero())
+            .unwrap();
+    let mut real_time_model = RealTimeModel::new();
+    let mut data = launch::read::build_transit_data(&base_model);
+
+    let base_model_usage = base_model.memory_usage();
+    assert!(
+        base_model_usage
+            .component("vehicle_journeys")
+            .unwrap()
+            .bytes
+            > 0
+    );
+    assert!(base_model_usage.component("stop_times").unwrap().bytes > 0);
+    assert!(base_model_usage.component("stop_points").unwrap().bytes > 0);
+    let component_bytes: usize = base_model_usage.components.iter().map(|c| c.bytes).sum();
+    assert_eq!(base_model_usage.bytes, component_bytes);
+
+    let data_usage = data.memory_usage();
+    let timetables_bytes = data_usage.component("utc_timetables").unwrap().bytes;
+    assert!(timetables_bytes > 0);
+    assert!(data_usage.component("stops").unwrap().bytes > 0);
+    assert_eq!(data_usage.component("real_time_stops").unwrap().bytes, 0);
+
+    let real_time_usage = real_time_model.memory_usage();
+    assert_eq!(real_time_usage.bytes, 0);
+
+    // "first" is delayed and now stops at a new stop point "D"
+    let date = "2020-01-01".as_date();
+    let stop_times = StopTimesBuilder::new()
+        .st("A", "10:20:00")
+        .st("B", "10:25:00")
+        .st("D", "10:30:00")
+        .finalize(&mut real_time_model, &base_model);
+    let base_vj_idx = base_model.vehicle_journey_idx("first").unwrap();
+    real_time_model.set_base_trip_version(
+        base_vj_idx,
+        &date,
+        TripVersion::Present(stop_times.clone()),
+    );
+    let modify_result = data.modify_real_time_vehicle(
+        stop_times.iter().map(|stop_time| stop_time.stop.clone()),
+        stop_times.iter().map(|stop_time| stop_time.flow_direction),
+        stop_times.iter().map(|stop_time| stop_time.board_time),
+        stop_times.iter().map(|stop_time| stop_time.debark_time),
+        base_model.loads_data(),
+        std::iter::once(date),
+        UTC,
+        &VehicleJourneyIdx::Base(base_vj_idx),
+    );
+    assert!(modify_result.is_ok());
+
+    let real_time_usage = real_time_model.memory_usage();
+    assert!(
+        real_time_usage
+            .component("modified_base_vehicle_journeys")
+            .unwrap()
+            .bytes
+            > 0
+    );
+    assert!(real_time_usage.component("new_stop_points").unwrap().bytes > 0);
+    assert_eq!(
+        real_time_usage
+            .component("new_vehicle_journeys")
+            .unwrap()
+            .bytes,
+        0
+    );
+
+    let data_usage = data.memory_usage();
+    assert!(data_usage.component("real_time_stops").unwrap().bytes > 0);
+    assert!(data_usage.component("utc_timetables").unwrap().bytes > timetables_bytes);
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+
+use anyhow::Error;
+use launch::{
+    config::{
+        launch_params::{InputParams, LocalFileParams},
+        InputDataType, TransfersParams,
+    },
+    read::{build_transit_data, read_model},
+    solver::Solver,
+};
+use loki::{
+    models::{real_time_model::RealTimeModel, ModelRefs},
+    DataTrait, PositiveDuration,
+};
+use utils::{
+    make_request_from_config,
+    networks::{rail_model, urban_model},
+    write_ntfs, Config,
+};
+
+#[test]
+fn test_merged_inputs() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let urban_directory = working_directory.path().join("urban");
+    let rail_directory = working_directory.path().join("rail");
+    write_ntfs(&urban_model(), &urban_directory)?;
+    write_ntfs(&rail_model(), &rail_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: urban_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: vec![InputParams {
+            input_data_path: rail_directory,
+            input_data_type: InputDataType::Ntfs,
+            prefix: Some("rail".to_string()),
+        }],
+    };
+    let base_model = read_model(
+        &data_files,
+        InputDataType::Ntfs,
+        PositiveDuration::from_hms(0, 1, 0),
+        &TransfersParams::default(),
+    )?;
+
+    assert_eq!(base_model.nb_of_vehicle_journeys(), 2);
+    let model = base_model.transit_model();
+    assert!(model.vehicle_journeys.contains_id("toto"));
+    assert!(model.vehicle_journeys.contains_id("rail:tata"));
+    assert!(model.stop_points.contains_id("rail:E"));
+
+    // a transfer is generated between the nearby stop points of the two datasets
+    assert!(model
+        .transfers
+        .values()
+        .any(|transfer| transfer.from_stop_id == "C" && transfer.to_stop_id == "rail:E"));
+
+    let data = build_transit_data(&base_model);
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+    let config = Config::new("2020-01-01T09:50:00", "A", "rail:F");
+    let request_input = make_request_from_config(&config)?;
+    let mut solver = Solver::new(data.nb_of_stops(), data.nb_of_missions());
+    let responses = solver.solve_journey_request(
+        &data,
+        &model_refs,
+        &request_input,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.first_vehicle.vehicle_journey),
+        "toto"
+    );
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.connections[0].2.vehicle_journey),
+        "rail:tata"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_merged_inputs_with_same_identifiers() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let urban_directory = working_directory.path().join("urban");
+    let rail_directory = working_directory.path().join("rail");
+    write_ntfs(&urban_model(), &urban_directory)?;
+    write_ntfs(&rail_model(), &rail_directory)?;
+    // without prefix, the objects created by default in both datasets
+    // (contributor, dataset, network...) have the same identifiers
+    let data_files = LocalFileParams {
+        input_data_path: urban_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: vec![InputParams {
+            input_data_path: rail_directory,
+            input_data_type: InputDataType::Ntfs,
+            prefix: None,
+        }],
+    };
+    let base_model = read_model(
+        &data_files,
+        InputDataType::Ntfs,
+        PositiveDuration::from_hms(0, 1, 0),
+        &TransfersParams::default(),
+    );
+
+    assert!(base_model.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+use anyhow::Error;
+use launch::{
+    loki::models::{real_time_model::RealTimeModel, ModelRefs},
+    solver::Solver,
+};
+use loki::{models::base_model::BaseModel, DataTrait, PositiveDuration, TransitData};
+use utils::{
+    make_request_from_config,
+    model_builder::{AsDateTime, ModelBuilder},
+    Config,
+};
+
+#[test]
+fn test_multiple_datetimes() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("toto", |vj_builder| {
+            vj_builder
+                .route("1")
+                .st("A", "10:00:00")
+                .st("B", "10:05:00");
+        })
+        .vj("tata", |vj_builder| {
+            vj_builder
+                .route("1")
+                .st("A", "11:00:00")
+                .st("B", "11:05:00");
+        })
+        .build();
+
+    let base_model =
+        BaseModel::from_transit_model(model, loki::LoadsData::empty(), PositiveDuration::zero())
+            .unwrap();
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+
+    let data: TransitData = launch::read::build_transit_data(&base_model);
+    let mut solver = Solver::new(data.nb_of_stops(), data.nb_of_missions());
+
+    let config = Config::new("2020-01-01T09:59:00", "A", "B");
+    let request_input = make_request_from_config(&config)?;
+
+    let datetimes = [
+        "2020-01-01T10:30:00".as_datetime(),
+        "2020-01-01T09:59:00".as_datetime(),
+        // finds the same journey as 09:59
+        "2020-01-01T09:50:00".as_datetime(),
+        // outside of the calendar, so it cannot be solved
+        "2021-01-01T09:50:00".as_datetime(),
+    ];
+
+    let journeys = solver.solve_journey_request_for_datetimes(
+        &data,
+        &model_refs,
+        &request_input,
+        &datetimes,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+
+    assert_eq!(journeys.len(), 2);
+
+    let (requested_datetime, journey) = &journeys[0];
+    assert_eq!(journey.first_vj_uri(&model_refs), "toto");
+    assert_eq!(*requested_datetime, "2020-01-01T09:59:00".as_datetime());
+
+    let (requested_datetime, journey) = &journeys[1];
+    assert_eq!(journey.first_vj_uri(&model_refs), "tata");
+    assert_eq!(*requested_datetime, "2020-01-01T10:30:00".as_datetime());
+
+    // fails when no datetime can be solved
+    let result = solver.solve_journey_request_for_datetimes(
+        &data,
+        &model_refs,
+        &request_input,
+        &["2021-01-01T09:50:00".as_datetime()],
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+
+use anyhow::Error;
+use launch::{
+    config::{launch_params::LocalFileParams, InputDataType, TransfersParams},
+    read::{build_transit_data, read_model},
+    solver::Solver,
+};
+use loki::{
+    chrono::NaiveDate,
+    chrono_tz,
+    models::{base_model::BaseModel, real_time_model::RealTimeModel, ModelRefs},
+    DataTrait, PositiveDuration,
+};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use utils::{make_request_from_config, Config};
+
+const NETEX_DIRECTORY: &str = "tests/fixtures/netex";
+
+fn read_netex(input_data_path: PathBuf) -> Result<BaseModel, Error> {
+    let data_files = LocalFileParams {
+        input_data_path,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    read_model(
+        &data_files,
+        InputDataType::Netex,
+        PositiveDuration::from_hms(0, 1, 0),
+        &TransfersParams::default(),
+    )
+}
+
+#[test]
+fn test_read_netex() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let base_model = read_netex(PathBuf::from(NETEX_DIRECTORY))?;
+    let model = base_model.transit_model();
+
+    // stop places and quays
+    assert_eq!(model.stop_points.len(), 5);
+    let quay_b = model.stop_points.get("FR:Quay:B:LOC").unwrap();
+    assert_eq!(quay_b.stop_area_id, "FR:StopPlace:B:LOC");
+    assert_eq!(quay_b.platform_code.as_deref(), Some("2"));
+    // given in Lambert 93, at the origin of the projection
+    assert!((quay_b.coord.lon - 3.0).abs() < 1e-6);
+    assert!((quay_b.coord.lat - 46.5).abs() < 1e-6);
+    let quay_a = model.stop_points.get("FR:Quay:A:LOC").unwrap();
+    assert_eq!(quay_a.stop_area_id, "FR:StopPlace:A:LOC");
+    // a quay without stop place gets its own stop area
+    let quay_d = model.stop_points.get("FR:Quay:D:LOC").unwrap();
+    assert!(model.stop_areas.contains_id(&quay_d.stop_area_id));
+
+    // lines and service journeys
+    let rail_line = model.lines.get("FR:Line:RAIL:LOC").unwrap();
+    assert_eq!(rail_line.network_id, "FR:Network:N1:LOC");
+    assert_eq!(rail_line.commercial_mode_id, "Train");
+    let bus_journey = model
+        .vehicle_journeys
+        .get("FR:ServiceJourney:BUS_1:LOC")
+        .unwrap();
+    assert_eq!(bus_journey.route_id, "FR:Route:BUS_FORWARD:LOC");
+    assert_eq!(bus_journey.company_id, "FR:Operator:O1:LOC");
+    assert_eq!(bus_journey.stop_times.len(), 3);
+    assert_eq!(bus_journey.stop_times[0].drop_off_type, 1);
+    assert_eq!(bus_journey.stop_times[2].pickup_type, 1);
+    let rail_journey = model
+        .vehicle_journeys
+        .get("FR:ServiceJourney:RAIL_1:LOC")
+        .unwrap();
+    assert_eq!(rail_journey.physical_mode_id, "Train");
+    assert_eq!(rail_journey.stop_times[1].arrival_time, "24:10:00".parse()?);
+
+    // day types
+    let date = |day| NaiveDate::from_ymd_opt(2020, 1, day).unwrap();
+    let rail_calendar = model.calendars.get(&rail_journey.service_id).unwrap();
+    assert_eq!(
+        rail_calendar.dates.iter().copied().collect::<Vec<_>>(),
+        vec![date(1), date(3), date(6), date(7)]
+    );
+    let bus_calendar = model.calendars.get(&bus_journey.service_id).unwrap();
+    assert_eq!(
+        bus_calendar.dates.iter().copied().collect::<Vec<_>>(),
+        vec![date(1), date(3), date(4), date(6), date(7)]
+    );
+
+    // interchanges
+    let transfer = model
+        .transfers
+        .values()
+        .find(|transfer| {
+            transfer.from_stop_id == "FR:Quay:C:LOC" && transfer.to_stop_id == "FR:Quay:D:LOC"
+        })
+        .unwrap();
+    assert_eq!(transfer.min_transfer_time, Some(300));
+
+    Ok(())
+}
+
+#[test]
+fn test_journey_with_netex_interchange() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let base_model = read_netex(PathBuf::from(NETEX_DIRECTORY))?;
+    let data = build_transit_data(&base_model);
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+    let config = Config::new_timezoned(
+        "2020-01-01T09:50:00",
+        chrono_tz::Europe::Paris,
+        "FR:Quay:A:LOC",
+        "FR:Quay:E:LOC",
+    );
+    let request_input = make_request_from_config(&config)?;
+    let mut solver = Solver::new(data.nb_of_stops(), data.nb_of_missions());
+    let responses = solver.solve_journey_request(
+        &data,
+        &model_refs,
+        &request_input,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.first_vehicle.vehicle_journey),
+        "FR:ServiceJourney:BUS_1:LOC"
+    );
+    assert_eq!(journey.connections.len(), 1);
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.connections[0].2.vehicle_journey),
+        "FR:ServiceJourney:RAIL_1:LOC"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_read_netex_archive() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let archive_path = working_directory.path().join("netex.zip");
+    let mut archive = zip::ZipWriter::new(std::fs::File::create(&archive_path)?);
+    for file_name in [
+        "arrets.xml",
+        "lignes.xml",
+        "calendriers.xml",
+        "offres/offre_BUS.xml",
+        "offres/offre_RAIL.xml",
+    ] {
+        archive.start_file(file_name, zip::write::FileOptions::default())?;
+        archive.write_all(&std::fs::read(Path::new(NETEX_DIRECTORY).join(file_name))?)?;
+    }
+    archive.finish()?;
+
+    let base_model = read_netex(archive_path)?;
+
+    assert_eq!(base_model.nb_of_vehicle_journeys(), 2);
+    assert_eq!(base_model.transit_model().stop_points.len(), 5);
+
+    Ok(())
+}
@@ -0,0 +1,163 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+
+use anyhow::Error;
+use loki::{
+    models::{base_model::BaseModel, real_time_model::RealTimeModel, ModelRefs},
+    transit_data::data_interface::DataIters,
+    DataTrait, PositiveDuration, RealTimeLevel, TransitData,
+};
+use std::collections::BTreeMap;
+use utils::model_builder::ModelBuilder;
+
+// for each mission id, the names of its stops, and the name and day of its trips
+type MissionsDescription = BTreeMap<usize, (Vec<String>, Vec<(String, String)>)>;
+
+fn describe_missions(data: &TransitData, model: &ModelRefs) -> MissionsDescription {
+    let mut result = BTreeMap::new();
+    for stop_point_idx in model.base.stop_points() {
+        let stop_point_idx = loki::models::StopPointIdx::Base(stop_point_idx);
+        let stop = match data.stop_point_idx_to_stop(&stop_point_idx) {
+            Some(stop) => stop,
+            None => continue,
+        };
+        for (mission, _) in data.missions_at(stop) {
+            let mission_id = data.mission_id(&mission);
+            if result.contains_key(&mission_id) {
+                continue;
+            }
+            let mut stops = Vec::new();
+            let mut position = Some(data.first_on_mission(&mission));
+            while let Some(current) = position {
+                let stop = data.stop_of(&current, &mission);
+                let stop_point_idx = data.stop_point_idx(&stop);
+                stops.push(model.stop_point_name(&stop_point_idx).to_string());
+                position = data.next_on_mission(&current, &mission);
+            }
+            let trips = data
+                .trips_of(&mission, RealTimeLevel::Base)
+                .map(|trip| {
+                    let vehicle_journey_idx = data.vehicle_journey_idx(&trip);
+                    (
+                        model.vehicle_journey_name(&vehicle_journey_idx).to_string(),
+                        data.day_of(&trip).to_string(),
+                    )
+                })
+                .collect();
+            result.insert(mission_id, (stops, trips));
+        }
+    }
+    result
+}
+
+#[test]
+fn test_build_does_not_depend_on_nb_of_threads() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-03")
+        .vj("first", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .st("C", "10:10:00");
+        })
+        .vj("second", |vj_builder| {
+            vj_builder
+                .st("A", "10:10:00")
+                .st("B", "10:15:00")
+                .st("C", "10:20:00");
+        })
+        // overtakes "second", so it cannot be in the same timetable
+        .vj("overtaking", |vj_builder| {
+            vj_builder
+                .st("A", "10:11:00")
+                .st("B", "10:13:00")
+                .st("C", "10:15:00");
+        })
+        // overtakes "first" and "second"
+        .vj("express", |vj_builder| {
+            vj_builder
+                .st("A", "09:59:00")
+                .st("B", "10:00:00")
+                .st("C", "10:01:00");
+        })
+        .vj("other_route", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .st("D", "10:10:00");
+        })
+        .vj("other_route_later", |vj_builder| {
+            vj_builder
+                .st("A", "11:00:00")
+                .st("B", "11:05:00")
+                .st("D", "11:10:00");
+        })
+        .vj("local_zone", |vj_builder| {
+            vj_builder
+                .st_detailed("A", "12:00:00", "12:00:00", 0u8, 0u8, Some(1u16))
+                .st_detailed("B", "12:05:00", "12:05:00", 0u8, 0u8, Some(1u16))
+                .st_detailed("C", "12:10:00", "12:10:00", 0u8, 0u8, Some(2u16));
+        })
+        .build();
+
+    let base_model =
+        BaseModel::from_transit_model(model, loki::LoadsData::empty(), PositiveDuration::zero())
+            .unwrap();
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+
+    let sequential_data = TransitData::new_with_nb_of_threads(&base_model, 1);
+    let sequential_missions = describe_missions(&sequential_data, &model_refs);
+
+    // "first", "second" and "other_route" each fill a timetable with the vehicles
+    // that do not overtake them, "overtaking" and "express" need a timetable of their own,
+    // and "local_zone" is inserted once for each of its local zones
+    assert_eq!(sequential_data.nb_of_missions(), 5);
+    assert_eq!(sequential_missions.len(), 5);
+    assert_eq!(sequential_data.nb_of_trips(), 8);
+
+    for nb_of_threads in [2, 4, 8] {
+        let data = TransitData::new_with_nb_of_threads(&base_model, nb_of_threads);
+        assert_eq!(data.nb_of_missions(), sequential_data.nb_of_missions());
+        assert_eq!(data.nb_of_trips(), sequential_data.nb_of_trips());
+        assert_eq!(describe_missions(&data, &model_refs), sequential_missions);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,440 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+use anyhow::Error;
+use launch::{
+    config::{launch_params::LocalFileParams, InputDataType, TransfersParams},
+    loki::models::{real_time_model::RealTimeModel, ModelRefs},
+    read::read_model,
+    snapshot::{read_snapshot, write_snapshot},
+};
+use loki::{
+    models::{base_model::BaseModel, pathways::compute_pathway_durations},
+    transit_model::{
+        model::Model,
+        objects::{
+            Availability::Available, Pathway, PathwayMode, StopLocation, StopType, Transfer,
+        },
+    },
+    LoadsData, PositiveDuration,
+};
+use utils::{build_and_solve, model_builder::ModelBuilder, write_ntfs, Config};
+
+fn pathway(
+    id: &str,
+    from: (&str, StopType),
+    to: (&str, StopType),
+    pathway_mode: PathwayMode,
+    traversal_time: u32,
+) -> Pathway {
+    Pathway {
+        id: id.to_string(),
+        from_stop_id: from.0.to_string(),
+        from_stop_type: from.1,
+        to_stop_id: to.0.to_string(),
+        to_stop_type: to.1,
+        pathway_mode,
+        is_bidirectional: true,
+        traversal_time: Some(traversal_time),
+        ..Default::default()
+    }
+}
+
+// The platforms "B" and "D" of a station are linked by a corridor to a hall,
+// and the hall is linked to "D" by stairs, and by a slower elevator.
+// The transfer from "B" to "D" has no duration.
+fn station_model() -> Result<Model, Error> {
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("toto", |vj_builder| {
+            vj_builder.st("A", "10:00:00").st("B", "10:05:00");
+        })
+        .vj("tata", |vj_builder| {
+            vj_builder.st("D", "10:06:00").st("E", "10:10:00");
+        })
+        .build();
+    add_station_pathways(model)
+}
+
+// The same station, where every stop point and vehicle journey is wheelchair accessible,
+// and where "titi" leaves "D" two minutes after "tata".
+fn accessible_station_model() -> Result<Model, Error> {
+    let mut model_builder = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .equipment("EQW", |e| e.wheelchair_boarding = Available);
+    for stop in ["A", "B", "D", "E"] {
+        model_builder = model_builder
+            .stop_area(&format!("sa:{}", stop), |_| {})
+            .stop_point(stop, |sp| sp.equipment_id = Some("EQW".to_string()));
+    }
+    let model = model_builder
+        .vj("toto", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .add_property("wheelchair_accessible", "1");
+        })
+        .vj("tata", |vj_builder| {
+            vj_builder
+                .st("D", "10:06:00")
+                .st("E", "10:10:00")
+                .add_property("wheelchair_accessible", "1");
+        })
+        .vj("titi", |vj_builder| {
+            vj_builder
+                .st("D", "10:08:00")
+                .st("E", "10:12:00")
+                .add_property("wheelchair_accessible", "1");
+        })
+        .build();
+    add_station_pathways(model)
+}
+
+fn add_station_pathways(model: Model) -> Result<Model, Error> {
+    let mut collections = model.into_collections();
+    collections.stop_locations.push(StopLocation {
+        id: "hall".to_string(),
+        name: "hall".to_string(),
+        parent_id: Some("sa:B".to_string()),
+        stop_type: StopType::GenericNode,
+        ..Default::default()
+    })?;
+    let pathways = [
+        pathway(
+            "corridor",
+            ("B", StopType::Point),
+            ("hall", StopType::GenericNode),
+            PathwayMode::Walkway,
+            30,
+        ),
+        pathway(
+            "stairs",
+            ("hall", StopType::GenericNode),
+            ("D", StopType::Point),
+            PathwayMode::Stairs,
+            20,
+        ),
+        pathway(
+            "elevator",
+            ("hall", StopType::GenericNode),
+            ("D", StopType::Point),
+            PathwayMode::Elevator,
+            60,
+        ),
+    ];
+    for pathway in pathways {
+        collections.pathways.push(pathway)?;
+    }
+    collections.transfers.push(Transfer {
+        from_stop_id: "B".to_string(),
+        to_stop_id: "D".to_string(),
+        min_transfer_time: None,
+        real_min_transfer_time: None,
+        equipment_id: None,
+    });
+    Model::new(collections)
+}
+
+#[test]
+fn test_transfer_durations_from_pathways() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let default_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+    let base_model = BaseModel::new(
+        station_model()?,
+        LoadsData::empty(),
+        default_transfer_duration,
+    )
+    .unwrap();
+
+    let transfer_idx = base_model
+        .transfers()
+        .find(|transfer_idx| {
+            base_model.from_stop_name(*transfer_idx) == "B"
+                && base_model.to_stop_name(*transfer_idx) == "D"
+        })
+        .expect("transfer from B to D");
+
+    // through the corridor and the stairs
+    assert_eq!(
+        base_model.transfer_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 50)
+    );
+    assert_eq!(
+        base_model.transfer_walking_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 50)
+    );
+    // through the corridor and the elevator
+    assert_eq!(
+        base_model.transfer_wheelchair_duration(transfer_idx),
+        Some(PositiveDuration::from_hms(0, 1, 30))
+    );
+
+    // the walk through pathways takes less than the default transfer duration,
+    // so the connection from "toto" at "B" to "tata" at "D" is possible
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+    let config = Config::new("2020-01-01T09:59:00", "A", "E");
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(journey.nb_of_transfers(), 1);
+    assert_eq!(journey.first_vj_uri(&model_refs), "toto");
+
+    Ok(())
+}
+
+#[test]
+fn test_transfer_without_pathways() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let mut collections = station_model()?.into_collections();
+    collections
+        .pathways
+        .retain(|pathway| pathway.id == "corridor");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+    let base_model = BaseModel::new(
+        Model::new(collections)?,
+        LoadsData::empty(),
+        default_transfer_duration,
+    )
+    .unwrap();
+
+    let transfer_idx = base_model
+        .transfers()
+        .find(|transfer_idx| {
+            base_model.from_stop_name(*transfer_idx) == "B"
+                && base_model.to_stop_name(*transfer_idx) == "D"
+        })
+        .expect("transfer from B to D");
+
+    // "B" and "D" are not linked by pathways anymore
+    assert_eq!(
+        base_model.transfer_duration(transfer_idx),
+        default_transfer_duration
+    );
+    assert_eq!(
+        base_model.transfer_wheelchair_duration(transfer_idx),
+        Some(default_transfer_duration)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_wheelchair_transfer_through_pathways() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let default_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+    let base_model = BaseModel::new(
+        accessible_station_model()?,
+        LoadsData::empty(),
+        default_transfer_duration,
+    )
+    .unwrap();
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+
+    let config = Config::new("2020-01-01T09:59:00", "A", "E");
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.connections[0].2.vehicle_journey),
+        "tata"
+    );
+
+    // the walk through the corridor and the elevator takes 90 seconds,
+    // so "tata" cannot be caught in a wheelchair, but "titi" can
+    let config = Config {
+        wheelchair_accessible: true,
+        ..config
+    };
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(journey.connections.len(), 1);
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.connections[0].2.vehicle_journey),
+        "titi"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_transfer_not_wheelchair_accessible() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    // without the elevator, "D" can only be reached from "B" by the stairs
+    let mut collections = accessible_station_model()?.into_collections();
+    collections
+        .pathways
+        .retain(|pathway| pathway.id != "elevator");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+    let base_model = BaseModel::new(
+        Model::new(collections)?,
+        LoadsData::empty(),
+        default_transfer_duration,
+    )
+    .unwrap();
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+
+    let config = Config::new("2020-01-01T09:59:00", "A", "E");
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 1);
+
+    // so the transfer is not usable in a wheelchair
+    let config = Config {
+        wheelchair_accessible: true,
+        ..config
+    };
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_pathway_durations_in_snapshot() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let ntfs_directory = working_directory.path().join("ntfs");
+    write_ntfs(&station_model()?, &ntfs_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: ntfs_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let snapshot_path = working_directory.path().join("data.snapshot");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+
+    write_snapshot(
+        &data_files,
+        InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+        &snapshot_path,
+    )?;
+    let (_, base_model) = read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+    )?;
+
+    // the durations stored in the snapshot are the ones computed from the model
+    let expected_durations = compute_pathway_durations(base_model.transit_model());
+    assert_eq!(base_model.pathway_durations(), &expected_durations);
+    assert!(!expected_durations.is_empty());
+
+    Ok(())
+}
+
+// In this gtfs, the platforms "B" and "C" of a station are close to each other
+// and linked by a walkway. The platform "D" is far away, and linked to "B" by a corridor
+// to a hall, and then by stairs.
+// The gtfs has no transfers.txt, so all transfers are generated.
+#[test]
+fn test_generated_transfers_through_pathways() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let data_files = LocalFileParams {
+        input_data_path: "tests/fixtures/pathways_gtfs".into(),
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let transfers_params = TransfersParams::default();
+    let waiting_time = transfers_params.waiting_time;
+    let base_model = read_model(
+        &data_files,
+        InputDataType::Gtfs,
+        PositiveDuration::from_hms(0, 5, 0),
+        &transfers_params,
+    )?;
+    let find_transfer = |from_stop_id: &str, to_stop_id: &str| {
+        base_model.transfers().find(|transfer_idx| {
+            base_model.from_stop_name(*transfer_idx) == from_stop_id
+                && base_model.to_stop_name(*transfer_idx) == to_stop_id
+        })
+    };
+
+    // the walkway is used instead of the walk in straight line
+    let transfer_idx = find_transfer("B", "C").expect("transfer from B to C");
+    assert_eq!(
+        base_model.transfer_walking_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 40)
+    );
+    assert_eq!(
+        base_model.transfer_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 40) + waiting_time
+    );
+
+    // "B" and "D" are too far away from each other to get a transfer generated,
+    // but they are linked through the pathways of the station
+    let transfer_idx = find_transfer("B", "D").expect("transfer from B to D");
+    assert_eq!(
+        base_model.transfer_walking_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 50)
+    );
+    assert_eq!(
+        base_model.transfer_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 0, 50) + waiting_time
+    );
+    // only through the stairs
+    assert_eq!(base_model.transfer_wheelchair_duration(transfer_idx), None);
+    let transfer_idx = find_transfer("D", "C").expect("transfer from D to C");
+    assert_eq!(
+        base_model.transfer_walking_duration(transfer_idx),
+        PositiveDuration::from_hms(0, 1, 30)
+    );
+
+    // so the connection from "toto" at "B" to "tata" at "D" is possible
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+    let config = Config::new("2020-01-01T09:59:00", "A", "E");
+    let responses = build_and_solve(&model_refs, &config)?;
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(journey.nb_of_transfers(), 1);
+    assert_eq!(journey.first_vj_uri(&model_refs), "toto");
+
+    Ok(())
+}
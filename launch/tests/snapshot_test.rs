@@ -0,0 +1,261 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+
+use anyhow::Error;
+use launch::{
+    config::{launch_params::LocalFileParams, InputDataType, TransfersParams},
+    snapshot::{read_snapshot, write_snapshot},
+    solver::Solver,
+};
+use loki::{
+    models::{base_model::BaseModel, real_time_model::RealTimeModel, ModelRefs},
+    DataTrait, PositiveDuration,
+};
+use std::path::Path;
+use utils::{make_request_from_config, networks::urban_and_rail_model_builder, write_ntfs, Config};
+
+// the urban and rail networks, with a transfer from "C" to "E"
+fn write_urban_and_rail_ntfs(directory: &Path) -> Result<(), Error> {
+    let model = urban_and_rail_model_builder()
+        .add_transfer("C", "E", "00:02:00")
+        .build();
+    write_ntfs(&model, directory)
+}
+
+#[test]
+fn test_read_snapshot() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let ntfs_directory = working_directory.path().join("ntfs");
+    write_urban_and_rail_ntfs(&ntfs_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: ntfs_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let snapshot_path = working_directory.path().join("data.snapshot");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 1, 0);
+
+    write_snapshot(
+        &data_files,
+        InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+        &snapshot_path,
+    )?;
+    let (data, base_model): (_, BaseModel) = read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+    )?;
+
+    assert_eq!(base_model.nb_of_vehicle_journeys(), 2);
+    assert_eq!(data.nb_of_missions(), 2);
+    assert_eq!(data.nb_of_trips(), 2);
+
+    // the data read from the snapshot should answer requests
+    // as the data built from the model
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+    let config = Config::new("2020-01-01T09:50:00", "A", "F");
+    let request_input = make_request_from_config(&config)?;
+    let mut solver = Solver::new(data.nb_of_stops(), data.nb_of_missions());
+    let responses = solver.solve_journey_request(
+        &data,
+        &model_refs,
+        &request_input,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+
+    assert_eq!(responses.len(), 1);
+    let journey = &responses[0];
+    assert_eq!(journey.nb_of_sections(), 4);
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.first_vehicle.vehicle_journey),
+        "toto"
+    );
+    assert_eq!(
+        model_refs.vehicle_journey_name(&journey.connections[0].2.vehicle_journey),
+        "tata"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_refused_when_input_changed() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let ntfs_directory = working_directory.path().join("ntfs");
+    write_urban_and_rail_ntfs(&ntfs_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: ntfs_directory.clone(),
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let snapshot_path = working_directory.path().join("data.snapshot");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 1, 0);
+
+    write_snapshot(
+        &data_files,
+        InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+        &snapshot_path,
+    )?;
+
+    // another default transfer duration
+    let other_transfer_duration = PositiveDuration::from_hms(0, 2, 0);
+    assert!(read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Ntfs,
+        other_transfer_duration,
+        &TransfersParams::default(),
+    )
+    .is_err());
+
+    // other parameters for the generated transfers
+    let other_transfers_params = TransfersParams {
+        max_distance: 500.0,
+        ..TransfersParams::default()
+    };
+    assert!(read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Ntfs,
+        default_transfer_duration,
+        &other_transfers_params,
+    )
+    .is_err());
+
+    // the input files are modified after the snapshot was written
+    std::fs::write(
+        ntfs_directory.join("comments.txt"),
+        "comment_id,comment_name\n",
+    )?;
+    assert!(read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Ntfs,
+        default_transfer_duration,
+        &TransfersParams::default(),
+    )
+    .is_err());
+
+    Ok(())
+}
+
+fn copy_directory(from: &Path, to: &Path) -> Result<(), Error> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let path = entry?.path();
+        let destination = to.join(path.file_name().expect("a file name"));
+        if path.is_dir() {
+            copy_directory(&path, &destination)?;
+        } else {
+            std::fs::copy(&path, &destination)?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_hash_of_netex_sub_directories() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let netex_directory = working_directory.path().join("netex");
+    copy_directory(Path::new("tests/fixtures/netex"), &netex_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: netex_directory.clone(),
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let snapshot_path = working_directory.path().join("data.snapshot");
+    let default_transfer_duration = PositiveDuration::from_hms(0, 1, 0);
+
+    write_snapshot(
+        &data_files,
+        InputDataType::Netex,
+        default_transfer_duration,
+        &TransfersParams::default(),
+        &snapshot_path,
+    )?;
+
+    // the same input data in another directory
+    let moved_netex_directory = working_directory.path().join("moved_netex");
+    copy_directory(&netex_directory, &moved_netex_directory)?;
+    let moved_data_files = LocalFileParams {
+        input_data_path: moved_netex_directory,
+        ..data_files.clone()
+    };
+    assert!(read_snapshot(
+        &snapshot_path,
+        &moved_data_files,
+        &InputDataType::Netex,
+        default_transfer_duration,
+        &TransfersParams::default(),
+    )
+    .is_ok());
+
+    // a file of the "offres" sub-directory is modified after the snapshot was written
+    let offer_path = netex_directory.join("offres").join("offre_BUS.xml");
+    let mut offer = std::fs::read_to_string(&offer_path)?;
+    offer.push('\n');
+    std::fs::write(&offer_path, offer)?;
+    assert!(read_snapshot(
+        &snapshot_path,
+        &data_files,
+        &InputDataType::Netex,
+        default_transfer_duration,
+        &TransfersParams::default(),
+    )
+    .is_err());
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+mod utils;
+use anyhow::Error;
+use launch::{
+    config::{
+        launch_params::{InputParams, LocalFileParams},
+        InputDataType, TransfersParams,
+    },
+    read::read_model,
+    transfers::{generate_transfers, TransferRules},
+};
+use loki::{
+    transit_model::{model::Model, objects::Transfer},
+    PositiveDuration,
+};
+use utils::{
+    networks::{rail_model, urban_and_rail_model, urban_model},
+    write_ntfs,
+};
+
+fn find_transfer<'a>(
+    model: &'a Model,
+    from_stop_id: &str,
+    to_stop_id: &str,
+) -> Option<&'a Transfer> {
+    model
+        .transfers
+        .values()
+        .find(|transfer| transfer.from_stop_id == from_stop_id && transfer.to_stop_id == to_stop_id)
+}
+
+#[test]
+fn test_generated_transfers_params() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let params = TransfersParams {
+        walking_speed: 1.0,
+        waiting_time: PositiveDuration::from_hms(0, 2, 0),
+        ..TransfersParams::default()
+    };
+    let rules = TransferRules::from_reader(
+        "rule,stop_area_id,from_stop_id,to_stop_id,duration\n\
+        extra_waiting_time,sa:E,,,60\n"
+            .as_bytes(),
+    )?;
+    let model = generate_transfers(urban_and_rail_model(), &params, &rules, None)?;
+
+    let to_rail = find_transfer(&model, "C", "E").expect("transfer from C to E");
+    let walking_duration = to_rail.min_transfer_time.unwrap();
+    assert!(40 < walking_duration && walking_duration < 70);
+    // the extra waiting time of sa:E is added
+    assert_eq!(
+        to_rail.real_min_transfer_time,
+        Some(walking_duration + 120 + 60)
+    );
+
+    let to_urban = find_transfer(&model, "E", "C").expect("transfer from E to C");
+    assert_eq!(to_urban.min_transfer_time, Some(walking_duration));
+    assert_eq!(
+        to_urban.real_min_transfer_time,
+        Some(walking_duration + 120)
+    );
+
+    // the stop points are too far away from each other
+    let params = TransfersParams {
+        max_distance: 10.0,
+        ..TransfersParams::default()
+    };
+    let model = generate_transfers(urban_and_rail_model(), &params, &rules, None)?;
+    assert!(find_transfer(&model, "C", "E").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_transfer_rules() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let working_directory = tempfile::tempdir()?;
+    let urban_directory = working_directory.path().join("urban");
+    let rail_directory = working_directory.path().join("rail");
+    write_ntfs(&urban_model(), &urban_directory)?;
+    write_ntfs(&rail_model(), &rail_directory)?;
+    let rules_path = working_directory.path().join("transfer_rules.csv");
+    std::fs::write(
+        &rules_path,
+        "rule,stop_area_id,from_stop_id,to_stop_id,duration\n\
+        forbidden,,C,rail:E,\n\
+        forced,,rail:E,C,300\n\
+        forced,,A,B,120\n\
+        forced,,A,unknown,120\n",
+    )?;
+    let data_files = LocalFileParams {
+        input_data_path: urban_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: vec![InputParams {
+            input_data_path: rail_directory,
+            input_data_type: InputDataType::Ntfs,
+            prefix: Some("rail".to_string()),
+        }],
+    };
+    let transfers_params = TransfersParams {
+        rules_path: Some(rules_path),
+        ..TransfersParams::default()
+    };
+    let base_model = read_model(
+        &data_files,
+        InputDataType::Ntfs,
+        PositiveDuration::from_hms(0, 1, 0),
+        &transfers_params,
+    )?;
+    let model = base_model.transit_model();
+
+    assert!(find_transfer(model, "C", "rail:E").is_none());
+
+    let forced = find_transfer(model, "rail:E", "C").expect("transfer from rail:E to C");
+    assert_eq!(forced.min_transfer_time, Some(300));
+    assert_eq!(forced.real_min_transfer_time, Some(300));
+
+    // a forced transfer is added even when the stop points are not close to each other
+    let added = find_transfer(model, "A", "B").expect("transfer from A to B");
+    assert_eq!(added.real_min_transfer_time, Some(120));
+
+    assert!(find_transfer(model, "A", "unknown").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_transfer_rules() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    // a forced transfer needs a duration
+    let rules = TransferRules::from_reader(
+        "rule,stop_area_id,from_stop_id,to_stop_id,duration\n\
+        forced,,A,B,\n"
+            .as_bytes(),
+    );
+    assert!(rules.is_err());
+
+    // unknown rule
+    let rules = TransferRules::from_reader(
+        "rule,stop_area_id,from_stop_id,to_stop_id,duration\n\
+        mandatory,,A,B,60\n"
+            .as_bytes(),
+    );
+    assert!(rules.is_err());
+
+    let working_directory = tempfile::tempdir()?;
+    let ntfs_directory = working_directory.path().join("ntfs");
+    write_ntfs(&urban_model(), &ntfs_directory)?;
+    let data_files = LocalFileParams {
+        input_data_path: ntfs_directory,
+        loads_data_path: None,
+        snapshot_path: None,
+        merged_inputs: Vec::new(),
+    };
+    let transfers_params = TransfersParams {
+        rules_path: Some(working_directory.path().join("missing.csv")),
+        ..TransfersParams::default()
+    };
+    let base_model = read_model(
+        &data_files,
+        InputDataType::Ntfs,
+        PositiveDuration::from_hms(0, 1, 0),
+        &transfers_params,
+    );
+    assert!(base_model.is_err());
+
+    Ok(())
+}
@@ -43,8 +43,21 @@ use loki::{
     chrono::NaiveDate,
     chrono_tz::UTC,
     models::{
-        self, base_model::BaseModel, real_time_model::RealTimeModel, ModelRefs, StopTime,
-        VehicleJourneyIdx,
+        self,
+        base_model::BaseModel,
+        real_time_disruption::{
+            chaos_disruption::{
+                cancel_chaos_disruption, store_and_apply_chaos_disruption, ChaosDisruption,
+            },
+            kirin_disruption::{
+                cancel_kirin_disruption, store_and_apply_kirin_disruption, KirinDisruption,
+                UpdateData, UpdateType,
+            },
+            time_periods::TimePeriod,
+            Effect, VehicleJourneyId,
+        },
+        real_time_model::{RealTimeModel, TripVersion},
+        ModelRefs, StopTime, VehicleJourneyIdx,
     },
     timetables::InsertionError,
     DataTrait, RealTimeLevel,
@@ -166,6 +179,329 @@ fn remove_vj() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+fn remove_vj_on_a_copy_of_data() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("first", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .st("C", "10:10:00");
+        })
+        .build();
+
+    let config = Config::new("2020-01-01T08:00:00", "A", "C");
+
+    let base_model = BaseModel::from_transit_model(
+        model,
+        loki::LoadsData::empty(),
+        config.default_transfer_duration,
+    )
+    .unwrap();
+
+    let real_time_model = RealTimeModel::new();
+    let model_refs = ModelRefs::new(&base_model, &real_time_model);
+
+    let data = launch::read::build_transit_data(&base_model);
+    let mut data_copy = data.clone();
+
+    let vehicle_journey_idx = base_model.vehicle_journey_idx("first").unwrap();
+    let vj_idx = VehicleJourneyIdx::Base(vehicle_journey_idx);
+
+    data_copy
+        .remove_real_time_vehicle(&vj_idx, "2020-01-01".as_date())
+        .unwrap();
+
+    let mut solver = Solver::new(data.nb_of_stops(), data.nb_of_missions());
+    let mut request_input = utils::make_request_from_config(&config)?;
+    request_input.real_time_level = RealTimeLevel::RealTime;
+
+    // the vehicle is removed from the copy
+    let responses = solver.solve_journey_request(
+        &data_copy,
+        &model_refs,
+        &request_input,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+    assert_eq!(responses.len(), 0);
+
+    // but is still present in the original data
+    let responses = solver.solve_journey_request(
+        &data,
+        &model_refs,
+        &request_input,
+        None,
+        &config.comparator_type,
+        &config.datetime_represent,
+    )?;
+    assert_eq!(responses.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn modified_vehicle_journeys_of_a_copy() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("first", |vj_builder| {
+            vj_builder.st("A", "10:00:00").st("B", "10:05:00");
+        })
+        .vj("second", |vj_builder| {
+            vj_builder.st("A", "11:00:00").st("B", "11:05:00");
+        })
+        .build();
+
+    let base_model = BaseModel::from_transit_model(
+        model,
+        loki::LoadsData::empty(),
+        loki::PositiveDuration::zero(),
+    )
+    .unwrap();
+
+    let first_idx = base_model.vehicle_journey_idx("first").unwrap();
+    let second_idx = base_model.vehicle_journey_idx("second").unwrap();
+    let date = "2020-01-01".as_date();
+
+    let mut real_time_model = RealTimeModel::new();
+    real_time_model.set_base_trip_version(first_idx, &date, TripVersion::Deleted());
+    real_time_model.set_base_trip_version(second_idx, &date, TripVersion::Deleted());
+
+    let mut real_time_copy = real_time_model.clone();
+    assert!(real_time_copy
+        .modified_vehicle_journeys(&real_time_model)
+        .is_empty());
+
+    real_time_copy.set_base_trip_version(second_idx, &date, TripVersion::Present(Vec::new()));
+    let new_vj_idx = real_time_copy.insert_new_vehicle_journey("new_vj");
+
+    let mut modified = real_time_copy.modified_vehicle_journeys(&real_time_model);
+    modified.sort();
+    assert_eq!(
+        modified,
+        vec![
+            VehicleJourneyIdx::Base(second_idx),
+            VehicleJourneyIdx::New(new_vj_idx)
+        ]
+    );
+
+    Ok(())
+}
+
+fn make_chaos_disruption(disruption_id: &str, impact_id: &str) -> ChaosDisruption {
+    let json = format!(
+        r#"{{
+            "id": "{disruption_id}",
+            "publication_period": {{ "start": "2020-01-01T00:00:00", "end": "2020-01-03T00:00:00" }},
+            "impacts": [
+                {{
+                    "id": "{impact_id}",
+                    "updated_at": "2020-01-01T08:00:00",
+                    "application_periods": [
+                        {{ "start": "2020-01-01T00:00:00", "end": "2020-01-02T00:00:00" }}
+                    ],
+                    "severity": {{ "effect": "no_service" }},
+                    "impacted_pt_objects": [ {{ "base_trip_deleted": {{ "id": "first" }} }} ]
+                }}
+            ]
+        }}"#
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+#[test]
+fn applied_chaos_disruptions() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("first", |vj_builder| {
+            vj_builder.st("A", "10:00:00").st("B", "10:05:00");
+        })
+        .build();
+
+    let base_model = BaseModel::from_transit_model(
+        model,
+        loki::LoadsData::empty(),
+        loki::PositiveDuration::zero(),
+    )
+    .unwrap();
+    let mut data = launch::read::build_transit_data(&base_model);
+    let mut real_time_model = RealTimeModel::new();
+
+    for (disruption_id, impact_id) in [
+        ("cancelled", "impact_1"),
+        ("updated", "impact_2"),
+        ("updated", "impact_3"),
+    ] {
+        let disruption = make_chaos_disruption(disruption_id, impact_id);
+        store_and_apply_chaos_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    }
+    cancel_chaos_disruption(&mut real_time_model, "cancelled", &base_model, &mut data);
+
+    // only the last version of "updated" is applied
+    let applied = real_time_model.applied_chaos_disruptions();
+    assert_eq!(applied.len(), 1);
+    assert_eq!(applied[0].id, "updated");
+    assert_eq!(applied[0].impacts[0].id, "impact_3");
+
+    // a cancelled disruption is applied again when a new version is received
+    let disruption = make_chaos_disruption("cancelled", "impact_4");
+    store_and_apply_chaos_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    let applied_ids: Vec<_> = real_time_model
+        .applied_chaos_disruptions()
+        .iter()
+        .map(|disruption| disruption.id.as_str())
+        .collect();
+    assert_eq!(applied_ids, vec!["updated", "cancelled"]);
+
+    Ok(())
+}
+
+fn make_kirin_disruption(
+    disruption_id: &str,
+    vehicle_journey_id: &str,
+    effect: Effect,
+    update: UpdateType,
+) -> KirinDisruption {
+    let date = "2020-01-01".as_date();
+    KirinDisruption {
+        id: disruption_id.to_string(),
+        contributor: None,
+        message: None,
+        updated_at: date.and_hms(8, 0, 0),
+        application_period: TimePeriod::new(date.and_hms(0, 0, 0), date.and_hms(23, 0, 0)).unwrap(),
+        effect,
+        trip_id: VehicleJourneyId {
+            id: vehicle_journey_id.to_string(),
+        },
+        trip_date: date,
+        update,
+    }
+}
+
+fn make_update_data(stop_times: StopTimesBuilder) -> UpdateData {
+    UpdateData {
+        stop_times: stop_times.stop_times,
+        company_id: None,
+        physical_mode_id: None,
+        headsign: None,
+    }
+}
+
+#[test]
+fn cancel_kirin_disruptions() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("first", |vj_builder| {
+            vj_builder.st("A", "10:00:00").st("B", "10:05:00");
+        })
+        .vj("second", |vj_builder| {
+            vj_builder.st("A", "11:00:00").st("B", "11:05:00");
+        })
+        .build();
+
+    let base_model = BaseModel::from_transit_model(
+        model,
+        loki::LoadsData::empty(),
+        loki::PositiveDuration::zero(),
+    )
+    .unwrap();
+    let mut data = launch::read::build_transit_data(&base_model);
+    let mut real_time_model = RealTimeModel::new();
+    let date = "2020-01-01".as_date();
+    let first_idx = base_model.vehicle_journey_idx("first").unwrap();
+    let second_idx = base_model.vehicle_journey_idx("second").unwrap();
+
+    // "first" is deleted by chaos, then updated by kirin
+    let disruption = make_chaos_disruption("chaos", "impact");
+    store_and_apply_chaos_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    let update = make_update_data(
+        StopTimesBuilder::new()
+            .st("A", "10:10:00")
+            .st("B", "10:15:00"),
+    );
+    let disruption = make_kirin_disruption(
+        "kirin_first",
+        "first",
+        Effect::SignificantDelays,
+        UpdateType::BaseTripUpdated(update),
+    );
+    store_and_apply_kirin_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    // "second" is deleted by kirin
+    let disruption = make_kirin_disruption(
+        "kirin_second",
+        "second",
+        Effect::NoService,
+        UpdateType::TripDeleted(),
+    );
+    store_and_apply_kirin_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    // and a new trip is added by kirin
+    let update = make_update_data(
+        StopTimesBuilder::new()
+            .st("A", "12:00:00")
+            .st("B", "12:05:00"),
+    );
+    let disruption = make_kirin_disruption(
+        "kirin_new",
+        "new",
+        Effect::AdditionalService,
+        UpdateType::NewTripUpdated(update),
+    );
+    store_and_apply_kirin_disruption(&mut real_time_model, disruption, &base_model, &mut data);
+    assert_eq!(real_time_model.applied_kirin_disruptions().len(), 3);
+    assert!(real_time_model.base_vehicle_journey_is_present(first_idx, date, &base_model));
+    assert!(!real_time_model.base_vehicle_journey_is_present(second_idx, date, &base_model));
+
+    for disruption_id in ["kirin_first", "kirin_second", "kirin_new"] {
+        cancel_kirin_disruption(&mut real_time_model, disruption_id, &base_model, &mut data);
+    }
+    assert!(real_time_model.applied_kirin_disruptions().is_empty());
+    // the chaos disruption applies again on "first"
+    assert!(!real_time_model.base_vehicle_journey_is_present(first_idx, date, &base_model));
+    // "second" is back to its base schedule
+    assert!(real_time_model.base_vehicle_journey_is_present(second_idx, date, &base_model));
+    match real_time_model.last_version(&VehicleJourneyIdx::Base(second_idx), date) {
+        Some(TripVersion::Present(stop_times)) => assert_eq!(stop_times.len(), 2),
+        _ => panic!("second should have its base stop times"),
+    }
+    // the new trip is deleted
+    let new_idx = real_time_model.new_vehicle_journey_idx("new").unwrap();
+    assert!(!real_time_model.new_vehicle_journey_is_present(new_idx, date));
+
+    Ok(())
+}
+
+#[test]
+fn count_base_insertion_errors() -> Result<(), Error> {
+    let _log_guard = launch::logger::init_test_logger();
+
+    let model = ModelBuilder::new("2020-01-01", "2020-01-02")
+        .vj("valid", |vj_builder| {
+            vj_builder.st("A", "10:00:00").st("B", "10:05:00");
+        })
+        .vj("single_stop", |vj_builder| {
+            vj_builder.st("A", "11:00:00");
+        })
+        .build();
+
+    let base_model = BaseModel::from_transit_model(
+        model,
+        loki::LoadsData::empty(),
+        loki::PositiveDuration::zero(),
+    )
+    .unwrap();
+    let data = launch::read::build_transit_data(&base_model);
+
+    assert_eq!(data.nb_of_base_insertion_errors(), 1);
+
+    Ok(())
+}
+
 #[test]
 fn remove_successive_vj() -> Result<(), Error> {
     let _log_guard = launch::logger::init_test_logger();
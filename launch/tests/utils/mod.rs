@@ -36,6 +36,7 @@
 #![allow(dead_code)]
 pub mod disruption_builder;
 pub mod model_builder;
+pub mod networks;
 
 use anyhow::{format_err, Error};
 use launch::{
@@ -46,9 +47,10 @@ use launch::{
     solver::Solver,
 };
 use loki::{
-    chrono::TimeZone,
+    chrono::{FixedOffset, TimeZone},
     filters::{parse_filter, Filters},
     models::ModelRefs,
+    transit_model::{self, model::Model},
     RealTimeLevel,
 };
 
@@ -56,6 +58,7 @@ use loki::{chrono_tz, tracing::debug};
 
 use loki::{NaiveDateTime, PositiveDuration, TransitData};
 use model_builder::AsDateTime;
+use std::path::Path;
 
 pub struct Config<'a> {
     pub request_params: config::RequestParams,
@@ -206,3 +209,10 @@ pub fn from_to_stop_point_names<'a>(
 
     Ok((from_stop_name, to_stop_name))
 }
+
+/// Writes `model` as a ntfs in `directory`.
+pub fn write_ntfs(model: &Model, directory: &Path) -> Result<(), Error> {
+    let datetime = FixedOffset::east(0).ymd(2020, 1, 1).and_hms(0, 0, 0);
+    transit_model::ntfs::write(model, directory, datetime)?;
+    Ok(())
+}
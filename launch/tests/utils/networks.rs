@@ -0,0 +1,91 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! Small networks shared by the tests that read or merge input datasets.
+
+use super::model_builder::ModelBuilder;
+use loki::transit_model::{model::Model, objects::Coord};
+
+// coordinates of the stop points "C" and "E", about 50 meters away from each other
+const C_COORD: Coord = Coord {
+    lon: 2.325624,
+    lat: 48.823395,
+};
+const E_COORD: Coord = Coord {
+    lon: 2.32618,
+    lat: 48.822944,
+};
+
+/// Adds an urban network, with a vehicle journey "toto" through "A", "B" and "C".
+pub fn urban_network(model_builder: ModelBuilder) -> ModelBuilder {
+    model_builder
+        .stop_area("sa:C", |_| {})
+        .stop_point("C", |stop_point| stop_point.coord = C_COORD)
+        .vj("toto", |vj_builder| {
+            vj_builder
+                .st("A", "10:00:00")
+                .st("B", "10:05:00")
+                .st("C", "10:10:00");
+        })
+}
+
+/// Adds a rail network, with a vehicle journey "tata" through "E" and "F".
+/// "E" is close to the last stop "C" of the urban network.
+pub fn rail_network(model_builder: ModelBuilder) -> ModelBuilder {
+    model_builder
+        .stop_area("sa:E", |_| {})
+        .stop_point("E", |stop_point| stop_point.coord = E_COORD)
+        .vj("tata", |vj_builder| {
+            vj_builder.st("E", "10:15:00").st("F", "10:20:00");
+        })
+}
+
+pub fn urban_model() -> Model {
+    urban_network(ModelBuilder::new("2020-01-01", "2020-01-02")).build()
+}
+
+pub fn rail_model() -> Model {
+    rail_network(ModelBuilder::new("2020-01-01", "2020-01-02")).build()
+}
+
+/// A single model with both the urban and the rail networks.
+pub fn urban_and_rail_model_builder() -> ModelBuilder {
+    rail_network(urban_network(ModelBuilder::new("2020-01-01", "2020-01-02")))
+}
+
+pub fn urban_and_rail_model() -> Model {
+    urban_and_rail_model_builder().build()
+}
@@ -34,7 +34,9 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use std::{fs::File, io::Write};
+use prost::Message;
+use prost_types::{field_descriptor_proto::Type, DescriptorProto, FileDescriptorSet};
+use std::{fs::File, io::Write, path::Path};
 
 static MOD_RS: &[u8] = b"
 /// Generated from protobuf.
@@ -52,6 +54,13 @@ pub mod kirin;
 pub mod chaos;
 ";
 
+const NAVITIA_PROTOS: &[&str] = &[
+    "navitia-proto/request.proto",
+    "navitia-proto/response.proto",
+    "navitia-proto/task.proto",
+    "navitia-proto/type.proto",
+];
+
 fn main() {
     // create rust usable structs from protobuf files
     // see https://docs.rs/prost-build/0.6.1/prost_build/
@@ -59,18 +68,42 @@ fn main() {
     use std::env;
     let out_dir = env::var("OUT_DIR").unwrap();
 
-    prost_build::compile_protos(
-        &[
-            "navitia-proto/request.proto",
-            "navitia-proto/response.proto",
-            "navitia-proto/task.proto",
-            "navitia-proto/type.proto",
-        ],
-        &["navitia-proto/"],
-    )
-    .expect("Failed to generate protobuf code for navitia-proto.");
+    // A first pass gives us the descriptors of the navitia protos
+    let descriptors_path = Path::new(&out_dir).join("navitia_proto_descriptors.bin");
+    prost_build::Config::new()
+        .file_descriptor_set_path(&descriptors_path)
+        .compile_protos(NAVITIA_PROTOS, &["navitia-proto/"])
+        .expect("Failed to generate protobuf code for navitia-proto.");
+    let descriptors_bytes =
+        std::fs::read(&descriptors_path).expect("Could not read navitia-proto descriptors.");
+    let descriptors = FileDescriptorSet::decode(descriptors_bytes.as_slice())
+        .expect("Could not decode navitia-proto descriptors.");
+
+    // responses are also sent as json by the http worker.
+    // Enum fields are stored as i32 by prost, so we tell serde
+    // to write the name of their value instead.
+    let mut config = prost_build::Config::new();
+    config
+        .file_descriptor_set_path(&descriptors_path)
+        .skip_protoc_run()
+        .type_attribute(".", "#[derive(serde::Serialize)]");
+    for (field_path, enum_name) in enum_fields(&descriptors) {
+        let attribute = format!(
+            "#[serde(serialize_with = \"crate::navitia_proto::enum_names::{}::serialize\")]",
+            enum_module_name(&enum_name)
+        );
+        config.field_attribute(field_path, attribute);
+    }
+    config
+        .compile_protos(NAVITIA_PROTOS, &["navitia-proto/"])
+        .expect("Failed to generate protobuf code for navitia-proto.");
     println!("Writing protobuf code in {}/pbnavitia.rs", out_dir);
 
+    File::create(Path::new(&out_dir).join("navitia_enum_names.rs"))
+        .expect("Could not create File navitia_enum_names.rs")
+        .write_all(enum_names_code(&descriptors).as_bytes())
+        .unwrap();
+
     protobuf_codegen_pure::Codegen::new()
         .out_dir(out_dir.as_str())
         .inputs(&[
@@ -86,3 +119,103 @@ fn main() {
         .write_all(MOD_RS)
         .unwrap();
 }
+
+// Returns the path of each enum field of the navitia protos,
+// alongside the fully qualified name of its enum
+fn enum_fields(descriptors: &FileDescriptorSet) -> Vec<(String, String)> {
+    fn visit(message_prefix: &str, message: &DescriptorProto, result: &mut Vec<(String, String)>) {
+        let message_path = format!("{}.{}", message_prefix, message.name());
+        for field in &message.field {
+            if field.r#type() != Type::Enum {
+                continue;
+            }
+            // prost looks for the attributes of a field of a oneof
+            // under the path of the oneof
+            let field_path = match field.oneof_index {
+                Some(oneof_index) => format!(
+                    "{}.{}.{}",
+                    message_path,
+                    message.oneof_decl[oneof_index as usize].name(),
+                    field.name()
+                ),
+                None => format!("{}.{}", message_path, field.name()),
+            };
+            result.push((field_path, field.type_name().to_string()));
+        }
+        for nested_message in &message.nested_type {
+            visit(&message_path, nested_message, result);
+        }
+    }
+
+    let mut result = Vec::new();
+    for file in &descriptors.file {
+        let package_prefix = format!(".{}", file.package());
+        for message in &file.message_type {
+            visit(&package_prefix, message, &mut result);
+        }
+    }
+    result
+}
+
+// A module for each enum, with the names of its values as written in the proto in lowercase,
+// and a function to serialize the fields of this enum with these names
+fn enum_names_code(descriptors: &FileDescriptorSet) -> String {
+    fn append_enum(
+        prefix: &str,
+        enum_descriptor: &prost_types::EnumDescriptorProto,
+        code: &mut String,
+    ) {
+        let enum_name = format!("{}.{}", prefix, enum_descriptor.name());
+        let names: Vec<String> = enum_descriptor
+            .value
+            .iter()
+            .map(|value| format!("({}, {:?})", value.number(), value.name().to_lowercase()))
+            .collect();
+        code.push_str(&format!(
+            "pub mod {} {{
+    const NAMES: &[(i32, &str)] = &[{}];
+
+    pub fn serialize<V, S>(value: &V, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        V: crate::navitia_enums::EnumValues,
+        S: serde::Serializer,
+    {{
+        value.serialize_names(NAMES, serializer)
+    }}
+}}
+",
+            enum_module_name(&enum_name),
+            names.join(", ")
+        ));
+    }
+
+    fn visit(prefix: &str, message: &DescriptorProto, code: &mut String) {
+        let message_path = format!("{}.{}", prefix, message.name());
+        for enum_descriptor in &message.enum_type {
+            append_enum(&message_path, enum_descriptor, code);
+        }
+        for nested_message in &message.nested_type {
+            visit(&message_path, nested_message, code);
+        }
+    }
+
+    let mut code = String::new();
+    for file in &descriptors.file {
+        let package_prefix = format!(".{}", file.package());
+        for enum_descriptor in &file.enum_type {
+            append_enum(&package_prefix, enum_descriptor, &mut code);
+        }
+        for message in &file.message_type {
+            visit(&package_prefix, message, &mut code);
+        }
+    }
+    code
+}
+
+// ".pbnavitia.Section.Type" gives "pbnavitia_section_type"
+fn enum_module_name(enum_name: &str) -> String {
+    enum_name
+        .trim_start_matches('.')
+        .replace('.', "_")
+        .to_lowercase()
+}
@@ -0,0 +1,443 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::{convert::Infallible, net::SocketAddr, thread};
+
+use anyhow::{format_err, Context, Error};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Server, StatusCode,
+};
+use launch::loki::{
+    chrono::NaiveDate,
+    memory_usage::MemoryUsage,
+    models::real_time_disruption::{
+        chaos_disruption::{ChaosDisruption, ChaosImpact, Impacted, Informed},
+        kirin_disruption::{KirinDisruption, UpdateType},
+        Effect,
+    },
+    tracing::{error, info, log::trace},
+    NaiveDateTime,
+};
+use serde::Serialize;
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, oneshot},
+};
+
+use crate::status_worker::BaseDataInfo;
+
+/// Answers the requests of operators over http :
+///  - `POST /reload/base_data`, `POST /reload/chaos` and `POST /reload/kirin`
+///  - `GET /disruptions/chaos` and `GET /disruptions/kirin` list the disruptions applied
+///  - `DELETE /disruptions/chaos/{id}` and `DELETE /disruptions/kirin/{id}` cancel a disruption
+///  - `GET /base_data_info` describes the base data loaded
+///  - `GET /memory_usage` gives the memory currently used by the data, including real time additions
+///
+/// Each request is forwarded to the data worker, which handles it
+/// between two real time updates, and then answers with json.
+pub struct AdminWorker {
+    endpoint: SocketAddr,
+    admin_requests_sender: mpsc::UnboundedSender<AdminRequest>,
+    // to send shutdown signal to Master when an error occurs inside AdminWorker
+    shutdown_sender: mpsc::Sender<()>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    ReloadBaseData,
+    ReloadChaos,
+    ReloadKirin,
+    ListChaosDisruptions,
+    ListKirinDisruptions,
+    CancelChaosDisruption(String),
+    CancelKirinDisruption(String),
+    BaseDataInfo,
+    MemoryUsage,
+}
+
+#[derive(Debug)]
+pub struct AdminRequest {
+    pub command: AdminCommand,
+    pub response_sender: oneshot::Sender<Result<AdminResponse, Error>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum AdminResponse {
+    Done {
+        message: String,
+    },
+    ChaosDisruptions {
+        chaos_disruptions: Vec<ChaosDisruptionInfo>,
+    },
+    KirinDisruptions {
+        kirin_disruptions: Vec<KirinDisruptionInfo>,
+    },
+    BaseDataInfo {
+        base_data_info: Option<BaseDataInfo>,
+    },
+    MemoryUsage {
+        memory_usage: MemoryUsage,
+    },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChaosDisruptionInfo {
+    pub id: String,
+    pub contributor: Option<String>,
+    pub impacts: Vec<ChaosImpactInfo>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ChaosImpactInfo {
+    pub id: String,
+    pub updated_at: NaiveDateTime,
+    pub effect: Effect,
+    pub impacted_objects: Vec<PtObjectInfo>,
+    pub informed_objects: Vec<PtObjectInfo>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PtObjectInfo {
+    #[serde(rename = "type")]
+    pub object_type: &'static str,
+    /// for a line section or a rail section, the id of the line
+    pub id: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct KirinDisruptionInfo {
+    pub id: String,
+    pub contributor: Option<String>,
+    pub updated_at: NaiveDateTime,
+    pub effect: Effect,
+    pub vehicle_journey: String,
+    pub date: NaiveDate,
+    /// `trip_deleted`, `base_trip_updated` or `new_trip_updated`
+    pub update: &'static str,
+}
+
+impl ChaosDisruptionInfo {
+    pub fn new(disruption: &ChaosDisruption) -> Self {
+        Self {
+            id: disruption.id.clone(),
+            contributor: disruption.contributor.clone(),
+            impacts: disruption
+                .impacts
+                .iter()
+                .map(ChaosImpactInfo::new)
+                .collect(),
+        }
+    }
+}
+
+impl ChaosImpactInfo {
+    fn new(impact: &ChaosImpact) -> Self {
+        Self {
+            id: impact.id.clone(),
+            updated_at: impact.updated_at,
+            effect: impact.severity.effect,
+            impacted_objects: impact
+                .impacted_pt_objects
+                .iter()
+                .map(PtObjectInfo::impacted)
+                .collect(),
+            informed_objects: impact
+                .informed_pt_objects
+                .iter()
+                .map(PtObjectInfo::informed)
+                .collect(),
+        }
+    }
+}
+
+impl PtObjectInfo {
+    fn impacted(impacted: &Impacted) -> Self {
+        let (object_type, id) = match impacted {
+            Impacted::NetworkDeleted(network) => ("network", &network.id),
+            Impacted::LineDeleted(line) => ("line", &line.id),
+            Impacted::RouteDeleted(route) => ("route", &route.id),
+            Impacted::RailSection(rail_section) => ("rail_section", &rail_section.line.id),
+            Impacted::LineSection(line_section) => ("line_section", &line_section.line.id),
+            Impacted::StopAreaDeleted(stop_area) => ("stop_area", &stop_area.id),
+            Impacted::StopPointDeleted(stop_point) => ("stop_point", &stop_point.id),
+            Impacted::BaseTripDeleted(vehicle_journey) => ("vehicle_journey", &vehicle_journey.id),
+        };
+        Self {
+            object_type,
+            id: id.clone(),
+        }
+    }
+
+    fn informed(informed: &Informed) -> Self {
+        let (object_type, id) = match informed {
+            Informed::Network(network) => ("network", &network.id),
+            Informed::Line(line) => ("line", &line.id),
+            Informed::Route(route) => ("route", &route.id),
+            Informed::Trip(vehicle_journey) => ("vehicle_journey", &vehicle_journey.id),
+            Informed::StopArea(stop_area) => ("stop_area", &stop_area.id),
+            Informed::StopPoint(stop_point) => ("stop_point", &stop_point.id),
+        };
+        Self {
+            object_type,
+            id: id.clone(),
+        }
+    }
+}
+
+impl KirinDisruptionInfo {
+    pub fn new(disruption: &KirinDisruption) -> Self {
+        let update = match disruption.update {
+            UpdateType::TripDeleted() => "trip_deleted",
+            UpdateType::BaseTripUpdated(_) => "base_trip_updated",
+            UpdateType::NewTripUpdated(_) => "new_trip_updated",
+        };
+        Self {
+            id: disruption.id.clone(),
+            contributor: disruption.contributor.clone(),
+            updated_at: disruption.updated_at,
+            effect: disruption.effect,
+            vehicle_journey: disruption.trip_id.id.clone(),
+            date: disruption.trip_date,
+            update,
+        }
+    }
+}
+
+/// Returned when cancelling a disruption that is not currently applied.
+/// Answered with a 404.
+#[derive(Debug)]
+pub struct DisruptionNotApplied {
+    pub disruption_id: String,
+}
+
+impl std::error::Error for DisruptionNotApplied {}
+
+impl std::fmt::Display for DisruptionNotApplied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Disruption {} is not applied.", self.disruption_id)
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    error: String,
+}
+
+impl AdminWorker {
+    pub fn new(
+        endpoint: SocketAddr,
+        admin_requests_sender: mpsc::UnboundedSender<AdminRequest>,
+        shutdown_sender: mpsc::Sender<()>,
+    ) -> Self {
+        Self {
+            endpoint,
+            admin_requests_sender,
+            shutdown_sender,
+        }
+    }
+
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
+        let thread_builder = thread::Builder::new().name("loki_admin_worker".to_string());
+        let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
+        Ok(handle)
+    }
+
+    async fn run(self) {
+        let run_err = self.run_server().await;
+        error!("AdminWorker stopped : {:?}", run_err);
+        // send shutdown signal
+        let _ = self.shutdown_sender.send(()).await;
+    }
+
+    async fn run_server(&self) -> Result<(), Error> {
+        let admin_requests_sender = self.admin_requests_sender.clone();
+        let make_service = make_service_fn(move |_connection| {
+            let admin_requests_sender = admin_requests_sender.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_http_request(request, admin_requests_sender.clone())
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&self.endpoint)
+            .with_context(|| format!("Could not bind admin endpoint {}", self.endpoint))?;
+        info!("Admin worker bound to endpoint {}", self.endpoint);
+        server
+            .serve(make_service)
+            .await
+            .context("Admin http server failed.")
+    }
+}
+
+async fn handle_http_request(
+    request: hyper::Request<Body>,
+    admin_requests_sender: mpsc::UnboundedSender<AdminRequest>,
+) -> Result<hyper::Response<Body>, Infallible> {
+    trace!(
+        "AdminWorker received a request {} {}",
+        request.method(),
+        request.uri()
+    );
+    let command = match parse_command(request.method(), request.uri().path()) {
+        Some(command) => command,
+        None => {
+            let error = format_err!(
+                "Unknown admin request {} {}",
+                request.method(),
+                request.uri().path()
+            );
+            return Ok(error_response(StatusCode::NOT_FOUND, &error));
+        }
+    };
+
+    match send_command(command, &admin_requests_sender).await {
+        Ok(response) => Ok(json_response(StatusCode::OK, &response)),
+        Err(err) if err.downcast_ref::<DisruptionNotApplied>().is_some() => {
+            Ok(error_response(StatusCode::NOT_FOUND, &err))
+        }
+        Err(err) => Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, &err)),
+    }
+}
+
+async fn send_command(
+    command: AdminCommand,
+    admin_requests_sender: &mpsc::UnboundedSender<AdminRequest>,
+) -> Result<AdminResponse, Error> {
+    let (response_sender, response_receiver) = oneshot::channel();
+    let request = AdminRequest {
+        command,
+        response_sender,
+    };
+    admin_requests_sender
+        .send(request)
+        .context("AdminWorker error while forwarding request to data worker.")?;
+    response_receiver
+        .await
+        .context("AdminWorker : channel to receive the response has closed.")?
+}
+
+// Returns None when `method` and `path` do not match a command.
+fn parse_command(method: &Method, path: &str) -> Option<AdminCommand> {
+    let segments: Vec<&str> = path
+        .trim_matches('/')
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let command = match (method, segments.as_slice()) {
+        (&Method::POST, ["reload", "base_data"]) => AdminCommand::ReloadBaseData,
+        (&Method::POST, ["reload", "chaos"]) => AdminCommand::ReloadChaos,
+        (&Method::POST, ["reload", "kirin"]) => AdminCommand::ReloadKirin,
+        (&Method::GET, ["disruptions", "chaos"]) => AdminCommand::ListChaosDisruptions,
+        (&Method::GET, ["disruptions", "kirin"]) => AdminCommand::ListKirinDisruptions,
+        (&Method::DELETE, ["disruptions", "chaos", disruption_id]) => {
+            AdminCommand::CancelChaosDisruption(disruption_id.to_string())
+        }
+        (&Method::DELETE, ["disruptions", "kirin", disruption_id]) => {
+            AdminCommand::CancelKirinDisruption(disruption_id.to_string())
+        }
+        (&Method::GET, ["base_data_info"]) => AdminCommand::BaseDataInfo,
+        (&Method::GET, ["memory_usage"]) => AdminCommand::MemoryUsage,
+        _ => return None,
+    };
+    Some(command)
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> hyper::Response<Body> {
+    match serde_json::to_vec(body) {
+        Ok(bytes) => hyper::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(bytes))
+            // unwrap is safe since the status and header are valid
+            .unwrap(),
+        Err(err) => {
+            error!(
+                "AdminWorker could not serialize response to json : {:?}",
+                err
+            );
+            let mut response = hyper::Response::new(Body::empty());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+    }
+}
+
+fn error_response(status: StatusCode, error: &Error) -> hyper::Response<Body> {
+    let body = ErrorBody {
+        error: format!("{:#}", error),
+    };
+    json_response(status, &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_admin_commands() {
+        assert_eq!(
+            parse_command(&Method::POST, "/reload/base_data/"),
+            Some(AdminCommand::ReloadBaseData)
+        );
+        assert_eq!(
+            parse_command(&Method::GET, "/disruptions/kirin"),
+            Some(AdminCommand::ListKirinDisruptions)
+        );
+        assert_eq!(
+            parse_command(&Method::DELETE, "/disruptions/chaos/d5a6e3c2"),
+            Some(AdminCommand::CancelChaosDisruption("d5a6e3c2".to_string()))
+        );
+        assert_eq!(
+            parse_command(&Method::GET, "/memory_usage"),
+            Some(AdminCommand::MemoryUsage)
+        );
+        // reloads are only triggered by POST requests
+        assert_eq!(parse_command(&Method::GET, "/reload/chaos"), None);
+        assert_eq!(
+            parse_command(&Method::DELETE, "/disruptions/kirin/d5a6e3c2"),
+            Some(AdminCommand::CancelKirinDisruption("d5a6e3c2".to_string()))
+        );
+        // a disruption id is needed to cancel a disruption
+        assert_eq!(parse_command(&Method::DELETE, "/disruptions/kirin"), None);
+        assert_eq!(parse_command(&Method::GET, "/status"), None);
+    }
+}
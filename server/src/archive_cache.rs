@@ -0,0 +1,177 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::{bail, Context, Error};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_FILE_NAME: &str = "archive.zip";
+const INFO_FILE_NAME: &str = "archive_info.json";
+const PENDING_ARCHIVE_FILE_NAME: &str = "archive.zip.pending";
+const PENDING_INFO_FILE_NAME: &str = "archive_info.json.pending";
+
+/// A copy, on local disk, of the last archive successfully loaded from the bucket,
+/// so that data can be loaded at startup while the bucket is unreachable.
+///
+/// A downloaded archive is first stored as pending, and replaces the cached
+/// archive only once it has been loaded successfully.
+/// The size and checksum of the cached archive are checked before it is read.
+pub struct ArchiveCache {
+    directory: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveInfo {
+    version_id: String,
+    size: u64,
+    crc32: u32,
+}
+
+impl ArchiveCache {
+    pub fn new(directory: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Could not create archive cache directory {:?}", directory))?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    /// Stores `data` as the pending archive, replacing the previous pending archive if any.
+    pub fn store_pending(&self, data: &[u8], version_id: &str) -> Result<(), Error> {
+        let info = ArchiveInfo {
+            version_id: version_id.to_string(),
+            size: data.len() as u64,
+            crc32: crc32fast::hash(data),
+        };
+        let archive_path = self.directory.join(PENDING_ARCHIVE_FILE_NAME);
+        std::fs::write(&archive_path, data)
+            .with_context(|| format!("Could not write archive to {:?}", archive_path))?;
+        let info_path = self.directory.join(PENDING_INFO_FILE_NAME);
+        let info = serde_json::to_vec(&info)?;
+        std::fs::write(&info_path, info)
+            .with_context(|| format!("Could not write archive info to {:?}", info_path))?;
+        Ok(())
+    }
+
+    /// The pending archive becomes the cached archive.
+    pub fn commit_pending(&self) -> Result<(), Error> {
+        let rename = |from: &str, to: &str| {
+            let from = self.directory.join(from);
+            let to = self.directory.join(to);
+            std::fs::rename(&from, &to)
+                .with_context(|| format!("Could not rename {:?} to {:?}", from, to))
+        };
+        // if we stop between the two renames, the cached archive
+        // will not match its info, and will be rejected when read
+        rename(PENDING_ARCHIVE_FILE_NAME, ARCHIVE_FILE_NAME)?;
+        rename(PENDING_INFO_FILE_NAME, INFO_FILE_NAME)?;
+        Ok(())
+    }
+
+    /// Reads the cached archive, and returns it along with its version_id.
+    ///
+    /// Fails if there is no cached archive, or if its size or checksum
+    /// do not match the ones recorded when it was stored.
+    pub fn read(&self) -> Result<(Vec<u8>, String), Error> {
+        let info_path = self.directory.join(INFO_FILE_NAME);
+        let info = std::fs::read(&info_path)
+            .with_context(|| format!("Could not read archive info from {:?}", info_path))?;
+        let info: ArchiveInfo = serde_json::from_slice(&info)
+            .with_context(|| format!("Could not parse archive info from {:?}", info_path))?;
+        let archive_path = self.directory.join(ARCHIVE_FILE_NAME);
+        let data = std::fs::read(&archive_path)
+            .with_context(|| format!("Could not read archive from {:?}", archive_path))?;
+        if data.len() as u64 != info.size {
+            bail!(
+                "Cached archive {:?} is corrupted : its size is {} while {} was expected.",
+                archive_path,
+                data.len(),
+                info.size
+            );
+        }
+        let crc32 = crc32fast::hash(&data);
+        if crc32 != info.crc32 {
+            bail!(
+                "Cached archive {:?} is corrupted : its checksum is {} while {} was expected.",
+                archive_path,
+                crc32,
+                info.crc32
+            );
+        }
+        Ok((data, info.version_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_archive_is_read_once_committed() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = ArchiveCache::new(directory.path()).unwrap();
+        assert!(cache.read().is_err());
+
+        cache.store_pending(b"first archive", "v1").unwrap();
+        assert!(cache.read().is_err());
+        cache.commit_pending().unwrap();
+        let (data, version_id) = cache.read().unwrap();
+        assert_eq!(data, b"first archive");
+        assert_eq!(version_id, "v1");
+
+        // a pending archive that is never committed does not replace the cached one
+        cache.store_pending(b"second archive", "v2").unwrap();
+        let cache = ArchiveCache::new(directory.path()).unwrap();
+        let (data, version_id) = cache.read().unwrap();
+        assert_eq!(data, b"first archive");
+        assert_eq!(version_id, "v1");
+    }
+
+    #[test]
+    fn corrupted_archive_is_rejected() {
+        let directory = tempfile::tempdir().unwrap();
+        let cache = ArchiveCache::new(directory.path()).unwrap();
+        cache.store_pending(b"some archive", "v1").unwrap();
+        cache.commit_pending().unwrap();
+
+        let archive_path = directory.path().join(ARCHIVE_FILE_NAME);
+        std::fs::write(&archive_path, b"some archivf").unwrap();
+        assert!(cache.read().is_err());
+
+        std::fs::write(&archive_path, b"some").unwrap();
+        assert!(cache.read().is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::{
+    chaos_proto, handle_chaos_message::handle_chaos_protobuf, server_config::ChaosLocalParams,
+};
+use anyhow::{Context, Error};
+use launch::loki::{
+    models::real_time_disruption::chaos_disruption::ChaosDisruption,
+    tracing::{error, info},
+};
+use protobuf::Message as ProtobufMessage;
+use serde::Deserialize;
+use std::{ffi::OsStr, path::Path};
+
+// A json file may contain a single disruption, or a list of disruptions
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonDisruptions {
+    One(Box<ChaosDisruption>),
+    Many(Vec<ChaosDisruption>),
+}
+
+/// Reads all chaos disruptions stored in the directory given in `chaos_params`.
+///
+/// Files that cannot be read or decoded are skipped.
+/// Disruptions are returned in the order of the names of the files they were read from.
+pub fn read_chaos_disruptions_from_directory(
+    chaos_params: &ChaosLocalParams,
+) -> Result<Vec<ChaosDisruption>, Error> {
+    let directory = &chaos_params.directory;
+    info!("Reading chaos disruptions from directory {:?}", directory);
+
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("Could not read chaos directory {:?}", directory))?
+    {
+        let path = entry?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut disruptions = Vec::new();
+    for path in paths {
+        let extension = path.extension().and_then(OsStr::to_str);
+        let result = match extension {
+            Some("pb") => read_protobuf_file(&path).map(|disruption| vec![disruption]),
+            Some("json") => read_json_file(&path),
+            _ => continue,
+        };
+        match result {
+            Ok(file_disruptions) => disruptions.extend(file_disruptions),
+            Err(err) => {
+                error!(
+                    "Could not read chaos disruptions from {:?}. {:?}",
+                    path, err
+                );
+            }
+        }
+    }
+
+    info!(
+        "Read {} chaos disruptions from directory {:?}",
+        disruptions.len(),
+        directory
+    );
+    Ok(disruptions)
+}
+
+fn read_protobuf_file(path: &Path) -> Result<ChaosDisruption, Error> {
+    let bytes = std::fs::read(path)?;
+    let proto = chaos_proto::chaos::Disruption::parse_from_bytes(&bytes)
+        .context("Could not decode file into a chaos Disruption protobuf")?;
+    handle_chaos_protobuf(&proto)
+}
+
+fn read_json_file(path: &Path) -> Result<Vec<ChaosDisruption>, Error> {
+    let file = std::fs::File::open(path)?;
+    let reader = std::io::BufReader::new(file);
+    let disruptions: JsonDisruptions =
+        serde_json::from_reader(reader).context("Could not decode json chaos disruptions")?;
+    match disruptions {
+        JsonDisruptions::One(disruption) => Ok(vec![*disruption]),
+        JsonDisruptions::Many(disruptions) => Ok(disruptions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch::loki::models::real_time_disruption::{chaos_disruption::Impacted, Effect};
+    use std::io::Write;
+
+    const DISRUPTION: &str = r#"{
+        "id": "disruption_1",
+        "contributor": "shortterm.tr_sytral",
+        "publication_period": {
+            "start": "2022-01-01T00:00:00",
+            "end": "2022-01-31T00:00:00"
+        },
+        "cause": { "wording": "strike", "category": "" },
+        "impacts": [
+            {
+                "id": "impact_1",
+                "updated_at": "2022-01-01T08:00:00",
+                "application_periods": [
+                    { "start": "2022-01-10T00:00:00", "end": "2022-01-11T00:00:00" }
+                ],
+                "severity": { "wording": "blocking", "effect": "no_service" },
+                "messages": [
+                    {
+                        "text": "no service on line 1",
+                        "channel_name": "web",
+                        "channel_types": ["web"]
+                    }
+                ],
+                "impacted_pt_objects": [ { "line_deleted": { "id": "line_1" } } ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn read_json_disruptions() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut single = std::fs::File::create(directory.path().join("a.json")).unwrap();
+        single.write_all(DISRUPTION.as_bytes()).unwrap();
+        let mut list = std::fs::File::create(directory.path().join("b.json")).unwrap();
+        write!(
+            list,
+            "[{}]",
+            DISRUPTION.replace("disruption_1", "disruption_2")
+        )
+        .unwrap();
+        // not a disruption file, should be ignored
+        std::fs::File::create(directory.path().join("readme.txt")).unwrap();
+        // a bad file, should be skipped
+        let mut bad = std::fs::File::create(directory.path().join("c.json")).unwrap();
+        write!(bad, "{{ \"id\": \"disruption_3\" }}").unwrap();
+
+        let params = ChaosLocalParams {
+            directory: directory.path().to_path_buf(),
+        };
+        let disruptions = read_chaos_disruptions_from_directory(&params).unwrap();
+
+        assert_eq!(disruptions.len(), 2);
+        assert_eq!(disruptions[0].id, "disruption_1");
+        assert_eq!(disruptions[1].id, "disruption_2");
+
+        let impact = &disruptions[0].impacts[0];
+        assert_eq!(impact.severity.effect, Effect::NoService);
+        assert_eq!(impact.application_periods.len(), 1);
+        assert!(matches!(
+            impact.impacted_pt_objects.as_slice(),
+            [Impacted::LineDeleted(line)] if line.id == "line_1"
+        ));
+    }
+
+    #[test]
+    fn reject_empty_period() {
+        let json = DISRUPTION.replace("2022-01-31T00:00:00", "2021-12-31T00:00:00");
+        let result: Result<JsonDisruptions, _> = serde_json::from_str(&json);
+        assert!(result.is_err());
+    }
+}
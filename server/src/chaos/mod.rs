@@ -1,3 +1,4 @@
+pub mod local;
 pub mod models;
 pub mod sql_types;
 // muse model_v2 when PR https://github.com/diesel-rs/diesel/pull/2254 is merged
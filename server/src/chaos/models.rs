@@ -37,7 +37,7 @@
 use crate::{
     chaos::sql_types::{ChannelType as ChannelTypeSQL, PtObjectType, SeverityEffect},
     chaos_proto, info,
-    server_config::ChaosParams,
+    server_config::ChaosDatabaseParams,
 };
 use anyhow::{bail, Context, Error};
 use diesel::{
@@ -48,25 +48,115 @@ use diesel::{
 use launch::loki::{
     chrono::{NaiveDate, NaiveTime, Timelike},
     models::real_time_disruption::chaos_disruption::BlockedStopArea,
-    tracing::error,
+    tracing::{debug, error},
     NaiveDateTime,
 };
 use std::collections::{hash_map::Entry::Vacant, HashMap, HashSet};
 use uuid::Uuid as Uid;
 
 pub fn read_chaos_disruption_from_database(
-    chaos_params: &ChaosParams,
+    chaos_params: &ChaosDatabaseParams,
     publication_period: (NaiveDate, NaiveDate),
     contributors: &[String],
 ) -> Result<Vec<chaos_proto::chaos::Disruption>, Error> {
     let connection = PgConnection::establish(&chaos_params.database)
         .context("Connection to chaos database failed")?;
 
+    info!("Querying chaos database {}", &chaos_params.database);
+    read_disruptions(
+        &connection,
+        chaos_params,
+        publication_period,
+        contributors,
+        None,
+    )
+}
+
+/// The changes in the chaos database since a given datetime
+pub struct ChaosUpdates {
+    /// ids of all disruptions that were created, updated or cancelled
+    pub changed_disruption_ids: Vec<String>,
+    /// current version of the changed disruptions that are still published
+    pub disruptions: Vec<chaos_proto::chaos::Disruption>,
+}
+
+/// Returns the current datetime of the chaos database, in UTC.
+///
+/// It should be obtained *before* reading disruptions, and used as the `since`
+/// argument of the next call to `read_chaos_updates_from_database()`,
+/// so that no change is missed.
+pub fn read_chaos_database_datetime(
+    chaos_params: &ChaosDatabaseParams,
+) -> Result<NaiveDateTime, Error> {
+    let connection = PgConnection::establish(&chaos_params.database)
+        .context("Connection to chaos database failed")?;
+    let rows = diesel::sql_query("SELECT now() AT TIME ZONE 'UTC' AS now")
+        .load::<DatabaseDatetime>(&connection)?;
+    rows.first()
+        .map(|row| row.now)
+        .context("Chaos database did not return its current datetime")
+}
+
+/// Reads the disruptions created, updated or cancelled since `since`
+pub fn read_chaos_updates_from_database(
+    chaos_params: &ChaosDatabaseParams,
+    since: NaiveDateTime,
+    publication_period: (NaiveDate, NaiveDate),
+    contributors: &[String],
+) -> Result<ChaosUpdates, Error> {
+    let connection = PgConnection::establish(&chaos_params.database)
+        .context("Connection to chaos database failed")?;
+
+    debug!(
+        "Querying chaos database {} for updates since {}",
+        &chaos_params.database, since
+    );
+    let changed_disruption_ids: Vec<Uid> =
+        diesel::sql_query(include_str!("updated_disruptions.sql"))
+            .bind::<Array<Text>, _>(contributors)
+            .bind::<Timestamp, _>(since)
+            .load::<UpdatedDisruptionRow>(&connection)?
+            .into_iter()
+            .map(|row| row.disruption_id)
+            .collect();
+
+    if changed_disruption_ids.is_empty() {
+        return Ok(ChaosUpdates {
+            changed_disruption_ids: Vec::new(),
+            disruptions: Vec::new(),
+        });
+    }
+
+    let disruptions = read_disruptions(
+        &connection,
+        chaos_params,
+        publication_period,
+        contributors,
+        Some(&changed_disruption_ids),
+    )?;
+
+    Ok(ChaosUpdates {
+        changed_disruption_ids: changed_disruption_ids
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        disruptions,
+    })
+}
+
+// Reads the published disruptions.
+// If `disruption_ids` is provided, only the disruptions with these ids are read.
+fn read_disruptions(
+    connection: &PgConnection,
+    chaos_params: &ChaosDatabaseParams,
+    publication_period: (NaiveDate, NaiveDate),
+    contributors: &[String],
+    disruption_ids: Option<&[Uid]>,
+) -> Result<Vec<chaos_proto::chaos::Disruption>, Error> {
     let mut disruption_maker = DisruptionMaker::default();
 
     let mut offset_query = 0_u32;
 
-    info!("Querying chaos database {}", &chaos_params.database);
     loop {
         let res = diesel::sql_query(include_str!("query.sql"))
             .bind::<Date, _>(publication_period.1)
@@ -75,7 +165,8 @@ pub fn read_chaos_disruption_from_database(
             .bind::<Array<Text>, _>(contributors)
             .bind::<Int8, _>(i64::from(chaos_params.batch_size))
             .bind::<Int8, _>(i64::from(offset_query))
-            .load::<ChaosRow>(&connection);
+            .bind::<Nullable<Array<Uuid>>, _>(disruption_ids)
+            .load::<ChaosRow>(connection);
         // Increment offset in query
         offset_query += chaos_params.batch_size;
 
@@ -94,6 +185,18 @@ pub fn read_chaos_disruption_from_database(
     Ok(disruption_maker.disruptions.into_values().collect())
 }
 
+#[derive(QueryableByName, Debug)]
+struct DatabaseDatetime {
+    #[sql_type = "Timestamp"]
+    now: NaiveDateTime,
+}
+
+#[derive(QueryableByName, Debug)]
+struct UpdatedDisruptionRow {
+    #[sql_type = "Uuid"]
+    disruption_id: Uid,
+}
+
 // In the SQL query to the Chaos database, we ask to sort the response's rows by disruption id.
 // Then, we feed the rows to DisruptionMaker **in the order we receive them**.
 //
@@ -97,6 +97,7 @@ WHERE (
     AND co.contributor_code = ANY($4)
     AND d.status = 'published'
     AND i.status = 'published'
+    AND ($7::uuid[] IS NULL OR d.id = ANY($7))
 GROUP BY d.id, co.id, c.id, cat.id, a.id, s.id, ls_line.id, rs_line.id,
          ls_start.id, ls_end.id, rs_start.id, rs_end.id, ls_route.id, rs_route.id,
          rail_section.id, t.id, p.id, i.id, m.id, ch.id, adp.value, pr.id, pt.id, ts.id
@@ -0,0 +1,12 @@
+SELECT DISTINCT
+    d.id as disruption_id
+FROM disruption AS d
+         JOIN contributor AS co ON d.contributor_id = co.id
+         LEFT JOIN impact AS i ON i.disruption_id = d.id
+WHERE co.contributor_code = ANY($1)
+    AND (
+        d.created_at > $2
+        OR d.updated_at > $2
+        OR i.created_at > $2
+        OR i.updated_at > $2
+    );
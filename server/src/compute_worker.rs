@@ -37,6 +37,9 @@
 use super::{navitia_proto, response};
 use crate::{
     load_balancer::WorkerId,
+    metrics::Metrics,
+    response_cache::ResponseCache,
+    shared_data::SharedData,
     zmq_worker::{RequestMessage, ResponseMessage},
 };
 use anyhow::{bail, format_err, Context, Error};
@@ -47,40 +50,37 @@ use launch::{
         self,
         chrono::{Duration, Utc},
         filters::{parse_filter, Filters},
-        models::{
-            base_model::{BaseModel, PREFIX_ID_STOP_POINT},
-            real_time_model::RealTimeModel,
-            ModelRefs,
-        },
+        models::{base_model::PREFIX_ID_STOP_POINT, ModelRefs, VehicleJourneyIdx},
         schedule::{self, ScheduleOn, ScheduleRequestInput},
         tracing::{debug, error, info, trace, warn},
         DataTrait, NaiveDateTime, PositiveDuration, RealTimeLevel, RequestInput, TransitData,
     },
     solver::Solver,
 };
-use std::{
-    convert::TryFrom,
-    ops::Deref,
-    sync::{Arc, RwLock},
-};
+use std::{convert::TryFrom, ops::Deref, sync::Arc};
 use tokio::sync::mpsc;
 
-type Data = TransitData;
 pub struct ComputeWorker {
-    data_and_models: Arc<RwLock<(Data, BaseModel, RealTimeModel)>>,
+    data_and_models: Arc<SharedData>,
     solver: Solver,
     worker_id: WorkerId,
     default_request_params: config::RequestParams,
+    default_comparator_type: config::ComparatorType,
     request_channel: mpsc::Receiver<RequestMessage>,
     responses_channel: mpsc::Sender<(WorkerId, ResponseMessage)>,
+    metrics: Arc<Metrics>,
+    response_cache: Option<Arc<ResponseCache>>,
 }
 
 impl ComputeWorker {
     pub fn new(
         worker_id: WorkerId,
-        data_and_models: Arc<RwLock<(TransitData, BaseModel, RealTimeModel)>>,
+        data_and_models: Arc<SharedData>,
         default_request_params: config::RequestParams,
+        default_comparator_type: config::ComparatorType,
         responses_channel: mpsc::Sender<(WorkerId, ResponseMessage)>,
+        metrics: Arc<Metrics>,
+        response_cache: Option<Arc<ResponseCache>>,
     ) -> (Self, mpsc::Sender<RequestMessage>) {
         let solver = Solver::new(0, 0);
 
@@ -91,8 +91,11 @@ impl ComputeWorker {
             solver,
             worker_id,
             default_request_params,
+            default_comparator_type,
             responses_channel,
             request_channel: requests_channel_receiver,
+            metrics,
+            response_cache,
         };
 
         (result, requests_channel_sender)
@@ -119,7 +122,11 @@ impl ComputeWorker {
 
             info!("Worker {} received a request.", self.worker_id.id);
 
+            let requested_api = request_message.payload.requested_api();
+            let solve_start = std::time::Instant::now();
             let reponse_result = self.handle_request(request_message.payload);
+            self.metrics
+                .observe_request(requested_api, solve_start.elapsed());
             let proto_response = match reponse_result {
                 Err(err) => {
                     error!("An error occured while solving a request : {:#?}", err);
@@ -154,6 +161,44 @@ impl ComputeWorker {
     ) -> Result<navitia_proto::Response, Error> {
         check_deadline(&proto_request)?;
 
+        let has_cache_key = self.response_cache.as_ref().and_then(|response_cache| {
+            let key = response_cache.key(&proto_request)?;
+            Some((response_cache.clone(), key))
+        });
+        let (response_cache, key) = match has_cache_key {
+            Some(cache_and_key) => cache_and_key,
+            None => {
+                let (response, _) = self.solve_request(proto_request)?;
+                return Ok(response);
+            }
+        };
+
+        match response_cache.get(&key) {
+            Ok(Some(response)) => {
+                self.metrics.response_cache_lookup(true);
+                return Ok(response);
+            }
+            Ok(None) => self.metrics.response_cache_lookup(false),
+            Err(err) => error!("Could not read from response cache. {:?}", err),
+        }
+
+        // the generation must be read before the data is accessed in solve_request()
+        let has_generation = response_cache.generation();
+        let (response, vehicle_journeys) = self.solve_request(proto_request)?;
+        let insert_result = has_generation.and_then(|generation| {
+            response_cache.insert(key, generation, &response, vehicle_journeys)
+        });
+        if let Err(err) = insert_result {
+            error!("Could not insert response in cache. {:?}", err);
+        }
+        Ok(response)
+    }
+
+    // Returns the response, along with the vehicle journeys it uses
+    fn solve_request(
+        &mut self,
+        proto_request: navitia_proto::Request,
+    ) -> Result<(navitia_proto::Response, Vec<VehicleJourneyIdx>), Error> {
         match proto_request.requested_api() {
             navitia_proto::Api::PtPlanner => {
                 let journey_request = proto_request.journeys.ok_or_else(|| {
@@ -193,12 +238,12 @@ impl ComputeWorker {
     fn handle_journey_request(
         &mut self,
         proto_request: Result<navitia_proto::JourneysRequest, Error>,
-    ) -> Result<navitia_proto::Response, Error> {
+    ) -> Result<(navitia_proto::Response, Vec<VehicleJourneyIdx>), Error> {
         match proto_request {
             Err(err) => {
                 // send a response saying that the journey request could not be handled
                 warn!("Could not handle journey request : {}", err);
-                Ok(make_error_response(&err))
+                Ok((make_error_response(&err), Vec::new()))
             }
             Ok(journey_request) => {
                 let real_time_level = match journey_request.realtime_level() {
@@ -207,15 +252,27 @@ impl ComputeWorker {
                         RealTimeLevel::RealTime
                     }
                 };
-                let rw_lock_read_guard = self.data_and_models.read().map_err(|err| {
-                    format_err!(
-                        "Compute worker {} failed to acquire read lock on data_and_models. {}",
-                        self.worker_id.id,
-                        err
+                let journey_params = match make_journey_params(
+                    &journey_request,
+                    &self.default_request_params,
+                    &self.default_comparator_type,
+                ) {
+                    Ok(journey_params) => journey_params,
+                    Err(err) => {
+                        warn!("Invalid parameters in journey request : {}", err);
+                        return Ok((make_bad_request_response(&err), Vec::new()));
+                    }
+                };
+                // we only hold the lock while cloning the snapshot, so that
+                // new data can be swapped in while this request is processed
+                let snapshot = self.data_and_models.snapshot().with_context(|| {
+                    format!(
+                        "Compute worker {} failed to access data_and_models.",
+                        self.worker_id.id
                     )
                 })?;
 
-                let (data, base_model, real_time_model) = rw_lock_read_guard.deref();
+                let (data, base_model, real_time_model) = snapshot.data_and_models.deref();
                 let model_refs = ModelRefs::new(base_model, real_time_model);
 
                 let solve_result = solve(
@@ -224,13 +281,21 @@ impl ComputeWorker {
                     &model_refs,
                     &mut self.solver,
                     &self.default_request_params,
-                    &config::ComparatorType::Basic,
+                    &journey_params,
                     real_time_level,
                 );
 
+                let vehicle_journeys = match &solve_result {
+                    Ok((_, journeys)) => journeys
+                        .iter()
+                        .flat_map(|(_, journey)| journey.vehicle_sections())
+                        .map(|vehicle_section| vehicle_section.vehicle_journey.clone())
+                        .collect(),
+                    Err(_) => Vec::new(),
+                };
                 let response = make_proto_response(solve_result, &model_refs);
-                Ok(response)
-                // RwLock is released
+                Ok((response, vehicle_journeys))
+                // snapshot is released
             }
         }
     }
@@ -238,23 +303,22 @@ impl ComputeWorker {
     fn handle_places_nearby(
         &mut self,
         proto_request: Result<navitia_proto::PlacesNearbyRequest, Error>,
-    ) -> Result<navitia_proto::Response, Error> {
+    ) -> Result<(navitia_proto::Response, Vec<VehicleJourneyIdx>), Error> {
         match proto_request {
             Err(err) => {
                 // send a response saying that the journey request could not be handled
                 warn!("Could not handle places nearby request : {}", err);
-                Ok(make_error_response(&err))
+                Ok((make_error_response(&err), Vec::new()))
             }
             Ok(places_nearby_request) => {
-                let rw_lock_read_guard = self.data_and_models.read().map_err(|err| {
-                    format_err!(
-                        "Compute worker {} failed to acquire read lock on data_and_models. {}",
-                        self.worker_id.id,
-                        err
+                let snapshot = self.data_and_models.snapshot().with_context(|| {
+                    format!(
+                        "Compute worker {} failed to access data_and_models.",
+                        self.worker_id.id
                     )
                 })?;
 
-                let (_, base_model, real_time_model) = rw_lock_read_guard.deref();
+                let (_, base_model, real_time_model) = snapshot.data_and_models.deref();
                 let model_refs = ModelRefs::new(base_model, real_time_model);
 
                 let radius = places_nearby_request.distance;
@@ -263,16 +327,18 @@ impl ComputeWorker {
                 let count = usize::try_from(places_nearby_request.count).unwrap_or(0);
                 let depth = usize::try_from(places_nearby_request.depth).unwrap_or(2);
 
-                match self.solver.solve_places_nearby(&model_refs, &uri, radius) {
-                    Ok(mut places_nearby_iter) => Ok(response::make_places_nearby_proto_response(
+                // places nearby do not depend on vehicle journeys
+                let response = match self.solver.solve_places_nearby(&model_refs, &uri, radius) {
+                    Ok(mut places_nearby_iter) => response::make_places_nearby_proto_response(
                         &model_refs,
                         &mut places_nearby_iter,
                         start_page,
                         count,
                         depth,
-                    )),
-                    Err(err) => Ok(make_error_response(&format_err!("{}", err))),
-                }
+                    ),
+                    Err(err) => make_error_response(&format_err!("{}", err)),
+                };
+                Ok((response, Vec::new()))
             }
         }
     }
@@ -281,25 +347,25 @@ impl ComputeWorker {
         &mut self,
         proto_request: Result<navitia_proto::NextStopTimeRequest, Error>,
         schedule_on: ScheduleOn,
-    ) -> Result<navitia_proto::Response, Error> {
+    ) -> Result<(navitia_proto::Response, Vec<VehicleJourneyIdx>), Error> {
         match proto_request {
             Err(err) => {
                 // send a response saying that the journey request could not be handled
                 warn!("Could not handle schedule request : {}", err);
-                Ok(make_error_response(&err))
+                Ok((make_error_response(&err), Vec::new()))
             }
             Ok(request) => {
-                let rw_lock_read_guard = self.data_and_models.read().map_err(|err| {
-                    format_err!(
-                        "Compute worker {} failed to acquire read lock on data_and_models. {}",
-                        self.worker_id.id,
-                        err
+                let snapshot = self.data_and_models.snapshot().with_context(|| {
+                    format!(
+                        "Compute worker {} failed to access data_and_models.",
+                        self.worker_id.id
                     )
                 })?;
 
-                let (data, base_model, real_time_model) = rw_lock_read_guard.deref();
+                let (data, base_model, real_time_model) = snapshot.data_and_models.deref();
                 let model_refs = ModelRefs::new(base_model, real_time_model);
 
+                let mut vehicle_journeys = Vec::new();
                 let response_proto = match make_schedule_request(
                     &request,
                     schedule_on,
@@ -323,6 +389,12 @@ impl ComputeWorker {
                                 make_error_response(&format_err!("{}", err))
                             }
                             Ok(response) => {
+                                vehicle_journeys = response
+                                    .iter()
+                                    .map(|schedule_response| {
+                                        schedule_response.vehicle_journey_idx.clone()
+                                    })
+                                    .collect();
                                 let start_page = usize::try_from(request.start_page).unwrap_or(0);
                                 let count = usize::try_from(request.count).unwrap_or(0);
 
@@ -355,7 +427,7 @@ impl ComputeWorker {
                     }
                 };
 
-                Ok(response_proto)
+                Ok((response_proto, vehicle_journeys))
             }
         }
     }
@@ -382,15 +454,96 @@ fn check_deadline(proto_request: &navitia_proto::Request) -> Result<(), Error> {
     Ok(())
 }
 
+// Parameters of a journey request that can be tuned per request.
+// When absent from the request, the configured defaults are used.
+#[derive(Debug)]
+struct JourneyParams {
+    leg_arrival_penalty: PositiveDuration,
+    leg_walking_penalty: PositiveDuration,
+    too_late_threshold: PositiveDuration,
+    comparator_type: config::ComparatorType,
+}
+
+const MAX_LEG_PENALTY_IN_SECONDS: u32 = 60 * 60; // 1 hour
+const MAX_TOO_LATE_THRESHOLD_IN_SECONDS: u32 = 24 * 60 * 60; // 1 day
+
+fn make_journey_params(
+    journey_request: &navitia_proto::JourneysRequest,
+    default_request_params: &config::RequestParams,
+    default_comparator_type: &config::ComparatorType,
+) -> Result<JourneyParams, Error> {
+    let leg_arrival_penalty = duration_param(
+        "arrival_transfer_penalty",
+        journey_request.arrival_transfer_penalty,
+        MAX_LEG_PENALTY_IN_SECONDS,
+    )?
+    .unwrap_or(default_request_params.leg_arrival_penalty);
+
+    let leg_walking_penalty = duration_param(
+        "walking_transfer_penalty",
+        journey_request.walking_transfer_penalty,
+        MAX_LEG_PENALTY_IN_SECONDS,
+    )?
+    .unwrap_or(default_request_params.leg_walking_penalty);
+
+    let too_late_threshold = duration_param(
+        "too_late_threshold",
+        journey_request.too_late_threshold,
+        MAX_TOO_LATE_THRESHOLD_IN_SECONDS,
+    )?
+    .unwrap_or(default_request_params.too_late_threshold);
+
+    let comparator_type = match journey_request.criteria {
+        None => default_comparator_type.clone(),
+        Some(criteria) => match navitia_proto::Criteria::from_i32(criteria) {
+            Some(navitia_proto::Criteria::Classic) => config::ComparatorType::Basic,
+            Some(navitia_proto::Criteria::Occupancy) => config::ComparatorType::Loads,
+            Some(other) => bail!("criteria {:?} is not supported.", other),
+            None => bail!("criteria {} is not a valid value.", criteria),
+        },
+    };
+
+    Ok(JourneyParams {
+        leg_arrival_penalty,
+        leg_walking_penalty,
+        too_late_threshold,
+        comparator_type,
+    })
+}
+
+// A duration given in seconds in the request, that must lie in [0, max_in_seconds]
+fn duration_param(
+    name: &str,
+    value_in_seconds: Option<i32>,
+    max_in_seconds: u32,
+) -> Result<Option<PositiveDuration>, Error> {
+    let value_in_seconds = match value_in_seconds {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+    let seconds = u32::try_from(value_in_seconds)
+        .ok()
+        .filter(|seconds| *seconds <= max_in_seconds)
+        .ok_or_else(|| {
+            format_err!(
+                "{} must be between 0 and {} seconds, but {} was given.",
+                name,
+                max_in_seconds,
+                value_in_seconds
+            )
+        })?;
+    Ok(Some(PositiveDuration::from_hms(0, 0, seconds)))
+}
+
 fn solve(
     journey_request: &navitia_proto::JourneysRequest,
     data: &TransitData,
     model: &ModelRefs<'_>,
     solver: &mut Solver,
     default_request_params: &config::RequestParams,
-    comparator_type: &config::ComparatorType,
+    journey_params: &JourneyParams,
     real_time_level: RealTimeLevel,
-) -> Result<(RequestInput, Vec<loki::response::Response>), Error> {
+) -> Result<(RequestInput, Vec<(loki::NaiveDateTime, loki::Response)>), Error> {
     // println!("{:#?}", journey_request);
     let departures_stop_point_and_fallback_duration = journey_request
         .origin
@@ -462,22 +615,29 @@ fn solve(
         })
         .collect();
 
-    let departure_timestamp_u64 = journey_request
+    if journey_request.datetimes.is_empty() {
+        bail!("No departure datetime provided.");
+    }
+    let departure_datetimes = journey_request
         .datetimes
-        .get(0)
-        .ok_or_else(|| format_err!("No departure datetime provided."))?;
-    let departure_timestamp_i64 = i64::try_from(*departure_timestamp_u64).with_context(|| {
-        format!(
-            "The departure datetime {} cannot be converted to a valid i64 timestamp.",
-            departure_timestamp_u64
-        )
-    })?;
-    let departure_datetime = loki::NaiveDateTime::from_timestamp(departure_timestamp_i64, 0);
-
-    info!(
-        "Requested timestamp {}, datetime {}",
-        departure_timestamp_u64, departure_datetime
-    );
+        .iter()
+        .map(|departure_timestamp_u64| {
+            let departure_timestamp_i64 =
+                i64::try_from(*departure_timestamp_u64).with_context(|| {
+                    format!(
+                        "The departure datetime {} cannot be converted to a valid i64 timestamp.",
+                        departure_timestamp_u64
+                    )
+                })?;
+            let departure_datetime =
+                loki::NaiveDateTime::from_timestamp(departure_timestamp_i64, 0);
+            info!(
+                "Requested timestamp {}, datetime {}",
+                departure_timestamp_u64, departure_datetime
+            );
+            Ok(departure_datetime)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
 
     let max_journey_duration = u32::try_from(journey_request.max_duration)
         .map(|duration| PositiveDuration::from_hms(0, 0, duration))
@@ -520,14 +680,14 @@ fn solve(
     );
 
     let request_input = RequestInput {
-        datetime: departure_datetime,
+        datetime: departure_datetimes[0],
         departures_stop_point_and_fallback_duration,
         arrivals_stop_point_and_fallback_duration,
-        leg_arrival_penalty: default_request_params.leg_arrival_penalty,
-        leg_walking_penalty: default_request_params.leg_walking_penalty,
+        leg_arrival_penalty: journey_params.leg_arrival_penalty,
+        leg_walking_penalty: journey_params.leg_walking_penalty,
         max_nb_of_legs,
         max_journey_duration,
-        too_late_threshold: default_request_params.too_late_threshold,
+        too_late_threshold: journey_params.too_late_threshold,
         real_time_level,
     };
 
@@ -537,22 +697,23 @@ fn solve(
     };
     trace!("{:#?}", request_input);
 
-    let responses = solver.solve_journey_request(
+    let responses = solver.solve_journey_request_for_datetimes(
         data,
         model,
         &request_input,
+        &departure_datetimes,
         data_filters,
-        comparator_type,
+        &journey_params.comparator_type,
         &datetime_represent,
     )?;
-    for response in &responses {
+    for (_, response) in &responses {
         debug!("{}", response.print(model)?);
     }
     Ok((request_input, responses))
 }
 
 fn make_proto_response(
-    solve_result: Result<(RequestInput, Vec<loki::Response>), Error>,
+    solve_result: Result<(RequestInput, Vec<(loki::NaiveDateTime, loki::Response)>), Error>,
     model: &ModelRefs<'_>,
 ) -> navitia_proto::Response {
     match solve_result {
@@ -579,6 +740,16 @@ fn make_proto_response(
     }
 }
 
+fn make_bad_request_response(error: &Error) -> navitia_proto::Response {
+    let mut proto_response = navitia_proto::Response::default();
+    proto_response.set_response_type(navitia_proto::ResponseType::NoSolution);
+    let mut proto_error = navitia_proto::Error::default();
+    proto_error.set_id(navitia_proto::error::ErrorId::BadFormat);
+    proto_error.message = Some(format!("{}", error));
+    proto_response.error = Some(proto_error);
+    proto_response
+}
+
 fn make_error_response(error: &Error) -> navitia_proto::Response {
     let mut proto_response = navitia_proto::Response::default();
     proto_response.set_response_type(navitia_proto::ResponseType::NoSolution);
@@ -646,3 +817,100 @@ fn make_schedule_request<'a>(
         schedule_on,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journey_params_fall_back_to_defaults() {
+        let default_request_params = config::RequestParams::default();
+        let journey_request = navitia_proto::JourneysRequest::default();
+        let journey_params = make_journey_params(
+            &journey_request,
+            &default_request_params,
+            &config::ComparatorType::Loads,
+        )
+        .unwrap();
+        assert_eq!(
+            journey_params.leg_arrival_penalty,
+            default_request_params.leg_arrival_penalty
+        );
+        assert_eq!(
+            journey_params.leg_walking_penalty,
+            default_request_params.leg_walking_penalty
+        );
+        assert_eq!(
+            journey_params.too_late_threshold,
+            default_request_params.too_late_threshold
+        );
+        assert!(matches!(
+            journey_params.comparator_type,
+            config::ComparatorType::Loads
+        ));
+    }
+
+    #[test]
+    fn journey_params_from_request() {
+        let journey_request = navitia_proto::JourneysRequest {
+            arrival_transfer_penalty: Some(0),
+            walking_transfer_penalty: Some(300),
+            too_late_threshold: Some(3600),
+            criteria: Some(navitia_proto::Criteria::Occupancy as i32),
+            ..Default::default()
+        };
+        let journey_params = make_journey_params(
+            &journey_request,
+            &config::RequestParams::default(),
+            &config::ComparatorType::Basic,
+        )
+        .unwrap();
+        assert_eq!(
+            journey_params.leg_arrival_penalty,
+            PositiveDuration::from_hms(0, 0, 0)
+        );
+        assert_eq!(
+            journey_params.leg_walking_penalty,
+            PositiveDuration::from_hms(0, 5, 0)
+        );
+        assert_eq!(
+            journey_params.too_late_threshold,
+            PositiveDuration::from_hms(1, 0, 0)
+        );
+        assert!(matches!(
+            journey_params.comparator_type,
+            config::ComparatorType::Loads
+        ));
+    }
+
+    #[test]
+    fn invalid_journey_params() {
+        let default_request_params = config::RequestParams::default();
+        let invalid_requests = [
+            navitia_proto::JourneysRequest {
+                arrival_transfer_penalty: Some(-1),
+                ..Default::default()
+            },
+            navitia_proto::JourneysRequest {
+                walking_transfer_penalty: Some(2 * 60 * 60),
+                ..Default::default()
+            },
+            navitia_proto::JourneysRequest {
+                too_late_threshold: Some(2 * 24 * 60 * 60),
+                ..Default::default()
+            },
+            navitia_proto::JourneysRequest {
+                criteria: Some(-1),
+                ..Default::default()
+            },
+        ];
+        for journey_request in &invalid_requests {
+            let result = make_journey_params(
+                journey_request,
+                &default_request_params,
+                &config::ComparatorType::Basic,
+            );
+            assert!(result.is_err(), "{:?}", journey_request);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::{bail, Error};
+use launch::loki::{chrono::NaiveDate, models::base_model::BaseModel, DataTrait, TransitData};
+
+use crate::server_config::DataChecksParams;
+
+/// The figures of a dataset that are compared by the data checks.
+#[derive(Debug, Clone)]
+pub struct DataSummary {
+    pub nb_of_vehicle_journeys: usize,
+    pub nb_of_stop_points: usize,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub nb_of_insertion_errors: usize,
+}
+
+impl DataSummary {
+    pub fn new(data: &TransitData, base_model: &BaseModel) -> Self {
+        let calendar = data.calendar();
+        Self {
+            nb_of_vehicle_journeys: base_model.nb_of_vehicle_journeys(),
+            nb_of_stop_points: base_model.nb_of_stop_points(),
+            start_date: calendar.first_date(),
+            end_date: calendar.last_date(),
+            nb_of_insertion_errors: data.nb_of_base_insertion_errors(),
+        }
+    }
+}
+
+/// Checks that `new_data` can replace `current_data`.
+///
+/// Returns an error explaining why `new_data` is rejected when one of the checks fails.
+pub fn check_new_data(
+    params: &DataChecksParams,
+    new_data: &DataSummary,
+    current_data: &DataSummary,
+    today: NaiveDate,
+) -> Result<(), Error> {
+    if params.check_validity_period && (today < new_data.start_date || new_data.end_date < today) {
+        bail!(
+            "The validity period of the new data [{}, {}] does not contain today {}.",
+            new_data.start_date,
+            new_data.end_date,
+            today
+        );
+    }
+    check_drop(
+        "vehicle journeys",
+        new_data.nb_of_vehicle_journeys,
+        current_data.nb_of_vehicle_journeys,
+        params.max_vehicle_journeys_drop_percent,
+    )?;
+    check_drop(
+        "stop points",
+        new_data.nb_of_stop_points,
+        current_data.nb_of_stop_points,
+        params.max_stop_points_drop_percent,
+    )?;
+    if new_data.nb_of_insertion_errors > params.max_insertion_errors {
+        bail!(
+            "{} vehicle journeys of the new data could not be inserted in the timetables, \
+             while at most {} are allowed.",
+            new_data.nb_of_insertion_errors,
+            params.max_insertion_errors
+        );
+    }
+    Ok(())
+}
+
+fn check_drop(
+    objects: &str,
+    new_count: usize,
+    current_count: usize,
+    max_drop_percent: f64,
+) -> Result<(), Error> {
+    if new_count >= current_count {
+        return Ok(());
+    }
+    let drop_percent = 100.0 * (current_count - new_count) as f64 / current_count as f64;
+    if drop_percent > max_drop_percent {
+        bail!(
+            "The number of {} drops by {:.1}% ({} in current data, {} in new data), \
+             while a drop of at most {}% is allowed.",
+            objects,
+            drop_percent,
+            current_count,
+            new_count,
+            max_drop_percent
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(nb_of_vehicle_journeys: usize, nb_of_stop_points: usize) -> DataSummary {
+        DataSummary {
+            nb_of_vehicle_journeys,
+            nb_of_stop_points,
+            start_date: NaiveDate::from_ymd(2022, 1, 1),
+            end_date: NaiveDate::from_ymd(2022, 12, 31),
+            nb_of_insertion_errors: 0,
+        }
+    }
+
+    #[test]
+    fn accept_similar_data() {
+        let params = DataChecksParams::default();
+        let today = NaiveDate::from_ymd(2022, 6, 1);
+        let current_data = summary(1000, 100);
+        check_new_data(&params, &summary(900, 100), &current_data, today).unwrap();
+        check_new_data(&params, &summary(2000, 150), &current_data, today).unwrap();
+    }
+
+    #[test]
+    fn reject_data_not_valid_today() {
+        let mut params = DataChecksParams::default();
+        let current_data = summary(1000, 100);
+        let new_data = summary(1000, 100);
+        let today = NaiveDate::from_ymd(2023, 1, 1);
+        assert!(check_new_data(&params, &new_data, &current_data, today).is_err());
+
+        params.check_validity_period = false;
+        check_new_data(&params, &new_data, &current_data, today).unwrap();
+    }
+
+    #[test]
+    fn reject_large_drops() {
+        let params = DataChecksParams::default();
+        let today = NaiveDate::from_ymd(2022, 6, 1);
+        let current_data = summary(1000, 100);
+
+        let err = check_new_data(&params, &summary(700, 100), &current_data, today).unwrap_err();
+        assert!(err.to_string().contains("vehicle journeys drops by 30.0%"));
+
+        let err = check_new_data(&params, &summary(1000, 50), &current_data, today).unwrap_err();
+        assert!(err.to_string().contains("stop points drops by 50.0%"));
+    }
+
+    #[test]
+    fn reject_too_many_insertion_errors() {
+        let params = DataChecksParams {
+            max_insertion_errors: 10,
+            ..DataChecksParams::default()
+        };
+        let today = NaiveDate::from_ymd(2022, 6, 1);
+        let current_data = summary(1000, 100);
+        let mut new_data = summary(1000, 100);
+        new_data.nb_of_insertion_errors = 10;
+        check_new_data(&params, &new_data, &current_data, today).unwrap();
+
+        new_data.nb_of_insertion_errors = 11;
+        assert!(check_new_data(&params, &new_data, &current_data, today).is_err());
+    }
+}
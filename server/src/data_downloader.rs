@@ -36,10 +36,11 @@
 
 use anyhow::{bail, Context, Error};
 use core::time::Duration;
+use launch::loki::tracing::{error, warn};
 use s3::{creds::Credentials, Bucket, Region};
 use std::io::Cursor;
 
-use crate::server_config::BucketParams;
+use crate::{archive_cache::ArchiveCache, server_config::BucketParams};
 pub struct DataDownloader {
     bucket: Bucket,
 
@@ -48,6 +49,12 @@ pub struct DataDownloader {
 
     // latest version_id of ntfs/gtfs file
     data_version_id: String,
+
+    // copy on local disk of the last archive loaded
+    cache: Option<ArchiveCache>,
+
+    // true when the last downloaded archive is stored as pending in the cache
+    has_pending_archive: bool,
 }
 
 pub enum DownloadStatus {
@@ -89,10 +96,18 @@ impl DataDownloader {
         let timeout = Duration::from_millis(u64::from(config.bucket_timeout_in_ms));
         bucket.set_request_timeout(Some(timeout));
 
+        let cache = config
+            .local_cache_directory
+            .as_ref()
+            .map(|directory| ArchiveCache::new(directory))
+            .transpose()?;
+
         Ok(Self {
             bucket,
             data_key: config.data_path_key.clone(),
             data_version_id: "".to_string(),
+            cache,
+            has_pending_archive: false,
         })
     }
 
@@ -141,9 +156,22 @@ impl DataDownloader {
     pub async fn download_data(&mut self) -> Result<DownloadStatus, Error> {
         // get meta info about file we are going to download
         // if file has already been download skip the download
-        let version_id = self.get_file_version_id(&self.data_key).await?;
+        let version_id = match self.get_file_version_id(&self.data_key).await {
+            Ok(version_id) => version_id,
+            Err(err) => return self.read_from_cache(err),
+        };
         if self.data_version_id != version_id {
-            let data = self.download_file(&self.data_key).await?;
+            let data = match self.download_file(&self.data_key).await {
+                Ok(data) => data,
+                Err(err) => return self.read_from_cache(err),
+            };
+            if let Some(cache) = &self.cache {
+                self.has_pending_archive = false;
+                match cache.store_pending(&data, &version_id) {
+                    Ok(()) => self.has_pending_archive = true,
+                    Err(err) => error!("Could not store archive in local cache. {:?}", err),
+                }
+            }
             let cursor = std::io::Cursor::new(data);
             self.data_version_id = version_id;
             Ok(DownloadStatus::Ok(cursor))
@@ -151,4 +179,38 @@ impl DataDownloader {
             Ok(DownloadStatus::AlreadyPresent)
         }
     }
+
+    /// To be called once the last downloaded archive has been loaded successfully,
+    /// so that it replaces the archive in local cache.
+    pub fn archive_loaded(&mut self) -> Result<(), Error> {
+        if let (Some(cache), true) = (&self.cache, self.has_pending_archive) {
+            self.has_pending_archive = false;
+            cache.commit_pending()?;
+        }
+        Ok(())
+    }
+
+    // When the bucket cannot be reached at startup, the archive in local cache is used instead.
+    // Afterwards, we keep the data in use until the bucket is reachable again.
+    fn read_from_cache(&mut self, download_error: Error) -> Result<DownloadStatus, Error> {
+        let cache = match &self.cache {
+            Some(cache) if self.data_version_id.is_empty() => cache,
+            _ => return Err(download_error),
+        };
+        match cache.read() {
+            Ok((data, version_id)) => {
+                warn!(
+                    "Could not download data from bucket {}. {:?}. \
+                     I'll use the archive in local cache, with version_id {}.",
+                    self.bucket.name, download_error, version_id
+                );
+                self.data_version_id = version_id;
+                Ok(DownloadStatus::Ok(std::io::Cursor::new(data)))
+            }
+            Err(err) => {
+                error!("Could not read archive from local cache. {:?}", err);
+                Err(download_error)
+            }
+        }
+    }
 }
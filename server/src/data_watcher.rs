@@ -0,0 +1,388 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::{bail, Context, Error};
+use launch::{
+    config::{launch_params::LocalFileParams, InputDataType, TransfersParams},
+    loki::{
+        models::base_model::BaseModel,
+        tracing::{error, info},
+        PositiveDuration,
+    },
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::server_config::WatchedDirectoryParams;
+
+const READY_MARKER_EXTENSION: &str = "ready";
+
+/// Detects new datasets published in a local directory.
+/// See [`WatchedDirectoryParams`] for the layout of the directory.
+pub struct DataWatcher {
+    params: WatchedDirectoryParams,
+
+    // name of the dataset in use
+    loaded_dataset: Option<String>,
+
+    // dataset returned by new_dataset(), not yet loaded
+    pending_dataset: Option<String>,
+
+    // newest dataset seen while polling, waiting to be stable for `debounce`
+    candidate: Option<Candidate>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub name: String,
+    pub path: PathBuf,
+    is_zip: bool,
+}
+
+struct Candidate {
+    name: String,
+    fingerprint: Fingerprint,
+    unchanged_since: Instant,
+    // a reload has already been triggered for this version of the candidate
+    triggered: bool,
+}
+
+// total size, number of files and last modification time of a dataset
+type Fingerprint = (u64, usize, Option<SystemTime>);
+
+impl DataWatcher {
+    pub fn new(params: &WatchedDirectoryParams) -> Self {
+        Self {
+            params: params.clone(),
+            loaded_dataset: None,
+            pending_dataset: None,
+            candidate: None,
+        }
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.params.poll_interval.total_seconds())
+    }
+
+    /// Called at each poll. Returns true when a dataset newer than the one in use
+    /// has not been modified for `debounce`, and should be loaded.
+    ///
+    /// Returns true only once for a given version of a dataset, so that a dataset
+    /// that cannot be loaded does not trigger a reload at each poll.
+    pub fn poll(&mut self) -> Result<bool, Error> {
+        let newest_dataset = match self.newest_dataset()? {
+            Some(dataset) if Some(&dataset.name) != self.loaded_dataset.as_ref() => dataset,
+            _ => {
+                self.candidate = None;
+                return Ok(false);
+            }
+        };
+        let fingerprint = fingerprint(&newest_dataset)?;
+        let debounce = to_duration(&self.params.debounce);
+        match &mut self.candidate {
+            Some(candidate)
+                if candidate.name == newest_dataset.name
+                    && candidate.fingerprint == fingerprint =>
+            {
+                if candidate.triggered || candidate.unchanged_since.elapsed() < debounce {
+                    return Ok(false);
+                }
+                candidate.triggered = true;
+                Ok(true)
+            }
+            _ => {
+                info!(
+                    "New dataset {} detected in {:?}.",
+                    newest_dataset.name, self.params.directory
+                );
+                let triggered = debounce.is_zero();
+                self.candidate = Some(Candidate {
+                    name: newest_dataset.name,
+                    fingerprint,
+                    unchanged_since: Instant::now(),
+                    triggered,
+                });
+                Ok(triggered)
+            }
+        }
+    }
+
+    /// Returns the newest dataset, or None if it is already in use.
+    pub fn new_dataset(&mut self) -> Result<Option<Dataset>, Error> {
+        let dataset = match self.newest_dataset()? {
+            Some(dataset) => dataset,
+            None => bail!("No dataset found in {:?}", self.params.directory),
+        };
+        if Some(&dataset.name) == self.loaded_dataset.as_ref() {
+            return Ok(None);
+        }
+        self.pending_dataset = Some(dataset.name.clone());
+        Ok(Some(dataset))
+    }
+
+    /// To be called once the dataset returned by `new_dataset()` has been loaded successfully.
+    /// Older datasets are then removed, except the `nb_of_datasets_kept - 1` most recent ones.
+    pub fn dataset_loaded(&mut self) {
+        let loaded_dataset = match self.pending_dataset.take() {
+            Some(dataset) => dataset,
+            None => return,
+        };
+        self.loaded_dataset = Some(loaded_dataset.clone());
+        if let Err(err) = self.remove_old_datasets(&loaded_dataset) {
+            error!("Could not remove old datasets. {:?}", err);
+        }
+    }
+
+    pub fn read_dataset(
+        &self,
+        dataset: &Dataset,
+        input_data_type: InputDataType,
+        default_transfer_duration: PositiveDuration,
+        transfers_params: &TransfersParams,
+    ) -> Result<BaseModel, Error> {
+        info!("Reading dataset {:?}", dataset.path);
+        if dataset.is_zip {
+            let file = File::open(&dataset.path)
+                .with_context(|| format!("Could not open {:?}", dataset.path))?;
+            let loads_data_file = self
+                .params
+                .loads_data_path
+                .as_ref()
+                .map(|path| File::open(path).with_context(|| format!("Could not open {:?}", path)))
+                .transpose()?;
+            launch::read::read_model_from_zip_reader(
+                file,
+                loads_data_file,
+                &dataset.name,
+                input_data_type,
+                default_transfer_duration,
+                transfers_params,
+            )
+        } else {
+            let local_file_params = LocalFileParams {
+                input_data_path: dataset.path.clone(),
+                loads_data_path: self.params.loads_data_path.clone(),
+                snapshot_path: None,
+                merged_inputs: Vec::new(),
+            };
+            launch::read::read_model(
+                &local_file_params,
+                input_data_type,
+                default_transfer_duration,
+                transfers_params,
+            )
+        }
+    }
+
+    // datasets sorted by name, the newest being the last
+    fn datasets(&self) -> Result<Vec<Dataset>, Error> {
+        let directory = &self.params.directory;
+        let entries = std::fs::read_dir(directory)
+            .with_context(|| format!("Could not read directory {:?}", directory))?;
+        let mut datasets = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(name) if !name.starts_with('.') => name.to_string(),
+                _ => continue,
+            };
+            let is_zip = matches!(path.extension().and_then(|ext| ext.to_str()), Some("zip"));
+            if !(entry.file_type()?.is_dir() || is_zip) {
+                continue;
+            }
+            if self.params.require_ready_marker && !ready_marker_path(&path).exists() {
+                continue;
+            }
+            datasets.push(Dataset { name, path, is_zip });
+        }
+        datasets.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        Ok(datasets)
+    }
+
+    fn newest_dataset(&self) -> Result<Option<Dataset>, Error> {
+        Ok(self.datasets()?.pop())
+    }
+
+    fn remove_old_datasets(&self, loaded_dataset: &str) -> Result<(), Error> {
+        let older_datasets: Vec<Dataset> = self
+            .datasets()?
+            .into_iter()
+            .filter(|dataset| dataset.name.as_str() < loaded_dataset)
+            .collect();
+        let nb_of_older_datasets_kept = self.params.nb_of_datasets_kept.saturating_sub(1);
+        let nb_to_remove = older_datasets
+            .len()
+            .saturating_sub(nb_of_older_datasets_kept);
+        for dataset in &older_datasets[..nb_to_remove] {
+            info!("Removing old dataset {:?}", dataset.path);
+            let removal = if dataset.is_zip {
+                std::fs::remove_file(&dataset.path)
+            } else {
+                std::fs::remove_dir_all(&dataset.path)
+            };
+            removal.with_context(|| format!("Could not remove {:?}", dataset.path))?;
+            let ready_marker = ready_marker_path(&dataset.path);
+            if ready_marker.exists() {
+                std::fs::remove_file(&ready_marker)
+                    .with_context(|| format!("Could not remove {:?}", ready_marker))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn ready_marker_path(dataset_path: &Path) -> PathBuf {
+    let mut marker = dataset_path.as_os_str().to_owned();
+    marker.push(".");
+    marker.push(READY_MARKER_EXTENSION);
+    PathBuf::from(marker)
+}
+
+fn fingerprint(dataset: &Dataset) -> Result<Fingerprint, Error> {
+    let mut fingerprint = (0, 0, None);
+    let mut add_file = |metadata: std::fs::Metadata| -> Result<(), Error> {
+        fingerprint.0 += metadata.len();
+        fingerprint.1 += 1;
+        fingerprint.2 = fingerprint.2.max(Some(metadata.modified()?));
+        Ok(())
+    };
+    if dataset.is_zip {
+        add_file(std::fs::metadata(&dataset.path)?)?;
+    } else {
+        for entry in std::fs::read_dir(&dataset.path)? {
+            add_file(entry?.metadata()?)?;
+        }
+    }
+    Ok(fingerprint)
+}
+
+fn to_duration(duration: &PositiveDuration) -> Duration {
+    Duration::from_secs(duration.total_seconds())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_config::default_watch_poll_interval;
+    use std::str::FromStr;
+
+    fn params(directory: &Path, require_ready_marker: bool) -> WatchedDirectoryParams {
+        WatchedDirectoryParams {
+            directory: directory.to_path_buf(),
+            loads_data_path: None,
+            require_ready_marker,
+            poll_interval: default_watch_poll_interval(),
+            debounce: PositiveDuration::from_str("00:00:00").unwrap(),
+            nb_of_datasets_kept: 2,
+        }
+    }
+
+    fn publish(directory: &Path, name: &str) {
+        let dataset = directory.join(name);
+        std::fs::create_dir(&dataset).unwrap();
+        std::fs::write(dataset.join("stops.txt"), "stop_id").unwrap();
+    }
+
+    #[test]
+    fn newest_dataset_is_detected_once() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut watcher = DataWatcher::new(&params(directory.path(), false));
+        assert!(watcher.new_dataset().is_err());
+        assert!(!watcher.poll().unwrap());
+
+        publish(directory.path(), "v01");
+        std::fs::write(directory.path().join("notes.txt"), "not a dataset").unwrap();
+        assert!(watcher.poll().unwrap());
+        assert!(!watcher.poll().unwrap());
+        let dataset = watcher.new_dataset().unwrap().unwrap();
+        assert_eq!(dataset.name, "v01");
+        watcher.dataset_loaded();
+        assert!(watcher.new_dataset().unwrap().is_none());
+
+        publish(directory.path(), "v02");
+        std::fs::write(directory.path().join("v00.zip"), "older").unwrap();
+        assert!(watcher.poll().unwrap());
+        assert_eq!(watcher.new_dataset().unwrap().unwrap().name, "v02");
+    }
+
+    #[test]
+    fn dataset_is_detected_once_ready() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut watcher = DataWatcher::new(&params(directory.path(), true));
+        publish(directory.path(), "v01");
+        assert!(!watcher.poll().unwrap());
+
+        std::fs::write(directory.path().join("v01.ready"), "").unwrap();
+        assert!(watcher.poll().unwrap());
+    }
+
+    #[test]
+    fn detection_waits_for_debounce() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut params = params(directory.path(), false);
+        params.debounce = PositiveDuration::from_str("00:01:00").unwrap();
+        let mut watcher = DataWatcher::new(&params);
+        publish(directory.path(), "v01");
+        assert!(!watcher.poll().unwrap());
+        assert!(!watcher.poll().unwrap());
+    }
+
+    #[test]
+    fn old_datasets_are_removed() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut watcher = DataWatcher::new(&params(directory.path(), false));
+        for name in ["v01", "v02", "v03"] {
+            publish(directory.path(), name);
+            std::fs::write(ready_marker_path(&directory.path().join(name)), "").unwrap();
+        }
+        watcher.new_dataset().unwrap();
+        watcher.dataset_loaded();
+
+        let names: Vec<String> = watcher
+            .datasets()
+            .unwrap()
+            .into_iter()
+            .map(|dataset| dataset.name)
+            .collect();
+        assert_eq!(names, vec!["v02", "v03"]);
+        assert!(!directory.path().join("v01.ready").exists());
+        assert!(directory.path().join("v02.ready").exists());
+    }
+}
@@ -35,12 +35,24 @@
 // www.navitia.io
 
 use crate::{
+    admin_worker::{
+        AdminCommand, AdminRequest, AdminResponse, ChaosDisruptionInfo, DisruptionNotApplied,
+        KirinDisruptionInfo,
+    },
     chaos, chaos_proto,
+    data_checks::{check_new_data, DataSummary},
     handle_chaos_message::make_datetime,
     handle_kirin_message::handle_kirin_protobuf,
-    load_balancer::{LoadBalancerChannels, LoadBalancerOrder},
+    handle_siri_message::{
+        make_chaos_disruption, make_kirin_disruption, parse_siri_xml, SiriCodes, SiriDelivery,
+    },
     master_worker::DataAndModels,
-    server_config::ServerConfig,
+    metrics::{Metrics, RealTimeSource},
+    real_time_journal::{base_data_fingerprint, RealTimeJournal},
+    real_time_sources::{launch_real_time_sources, LocalMessage},
+    response_cache::ResponseCache,
+    server_config::{ChaosParams, ServerConfig, SiriParams},
+    shared_data::SharedData,
     status_worker::{BaseDataInfo, StatusUpdate},
 };
 
@@ -65,13 +77,16 @@ use std::ops::Deref;
 
 use futures::StreamExt;
 use launch::loki::{
-    chrono::Utc,
+    chrono::{self, NaiveDate, Utc},
     chrono_tz,
+    memory_usage::MemoryUsage,
     models::{
         base_model::BaseModel,
         real_time_disruption::{
-            chaos_disruption::{cancel_chaos_disruption, store_and_apply_chaos_disruption},
-            kirin_disruption::store_and_apply_kirin_disruption,
+            chaos_disruption::{
+                cancel_chaos_disruption, store_and_apply_chaos_disruption, ChaosDisruption,
+            },
+            kirin_disruption::{cancel_kirin_disruption, store_and_apply_kirin_disruption},
         },
         RealTimeModel,
     },
@@ -79,35 +94,80 @@ use launch::loki::{
     DataTrait, NaiveDateTime,
 };
 
-use std::{
-    sync::{Arc, RwLock},
-    thread,
-};
+use std::{sync::Arc, thread};
 
 use crate::{
     data_downloader::{DataDownloader, DownloadStatus},
+    data_watcher::DataWatcher,
     handle_chaos_message::handle_chaos_protobuf,
+    rabbitmq_connection::RabbitMqConnection,
     server_config::DataSourceParams,
 };
 use launch::config::launch_params::LocalFileParams;
-use tokio::{runtime::Builder, sync::mpsc, time::Duration};
+use tokio::{runtime::Handle, sync::mpsc, time::Duration};
+
+// Changes committed in the chaos database shortly before it was last read
+// may have a created_at/updated_at older than the datetime we read,
+// so the incremental sync reads again the changes of this last period.
+// Applying a disruption again is harmless, since its previous version is cancelled first.
+const CHAOS_SYNC_OVERLAP_MINUTES: i64 = 1;
+
+// The chaos disruptions read, their protobufs when read from the database,
+// and the datetime of the database when it was read
+type ChaosRead = (
+    Vec<ChaosDisruption>,
+    Vec<chaos_proto::chaos::Disruption>,
+    Option<NaiveDateTime>,
+);
 
 pub struct DataWorker {
     config: ServerConfig,
 
-    data_and_models: Arc<RwLock<DataAndModels>>,
+    data_and_models: Arc<SharedData>,
 
-    load_balancer_channels: LoadBalancerChannels,
+    // invalidated when the data and models are modified
+    response_cache: Option<Arc<ResponseCache>>,
 
     host_name: String,
     real_time_queue_name: String,
     reload_queue_name: String,
+    siri_queue_name: String,
 
     kirin_messages: Vec<gtfs_realtime::FeedMessage>,
     kirin_reload_done: bool,
 
+    // xml of the SIRI deliveries received and not yet applied
+    siri_deliveries: Vec<String>,
+    // xml of the SIRI deliveries applied on the current data,
+    // to apply them again when new base data is loaded
+    applied_siri_deliveries: Vec<String>,
+    siri_codes: Arc<SiriCodes>,
+
+    // datetime of the chaos database when it was last read
+    last_chaos_sync: Option<NaiveDateTime>,
+
+    real_time_journal: Option<RealTimeJournal>,
+    // true if the real time journal was replayed during the last data load
+    journal_replayed: bool,
+
+    // to receive real time messages from the local real time sources
+    real_time_sources_sender: mpsc::UnboundedSender<LocalMessage>,
+    real_time_sources_receiver: mpsc::UnboundedReceiver<LocalMessage>,
+
+    // to receive the commands of the admin worker
+    admin_requests_sender: mpsc::UnboundedSender<AdminRequest>,
+    admin_requests_receiver: mpsc::UnboundedReceiver<AdminRequest>,
+
+    // describes the base data currently loaded
+    base_data_info: Option<BaseDataInfo>,
+
     status_update_sender: mpsc::UnboundedSender<StatusUpdate>,
 
+    // shared with the data workers of the other instances using the same endpoint
+    rabbitmq_connection: Arc<RabbitMqConnection>,
+
+    metrics: Arc<Metrics>,
+
     shutdown_sender: mpsc::Sender<()>,
 
     data_source: DataSource,
@@ -116,9 +176,11 @@ pub struct DataWorker {
 impl DataWorker {
     pub fn new(
         config: ServerConfig,
-        data_and_models: Arc<RwLock<DataAndModels>>,
-        load_balancer_channels: LoadBalancerChannels,
+        data_and_models: Arc<SharedData>,
+        response_cache: Option<Arc<ResponseCache>>,
         status_update_sender: mpsc::UnboundedSender<StatusUpdate>,
+        rabbitmq_connection: Arc<RabbitMqConnection>,
+        metrics: Arc<Metrics>,
         shutdown_sender: mpsc::Sender<()>,
     ) -> Result<Self, Error> {
         let host_name = hostname::get()
@@ -139,6 +201,7 @@ impl DataWorker {
         let instance_name = &config.instance_name;
         let real_time_queue_name = format!("loki_{}_{}_real_time", host_name, instance_name);
         let reload_queue_name = format!("loki_{}_{}_reload", host_name, instance_name);
+        let siri_queue_name = format!("loki_{}_{}_siri", host_name, instance_name);
 
         let data_source = match &config.data_source {
             DataSourceParams::Local(local_file_params) => {
@@ -148,19 +211,45 @@ impl DataWorker {
                 let data_downloader = DataDownloader::new(bucket_params)?;
                 DataSource::S3(data_downloader)
             }
+            DataSourceParams::WatchedDirectory(watched_directory_params) => {
+                DataSource::WatchedDirectory(DataWatcher::new(watched_directory_params))
+            }
         };
 
+        let (real_time_sources_sender, real_time_sources_receiver) = mpsc::unbounded_channel();
+        let (admin_requests_sender, admin_requests_receiver) = mpsc::unbounded_channel();
+
+        let real_time_journal = config
+            .real_time_journal
+            .as_ref()
+            .map(RealTimeJournal::new)
+            .transpose()?;
+
         info!("Data worker created.");
         Ok(Self {
             config,
             data_and_models,
-            load_balancer_channels,
+            response_cache,
             host_name,
             real_time_queue_name,
             reload_queue_name,
+            siri_queue_name,
             kirin_messages: Vec::new(),
             kirin_reload_done: false,
+            siri_deliveries: Vec::new(),
+            applied_siri_deliveries: Vec::new(),
+            siri_codes: Arc::new(SiriCodes::empty()),
+            last_chaos_sync: None,
+            real_time_journal,
+            journal_replayed: false,
+            real_time_sources_sender,
+            real_time_sources_receiver,
+            admin_requests_sender,
+            admin_requests_receiver,
+            base_data_info: None,
             status_update_sender,
+            rabbitmq_connection,
+            metrics,
             shutdown_sender,
             data_source,
         })
@@ -175,14 +264,25 @@ impl DataWorker {
 
     async fn run_loop(&mut self) -> Result<(), Error> {
         debug!("DataWorker starts initial load data.");
-        self.load_data()
+        self.load_data(true)
             .await
             .with_context(|| "Error while loading data".to_string())?;
 
-        // After loading data from disk, load all disruption in chaos database
-        // Then apply all extracted disruptions
-        if let Err(err) = self.reload_chaos().await {
-            error!("Error while reloading chaos. {:?}", err);
+        // When the real time state was restored from the journal,
+        // we don't need to ask Kirin for a full reload.
+        if self.journal_replayed {
+            info!("Real time state restored from journal. I skip the reload of kirin.");
+            self.kirin_reload_done = true;
+        }
+
+        launch_real_time_sources(
+            &self.config.real_time_sources,
+            &self.real_time_sources_sender,
+        )?;
+
+        if !self.config.rabbitmq.enabled {
+            info!("RabbitMq is disabled. Real time messages will be received only from local sources.");
+            return self.local_loop().await;
         }
 
         let rabbitmq_connect_retry_interval =
@@ -195,10 +295,23 @@ impl DataWorker {
         // https://docs.rs/tokio/1.14.0/tokio/time/enum.MissedTickBehavior.html#variant.Skip
         retry_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
+        let mut watch_interval = self.watch_interval();
+
         loop {
-            // the first tick() completes immediately
-            // cf https://docs.rs/tokio/1.14.0/tokio/time/fn.interval.html
-            retry_interval.tick().await;
+            // admin requests and new datasets are handled while waiting to (re)connect
+            loop {
+                tokio::select! {
+                    // the first tick() completes immediately
+                    // cf https://docs.rs/tokio/1.14.0/tokio/time/fn.interval.html
+                    _ = retry_interval.tick() => break,
+                    has_admin_request = self.admin_requests_receiver.recv() => {
+                        self.handle_admin_request(has_admin_request, None).await?;
+                    }
+                    _ = next_tick(&mut watch_interval) => {
+                        self.check_watched_directory(None).await?;
+                    }
+                }
+            }
 
             let has_connection = self.connect().await;
 
@@ -245,6 +358,11 @@ impl DataWorker {
 
         let mut reload_consumer = create_consumer(channel, &self.reload_queue_name).await?;
 
+        let mut siri_consumer = match self.config.siri {
+            Some(_) => Some(create_consumer(channel, &self.siri_queue_name).await?),
+            None => None,
+        };
+
         let interval = tokio::time::interval(Duration::from_secs(
             self.config
                 .rabbitmq
@@ -253,12 +371,15 @@ impl DataWorker {
         ));
         tokio::pin!(interval);
 
+        let mut chaos_sync_interval = self.chaos_sync_interval();
+        let mut watch_interval = self.watch_interval();
+
         loop {
             tokio::select! {
                 // sends all messages in the buffer every X seconds
                 _ = interval.tick() => {
-                    if ! self.kirin_messages.is_empty() {
-                        trace!("It's time to apply {} real time updates.", self.kirin_messages.len());
+                    if ! self.kirin_messages.is_empty() || ! self.siri_deliveries.is_empty() {
+                        trace!("It's time to apply {} real time updates.", self.kirin_messages.len() + self.siri_deliveries.len());
                         self.apply_realtime_messages().await?;
                         trace!("Successfully applied real time updates.");
                     }
@@ -273,13 +394,103 @@ impl DataWorker {
                     info!("Received a message on the reload queue.");
                     self.handle_reload_message(has_reload_message, channel).await?;
                 }
+                // when a SIRI delivery arrives, put it in the buffer
+                has_siri_message = next_delivery(&mut siri_consumer) => {
+                    info!("Received a SIRI message.");
+                    self.handle_incoming_siri_message(has_siri_message).await?;
+                }
+                // when a real time message arrives from a local source, put it in the buffer
+                has_local_message = self.real_time_sources_receiver.recv() => {
+                    self.handle_local_message(has_local_message, Some(channel)).await?;
+                }
+                // incremental sync of chaos disruptions
+                _ = next_tick(&mut chaos_sync_interval) => {
+                    self.sync_chaos().await?;
+                }
+                // commands received on the admin endpoint
+                has_admin_request = self.admin_requests_receiver.recv() => {
+                    self.handle_admin_request(has_admin_request, Some(channel)).await?;
+                }
+                // new datasets published in the watched directory
+                _ = next_tick(&mut watch_interval) => {
+                    self.check_watched_directory(Some(channel)).await?;
+                }
             }
         }
     }
 
-    async fn load_data(&mut self) -> Result<DataReloadStatus, Error> {
+    // Same as main_loop(), but without rabbitmq
+    async fn local_loop(&mut self) -> Result<(), Error> {
+        let interval = tokio::time::interval(Duration::from_secs(
+            self.config
+                .rabbitmq
+                .real_time_update_interval
+                .total_seconds(),
+        ));
+        tokio::pin!(interval);
+
+        let mut chaos_sync_interval = self.chaos_sync_interval();
+        let mut watch_interval = self.watch_interval();
+
+        loop {
+            tokio::select! {
+                // sends all messages in the buffer every X seconds
+                _ = interval.tick() => {
+                    if ! self.kirin_messages.is_empty() {
+                        trace!("It's time to apply {} real time updates.", self.kirin_messages.len());
+                        self.apply_realtime_messages().await?;
+                        trace!("Successfully applied real time updates.");
+                    }
+                }
+                // when a real time message arrives from a local source, put it in the buffer
+                has_local_message = self.real_time_sources_receiver.recv() => {
+                    self.handle_local_message(has_local_message, None).await?;
+                }
+                // incremental sync of chaos disruptions
+                _ = next_tick(&mut chaos_sync_interval) => {
+                    self.sync_chaos().await?;
+                }
+                // commands received on the admin endpoint
+                has_admin_request = self.admin_requests_receiver.recv() => {
+                    self.handle_admin_request(has_admin_request, None).await?;
+                }
+                // new datasets published in the watched directory
+                _ = next_tick(&mut watch_interval) => {
+                    self.check_watched_directory(None).await?;
+                }
+            }
+        }
+    }
+
+    async fn handle_local_message(
+        &mut self,
+        has_local_message: Option<LocalMessage>,
+        channel: Option<&lapin::Channel>,
+    ) -> Result<(), Error> {
+        let message = has_local_message.ok_or_else(|| {
+            format_err!("Channel to receive local real time messages has closed.")
+        })?;
+        match message {
+            LocalMessage::FeedMessage(message) => {
+                debug!("Received a real time message from a local source.");
+                self.kirin_messages.push(message);
+            }
+            LocalMessage::Reload => {
+                info!("Received a Reload order from a local source.");
+                self.reload(channel).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // When `replay_journal` is true, the messages of the real time journal
+    // are applied on the new data before it is used to answer requests.
+    async fn load_data(&mut self, replay_journal: bool) -> Result<DataReloadStatus, Error> {
+        let load_start = std::time::Instant::now();
         let config = &self.config;
 
+        // set when the data is read from a snapshot, and thus does not need to be built
+        let mut snapshot_data = None;
         let new_base_model = match &mut self.data_source {
             DataSource::S3(data_downloader) => match data_downloader.download_data().await {
                 Ok(DownloadStatus::Ok(data_reader)) => launch::read::read_model_from_zip_reader(
@@ -288,180 +499,538 @@ impl DataWorker {
                     "S3",
                     config.input_data_type.clone(),
                     config.default_transfer_duration,
+                    &config.transfers,
                 ),
                 Ok(DownloadStatus::AlreadyPresent) => return Ok(DataReloadStatus::Skipped),
                 Err(err) => Err(err),
             },
-            DataSource::Local(local_files) => launch::read::read_model(
-                local_files,
-                config.input_data_type.clone(),
-                config.default_transfer_duration,
+            DataSource::Local(local_files) => {
+                let snapshot = local_files.snapshot_path.as_ref().and_then(|snapshot_path| {
+                    launch::snapshot::read_snapshot(
+                        snapshot_path,
+                        local_files,
+                        &config.input_data_type,
+                        config.default_transfer_duration,
+                        &config.transfers,
+                    )
+                    .map_err(|err| {
+                        warn!(
+                            "Could not use snapshot {:?}. I'll build the data from the input files. {:?}",
+                            snapshot_path, err
+                        );
+                    })
+                    .ok()
+                });
+                match snapshot {
+                    Some((data, base_model)) => {
+                        snapshot_data = Some(data);
+                        Ok(base_model)
+                    }
+                    None => launch::read::read_model(
+                        local_files,
+                        config.input_data_type.clone(),
+                        config.default_transfer_duration,
+                        &config.transfers,
+                    ),
+                }
+            }
+            DataSource::WatchedDirectory(data_watcher) => match data_watcher.new_dataset() {
+                Ok(Some(dataset)) => data_watcher.read_dataset(
+                    &dataset,
+                    config.input_data_type.clone(),
+                    config.default_transfer_duration,
+                    &config.transfers,
+                ),
+                Ok(None) => return Ok(DataReloadStatus::Skipped),
+                Err(err) => Err(err),
+            },
+        };
+
+        let has_current_data = self.base_data_info.is_some();
+        let data_is_read = new_base_model.is_ok();
+        let new_base_model = match new_base_model {
+            Ok(new_base_model) => new_base_model,
+            Err(err) if has_current_data => {
+                error!(
+                    "Could not read data. {:?}. I'll keep using the current data.",
+                    err
+                );
+                let reason = format!("Could not read data. {:?}", err);
+                self.send_status_update(StatusUpdate::BaseDataLoadFailed(reason.clone()))?;
+                return Ok(DataReloadStatus::Rejected(reason));
+            }
+            Err(err) => {
+                error!(
+                    "Could not read data. {:?}.I'll keep running with an empty model.",
+                    err
+                );
+                BaseModel::empty()
+            }
+        };
+
+        let fingerprint = base_data_fingerprint(&new_base_model);
+        let (journal_messages, journal_siri_deliveries) = match &self.real_time_journal {
+            Some(journal) if replay_journal => (
+                journal.read(&fingerprint).unwrap_or_else(|err| {
+                    error!("Could not read real time journal. {:?}", err);
+                    Vec::new()
+                }),
+                journal
+                    .read_siri_deliveries(&fingerprint)
+                    .unwrap_or_else(|err| {
+                        error!(
+                            "Could not read SIRI deliveries of real time journal. {:?}",
+                            err
+                        );
+                        Vec::new()
+                    }),
+            ),
+            // the real time messages applied on the current data are carried over
+            // to the new data, until kirin answers the request for a full reload
+            Some(journal) if has_current_data => (
+                journal.read_all().unwrap_or_else(|err| {
+                    error!("Could not read real time journal. {:?}", err);
+                    Vec::new()
+                }),
+                self.applied_siri_deliveries.clone(),
             ),
+            _ if has_current_data => (Vec::new(), self.applied_siri_deliveries.clone()),
+            _ => (Vec::new(), Vec::new()),
         };
+        let journal_replayed =
+            replay_journal && (!journal_messages.is_empty() || !journal_siri_deliveries.is_empty());
+
+        // The new data is built while the current one keeps answering requests.
+        // Requests are only paused while the pointer to the data is swapped.
+        info!("Model loaded");
+        let new_data = match snapshot_data {
+            Some(data) => data,
+            None => {
+                info!("Starting to build data");
+                launch::read::build_transit_data(&new_base_model)
+            }
+        };
+        info!("Data loaded");
+        if let (Some(data_checks), true) = (&self.config.data_checks, has_current_data) {
+            let new_summary = DataSummary::new(&new_data, &new_base_model);
+            let current_summary = {
+                let snapshot = self.data_and_models.snapshot()?;
+                let (data, base_model, _) = snapshot.data_and_models.deref();
+                DataSummary::new(data, base_model)
+            };
+            let today = Utc::now().naive_utc().date();
+            if let Err(err) = check_new_data(data_checks, &new_summary, &current_summary, today) {
+                error!(
+                    "New data rejected, I'll keep using the current data. {}",
+                    err
+                );
+                self.metrics.data_reload_rejected();
+                self.send_status_update(StatusUpdate::BaseDataLoadFailed(err.to_string()))?;
+                return Ok(DataReloadStatus::Rejected(err.to_string()));
+            }
+        }
+        let mut new_data_and_models = (new_data, Arc::new(new_base_model), RealTimeModel::new());
 
-        let updater = move |data_and_models: &mut DataAndModels| {
-            let new_base_model = new_base_model
-                .map_err(|err| {
-                    error!(
-                        "Could not read data. {:?}.I'll keep running with an empty model.",
-                        err
-                    );
-                })
-                .unwrap_or_else(|_| BaseModel::empty());
-
-            info!("Model loaded");
-            info!("Starting to build data");
-            let new_data = launch::read::build_transit_data(&new_base_model);
-            info!("Data loaded");
-            let new_real_time_model = RealTimeModel::new();
-            data_and_models.0 = new_data;
-            data_and_models.1 = new_base_model;
-            data_and_models.2 = new_real_time_model;
-
-            let calendar = data_and_models.0.calendar();
+        if !journal_messages.is_empty() {
+            info!(
+                "Replaying {} messages from real time journal.",
+                journal_messages.len()
+            );
+            for message in &journal_messages {
+                let result =
+                    handle_realtime_message(&mut new_data_and_models, message, &self.metrics);
+                if let Err(err) = result {
+                    error!("Could not replay real time message. {:?}", err);
+                }
+            }
+        }
+        let mut applied_siri_deliveries = Vec::new();
+        if let (Some(siri_params), false) = (&self.config.siri, journal_siri_deliveries.is_empty())
+        {
+            info!(
+                "Replaying {} SIRI deliveries.",
+                journal_siri_deliveries.len()
+            );
+            let siri_codes = SiriCodes::new(&new_data_and_models.1, siri_params);
+            for (xml, delivery) in parse_siri_deliveries(journal_siri_deliveries) {
+                handle_siri_delivery(
+                    &mut new_data_and_models,
+                    &delivery,
+                    &siri_codes,
+                    siri_params,
+                );
+                applied_siri_deliveries.push(xml);
+            }
+        }
+
+        // When the real time state was restored from the journal,
+        // we don't need to read the chaos database.
+        // Disruptions read from a local chaos directory are not journaled, so we still read them.
+        let chaos_read =
+            if journal_replayed && !matches!(self.config.chaos, Some(ChaosParams::Local(_))) {
+                None
+            } else {
+                let calendar = new_data_and_models.0.calendar();
+                match self.read_chaos((calendar.first_date(), calendar.last_date())) {
+                    Ok(chaos_read) => chaos_read,
+                    Err(err) => {
+                        error!("Error while reloading chaos. {:?}", err);
+                        None
+                    }
+                }
+            };
+        let chaos_read = chaos_read.map(|(disruptions, protos, sync_datetime)| {
+            info!("Applying {} chaos disruptions.", disruptions.len());
+            apply_chaos_disruptions(&mut new_data_and_models, disruptions);
+            (protos, sync_datetime)
+        });
+
+        let base_data_info = {
+            let (data, base_model, _) = &new_data_and_models;
+            let calendar = data.calendar();
             let now = Utc::now().naive_utc();
-            let base_data_info = BaseDataInfo {
+            BaseDataInfo {
                 start_date: calendar.first_date(),
                 end_date: calendar.last_date(),
                 last_load_at: now,
-                dataset_created_at: data_and_models.1.dataset_created_at(),
-                timezone: data_and_models.1.timezone_model().unwrap_or(chrono_tz::UTC),
-                contributors: data_and_models.1.contributors().map(|c| c.id).collect(),
-                publisher_name: data_and_models.1.pubisher_name().map(ToString::to_string),
-            };
-            Ok(base_data_info)
+                dataset_created_at: base_model.dataset_created_at(),
+                timezone: base_model.timezone_model().unwrap_or(chrono_tz::UTC),
+                contributors: base_model.contributors().map(|c| c.id).collect(),
+                publisher_name: base_model.pubisher_name().map(ToString::to_string),
+                memory_usage: memory_usage(&new_data_and_models),
+            }
         };
+        info!("Memory usage :\n{}", base_data_info.memory_usage);
 
-        let load_result = self.update_data_and_models(updater).await;
+        let swap_result = self.data_and_models.swap(new_data_and_models);
 
-        match load_result {
-            Ok(base_data_info) => {
+        match swap_result {
+            Ok((epoch, previous_data_and_models)) => {
+                info!("New data is now used to answer requests. Epoch {}", epoch);
+                if let Some(response_cache) = &self.response_cache {
+                    if let Err(err) = response_cache.invalidate_all() {
+                        error!("Could not invalidate response cache. {:?}", err);
+                    }
+                }
+                self.metrics.observe_data_reload(load_start.elapsed());
+                // the previous data is freed once the last request using it completes
+                drop(previous_data_and_models);
+                self.base_data_info = Some(base_data_info.clone());
                 self.send_status_update(StatusUpdate::BaseDataLoad(base_data_info))?;
+                match (&mut self.data_source, data_is_read) {
+                    (DataSource::S3(data_downloader), true) => {
+                        if let Err(err) = data_downloader.archive_loaded() {
+                            error!("Could not update the archive in local cache. {:?}", err);
+                        }
+                    }
+                    (DataSource::WatchedDirectory(data_watcher), true) => {
+                        data_watcher.dataset_loaded();
+                    }
+                    _ => (),
+                }
+                self.update_siri_codes()?;
+                self.journal_replayed = journal_replayed;
+                if !journal_replayed {
+                    // the journal will now store the messages applied on the new data
+                    if let Some(journal) = &mut self.real_time_journal {
+                        if let Err(err) = journal.reset(&fingerprint) {
+                            error!("Could not reset real time journal. {:?}", err);
+                        }
+                    }
+                    self.append_to_journal(|journal| journal.append(&journal_messages));
+                    self.append_to_journal(|journal| {
+                        journal.append_siri_deliveries(&applied_siri_deliveries)
+                    });
+                }
+                self.applied_siri_deliveries = applied_siri_deliveries;
+                // when chaos could not be read, the next sync will trigger a full reload
+                self.last_chaos_sync = None;
+                if let Some((protos, sync_datetime)) = chaos_read {
+                    self.last_chaos_sync = sync_datetime;
+                    let now = Utc::now().naive_utc();
+                    self.append_to_journal(|journal| {
+                        journal.append_chaos_disruptions(&protos, now)
+                    });
+                    self.send_status_update(StatusUpdate::ChaosReload(now))?;
+                }
                 Ok(DataReloadStatus::Ok)
             }
             Err(err) => {
-                self.send_status_update(StatusUpdate::BaseDataLoadFailed)?;
+                self.send_status_update(StatusUpdate::BaseDataLoadFailed(format!("{:?}", err)))?;
                 Err(err)
             }
         }
     }
 
     async fn reload_chaos(&mut self) -> Result<(), Error> {
-        let chaos_params = match &self.config.chaos {
-            Some(chaos_params) => chaos_params,
+        let (disruptions, protos, sync_datetime) = match self.read_chaos(self.data_period()?)? {
+            Some(chaos_read) => chaos_read,
             None => {
                 warn!("Chaos is not configured. I skip reload of chaos disruptions.");
                 return Ok(());
             }
         };
-        let (start_date, end_date) = {
-            let rw_lock_read_guard = self.data_and_models.read().map_err(|err| {
-                format_err!(
-                    "DataWorker failed to acquire read lock on data_and_models : {}",
-                    err
+        self.update_data_and_models(|data_and_models| {
+            apply_chaos_disruptions(data_and_models, disruptions);
+            Ok(())
+        })?;
+        self.last_chaos_sync = sync_datetime;
+
+        let now = Utc::now().naive_utc();
+        self.append_to_journal(|journal| journal.append_chaos_disruptions(&protos, now));
+        self.send_status_update(StatusUpdate::ChaosReload(now))?;
+        Ok(())
+    }
+
+    // Reads the disruptions of chaos over the given period, with their protobufs when read from the database
+    // and the datetime of the database when it was read.
+    // Returns None when chaos is not configured.
+    fn read_chaos(
+        &self,
+        (start_date, end_date): (NaiveDate, NaiveDate),
+    ) -> Result<Option<ChaosRead>, Error> {
+        let chaos_params = match &self.config.chaos {
+            Some(chaos_params) => chaos_params,
+            None => return Ok(None),
+        };
+        let topics = &self.config.rabbitmq.real_time_topics;
+        let read_result = match chaos_params {
+            ChaosParams::Database(database_params) => {
+                // the datetime of the database is read *before* the disruptions
+                // so that the next incremental sync does not miss any change
+                chaos::models::read_chaos_database_datetime(database_params).and_then(
+                    |sync_datetime| {
+                        let protos = chaos::models::read_chaos_disruption_from_database(
+                            database_params,
+                            (start_date, end_date),
+                            topics,
+                        )?;
+                        let disruptions = decode_chaos_protobufs(&protos, &self.metrics);
+                        Ok((disruptions, protos, Some(sync_datetime)))
+                    },
                 )
-            })?;
-            let (data, _, _) = rw_lock_read_guard.deref();
-            let calendar = data.calendar();
-            (calendar.first_date(), calendar.last_date())
-        }; // lock is released
-        match chaos::models::read_chaos_disruption_from_database(
-            chaos_params,
-            (start_date, end_date),
-            &self.config.rabbitmq.real_time_topics,
-        ) {
-            Err(err) => error!("Loading chaos database failed : {:?}.", err),
-            Ok(disruptions) => {
-                let updater = |data_and_models: &mut DataAndModels| {
-                    let data = &mut data_and_models.0;
-                    let base_model = &data_and_models.1;
-                    let real_time_model = &mut data_and_models.2;
-                    for chaos_disruption in disruptions {
-                        match handle_chaos_protobuf(&chaos_disruption) {
-                            Ok(disruption) => {
-                                store_and_apply_chaos_disruption(
-                                    real_time_model,
-                                    disruption,
-                                    base_model,
-                                    data,
-                                );
-                            }
-                            Err(err) => {
-                                error!(
-                                    "Error while decoding chaos disruption protobuf : {:?}",
-                                    err
-                                );
-                            }
-                        }
+            }
+            ChaosParams::Local(local_params) => {
+                chaos::local::read_chaos_disruptions_from_directory(local_params)
+                    .map(|disruptions| (disruptions, Vec::new(), None))
+            }
+        };
+        read_result
+            .map(Some)
+            .map_err(|err| err.context("Loading chaos disruptions failed."))
+    }
+
+    // Apply the disruptions created, updated or cancelled in the chaos database
+    // since the last time we read it
+    async fn sync_chaos(&mut self) -> Result<(), Error> {
+        let database_params = match &self.config.chaos {
+            Some(ChaosParams::Database(database_params)) => database_params,
+            _ => return Ok(()),
+        };
+        let since = match self.last_chaos_sync {
+            Some(last_chaos_sync) => {
+                last_chaos_sync - chrono::Duration::minutes(CHAOS_SYNC_OVERLAP_MINUTES)
+            }
+            None => {
+                // the chaos database was never read successfully since the last data load
+                // so we need a full reload
+                if let Err(err) = self.reload_chaos().await {
+                    error!("Error while reloading chaos. {:?}", err);
+                }
+                return Ok(());
+            }
+        };
+        let (start_date, end_date) = self.data_period()?;
+        let topics = &self.config.rabbitmq.real_time_topics;
+        let read_result = chaos::models::read_chaos_database_datetime(database_params).and_then(
+            |sync_datetime| {
+                let updates = chaos::models::read_chaos_updates_from_database(
+                    database_params,
+                    since,
+                    (start_date, end_date),
+                    topics,
+                )?;
+                Ok((updates, sync_datetime))
+            },
+        );
+        let (updates, sync_datetime) = match read_result {
+            Ok(result) => result,
+            Err(err) => {
+                error!("Incremental sync of chaos disruptions failed : {:?}.", err);
+                return Ok(());
+            }
+        };
+
+        if !updates.changed_disruption_ids.is_empty() {
+            info!(
+                "{} chaos disruptions changed since {}.",
+                updates.changed_disruption_ids.len(),
+                since
+            );
+            let changed_disruption_ids = updates.changed_disruption_ids;
+            let disruptions = decode_chaos_protobufs(&updates.disruptions, &self.metrics);
+            let updater = move |data_and_models: &mut DataAndModels| {
+                let data = &mut data_and_models.0;
+                let base_model = &data_and_models.1;
+                let real_time_model = &mut data_and_models.2;
+                // cancel the old version of all changed disruptions
+                let mut cancelled_disruption_ids = Vec::new();
+                for disruption_id in changed_disruption_ids {
+                    if real_time_model.contains_chaos_disruption(&disruption_id) {
+                        cancel_chaos_disruption(real_time_model, &disruption_id, base_model, data);
+                        cancelled_disruption_ids.push(disruption_id);
                     }
-                    Ok(())
-                };
-                self.update_data_and_models(updater).await?;
+                }
+                // and apply the new version of the ones still published
+                for disruption in disruptions {
+                    store_and_apply_chaos_disruption(real_time_model, disruption, base_model, data);
+                }
+                Ok(cancelled_disruption_ids)
+            };
+            let cancelled_disruption_ids = self.update_data_and_models(updater)?;
 
-                let now = Utc::now().naive_utc();
-                self.send_status_update(StatusUpdate::ChaosReload(now))?;
+            let now = Utc::now().naive_utc();
+            self.append_to_journal(|journal| {
+                journal.append_chaos_cancellations(&cancelled_disruption_ids, now)?;
+                journal.append_chaos_disruptions(&updates.disruptions, now)
+            });
+            self.send_status_update(StatusUpdate::ChaosReload(now))?;
+        }
+        self.last_chaos_sync = Some(sync_datetime);
+        Ok(())
+    }
+
+    fn data_period(&self) -> Result<(NaiveDate, NaiveDate), Error> {
+        let snapshot = self.data_and_models.snapshot()?;
+        let (data, _, _) = snapshot.data_and_models.deref();
+        let calendar = data.calendar();
+        Ok((calendar.first_date(), calendar.last_date()))
+    }
+
+    fn chaos_sync_interval(&self) -> Option<tokio::time::Interval> {
+        match &self.config.chaos {
+            Some(ChaosParams::Database(database_params)) => {
+                database_params.sync_interval.as_ref().map(|sync_interval| {
+                    let mut interval =
+                        tokio::time::interval(Duration::from_secs(sync_interval.total_seconds()));
+                    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                    interval
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn watch_interval(&self) -> Option<tokio::time::Interval> {
+        match &self.data_source {
+            DataSource::WatchedDirectory(data_watcher) => {
+                let mut interval = tokio::time::interval(data_watcher.poll_interval());
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                Some(interval)
+            }
+            _ => None,
+        }
+    }
+
+    // Reloads base data when a new dataset is ready in the watched directory,
+    // in the same way as when a reload message is received.
+    async fn check_watched_directory(
+        &mut self,
+        channel: Option<&lapin::Channel>,
+    ) -> Result<(), Error> {
+        let poll_result = match &mut self.data_source {
+            DataSource::WatchedDirectory(data_watcher) => data_watcher.poll(),
+            _ => return Ok(()),
+        };
+        match poll_result {
+            Ok(true) => {
+                info!("New dataset ready in watched directory. I'll reload base data.");
+                self.reload(channel).await?;
+            }
+            Ok(false) => (),
+            Err(err) => {
+                error!(
+                    "Could not look for new datasets in watched directory. {:?}",
+                    err
+                );
             }
         }
         Ok(())
     }
 
+    fn update_siri_codes(&mut self) -> Result<(), Error> {
+        let siri_params = match &self.config.siri {
+            Some(siri_params) => siri_params,
+            None => return Ok(()),
+        };
+        let siri_codes = {
+            let snapshot = self.data_and_models.snapshot()?;
+            let (_, base_model, _) = snapshot.data_and_models.deref();
+            SiriCodes::new(base_model, siri_params)
+        }; // snapshot is released
+        self.siri_codes = Arc::new(siri_codes);
+        Ok(())
+    }
+
     async fn apply_realtime_messages(&mut self) -> Result<(), Error> {
         let messages = std::mem::take(&mut self.kirin_messages);
-        let updater = |data_and_models: &mut DataAndModels| {
+        self.append_to_journal(|journal| journal.append(&messages));
+        let (siri_xmls, siri_deliveries): (Vec<_>, Vec<_>) =
+            parse_siri_deliveries(std::mem::take(&mut self.siri_deliveries))
+                .into_iter()
+                .unzip();
+        self.append_to_journal(|journal| journal.append_siri_deliveries(&siri_xmls));
+        self.applied_siri_deliveries.extend(siri_xmls);
+        let siri_codes = self.siri_codes.clone();
+        let siri_params = self.config.siri.clone();
+        let metrics = self.metrics.clone();
+        let updater = move |data_and_models: &mut DataAndModels| {
             for message in messages {
-                let result = handle_realtime_message(data_and_models, &message);
+                let result = handle_realtime_message(data_and_models, &message, &metrics);
                 if let Err(err) = result {
                     error!("Could not handle real time message. {:?}", err);
                 }
             }
+            if let Some(siri_params) = siri_params {
+                for delivery in siri_deliveries {
+                    handle_siri_delivery(data_and_models, &delivery, &siri_codes, &siri_params);
+                }
+            }
             Ok(())
         };
 
-        self.update_data_and_models(updater).await?;
+        self.update_data_and_models(updater)?;
 
         let now = Utc::now().naive_utc();
         self.send_status_update(StatusUpdate::RealTimeUpdate(now))
     }
 
-    async fn update_data_and_models<Updater, T>(&mut self, updater: Updater) -> Result<T, Error>
+    fn append_to_journal<F>(&mut self, append: F)
     where
-        Updater: FnOnce(&mut DataAndModels) -> Result<T, Error>,
+        F: FnOnce(&mut RealTimeJournal) -> Result<(), Error>,
     {
-        trace!("DataWorker ask LoadBalancer to Stop.");
-        self.send_order_to_load_balancer(LoadBalancerOrder::Stop)
-            .await?;
-
-        self.load_balancer_channels
-            .stopped_receiver
-            .recv()
-            .await
-            .ok_or_else(|| format_err!("Channel load_balancer_stopped has closed."))?;
-        trace!("DataWorker received Stopped signal from LoadBalancer.");
-
-        let update_result = {
-            let mut lock_guard = self.data_and_models.write().map_err(|err| {
-                format_err!(
-                    "DataWorker worker failed to acquire write lock on data_and_models. {}.",
-                    err
-                )
-            })?;
-
-            updater(&mut *lock_guard)
-        }; // lock_guard is now released
-
-        trace!("DataWorker ask LoadBalancer to Start.");
-        self.send_order_to_load_balancer(LoadBalancerOrder::Start)
-            .await?;
-
-        update_result
+        if let Some(journal) = &mut self.real_time_journal {
+            if let Err(err) = append(journal) {
+                error!("Could not write to real time journal. {:?}", err);
+            }
+        }
     }
 
-    async fn send_order_to_load_balancer(&mut self, order: LoadBalancerOrder) -> Result<(), Error> {
-        self.load_balancer_channels
-            .order_sender
-            .send(order.clone())
-            .await
-            .with_context(|| format!("Could not send order {:?} to load balancer.", order))
+    // Real time updates are applied on a copy of the data and models,
+    // so compute workers keep answering requests while the update is applied.
+    fn update_data_and_models<Updater, T>(&mut self, updater: Updater) -> Result<T, Error>
+    where
+        Updater: FnOnce(&mut DataAndModels) -> Result<T, Error>,
+    {
+        let (result, modified_vehicle_journeys) = self.data_and_models.update(updater)?;
+        if let Some(response_cache) = &self.response_cache {
+            let invalidate_result =
+                response_cache.invalidate_vehicle_journeys(&modified_vehicle_journeys);
+            if let Err(err) = invalidate_result {
+                error!("Could not invalidate response cache. {:?}", err);
+            }
+        }
+        Ok(result)
     }
 
     async fn handle_incoming_kirin_message(
@@ -490,6 +1059,7 @@ impl DataWorker {
                     }
                     Err(err) => {
                         error!("Could not decode kirin message into protobuf. {:?}", err);
+                        self.metrics.real_time_message_failed(RealTimeSource::Kirin);
                         Ok(())
                     }
                 }
@@ -504,6 +1074,45 @@ impl DataWorker {
         }
     }
 
+    async fn handle_incoming_siri_message(
+        &mut self,
+        has_siri_message: Option<Result<lapin::message::Delivery, lapin::Error>>,
+    ) -> Result<(), Error> {
+        match has_siri_message {
+            Some(Ok(delivery)) => {
+                // acknowledge reception of the message
+                let _ = delivery
+                    .ack(BasicAckOptions::default())
+                    .await
+                    .map_err(|err| {
+                        error!(
+                            "Error while acknowledging reception of SIRI message : {:?}",
+                            err
+                        );
+                    });
+
+                // the xml is parsed when the delivery is applied,
+                // and journaled as is
+                match std::str::from_utf8(delivery.data.as_slice()) {
+                    Ok(xml) => {
+                        self.siri_deliveries.push(xml.to_string());
+                    }
+                    Err(err) => {
+                        error!("SIRI message is not valid utf8. {:?}", err);
+                    }
+                }
+                Ok(())
+            }
+            Some(Err(err)) => {
+                error!("Error while receiving a SIRI message. {:?}", err);
+                Ok(())
+            }
+            None => {
+                bail!("Consumer for SIRI messages has closed.");
+            }
+        }
+    }
+
     async fn handle_reload_message(
         &mut self,
         has_reload_message: Option<Result<lapin::message::Delivery, lapin::Error>>,
@@ -528,24 +1137,7 @@ impl DataWorker {
                         let action = proto_message.action();
                         if let navitia_proto::Action::Reload = action {
                             debug!("Received a Reload order.");
-                            let reload_result = self.load_data().await?;
-                            match reload_result {
-                                DataReloadStatus::Ok => {
-                                    // if we have unhandled kirin messages, we clear them,
-                                    // since we are going to request a full reload from kirin
-                                    self.kirin_messages.clear();
-                                    // After loading data from disk, load all disruption in chaos database
-                                    // Then apply all extracted disruptions
-                                    if let Err(err) = self.reload_chaos().await {
-                                        error!("Error during reload of Chaos database : {:?}", err);
-                                    }
-                                    self.reload_kirin(channel).await?;
-                                    debug!("Reload completed successfully.");
-                                }
-                                DataReloadStatus::Skipped => {
-                                    info!("Reload skipped");
-                                }
-                            }
+                            self.reload(Some(channel)).await?;
                         } else {
                             error!(
                                 "Receive a reload message with unhandled action value : {:?}",
@@ -571,18 +1163,183 @@ impl DataWorker {
         }
     }
 
-    async fn connect(&self) -> Result<lapin::Channel, Error> {
-        let endpoint = &self.config.rabbitmq.endpoint;
-        let connection =
-            lapin::Connection::connect(endpoint, lapin::ConnectionProperties::default())
-                .await
-                .with_context(|| format!("Could not connect to rabbitmq endpoint {}", endpoint))?;
+    // Loads new base data, with the chaos disruptions applied.
+    // When connected to rabbitmq, kirin is also asked for a full reload.
+    async fn reload(
+        &mut self,
+        channel: Option<&lapin::Channel>,
+    ) -> Result<DataReloadStatus, Error> {
+        let reload_result = self.load_data(false).await?;
+        match reload_result {
+            DataReloadStatus::Ok => {
+                if let Some(channel) = channel {
+                    // if we have unhandled kirin messages, we clear them,
+                    // since we are going to request a full reload from kirin
+                    self.kirin_messages.clear();
+                    self.reload_kirin(channel).await?;
+                }
+                debug!("Reload completed successfully.");
+            }
+            DataReloadStatus::Skipped => {
+                info!("Reload skipped");
+            }
+            DataReloadStatus::Rejected(_) => {
+                info!("Reload aborted, new data was rejected.");
+            }
+        }
+        Ok(reload_result)
+    }
 
-        info!(
-            "Successfully connected to rabbitmq at endpoint {}",
-            endpoint
-        );
-        let channel = connection.create_channel().await?;
+    async fn handle_admin_request(
+        &mut self,
+        has_admin_request: Option<AdminRequest>,
+        channel: Option<&lapin::Channel>,
+    ) -> Result<(), Error> {
+        let admin_request = has_admin_request
+            .ok_or_else(|| format_err!("Channel to receive admin requests has closed."))?;
+        info!("Received admin command {:?}.", admin_request.command);
+        let result = self.run_admin_command(admin_request.command, channel).await;
+        if let Err(err) = &result {
+            error!("Admin command failed. {:?}", err);
+        }
+        // the admin worker may have stopped waiting for the response
+        let _ = admin_request.response_sender.send(result);
+        Ok(())
+    }
+
+    async fn run_admin_command(
+        &mut self,
+        command: AdminCommand,
+        channel: Option<&lapin::Channel>,
+    ) -> Result<AdminResponse, Error> {
+        let message = match command {
+            AdminCommand::ReloadBaseData => match self.reload(channel).await? {
+                DataReloadStatus::Ok => "Base data reloaded.",
+                DataReloadStatus::Skipped => "Reload skipped, this data is already loaded.",
+                DataReloadStatus::Rejected(reason) => {
+                    bail!("New data rejected, the current data is kept. {}", reason)
+                }
+            },
+            AdminCommand::ReloadChaos => {
+                if self.config.chaos.is_none() {
+                    bail!("Chaos is not configured.");
+                }
+                self.reload_chaos().await?;
+                "Chaos disruptions reloaded."
+            }
+            AdminCommand::ReloadKirin => {
+                let channel = channel.ok_or_else(|| {
+                    format_err!("Not connected to rabbitmq, kirin cannot be asked for a reload.")
+                })?;
+                self.reload_kirin(channel).await?;
+                "Kirin was asked for a full reload."
+            }
+            AdminCommand::CancelChaosDisruption(disruption_id) => {
+                self.cancel_chaos_disruption(disruption_id)?;
+                "Chaos disruption cancelled."
+            }
+            AdminCommand::CancelKirinDisruption(disruption_id) => {
+                self.cancel_kirin_disruption(disruption_id)?;
+                "Kirin disruption cancelled."
+            }
+            AdminCommand::ListChaosDisruptions => {
+                let snapshot = self.data_and_models.snapshot()?;
+                let (_, _, real_time_model) = snapshot.data_and_models.deref();
+                let chaos_disruptions = real_time_model
+                    .applied_chaos_disruptions()
+                    .into_iter()
+                    .map(ChaosDisruptionInfo::new)
+                    .collect();
+                return Ok(AdminResponse::ChaosDisruptions { chaos_disruptions });
+            }
+            AdminCommand::ListKirinDisruptions => {
+                let snapshot = self.data_and_models.snapshot()?;
+                let (_, _, real_time_model) = snapshot.data_and_models.deref();
+                let kirin_disruptions = real_time_model
+                    .applied_kirin_disruptions()
+                    .into_iter()
+                    .map(KirinDisruptionInfo::new)
+                    .collect();
+                return Ok(AdminResponse::KirinDisruptions { kirin_disruptions });
+            }
+            AdminCommand::BaseDataInfo => {
+                return Ok(AdminResponse::BaseDataInfo {
+                    base_data_info: self.base_data_info.clone(),
+                });
+            }
+            AdminCommand::MemoryUsage => {
+                let snapshot = self.data_and_models.snapshot()?;
+                return Ok(AdminResponse::MemoryUsage {
+                    memory_usage: memory_usage(snapshot.data_and_models.deref()),
+                });
+            }
+        };
+        Ok(AdminResponse::Done {
+            message: message.to_string(),
+        })
+    }
+
+    // The cancellation is journaled, but a later reload of chaos
+    // will apply the disruption again if it is still published in chaos.
+    fn cancel_chaos_disruption(&mut self, disruption_id: String) -> Result<(), Error> {
+        let updater = |data_and_models: &mut DataAndModels| {
+            let data = &mut data_and_models.0;
+            let base_model = &data_and_models.1;
+            let real_time_model = &mut data_and_models.2;
+            let is_applied = real_time_model
+                .applied_chaos_disruptions()
+                .iter()
+                .any(|disruption| disruption.id == disruption_id);
+            if !is_applied {
+                return Err(Error::new(DisruptionNotApplied {
+                    disruption_id: disruption_id.clone(),
+                }));
+            }
+            cancel_chaos_disruption(real_time_model, &disruption_id, base_model, data);
+            Ok(())
+        };
+        self.update_data_and_models(updater)?;
+
+        let now = Utc::now().naive_utc();
+        self.append_to_journal(|journal| journal.append_chaos_cancellations(&[disruption_id], now));
+        self.send_status_update(StatusUpdate::RealTimeUpdate(now))
+    }
+
+    // The (vehicle_journey, date) of the disruption goes back to its base schedule,
+    // until kirin sends a new update on it.
+    fn cancel_kirin_disruption(&mut self, disruption_id: String) -> Result<(), Error> {
+        let updater = |data_and_models: &mut DataAndModels| {
+            let data = &mut data_and_models.0;
+            let base_model = &data_and_models.1;
+            let real_time_model = &mut data_and_models.2;
+            let has_applied_disruption = real_time_model
+                .applied_kirin_disruptions()
+                .into_iter()
+                .find(|disruption| disruption.id == disruption_id)
+                .map(|disruption| (disruption.trip_id.id.clone(), disruption.trip_date));
+            let (vehicle_journey_id, date) = has_applied_disruption.ok_or_else(|| {
+                Error::new(DisruptionNotApplied {
+                    disruption_id: disruption_id.clone(),
+                })
+            })?;
+            cancel_kirin_disruption(real_time_model, &disruption_id, base_model, data);
+            Ok((vehicle_journey_id, date))
+        };
+        let (vehicle_journey_id, date) = self.update_data_and_models(updater)?;
+
+        let now = Utc::now().naive_utc();
+        self.append_to_journal(|journal| {
+            journal.append_kirin_cancellation(&disruption_id, &vehicle_journey_id, date, now)
+        });
+        self.send_status_update(StatusUpdate::RealTimeUpdate(now))
+    }
+
+    pub fn admin_requests_sender(&self) -> mpsc::UnboundedSender<AdminRequest> {
+        self.admin_requests_sender.clone()
+    }
+
+    async fn connect(&self) -> Result<lapin::Channel, Error> {
+        let channel = self.rabbitmq_connection.create_channel().await?;
 
         // we declare the exchange
         let exchange = &self.config.rabbitmq.exchange;
@@ -601,6 +1358,9 @@ impl DataWorker {
 
         self.connect_real_time_queue(&channel).await?;
         self.connect_reload_queue(&channel).await?;
+        if let Some(siri_params) = &self.config.siri {
+            self.connect_siri_queue(&channel, siri_params).await?;
+        }
 
         Ok(channel)
     }
@@ -700,6 +1460,52 @@ impl DataWorker {
         Ok(())
     }
 
+    async fn connect_siri_queue(
+        &self,
+        channel: &lapin::Channel,
+        siri_params: &SiriParams,
+    ) -> Result<(), Error> {
+        // let's first delete the queue, in case it existed and was not properly deleted
+        delete_queue(channel, &self.siri_queue_name).await?;
+
+        channel
+            .queue_declare(
+                &self.siri_queue_name,
+                QueueDeclareOptions::default(),
+                FieldTable::default(),
+            )
+            .await
+            .with_context(|| format!("Could not declare queue {}", &self.siri_queue_name))?;
+
+        info!("Queue declared for SIRI : {}", &self.siri_queue_name);
+
+        let exchange = &self.config.rabbitmq.exchange;
+        for topic in &siri_params.topics {
+            channel
+                .queue_bind(
+                    &self.siri_queue_name,
+                    exchange,
+                    topic,
+                    QueueBindOptions::default(),
+                    FieldTable::default(),
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Could not bind queue {} to topic {}",
+                        &self.siri_queue_name, topic
+                    )
+                })?;
+
+            info!(
+                "SIRI queue {} binded successfully to topic {} on exchange {}",
+                &self.siri_queue_name, topic, exchange,
+            );
+        }
+
+        Ok(())
+    }
+
     async fn reload_kirin(&mut self, channel: &lapin::Channel) -> Result<(), Error> {
         info!("Asking Kirin for a full realtime reload.");
 
@@ -733,18 +1539,13 @@ impl DataWorker {
             task.set_action(navitia_proto::Action::LoadRealtime);
 
             let (start_date, end_date) = {
-                let lock = self.data_and_models.read().map_err(|err| {
-                    format_err!(
-                        "DataWorker failed to acquire read lock on data_and_models : {}",
-                        err
-                    )
-                })?;
+                let snapshot = self.data_and_models.snapshot()?;
 
-                let (data, _, _) = lock.deref();
+                let (data, _, _) = snapshot.data_and_models.deref();
                 let start_date = data.calendar().first_date().format("%Y%m%d").to_string();
                 let end_date = data.calendar().last_date().format("%Y%m%d").to_string();
                 (start_date, end_date)
-            }; // lock is dropped here
+            }; // snapshot is dropped here
 
             let load_realtime = navitia_proto::LoadRealtime {
                 queue_name: queue_name.clone(),
@@ -827,14 +1628,9 @@ impl DataWorker {
             })
     }
 
-    pub fn run_in_a_thread(self) -> Result<std::thread::JoinHandle<()>, Error> {
-        // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
-
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
-
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
         let thread_builder = thread::Builder::new().name("loki_data_worker".to_string());
         let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
         Ok(handle)
@@ -861,6 +1657,28 @@ async fn create_consumer(
         .with_context(|| format!("Could not create consumer to queue {}.", queue_name))
 }
 
+// Waits for the next delivery of `consumer`.
+// When there is no consumer, the returned future never completes.
+async fn next_delivery(
+    consumer: &mut Option<lapin::Consumer>,
+) -> Option<Result<lapin::message::Delivery, lapin::Error>> {
+    match consumer {
+        Some(consumer) => consumer.next().await,
+        None => futures::future::pending().await,
+    }
+}
+
+// Waits for the next tick of `interval`.
+// When there is no interval, the returned future never completes.
+async fn next_tick(interval: &mut Option<tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => futures::future::pending().await,
+    }
+}
+
 async fn delete_queue(channel: &lapin::Channel, queue_name: &str) -> Result<u32, Error> {
     channel
         .queue_delete(queue_name, lapin::options::QueueDeleteOptions::default())
@@ -868,25 +1686,54 @@ async fn delete_queue(channel: &lapin::Channel, queue_name: &str) -> Result<u32,
         .with_context(|| format!("Could not create delete to queue {}.", queue_name))
 }
 
+fn memory_usage(data_and_models: &DataAndModels) -> MemoryUsage {
+    let (data, base_model, real_time_model) = data_and_models;
+    MemoryUsage::with_components(
+        "data_and_models",
+        vec![
+            base_model.memory_usage(),
+            data.memory_usage(),
+            real_time_model.memory_usage(),
+        ],
+    )
+}
+
 fn handle_realtime_message(
     data_and_models: &mut DataAndModels,
     message: &chaos_proto::gtfs_realtime::FeedMessage,
+    metrics: &Metrics,
 ) -> Result<(), Error> {
     let header_datetime = parse_header_datetime(message)
         .context("Received a FeedMessage with a bad header datetime.")?;
 
     for feed_entity in &message.entity {
+        let source = feed_entity_source(feed_entity);
         let result = handle_feed_entity(data_and_models, feed_entity, &header_datetime);
-        if let Err(err) = result {
-            error!(
-                "An error occured while handling FeedMessage with timestamp {}. {:?}",
-                header_datetime, err
-            );
+        match result {
+            Ok(()) => metrics.real_time_message_applied(source),
+            Err(err) => {
+                metrics.real_time_message_failed(source);
+                error!(
+                    "An error occured while handling FeedMessage with timestamp {}. {:?}",
+                    header_datetime, err
+                );
+            }
         }
     }
     Ok(())
 }
 
+// cancellations and disruptions are sent by chaos, trip updates by kirin.
+// Cancellations of kirin disruptions, made through the admin api, carry a trip update.
+fn feed_entity_source(feed_entity: &chaos_proto::gtfs_realtime::FeedEntity) -> RealTimeSource {
+    let is_chaos_cancellation = feed_entity.get_is_deleted() && !feed_entity.has_trip_update();
+    if is_chaos_cancellation || exts::disruption.get(feed_entity).is_some() {
+        RealTimeSource::Chaos
+    } else {
+        RealTimeSource::Kirin
+    }
+}
+
 fn handle_feed_entity(
     data_and_models: &mut DataAndModels,
     feed_entity: &chaos_proto::gtfs_realtime::FeedEntity,
@@ -901,7 +1748,9 @@ fn handle_feed_entity(
     let base_model = &data_and_models.1;
     let real_time_model = &mut data_and_models.2;
 
-    if feed_entity.get_is_deleted() {
+    if feed_entity.get_is_deleted() && feed_entity.has_trip_update() {
+        cancel_kirin_disruption(real_time_model, id, base_model, data);
+    } else if feed_entity.get_is_deleted() {
         cancel_chaos_disruption(real_time_model, id, base_model, data);
     } else if let Some(chaos_disruption) = exts::disruption.get(feed_entity) {
         let chaos_disruption = handle_chaos_protobuf(&chaos_disruption)
@@ -923,6 +1772,126 @@ fn handle_feed_entity(
     Ok(())
 }
 
+fn decode_chaos_protobufs(
+    protos: &[chaos_proto::chaos::Disruption],
+    metrics: &Metrics,
+) -> Vec<ChaosDisruption> {
+    protos
+        .iter()
+        .filter_map(|proto| match handle_chaos_protobuf(proto) {
+            Ok(disruption) => {
+                metrics.real_time_message_applied(RealTimeSource::Chaos);
+                Some(disruption)
+            }
+            Err(err) => {
+                metrics.real_time_message_failed(RealTimeSource::Chaos);
+                error!("Error while decoding chaos disruption protobuf : {:?}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn apply_chaos_disruptions(data_and_models: &mut DataAndModels, disruptions: Vec<ChaosDisruption>) {
+    let data = &mut data_and_models.0;
+    let base_model = &data_and_models.1;
+    let real_time_model = &mut data_and_models.2;
+    for disruption in disruptions {
+        // the disruption may already be applied
+        // (e.g. when it was replayed from the real time journal)
+        // so we replace it by the version read from chaos
+        if real_time_model.contains_chaos_disruption(&disruption.id) {
+            cancel_chaos_disruption(real_time_model, &disruption.id, base_model, data);
+        }
+        store_and_apply_chaos_disruption(real_time_model, disruption, base_model, data);
+    }
+}
+
+// Returns the deliveries that could be parsed, alongside their xml
+fn parse_siri_deliveries(xmls: Vec<String>) -> Vec<(String, SiriDelivery)> {
+    xmls.into_iter()
+        .filter_map(|xml| match parse_siri_xml(&xml) {
+            Ok(delivery) => Some((xml, delivery)),
+            Err(err) => {
+                error!("Could not decode SIRI message. {:?}", err);
+                None
+            }
+        })
+        .collect()
+}
+
+fn handle_siri_delivery(
+    data_and_models: &mut DataAndModels,
+    delivery: &SiriDelivery,
+    siri_codes: &SiriCodes,
+    siri_params: &SiriParams,
+) {
+    let data = &mut data_and_models.0;
+    let base_model = &data_and_models.1;
+    let real_time_model = &mut data_and_models.2;
+
+    for estimated_vehicle_journey in &delivery.estimated_vehicle_journeys {
+        let result = make_kirin_disruption(
+            estimated_vehicle_journey,
+            &delivery.response_timestamp,
+            base_model,
+            real_time_model,
+            siri_codes,
+            siri_params,
+        );
+        match result {
+            Ok(kirin_disruption) => {
+                store_and_apply_kirin_disruption(
+                    real_time_model,
+                    kirin_disruption,
+                    base_model,
+                    data,
+                );
+            }
+            Err(err) => {
+                error!(
+                    "Could not handle EstimatedVehicleJourney {}. {:?}",
+                    estimated_vehicle_journey.dated_vehicle_journey_ref, err
+                );
+            }
+        }
+    }
+
+    for situation in &delivery.situations {
+        let situation_id = &situation.situation_number;
+        // a new version of a situation replaces the previous one
+        if real_time_model.contains_chaos_disruption(situation_id) {
+            cancel_chaos_disruption(real_time_model, situation_id, base_model, data);
+        }
+        if situation.is_closed {
+            continue;
+        }
+        let result = make_chaos_disruption(
+            situation,
+            &delivery.response_timestamp,
+            base_model,
+            siri_codes,
+            siri_params,
+        );
+        match result {
+            Ok(chaos_disruption) => {
+                store_and_apply_chaos_disruption(
+                    real_time_model,
+                    chaos_disruption,
+                    base_model,
+                    data,
+                );
+            }
+            Err(err) => {
+                error!(
+                    "Could not handle PtSituationElement {}. {:?}",
+                    situation_id, err
+                );
+            }
+        }
+    }
+}
+
 fn parse_header_datetime(
     message: &chaos_proto::gtfs_realtime::FeedMessage,
 ) -> Result<NaiveDateTime, Error> {
@@ -942,9 +1911,13 @@ fn parse_header_datetime(
 enum DataReloadStatus {
     Ok,
     Skipped,
+    // the new data could not be read, or failed the data checks,
+    // so the current data is kept
+    Rejected(String),
 }
 
 pub enum DataSource {
     Local(LocalFileParams),
     S3(DataDownloader),
+    WatchedDirectory(DataWatcher),
 }
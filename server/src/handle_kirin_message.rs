@@ -97,34 +97,8 @@ pub fn handle_kirin_protobuf(
 
     let stop_times = make_stop_times(trip_update, reference_date)?;
 
-    let base_application_period =
-        if let Some(idx) = base_model.vehicle_journey_idx(&vehicle_journey_id) {
-            base_model.trip_time_period(idx, reference_date)
-        } else {
-            None
-        };
-    let model_validity_period = {
-        let (start_date, end_date) = base_model.validity_period();
-        TimePeriod::new(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59))
-            .with_context(|| "BaseModel has a bad validity period".to_string())?
-    };
-
-    let stop_times_time_period = make_time_period(&stop_times, reference_date);
-
-    // we want the application period to cover
-    // - the base vehicle period (if any)
-    // - the period of the stop_times in the kirin message (if any)
-    // When both are absent, we use the validity period of the model,
-    let application_period = match (base_application_period, stop_times_time_period) {
-        (None, None) => model_validity_period,
-        (Some(base_period), None) => base_period,
-        (None, Some(stop_times_period)) => stop_times_period,
-        (Some(base_period), Some(stop_times_period)) => {
-            let start = std::cmp::min(base_period.start(), stop_times_period.end());
-            let end = std::cmp::max(base_period.end(), stop_times_period.end());
-            TimePeriod::new(start, end).unwrap_or(model_validity_period)
-        }
-    };
+    let application_period =
+        make_application_period(base_model, &vehicle_journey_id, reference_date, &stop_times)?;
 
     let company_id = chaos_proto::kirin::exts::company_id.get(trip_descriptor);
     let physical_mode_id =
@@ -173,6 +147,43 @@ pub fn handle_kirin_protobuf(
     })
 }
 
+// we want the application period to cover
+// - the base vehicle period (if any)
+// - the period of the stop_times in the real time message (if any)
+// When both are absent, we use the validity period of the model,
+pub fn make_application_period(
+    base_model: &BaseModel,
+    vehicle_journey_id: &str,
+    reference_date: NaiveDate,
+    stop_times: &[kirin_disruption::StopTime],
+) -> Result<TimePeriod, Error> {
+    let base_application_period =
+        if let Some(idx) = base_model.vehicle_journey_idx(vehicle_journey_id) {
+            base_model.trip_time_period(idx, reference_date)
+        } else {
+            None
+        };
+    let model_validity_period = {
+        let (start_date, end_date) = base_model.validity_period();
+        TimePeriod::new(start_date.and_hms(0, 0, 0), end_date.and_hms(23, 59, 59))
+            .with_context(|| "BaseModel has a bad validity period".to_string())?
+    };
+
+    let stop_times_time_period = make_time_period(stop_times, reference_date);
+
+    let application_period = match (base_application_period, stop_times_time_period) {
+        (None, None) => model_validity_period,
+        (Some(base_period), None) => base_period,
+        (None, Some(stop_times_period)) => stop_times_period,
+        (Some(base_period), Some(stop_times_period)) => {
+            let start = std::cmp::min(base_period.start(), stop_times_period.end());
+            let end = std::cmp::max(base_period.end(), stop_times_period.end());
+            TimePeriod::new(start, end).unwrap_or(model_validity_period)
+        }
+    };
+    Ok(application_period)
+}
+
 fn make_time_period(
     stop_times: &[kirin_disruption::StopTime],
     reference_date: NaiveDate,
@@ -299,6 +310,13 @@ fn read_time(
         )
     })?;
 
+    make_seconds_since_reference_date(naive_datetime, reference_date)
+}
+
+pub fn make_seconds_since_reference_date(
+    naive_datetime: NaiveDateTime,
+    reference_date: NaiveDate,
+) -> Result<SecondsSinceTimezonedDayStart, Error> {
     let reference_date_at_midnight = reference_date.and_hms(0, 0, 0);
     let duration_from_ref = naive_datetime.signed_duration_since(reference_date_at_midnight);
     let duration_i64 = duration_from_ref.num_seconds();
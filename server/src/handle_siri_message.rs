@@ -0,0 +1,1158 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::collections::HashMap;
+
+use anyhow::{bail, format_err, Context, Error};
+use launch::loki::{
+    chrono::{DateTime, Duration, NaiveDate},
+    models::{
+        base_model::BaseModel,
+        real_time_disruption::{
+            chaos_disruption::{
+                Cause, ChannelType, ChaosDisruption, ChaosImpact, Impacted, Informed, LineId,
+                Message, NetworkId, Severity, StopPointId,
+            },
+            kirin_disruption::{self, KirinDisruption, UpdateData, UpdateType},
+            time_periods::TimePeriod,
+            Effect, VehicleJourneyId,
+        },
+        real_time_model::TripVersion,
+        ModelRefs, RealTimeModel, StopTime, VehicleJourneyIdx,
+    },
+    timetables::FlowDirection,
+    tracing::error,
+    NaiveDateTime,
+};
+use roxmltree::Node;
+
+use crate::{
+    handle_kirin_message::{make_application_period, make_seconds_since_reference_date},
+    server_config::SiriParams,
+};
+
+/// The content of a SIRI `ServiceDelivery`, restricted to the
+/// `EstimatedVehicleJourney` (from SIRI ET) and `PtSituationElement` (from SIRI SX)
+/// it contains.
+#[derive(Debug, Clone)]
+pub struct SiriDelivery {
+    pub response_timestamp: NaiveDateTime,
+    pub estimated_vehicle_journeys: Vec<EstimatedVehicleJourney>,
+    pub situations: Vec<PtSituation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EstimatedVehicleJourney {
+    pub line_ref: Option<String>,
+    pub dated_vehicle_journey_ref: String,
+    pub data_frame_ref: Option<String>,
+    pub cancellation: bool,
+    pub extra_journey: bool,
+    pub calls: Vec<EstimatedCall>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EstimatedCall {
+    pub stop_point_ref: String,
+    pub order: Option<u32>,
+    pub aimed_arrival: Option<NaiveDateTime>,
+    pub expected_arrival: Option<NaiveDateTime>,
+    pub aimed_departure: Option<NaiveDateTime>,
+    pub expected_departure: Option<NaiveDateTime>,
+    pub is_cancelled: bool,
+    pub arrival_cancelled: bool,
+    pub departure_cancelled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PtSituation {
+    pub situation_number: String,
+    pub creation_time: Option<NaiveDateTime>,
+    pub versioned_at_time: Option<NaiveDateTime>,
+    pub is_closed: bool,
+    pub validity_periods: Vec<(NaiveDateTime, Option<NaiveDateTime>)>,
+    pub publication_window: Option<(NaiveDateTime, Option<NaiveDateTime>)>,
+    pub reason: Option<String>,
+    pub severity: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub conditions: Vec<String>,
+    pub affected_networks: Vec<String>,
+    pub affected_lines: Vec<String>,
+    pub affected_stop_points: Vec<String>,
+    pub affected_vehicle_journeys: Vec<String>,
+}
+
+/// Maps the references found in SIRI deliveries
+/// (`DatedVehicleJourneyRef`, `StopPointRef`, `LineRef`)
+/// to the ids of the objects in the base model, using
+/// the codes of type given in `SiriParams`.
+pub struct SiriCodes {
+    vehicle_journeys: HashMap<String, String>,
+    stop_points: HashMap<String, String>,
+    lines: HashMap<String, String>,
+}
+
+impl SiriCodes {
+    pub fn new(base_model: &BaseModel, siri_params: &SiriParams) -> Self {
+        let mut vehicle_journeys = HashMap::new();
+        for idx in base_model.vehicle_journeys() {
+            let vehicle_journey = base_model.vehicle_journey(idx);
+            for (code_type, code_value) in &vehicle_journey.codes {
+                if *code_type == siri_params.vehicle_journey_code_type {
+                    vehicle_journeys.insert(code_value.clone(), vehicle_journey.id.clone());
+                }
+            }
+        }
+
+        let mut stop_points = HashMap::new();
+        for idx in base_model.stop_points() {
+            for (code_type, code_value) in base_model.codes(idx).into_iter().flatten() {
+                if *code_type == siri_params.stop_point_code_type {
+                    stop_points.insert(
+                        code_value.clone(),
+                        base_model.stop_point_id(idx).to_string(),
+                    );
+                }
+            }
+        }
+
+        let mut lines = HashMap::new();
+        for line in base_model.lines() {
+            for (code_type, code_value) in &line.codes {
+                if *code_type == siri_params.line_code_type {
+                    lines.insert(code_value.clone(), line.id.clone());
+                }
+            }
+        }
+
+        Self {
+            vehicle_journeys,
+            stop_points,
+            lines,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            vehicle_journeys: HashMap::new(),
+            stop_points: HashMap::new(),
+            lines: HashMap::new(),
+        }
+    }
+
+    // When no code matches the reference, we assume that the
+    // reference is directly the id of the object in the base model
+    pub fn vehicle_journey_id<'a>(&'a self, siri_ref: &'a str) -> &'a str {
+        self.vehicle_journeys
+            .get(siri_ref)
+            .map_or(siri_ref, String::as_str)
+    }
+
+    pub fn stop_point_id<'a>(&'a self, siri_ref: &'a str) -> &'a str {
+        self.stop_points
+            .get(siri_ref)
+            .map_or(siri_ref, String::as_str)
+    }
+
+    pub fn line_id<'a>(&'a self, siri_ref: &'a str) -> &'a str {
+        self.lines.get(siri_ref).map_or(siri_ref, String::as_str)
+    }
+}
+
+pub fn parse_siri_xml(xml: &str) -> Result<SiriDelivery, Error> {
+    let document = roxmltree::Document::parse(xml).context("Could not parse SIRI xml.")?;
+
+    let service_delivery = document
+        .descendants()
+        .find(|node| is_element(node, "ServiceDelivery"))
+        .ok_or_else(|| format_err!("SIRI xml has no ServiceDelivery."))?;
+
+    let response_timestamp = read_datetime(service_delivery, "ResponseTimestamp")?
+        .ok_or_else(|| format_err!("ServiceDelivery has no ResponseTimestamp."))?;
+
+    let mut estimated_vehicle_journeys = Vec::new();
+    for node in service_delivery
+        .descendants()
+        .filter(|node| is_element(node, "EstimatedVehicleJourney"))
+    {
+        match parse_estimated_vehicle_journey(node) {
+            Ok(estimated_vehicle_journey) => {
+                estimated_vehicle_journeys.push(estimated_vehicle_journey);
+            }
+            Err(err) => {
+                error!(
+                    "Could not parse an EstimatedVehicleJourney. I'll skip it. {:?}",
+                    err
+                );
+            }
+        }
+    }
+
+    let mut situations = Vec::new();
+    for node in service_delivery
+        .descendants()
+        .filter(|node| is_element(node, "PtSituationElement"))
+    {
+        match parse_situation(node) {
+            Ok(situation) => {
+                situations.push(situation);
+            }
+            Err(err) => {
+                error!(
+                    "Could not parse a PtSituationElement. I'll skip it. {:?}",
+                    err
+                );
+            }
+        }
+    }
+
+    Ok(SiriDelivery {
+        response_timestamp,
+        estimated_vehicle_journeys,
+        situations,
+    })
+}
+
+fn parse_estimated_vehicle_journey(node: Node) -> Result<EstimatedVehicleJourney, Error> {
+    let framed_vehicle_journey_ref = child(node, "FramedVehicleJourneyRef");
+    let dated_vehicle_journey_ref = framed_vehicle_journey_ref
+        .and_then(|framed_ref| read_text(framed_ref, "DatedVehicleJourneyRef"))
+        .or_else(|| read_text(node, "DatedVehicleJourneyRef"))
+        .or_else(|| read_text(node, "EstimatedVehicleJourneyCode"))
+        .ok_or_else(|| format_err!("EstimatedVehicleJourney has no DatedVehicleJourneyRef."))?;
+    let data_frame_ref =
+        framed_vehicle_journey_ref.and_then(|framed_ref| read_text(framed_ref, "DataFrameRef"));
+
+    let mut calls = Vec::new();
+    let call_nodes = children(node, "RecordedCalls")
+        .flat_map(|recorded_calls| children(recorded_calls, "RecordedCall"))
+        .chain(
+            children(node, "EstimatedCalls")
+                .flat_map(|estimated_calls| children(estimated_calls, "EstimatedCall")),
+        );
+    for (idx, call_node) in call_nodes.enumerate() {
+        let call = parse_call(call_node).with_context(|| {
+            format!(
+                "Could not parse {}-th call of vehicle journey {}",
+                idx, dated_vehicle_journey_ref
+            )
+        })?;
+        calls.push(call);
+    }
+    if calls.iter().all(|call| call.order.is_some()) {
+        calls.sort_by_key(|call| call.order);
+    }
+
+    Ok(EstimatedVehicleJourney {
+        line_ref: read_text(node, "LineRef"),
+        dated_vehicle_journey_ref,
+        data_frame_ref,
+        cancellation: read_bool(node, "Cancellation"),
+        extra_journey: read_bool(node, "ExtraJourney"),
+        calls,
+    })
+}
+
+fn parse_call(node: Node) -> Result<EstimatedCall, Error> {
+    let stop_point_ref =
+        read_text(node, "StopPointRef").ok_or_else(|| format_err!("Call has no StopPointRef."))?;
+
+    let order = read_text(node, "Order")
+        .map(|order| order.parse::<u32>())
+        .transpose()
+        .context("Call has a bad Order.")?;
+
+    let arrival_status_cancelled = read_text(node, "ArrivalStatus").as_deref() == Some("cancelled");
+    let departure_status_cancelled =
+        read_text(node, "DepartureStatus").as_deref() == Some("cancelled");
+    let is_cancelled =
+        read_bool(node, "Cancellation") || (arrival_status_cancelled && departure_status_cancelled);
+    let arrival_cancelled = is_cancelled
+        || arrival_status_cancelled
+        || matches!(
+            read_text(node, "ArrivalBoardingActivity").as_deref(),
+            Some("noAlighting" | "passThru")
+        );
+    let departure_cancelled = is_cancelled
+        || departure_status_cancelled
+        || matches!(
+            read_text(node, "DepartureBoardingActivity").as_deref(),
+            Some("noBoarding" | "passThru")
+        );
+
+    let expected_arrival = match read_datetime(node, "ActualArrivalTime")? {
+        Some(actual) => Some(actual),
+        None => read_datetime(node, "ExpectedArrivalTime")?,
+    };
+    let expected_departure = match read_datetime(node, "ActualDepartureTime")? {
+        Some(actual) => Some(actual),
+        None => read_datetime(node, "ExpectedDepartureTime")?,
+    };
+
+    Ok(EstimatedCall {
+        stop_point_ref,
+        order,
+        aimed_arrival: read_datetime(node, "AimedArrivalTime")?,
+        expected_arrival,
+        aimed_departure: read_datetime(node, "AimedDepartureTime")?,
+        expected_departure,
+        is_cancelled,
+        arrival_cancelled,
+        departure_cancelled,
+    })
+}
+
+fn parse_situation(node: Node) -> Result<PtSituation, Error> {
+    let situation_number = read_text(node, "SituationNumber")
+        .ok_or_else(|| format_err!("PtSituationElement has no SituationNumber."))?;
+
+    let mut validity_periods = Vec::new();
+    for period_node in children(node, "ValidityPeriod") {
+        let period = parse_period(period_node).with_context(|| {
+            format!(
+                "Could not parse ValidityPeriod of situation {}",
+                situation_number
+            )
+        })?;
+        validity_periods.push(period);
+    }
+
+    let publication_window = child(node, "PublicationWindow")
+        .map(parse_period)
+        .transpose()
+        .with_context(|| {
+            format!(
+                "Could not parse PublicationWindow of situation {}",
+                situation_number
+            )
+        })?;
+
+    // the reason is given by one of the
+    // MiscellaneousReason, PersonnelReason, EquipmentReason, EnvironmentReason, UnknownReason
+    // elements, and may be described by a ReasonName
+    let reason = read_text(node, "ReasonName").or_else(|| {
+        node.children()
+            .filter(|child| child.is_element() && child.tag_name().name().ends_with("Reason"))
+            .find_map(|child| child.text())
+            .map(|text| text.trim().to_string())
+    });
+
+    let conditions = children(node, "Consequences")
+        .flat_map(|consequences| children(consequences, "Consequence"))
+        .filter_map(|consequence| read_text(consequence, "Condition"))
+        .collect();
+
+    let mut affected_networks = Vec::new();
+    let mut affected_lines = Vec::new();
+    let mut affected_stop_points = Vec::new();
+    let mut affected_vehicle_journeys = Vec::new();
+    if let Some(affects) = child(node, "Affects") {
+        for network in
+            children(affects, "Networks").flat_map(|networks| children(networks, "AffectedNetwork"))
+        {
+            let lines: Vec<String> = children(network, "AffectedLine")
+                .filter_map(|line| read_text(line, "LineRef"))
+                .collect();
+            if lines.is_empty() {
+                affected_networks.extend(read_text(network, "NetworkRef"));
+            }
+            affected_lines.extend(lines);
+        }
+        affected_stop_points.extend(
+            children(affects, "StopPoints")
+                .flat_map(|stop_points| children(stop_points, "AffectedStopPoint"))
+                .filter_map(|stop_point| read_text(stop_point, "StopPointRef")),
+        );
+        affected_vehicle_journeys.extend(
+            children(affects, "VehicleJourneys")
+                .flat_map(|vehicle_journeys| children(vehicle_journeys, "AffectedVehicleJourney"))
+                .filter_map(|vehicle_journey| {
+                    child(vehicle_journey, "FramedVehicleJourneyRef")
+                        .and_then(|framed_ref| read_text(framed_ref, "DatedVehicleJourneyRef"))
+                        .or_else(|| read_text(vehicle_journey, "DatedVehicleJourneyRef"))
+                        .or_else(|| read_text(vehicle_journey, "VehicleJourneyRef"))
+                }),
+        );
+    }
+
+    Ok(PtSituation {
+        creation_time: read_datetime(node, "CreationTime")?,
+        versioned_at_time: read_datetime(node, "VersionedAtTime")?,
+        is_closed: read_text(node, "Progress").as_deref() == Some("closed"),
+        validity_periods,
+        publication_window,
+        reason,
+        severity: read_text(node, "Severity"),
+        summary: read_text(node, "Summary"),
+        description: read_text(node, "Description"),
+        conditions,
+        affected_networks,
+        affected_lines,
+        affected_stop_points,
+        affected_vehicle_journeys,
+        situation_number,
+    })
+}
+
+fn parse_period(node: Node) -> Result<(NaiveDateTime, Option<NaiveDateTime>), Error> {
+    let start =
+        read_datetime(node, "StartTime")?.ok_or_else(|| format_err!("Period has no StartTime."))?;
+    let end = read_datetime(node, "EndTime")?;
+    Ok((start, end))
+}
+
+/// A delivery may contain only some calls of the trip (e.g. only the `EstimatedCalls`
+/// of the stops that are not served yet). The delivered calls then update the current
+/// stop times of the trip, and the stop times that were not delivered are kept.
+pub fn make_kirin_disruption(
+    estimated_vehicle_journey: &EstimatedVehicleJourney,
+    response_timestamp: &NaiveDateTime,
+    base_model: &BaseModel,
+    real_time_model: &RealTimeModel,
+    siri_codes: &SiriCodes,
+    siri_params: &SiriParams,
+) -> Result<KirinDisruption, Error> {
+    let vehicle_journey_id = siri_codes
+        .vehicle_journey_id(&estimated_vehicle_journey.dated_vehicle_journey_ref)
+        .to_string();
+
+    let reference_date = make_reference_date(estimated_vehicle_journey)
+        .with_context(|| format!("Could not find the date of trip {}", vehicle_journey_id))?;
+
+    let delivered_stop_times = estimated_vehicle_journey
+        .calls
+        .iter()
+        .enumerate()
+        .map(|(idx, call)| {
+            make_stop_time(call, reference_date, siri_codes)
+                .with_context(|| format!("Could not handle {}-th call", idx))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let current_stop_times = if estimated_vehicle_journey.cancellation {
+        None
+    } else {
+        current_stop_times(
+            &vehicle_journey_id,
+            reference_date,
+            base_model,
+            real_time_model,
+        )
+    };
+    let stop_times = match current_stop_times {
+        Some(current_stop_times) => merge_calls(
+            current_stop_times,
+            &estimated_vehicle_journey.calls,
+            delivered_stop_times,
+        )
+        .with_context(|| {
+            format!(
+                "Could not merge the calls into the stop times of trip {}",
+                vehicle_journey_id
+            )
+        })?,
+        None => delivered_stop_times,
+    };
+
+    let application_period =
+        make_application_period(base_model, &vehicle_journey_id, reference_date, &stop_times)?;
+
+    let effect = if estimated_vehicle_journey.cancellation {
+        Effect::NoService
+    } else if estimated_vehicle_journey.extra_journey {
+        Effect::AdditionalService
+    } else if estimated_vehicle_journey
+        .calls
+        .iter()
+        .any(|call| call.is_cancelled)
+    {
+        Effect::ReducedService
+    } else if estimated_vehicle_journey.calls.iter().any(|call| {
+        (call.expected_arrival.is_some() && call.expected_arrival != call.aimed_arrival)
+            || (call.expected_departure.is_some()
+                && call.expected_departure != call.aimed_departure)
+    }) {
+        Effect::SignificantDelays
+    } else {
+        Effect::UnknownEffect
+    };
+
+    let update_data = UpdateData {
+        stop_times,
+        company_id: None,
+        physical_mode_id: None,
+        headsign: None,
+    };
+    let update = match effect {
+        Effect::NoService => UpdateType::TripDeleted(),
+        Effect::AdditionalService => UpdateType::NewTripUpdated(update_data),
+        _ => UpdateType::BaseTripUpdated(update_data),
+    };
+
+    Ok(KirinDisruption {
+        id: format!(
+            "{}:{}:{}",
+            siri_params.contributor,
+            vehicle_journey_id,
+            reference_date.format("%Y%m%d")
+        ),
+        contributor: Some(siri_params.contributor.clone()),
+        message: None,
+        updated_at: *response_timestamp,
+        application_period,
+        effect,
+        trip_id: VehicleJourneyId {
+            id: vehicle_journey_id,
+        },
+        trip_date: reference_date,
+        update,
+    })
+}
+
+// The stop times of the last real time version of the trip if any,
+// or else its base stop times.
+// None when the trip is unknown, or is not circulating at this date.
+fn current_stop_times(
+    vehicle_journey_id: &str,
+    reference_date: NaiveDate,
+    base_model: &BaseModel,
+    real_time_model: &RealTimeModel,
+) -> Option<Vec<kirin_disruption::StopTime>> {
+    let model_refs = ModelRefs::new(base_model, real_time_model);
+    let to_kirin_stop_time = |stop_time: &StopTime| kirin_disruption::StopTime {
+        stop_id: model_refs.stop_point_id(&stop_time.stop).to_string(),
+        arrival_time: stop_time.debark_time,
+        departure_time: stop_time.board_time,
+        flow_direction: stop_time.flow_direction,
+    };
+
+    let vehicle_journey_idx =
+        real_time_model.vehicle_journey_idx(vehicle_journey_id, base_model)?;
+    match real_time_model.last_version(&vehicle_journey_idx, reference_date) {
+        Some(TripVersion::Present(stop_times)) => {
+            Some(stop_times.iter().map(to_kirin_stop_time).collect())
+        }
+        Some(TripVersion::Deleted()) => None,
+        None => match vehicle_journey_idx {
+            VehicleJourneyIdx::Base(idx) if base_model.trip_exists(idx, reference_date) => {
+                let stop_times = base_model.stop_times(idx).ok()?;
+                Some(
+                    stop_times
+                        .map(|stop_time| to_kirin_stop_time(&stop_time))
+                        .collect(),
+                )
+            }
+            _ => None,
+        },
+    }
+}
+
+// Each delivered call replaces the stop time it refers to, which is the stop time
+// at its Order if it has the same stop point, or else the first stop time at its stop point
+// that comes after the stop time of the previous call.
+fn merge_calls(
+    mut stop_times: Vec<kirin_disruption::StopTime>,
+    calls: &[EstimatedCall],
+    delivered_stop_times: Vec<kirin_disruption::StopTime>,
+) -> Result<Vec<kirin_disruption::StopTime>, Error> {
+    let mut next_position = 0;
+    for (call, delivered_stop_time) in calls.iter().zip(delivered_stop_times) {
+        let position_by_order = call
+            .order
+            .and_then(|order| usize::try_from(order).ok()?.checked_sub(1))
+            .filter(|position| {
+                matches!(stop_times.get(*position),
+                    Some(stop_time) if stop_time.stop_id == delivered_stop_time.stop_id)
+            });
+        let position = position_by_order
+            .or_else(|| {
+                stop_times[next_position..]
+                    .iter()
+                    .position(|stop_time| stop_time.stop_id == delivered_stop_time.stop_id)
+                    .map(|position| next_position + position)
+            })
+            .ok_or_else(|| {
+                format_err!(
+                    "The call at {} does not match any stop time of the trip.",
+                    call.stop_point_ref
+                )
+            })?;
+        stop_times[position] = delivered_stop_time;
+        next_position = position + 1;
+    }
+    Ok(stop_times)
+}
+
+fn make_reference_date(
+    estimated_vehicle_journey: &EstimatedVehicleJourney,
+) -> Result<NaiveDate, Error> {
+    if let Some(data_frame_ref) = &estimated_vehicle_journey.data_frame_ref {
+        return NaiveDate::parse_from_str(data_frame_ref, "%Y-%m-%d").with_context(|| {
+            format!(
+                "DataFrameRef {} could not be parsed as a date.",
+                data_frame_ref
+            )
+        });
+    }
+    // without a DataFrameRef, the date of the trip is the date of its first aimed time
+    estimated_vehicle_journey
+        .calls
+        .iter()
+        .find_map(|call| call.aimed_departure.or(call.aimed_arrival))
+        .map(|datetime| datetime.date())
+        .ok_or_else(|| format_err!("No DataFrameRef and no aimed time in calls."))
+}
+
+fn make_stop_time(
+    call: &EstimatedCall,
+    reference_date: NaiveDate,
+    siri_codes: &SiriCodes,
+) -> Result<kirin_disruption::StopTime, Error> {
+    let has_arrival = call.expected_arrival.or(call.aimed_arrival);
+    let has_departure = call.expected_departure.or(call.aimed_departure);
+    let (arrival, departure) = match (has_arrival, has_departure) {
+        (Some(arrival), Some(departure)) => (arrival, departure),
+        (Some(arrival), None) => (arrival, arrival),
+        (None, Some(departure)) => (departure, departure),
+        (None, None) => {
+            bail!("Call does not have an arrival time nor a departure time.");
+        }
+    };
+
+    let can_board = has_departure.is_some() && !call.departure_cancelled;
+    let can_debark = has_arrival.is_some() && !call.arrival_cancelled;
+    let flow_direction = match (can_board, can_debark) {
+        (true, true) => FlowDirection::BoardAndDebark,
+        (true, false) => FlowDirection::BoardOnly,
+        (false, true) => FlowDirection::DebarkOnly,
+        (false, false) => FlowDirection::NoBoardDebark,
+    };
+
+    Ok(kirin_disruption::StopTime {
+        stop_id: siri_codes.stop_point_id(&call.stop_point_ref).to_string(),
+        arrival_time: make_seconds_since_reference_date(arrival, reference_date)
+            .context("Call has a bad arrival time.")?,
+        departure_time: make_seconds_since_reference_date(departure, reference_date)
+            .context("Call has a bad departure time.")?,
+        flow_direction,
+    })
+}
+
+pub fn make_chaos_disruption(
+    situation: &PtSituation,
+    response_timestamp: &NaiveDateTime,
+    base_model: &BaseModel,
+    siri_codes: &SiriCodes,
+    siri_params: &SiriParams,
+) -> Result<ChaosDisruption, Error> {
+    // periods without an EndTime are open ended,
+    // so we close them at the end of the validity period of the model
+    let model_end = base_model.time_period().end();
+
+    let application_periods = situation
+        .validity_periods
+        .iter()
+        .map(|(start, end)| make_time_period(*start, *end, model_end))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Could not handle ValidityPeriod")?;
+
+    let publication_period = match (
+        &situation.publication_window,
+        application_periods.as_slice(),
+    ) {
+        (Some((start, end)), _) => make_time_period(*start, *end, model_end)
+            .context("Could not handle PublicationWindow")?,
+        (None, []) => {
+            let start = situation.creation_time.unwrap_or(*response_timestamp);
+            make_time_period(start, None, model_end)?
+        }
+        (None, periods) => {
+            // unwraps are safe since periods is not empty
+            let start = periods.iter().map(TimePeriod::start).min().unwrap();
+            let end = periods.iter().map(TimePeriod::end).max().unwrap();
+            TimePeriod::new(start, end)?
+        }
+    };
+
+    let effect = situation
+        .conditions
+        .iter()
+        .map(|condition| make_effect(condition))
+        .max()
+        .unwrap_or(Effect::UnknownEffect);
+
+    let mut impacted_pt_objects = Vec::new();
+    let mut informed_pt_objects = Vec::new();
+    for network in &situation.affected_networks {
+        let network_id = NetworkId {
+            id: network.clone(),
+        };
+        match effect {
+            Effect::NoService => impacted_pt_objects.push(Impacted::NetworkDeleted(network_id)),
+            _ => informed_pt_objects.push(Informed::Network(network_id)),
+        }
+    }
+    for line in &situation.affected_lines {
+        let line_id = LineId {
+            id: siri_codes.line_id(line).to_string(),
+        };
+        match effect {
+            Effect::NoService => impacted_pt_objects.push(Impacted::LineDeleted(line_id)),
+            _ => informed_pt_objects.push(Informed::Line(line_id)),
+        }
+    }
+    for stop_point in &situation.affected_stop_points {
+        let stop_point_id = StopPointId {
+            id: siri_codes.stop_point_id(stop_point).to_string(),
+        };
+        match effect {
+            Effect::NoService | Effect::Detour => {
+                impacted_pt_objects.push(Impacted::StopPointDeleted(stop_point_id));
+            }
+            _ => informed_pt_objects.push(Informed::StopPoint(stop_point_id)),
+        }
+    }
+    for vehicle_journey in &situation.affected_vehicle_journeys {
+        let vehicle_journey_id = VehicleJourneyId {
+            id: siri_codes.vehicle_journey_id(vehicle_journey).to_string(),
+        };
+        match effect {
+            Effect::NoService => {
+                impacted_pt_objects.push(Impacted::BaseTripDeleted(vehicle_journey_id));
+            }
+            _ => informed_pt_objects.push(Informed::Trip(vehicle_journey_id)),
+        }
+    }
+
+    let mut messages = Vec::new();
+    if let Some(summary) = &situation.summary {
+        messages.push(Message {
+            text: summary.clone(),
+            channel_id: None,
+            channel_name: "summary".to_string(),
+            channel_content_type: Some("text/plain".to_string()),
+            channel_types: vec![ChannelType::Title],
+        });
+    }
+    if let Some(description) = &situation.description {
+        messages.push(Message {
+            text: description.clone(),
+            channel_id: None,
+            channel_name: "description".to_string(),
+            channel_content_type: Some("text/plain".to_string()),
+            channel_types: vec![ChannelType::Web],
+        });
+    }
+
+    let impact = ChaosImpact {
+        id: situation.situation_number.clone(),
+        updated_at: situation
+            .versioned_at_time
+            .or(situation.creation_time)
+            .unwrap_or(*response_timestamp),
+        application_periods,
+        application_patterns: Vec::new(),
+        severity: Severity {
+            wording: situation.severity.clone(),
+            color: None,
+            priority: None,
+            effect,
+        },
+        messages,
+        impacted_pt_objects,
+        informed_pt_objects,
+    };
+
+    Ok(ChaosDisruption {
+        id: situation.situation_number.clone(),
+        reference: None,
+        contributor: Some(siri_params.contributor.clone()),
+        publication_period,
+        cause: Cause {
+            wording: situation.reason.clone().unwrap_or_default(),
+            category: String::new(),
+        },
+        tags: Vec::new(),
+        properties: Vec::new(),
+        impacts: vec![impact],
+    })
+}
+
+fn make_time_period(
+    start: NaiveDateTime,
+    end: Option<NaiveDateTime>,
+    default_end: NaiveDateTime,
+) -> Result<TimePeriod, Error> {
+    let end = end.unwrap_or_else(|| std::cmp::max(default_end, start + Duration::days(1)));
+    TimePeriod::new(start, end).map_err(Error::from)
+}
+
+// see the ServiceConditionEnumeration of SIRI
+fn make_effect(condition: &str) -> Effect {
+    match condition {
+        "noService" | "cancelled" => Effect::NoService,
+        "diverted" => Effect::Detour,
+        "delayed" => Effect::SignificantDelays,
+        "altered" | "disrupted" | "intermittentService" | "shortFormedService" => {
+            Effect::ReducedService
+        }
+        "additionalService" | "extendedService" | "replacementService" => Effect::AdditionalService,
+        "unknown" | "undefinedServiceInformation" => Effect::UnknownEffect,
+        _ => Effect::OtherEffect,
+    }
+}
+
+fn is_element(node: &Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| is_element(child, name))
+}
+
+fn children<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |child| is_element(child, name))
+}
+
+fn read_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(ToString::to_string)
+}
+
+fn read_bool(node: Node, name: &str) -> bool {
+    read_text(node, name).as_deref() == Some("true")
+}
+
+// SIRI datetimes are xsd:dateTime with a timezone offset
+// we convert them to utc
+fn read_datetime(node: Node, name: &str) -> Result<Option<NaiveDateTime>, Error> {
+    read_text(node, name)
+        .map(|text| {
+            DateTime::parse_from_rfc3339(&text)
+                .map(|datetime| datetime.naive_utc())
+                .with_context(|| format!("Could not parse {} {} as a datetime.", name, text))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch::loki::{time::SecondsSinceTimezonedDayStart, LoadsData, PositiveDuration};
+
+    const ESTIMATED_TIMETABLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Siri xmlns="http://www.siri.org.uk/siri" version="2.0">
+  <ServiceDelivery>
+    <ResponseTimestamp>2022-01-01T10:00:00+01:00</ResponseTimestamp>
+    <EstimatedTimetableDelivery version="2.0">
+      <EstimatedJourneyVersionFrame>
+        <EstimatedVehicleJourney>
+          <LineRef>line:1</LineRef>
+          <FramedVehicleJourneyRef>
+            <DataFrameRef>2022-01-01</DataFrameRef>
+            <DatedVehicleJourneyRef>vj:1</DatedVehicleJourneyRef>
+          </FramedVehicleJourneyRef>
+          <RecordedCalls>
+            <RecordedCall>
+              <StopPointRef>stop:A</StopPointRef>
+              <Order>1</Order>
+              <AimedDepartureTime>2022-01-01T10:00:00+01:00</AimedDepartureTime>
+              <ActualDepartureTime>2022-01-01T10:05:00+01:00</ActualDepartureTime>
+            </RecordedCall>
+          </RecordedCalls>
+          <EstimatedCalls>
+            <EstimatedCall>
+              <StopPointRef>stop:B</StopPointRef>
+              <Order>2</Order>
+              <AimedArrivalTime>2022-01-01T10:30:00+01:00</AimedArrivalTime>
+              <ExpectedArrivalTime>2022-01-01T10:35:00+01:00</ExpectedArrivalTime>
+              <DepartureBoardingActivity>noBoarding</DepartureBoardingActivity>
+            </EstimatedCall>
+          </EstimatedCalls>
+        </EstimatedVehicleJourney>
+      </EstimatedJourneyVersionFrame>
+    </EstimatedTimetableDelivery>
+  </ServiceDelivery>
+</Siri>"#;
+
+    // only the calls of trip "matin" of tests/a_small_ntfs that are not served yet
+    const PARTIAL_ESTIMATED_TIMETABLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Siri xmlns="http://www.siri.org.uk/siri" version="2.0">
+  <ServiceDelivery>
+    <ResponseTimestamp>2021-01-01T08:30:00Z</ResponseTimestamp>
+    <EstimatedTimetableDelivery version="2.0">
+      <EstimatedJourneyVersionFrame>
+        <EstimatedVehicleJourney>
+          <FramedVehicleJourneyRef>
+            <DataFrameRef>2021-01-01</DataFrameRef>
+            <DatedVehicleJourneyRef>matin</DatedVehicleJourneyRef>
+          </FramedVehicleJourneyRef>
+          <EstimatedCalls>
+            <EstimatedCall>
+              <StopPointRef>paris</StopPointRef>
+              <Order>2</Order>
+              <AimedArrivalTime>2021-01-01T09:00:00Z</AimedArrivalTime>
+              <ExpectedArrivalTime>2021-01-01T09:10:00Z</ExpectedArrivalTime>
+              <AimedDepartureTime>2021-01-01T09:00:00Z</AimedDepartureTime>
+              <ExpectedDepartureTime>2021-01-01T09:10:00Z</ExpectedDepartureTime>
+            </EstimatedCall>
+            <EstimatedCall>
+              <StopPointRef>cdg</StopPointRef>
+              <AimedArrivalTime>2021-01-01T09:30:00Z</AimedArrivalTime>
+              <ExpectedArrivalTime>2021-01-01T09:40:00Z</ExpectedArrivalTime>
+            </EstimatedCall>
+          </EstimatedCalls>
+        </EstimatedVehicleJourney>
+      </EstimatedJourneyVersionFrame>
+    </EstimatedTimetableDelivery>
+  </ServiceDelivery>
+</Siri>"#;
+
+    const SITUATION_EXCHANGE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Siri xmlns="http://www.siri.org.uk/siri" version="2.0">
+  <ServiceDelivery>
+    <ResponseTimestamp>2022-01-01T10:00:00Z</ResponseTimestamp>
+    <SituationExchangeDelivery version="2.0">
+      <Situations>
+        <PtSituationElement>
+          <CreationTime>2022-01-01T09:00:00Z</CreationTime>
+          <SituationNumber>situation:1</SituationNumber>
+          <Progress>open</Progress>
+          <ValidityPeriod>
+            <StartTime>2022-01-01T12:00:00Z</StartTime>
+            <EndTime>2022-01-01T18:00:00Z</EndTime>
+          </ValidityPeriod>
+          <EquipmentReason>signalProblem</EquipmentReason>
+          <Severity>severe</Severity>
+          <Summary>Line 1 is interrupted</Summary>
+          <Affects>
+            <Networks>
+              <AffectedNetwork>
+                <AffectedLine>
+                  <LineRef>line:1</LineRef>
+                </AffectedLine>
+              </AffectedNetwork>
+            </Networks>
+          </Affects>
+          <Consequences>
+            <Consequence>
+              <Condition>noService</Condition>
+            </Consequence>
+          </Consequences>
+        </PtSituationElement>
+      </Situations>
+    </SituationExchangeDelivery>
+  </ServiceDelivery>
+</Siri>"#;
+
+    fn siri_params() -> SiriParams {
+        SiriParams {
+            topics: Vec::new(),
+            contributor: "siri".to_string(),
+            vehicle_journey_code_type: "source".to_string(),
+            stop_point_code_type: "source".to_string(),
+            line_code_type: "source".to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_estimated_timetable() {
+        let delivery = parse_siri_xml(ESTIMATED_TIMETABLE).unwrap();
+        assert_eq!(
+            delivery.response_timestamp,
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0)
+        );
+        assert!(delivery.situations.is_empty());
+        assert_eq!(delivery.estimated_vehicle_journeys.len(), 1);
+
+        let journey = &delivery.estimated_vehicle_journeys[0];
+        assert_eq!(journey.dated_vehicle_journey_ref, "vj:1");
+        assert_eq!(journey.data_frame_ref.as_deref(), Some("2022-01-01"));
+        assert_eq!(journey.calls.len(), 2);
+        assert_eq!(
+            journey.calls[0].expected_departure,
+            Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 5, 0))
+        );
+        assert!(journey.calls[1].departure_cancelled);
+    }
+
+    #[test]
+    fn estimated_vehicle_journey_to_kirin_disruption() {
+        let delivery = parse_siri_xml(ESTIMATED_TIMETABLE).unwrap();
+        let base_model = BaseModel::empty();
+        let disruption = make_kirin_disruption(
+            &delivery.estimated_vehicle_journeys[0],
+            &delivery.response_timestamp,
+            &base_model,
+            &RealTimeModel::new(),
+            &SiriCodes::empty(),
+            &siri_params(),
+        )
+        .unwrap();
+
+        assert_eq!(disruption.id, "siri:vj:1:20220101");
+        assert_eq!(disruption.trip_date, NaiveDate::from_ymd(2022, 1, 1));
+        assert_eq!(disruption.effect, Effect::SignificantDelays);
+        match &disruption.update {
+            UpdateType::BaseTripUpdated(update_data) => {
+                let stop_times = &update_data.stop_times;
+                assert_eq!(stop_times.len(), 2);
+                assert_eq!(stop_times[0].stop_id, "stop:A");
+                assert_eq!(stop_times[0].flow_direction, FlowDirection::BoardOnly);
+                assert_eq!(stop_times[1].stop_id, "stop:B");
+                assert_eq!(stop_times[1].flow_direction, FlowDirection::DebarkOnly);
+            }
+            _ => panic!("Expected a BaseTripUpdated"),
+        }
+    }
+
+    fn seconds(hours: i64, minutes: i64) -> SecondsSinceTimezonedDayStart {
+        SecondsSinceTimezonedDayStart::from_seconds_i64(hours * 3600 + minutes * 60).unwrap()
+    }
+
+    fn updated_stop_times(disruption: &KirinDisruption) -> &[kirin_disruption::StopTime] {
+        match &disruption.update {
+            UpdateType::BaseTripUpdated(update_data) => &update_data.stop_times,
+            _ => panic!("Expected a BaseTripUpdated"),
+        }
+    }
+
+    #[test]
+    fn partial_delivery_keeps_stop_times_not_delivered() {
+        let model = launch::loki::transit_model::ntfs::read("tests/a_small_ntfs").unwrap();
+        let base_model =
+            BaseModel::from_transit_model(model, LoadsData::empty(), PositiveDuration::zero())
+                .unwrap();
+        let delivery = parse_siri_xml(PARTIAL_ESTIMATED_TIMETABLE).unwrap();
+        let estimated_vehicle_journey = &delivery.estimated_vehicle_journeys[0];
+        let date = NaiveDate::from_ymd(2021, 1, 1);
+
+        // without real time data, the calls are merged into the base stop times
+        let mut real_time_model = RealTimeModel::new();
+        let disruption = make_kirin_disruption(
+            estimated_vehicle_journey,
+            &delivery.response_timestamp,
+            &base_model,
+            &real_time_model,
+            &SiriCodes::empty(),
+            &siri_params(),
+        )
+        .unwrap();
+        assert_eq!(disruption.effect, Effect::SignificantDelays);
+        let stop_times = updated_stop_times(&disruption);
+        let stop_ids: Vec<&str> = stop_times.iter().map(|st| st.stop_id.as_str()).collect();
+        assert_eq!(stop_ids, vec!["massy", "paris", "cdg"]);
+        assert_eq!(stop_times[0].departure_time, seconds(8, 0));
+        assert_eq!(stop_times[1].arrival_time, seconds(9, 10));
+        assert_eq!(stop_times[1].departure_time, seconds(9, 10));
+        assert_eq!(stop_times[2].arrival_time, seconds(9, 40));
+        assert_eq!(stop_times[2].flow_direction, FlowDirection::DebarkOnly);
+
+        // with a real time version of the trip, the calls are merged into its stop times
+        let vehicle_journey_idx = base_model.vehicle_journey_idx("matin").unwrap();
+        let mut current_stop_times: Vec<StopTime> = base_model
+            .stop_times(vehicle_journey_idx)
+            .unwrap()
+            .collect();
+        current_stop_times[0].board_time = seconds(8, 2);
+        real_time_model.set_base_trip_version(
+            vehicle_journey_idx,
+            &date,
+            TripVersion::Present(current_stop_times),
+        );
+        let disruption = make_kirin_disruption(
+            estimated_vehicle_journey,
+            &delivery.response_timestamp,
+            &base_model,
+            &real_time_model,
+            &SiriCodes::empty(),
+            &siri_params(),
+        )
+        .unwrap();
+        let stop_times = updated_stop_times(&disruption);
+        assert_eq!(stop_times.len(), 3);
+        assert_eq!(stop_times[0].departure_time, seconds(8, 2));
+        assert_eq!(stop_times[2].arrival_time, seconds(9, 40));
+    }
+
+    #[test]
+    fn situation_to_chaos_disruption() {
+        let delivery = parse_siri_xml(SITUATION_EXCHANGE).unwrap();
+        assert!(delivery.estimated_vehicle_journeys.is_empty());
+        assert_eq!(delivery.situations.len(), 1);
+
+        let situation = &delivery.situations[0];
+        assert!(!situation.is_closed);
+        assert_eq!(situation.reason.as_deref(), Some("signalProblem"));
+
+        let base_model = BaseModel::empty();
+        let disruption = make_chaos_disruption(
+            situation,
+            &delivery.response_timestamp,
+            &base_model,
+            &SiriCodes::empty(),
+            &siri_params(),
+        )
+        .unwrap();
+
+        assert_eq!(disruption.id, "situation:1");
+        assert_eq!(disruption.impacts.len(), 1);
+        let impact = &disruption.impacts[0];
+        assert_eq!(impact.severity.effect, Effect::NoService);
+        assert_eq!(impact.application_periods.len(), 1);
+        assert!(matches!(
+            impact.impacted_pt_objects.as_slice(),
+            [Impacted::LineDeleted(LineId { id })] if id == "line:1"
+        ));
+        assert_eq!(
+            disruption.publication_period.start(),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn bad_xml() {
+        assert!(parse_siri_xml("<Siri>").is_err());
+        assert!(parse_siri_xml("<Siri></Siri>").is_err());
+    }
+}
@@ -0,0 +1,578 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::{convert::Infallible, net::SocketAddr, thread};
+
+use anyhow::{format_err, Context, Error};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Server, StatusCode,
+};
+use launch::loki::{
+    tracing::{error, info, log::trace},
+    NaiveDateTime,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{
+    navitia_proto,
+    zmq_worker::{ClientId, HttpWorkerToZmqChannels, RequestMessage},
+};
+
+pub const HTTP_DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+const STOP_POINT_PREFIX: &str = "stop_point:";
+
+/// Answers json requests received over http.
+///
+/// Each request is translated into a navitia protobuf request, which is handled
+/// by the same load balancer and status worker as the requests received on the zmq socket.
+/// The protobuf response is then sent back as json.
+pub struct HttpWorker {
+    endpoint: SocketAddr,
+    zmq_channels: HttpWorkerToZmqChannels,
+    // to send shutdown signal to Master when an error occurs insider HttpWorker
+    shutdown_sender: mpsc::Sender<()>,
+}
+
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+enum RealTimeLevelParam {
+    #[default]
+    Base,
+    Realtime,
+}
+
+impl RealTimeLevelParam {
+    fn to_proto(self) -> navitia_proto::RtLevel {
+        match self {
+            RealTimeLevelParam::Base => navitia_proto::RtLevel::BaseSchedule,
+            RealTimeLevelParam::Realtime => navitia_proto::RtLevel::Realtime,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct JourneysQuery {
+    /// stop_point id, with its prefix (e.g. `stop_point:SP1`)
+    from: String,
+    to: String,
+    /// utc datetime, formatted as `%Y%m%dT%H%M%S`
+    datetime: String,
+    /// if true, `datetime` is the earliest departure, else the latest arrival
+    #[serde(default = "default_clockwise")]
+    clockwise: bool,
+    /// in seconds
+    #[serde(default = "default_max_duration")]
+    max_duration: i32,
+    #[serde(default = "default_max_nb_transfers")]
+    max_nb_transfers: i32,
+    #[serde(default)]
+    wheelchair: bool,
+    #[serde(default)]
+    realtime_level: RealTimeLevelParam,
+    /// when absent, the server's default comparator is used
+    criteria: Option<CriteriaParam>,
+    /// in seconds, when absent the server's default request params are used
+    arrival_transfer_penalty: Option<i32>,
+    walking_transfer_penalty: Option<i32>,
+    too_late_threshold: Option<i32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum CriteriaParam {
+    Classic,
+    Occupancy,
+}
+
+impl CriteriaParam {
+    fn to_proto(self) -> navitia_proto::Criteria {
+        match self {
+            CriteriaParam::Classic => navitia_proto::Criteria::Classic,
+            CriteriaParam::Occupancy => navitia_proto::Criteria::Occupancy,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ScheduleQuery {
+    /// the stop_point, stop_area, line, route, network, physical_mode or commercial_mode
+    /// whose stops are looked at, with its prefix (e.g. `line:L1`)
+    uri: String,
+    /// utc datetime, formatted as `%Y%m%dT%H%M%S`
+    from_datetime: String,
+    /// in seconds
+    #[serde(default = "default_max_duration")]
+    duration: i32,
+    #[serde(default = "default_count")]
+    count: i32,
+    #[serde(default)]
+    realtime_level: RealTimeLevelParam,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct PlacesNearbyQuery {
+    /// a stop_point/stop_area id, or coordinates `lon;lat`
+    uri: String,
+    /// in meters
+    #[serde(default = "default_distance")]
+    distance: f64,
+    #[serde(default = "default_count")]
+    count: i32,
+    #[serde(default)]
+    start_page: i32,
+    #[serde(default = "default_depth")]
+    depth: i32,
+}
+
+fn default_clockwise() -> bool {
+    true
+}
+
+fn default_max_duration() -> i32 {
+    24 * 60 * 60 // 1 day
+}
+
+fn default_max_nb_transfers() -> i32 {
+    10
+}
+
+fn default_count() -> i32 {
+    10
+}
+
+fn default_distance() -> f64 {
+    500.0
+}
+
+fn default_depth() -> i32 {
+    1
+}
+
+impl HttpWorker {
+    pub fn new(
+        endpoint: SocketAddr,
+        zmq_channels: HttpWorkerToZmqChannels,
+        shutdown_sender: mpsc::Sender<()>,
+    ) -> Self {
+        Self {
+            endpoint,
+            zmq_channels,
+            shutdown_sender,
+        }
+    }
+
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
+        let thread_builder = thread::Builder::new().name("loki_http_worker".to_string());
+        let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
+        Ok(handle)
+    }
+
+    async fn run(self) {
+        let run_err = self.run_server().await;
+        error!("HttpWorker stopped : {:?}", run_err);
+        // send shutdown signal
+        let _ = self.shutdown_sender.send(()).await;
+    }
+
+    async fn run_server(&self) -> Result<(), Error> {
+        let zmq_channels = self.zmq_channels.clone();
+        let make_service = make_service_fn(move |_connection| {
+            let zmq_channels = zmq_channels.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_http_request(request, zmq_channels.clone())
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&self.endpoint)
+            .with_context(|| format!("Could not bind http endpoint {}", self.endpoint))?;
+        info!("Http worker bound to endpoint {}", self.endpoint);
+        server
+            .serve(make_service)
+            .await
+            .context("Http server failed.")
+    }
+}
+
+async fn handle_http_request(
+    request: hyper::Request<Body>,
+    zmq_channels: HttpWorkerToZmqChannels,
+) -> Result<hyper::Response<Body>, Infallible> {
+    trace!("HttpWorker received a request on {}", request.uri());
+    if request.method() != Method::GET {
+        let error = format_err!("Only GET requests are supported.");
+        return Ok(error_response(StatusCode::METHOD_NOT_ALLOWED, &error));
+    }
+
+    let path = request.uri().path();
+    let query = request.uri().query().unwrap_or("");
+    let proto_request = match make_proto_request(path, query) {
+        Ok(Some(proto_request)) => proto_request,
+        Ok(None) => {
+            let error = format_err!("Unknown endpoint {}", path);
+            return Ok(error_response(StatusCode::NOT_FOUND, &error));
+        }
+        Err(err) => {
+            return Ok(error_response(StatusCode::BAD_REQUEST, &err));
+        }
+    };
+
+    match solve(proto_request, &zmq_channels).await {
+        Ok(proto_response) => {
+            let status = response_status(&proto_response);
+            Ok(json_response(status, &proto_response))
+        }
+        Err(err) => {
+            error!("HttpWorker could not answer a request : {:?}", err);
+            Ok(error_response(StatusCode::INTERNAL_SERVER_ERROR, &err))
+        }
+    }
+}
+
+// requests rejected because the server is overloaded are answered with a 503,
+// so that clients and proxies can retry later
+fn response_status(proto_response: &navitia_proto::Response) -> StatusCode {
+    match &proto_response.error {
+        Some(error) if error.id() == navitia_proto::error::ErrorId::ServiceUnavailable => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::OK,
+    }
+}
+
+async fn solve(
+    proto_request: navitia_proto::Request,
+    zmq_channels: &HttpWorkerToZmqChannels,
+) -> Result<navitia_proto::Response, Error> {
+    let (response_sender, response_receiver) = oneshot::channel();
+    let requested_api = proto_request.requested_api();
+    let request_message = RequestMessage {
+        payload: proto_request,
+        client_id: ClientId::Http(response_sender),
+    };
+
+    use navitia_proto::Api;
+    match requested_api {
+        Api::Status | Api::Metadatas => {
+            zmq_channels
+                .status_requests_sender
+                .send(request_message)
+                .context("HttpWorker error while forwarding request to status worker.")?
+        }
+        _ => zmq_channels
+            .requests_sender
+            .send(request_message)
+            .context("HttpWorker error while forwarding request to load balancer.")?,
+    };
+
+    response_receiver
+        .await
+        .context("HttpWorker : channel to receive the response has closed.")
+}
+
+// Returns Ok(None) when `path` is not a known endpoint.
+fn make_proto_request(path: &str, query: &str) -> Result<Option<navitia_proto::Request>, Error> {
+    let request = match path.trim_end_matches('/') {
+        "/journeys" => make_journeys_request(parse_query(query)?)?,
+        "/next_departures" => {
+            make_schedule_request(parse_query(query)?, navitia_proto::Api::NextDepartures)?
+        }
+        "/next_arrivals" => {
+            make_schedule_request(parse_query(query)?, navitia_proto::Api::NextArrivals)?
+        }
+        "/places_nearby" => make_places_nearby_request(parse_query(query)?),
+        "/status" => make_api_request(navitia_proto::Api::Status),
+        "/metadatas" => make_api_request(navitia_proto::Api::Metadatas),
+        _ => return Ok(None),
+    };
+    Ok(Some(request))
+}
+
+fn parse_query<'a, T: Deserialize<'a>>(query: &'a str) -> Result<T, Error> {
+    serde_urlencoded::from_str(query)
+        .with_context(|| format!("Invalid query parameters '{}'", query))
+}
+
+fn parse_timestamp(datetime: &str) -> Result<u64, Error> {
+    let datetime =
+        NaiveDateTime::parse_from_str(datetime, HTTP_DATETIME_FORMAT).with_context(|| {
+            format!(
+                "Could not parse datetime '{}', expected format is {}",
+                datetime, HTTP_DATETIME_FORMAT
+            )
+        })?;
+    u64::try_from(datetime.timestamp())
+        .with_context(|| format!("Datetime {} is before 1970.", datetime))
+}
+
+// The journeys solver only knows stop points, so any other kind of place
+// would silently give no journey.
+fn parse_stop_point(place: String) -> Result<String, Error> {
+    if place.starts_with(STOP_POINT_PREFIX) {
+        Ok(place)
+    } else {
+        Err(format_err!(
+            "Invalid place '{}', expected a stop_point id starting with '{}'",
+            place,
+            STOP_POINT_PREFIX
+        ))
+    }
+}
+
+fn make_journeys_request(query: JourneysQuery) -> Result<navitia_proto::Request, Error> {
+    let origin = navitia_proto::LocationContext {
+        place: parse_stop_point(query.from)?,
+        ..Default::default()
+    };
+    let destination = navitia_proto::LocationContext {
+        place: parse_stop_point(query.to)?,
+        ..Default::default()
+    };
+    let mut journeys = navitia_proto::JourneysRequest {
+        origin: vec![origin],
+        destination: vec![destination],
+        datetimes: vec![parse_timestamp(&query.datetime)?],
+        clockwise: query.clockwise,
+        max_duration: query.max_duration,
+        max_transfers: query.max_nb_transfers,
+        wheelchair: Some(query.wheelchair),
+        criteria: query.criteria.map(|criteria| criteria.to_proto() as i32),
+        arrival_transfer_penalty: query.arrival_transfer_penalty,
+        walking_transfer_penalty: query.walking_transfer_penalty,
+        too_late_threshold: query.too_late_threshold,
+        ..Default::default()
+    };
+    journeys.set_realtime_level(query.realtime_level.to_proto());
+
+    let mut request = navitia_proto::Request {
+        journeys: Some(journeys),
+        ..Default::default()
+    };
+    request.set_requested_api(navitia_proto::Api::PtPlanner);
+    Ok(request)
+}
+
+fn make_schedule_request(
+    query: ScheduleQuery,
+    api: navitia_proto::Api,
+) -> Result<navitia_proto::Request, Error> {
+    let mut next_stop_request = navitia_proto::NextStopTimeRequest {
+        from_datetime: Some(parse_timestamp(&query.from_datetime)?),
+        duration: query.duration,
+        nb_stoptimes: query.count,
+        count: query.count,
+        start_page: 0,
+        ..Default::default()
+    };
+    if api == navitia_proto::Api::NextArrivals {
+        next_stop_request.arrival_filter = query.uri;
+    } else {
+        next_stop_request.departure_filter = query.uri;
+    }
+    next_stop_request.set_realtime_level(query.realtime_level.to_proto());
+
+    let mut request = navitia_proto::Request {
+        next_stop_times: Some(next_stop_request),
+        ..Default::default()
+    };
+    request.set_requested_api(api);
+    Ok(request)
+}
+
+fn make_places_nearby_request(query: PlacesNearbyQuery) -> navitia_proto::Request {
+    let places_nearby_request = navitia_proto::PlacesNearbyRequest {
+        distance: query.distance,
+        uri: query.uri,
+        count: query.count,
+        start_page: query.start_page,
+        depth: query.depth,
+        ..Default::default()
+    };
+    let mut request = navitia_proto::Request {
+        places_nearby: Some(places_nearby_request),
+        ..Default::default()
+    };
+    request.set_requested_api(navitia_proto::Api::PlacesNearby);
+    request
+}
+
+fn make_api_request(api: navitia_proto::Api) -> navitia_proto::Request {
+    let mut request = navitia_proto::Request::default();
+    request.set_requested_api(api);
+    request
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> hyper::Response<Body> {
+    match serde_json::to_vec(body) {
+        Ok(bytes) => hyper::Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(bytes))
+            // unwrap is safe since the status and header are valid
+            .unwrap(),
+        Err(err) => {
+            error!(
+                "HttpWorker could not serialize response to json : {:?}",
+                err
+            );
+            let mut response = hyper::Response::new(Body::empty());
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+    }
+}
+
+fn error_response(status: StatusCode, error: &Error) -> hyper::Response<Body> {
+    let body = ErrorBody {
+        error: format!("{:#}", error),
+    };
+    json_response(status, &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journeys_query_with_defaults() {
+        let request = make_proto_request(
+            "/journeys",
+            "from=stop_point%3ASP1&to=stop_point%3ASP2&datetime=20210101T080000",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.requested_api(), navitia_proto::Api::PtPlanner);
+        let journeys = request.journeys.unwrap();
+        assert_eq!(journeys.origin[0].place, "stop_point:SP1");
+        assert_eq!(journeys.destination[0].place, "stop_point:SP2");
+        assert_eq!(journeys.datetimes, vec![1_609_488_000]);
+        assert!(journeys.clockwise);
+        assert_eq!(journeys.max_duration, default_max_duration());
+        assert_eq!(
+            journeys.realtime_level(),
+            navitia_proto::RtLevel::BaseSchedule
+        );
+        assert_eq!(journeys.criteria, None);
+        assert_eq!(journeys.arrival_transfer_penalty, None);
+    }
+
+    #[test]
+    fn journeys_query_with_tuning() {
+        let request = make_proto_request(
+            "/journeys",
+            "from=stop_point%3ASP1&to=stop_point%3ASP2&datetime=20210101T080000\
+            &criteria=occupancy&walking_transfer_penalty=300&too_late_threshold=3600",
+        )
+        .unwrap()
+        .unwrap();
+        let journeys = request.journeys.unwrap();
+        assert_eq!(
+            journeys.criteria,
+            Some(navitia_proto::Criteria::Occupancy as i32)
+        );
+        assert_eq!(journeys.walking_transfer_penalty, Some(300));
+        assert_eq!(journeys.too_late_threshold, Some(3600));
+        assert_eq!(journeys.arrival_transfer_penalty, None);
+    }
+
+    #[test]
+    fn next_arrivals_query() {
+        let request = make_proto_request(
+            "/next_arrivals/",
+            "uri=line%3AL1&from_datetime=20210101T080000&count=3&realtime_level=realtime",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.requested_api(), navitia_proto::Api::NextArrivals);
+        let next_stop_times = request.next_stop_times.unwrap();
+        assert_eq!(next_stop_times.arrival_filter, "line:L1");
+        assert!(next_stop_times.departure_filter.is_empty());
+        assert_eq!(next_stop_times.nb_stoptimes, 3);
+        assert_eq!(
+            next_stop_times.realtime_level(),
+            navitia_proto::RtLevel::Realtime
+        );
+    }
+
+    #[test]
+    fn invalid_queries() {
+        // missing destination
+        assert!(make_proto_request(
+            "/journeys",
+            "from=stop_point%3ASP1&datetime=20210101T080000"
+        )
+        .is_err());
+        // badly formatted datetime
+        assert!(make_proto_request(
+            "/journeys",
+            "from=stop_point%3ASP1&to=stop_point%3ASP2&datetime=2021-01-01"
+        )
+        .is_err());
+        // places that are not stop points
+        assert!(make_proto_request(
+            "/journeys",
+            "from=stop_area%3ASA1&to=stop_point%3ASP2&datetime=20210101T080000"
+        )
+        .is_err());
+        assert!(make_proto_request(
+            "/journeys",
+            "from=stop_point%3ASP1&to=SP2&datetime=20210101T080000"
+        )
+        .is_err());
+        // unknown parameter
+        assert!(make_proto_request("/places_nearby", "uri=SA1&radius=10").is_err());
+        // unknown endpoint
+        assert!(make_proto_request("/isochrones", "").unwrap().is_none());
+    }
+}
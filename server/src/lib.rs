@@ -36,6 +36,12 @@
 
 pub mod navitia_proto {
     include!(concat!(env!("OUT_DIR"), "/pbnavitia.rs"));
+
+    /// Names of the values of each enum, used to write enum fields in json.
+    /// Generated by the build script, cf `navitia_enums`
+    pub mod enum_names {
+        include!(concat!(env!("OUT_DIR"), "/navitia_enum_names.rs"));
+    }
 }
 
 pub mod chaos_proto {
@@ -51,13 +57,26 @@ extern crate core;
 
 pub mod handle_chaos_message;
 pub mod handle_kirin_message;
+pub mod handle_siri_message;
 pub mod response;
 
+pub mod admin_worker;
+pub mod archive_cache;
 pub mod chaos;
 pub mod compute_worker;
+pub mod data_checks;
 pub mod data_downloader;
+pub mod data_watcher;
+pub mod http_worker;
 pub mod load_balancer;
 pub mod master_worker;
+pub mod metrics;
+pub mod navitia_enums;
+pub mod rabbitmq_connection;
+pub mod real_time_journal;
+pub mod real_time_sources;
+pub mod response_cache;
+pub mod shared_data;
 pub mod status_worker;
 pub mod zmq_worker;
 
@@ -65,7 +84,7 @@ pub mod data_worker;
 pub mod server_config;
 
 use launch::loki::tracing::{debug, info};
-use server_config::ServerConfig;
+use server_config::{InstancesConfig, ServerConfig};
 
 use structopt::StructOpt;
 
@@ -90,8 +109,8 @@ pub struct Options {
 
 pub fn launch_server() -> Result<(), Error> {
     let options = Options::from_args();
-    let config = read_config(&options.config_file)?;
-    launch_master_worker(config)
+    let configs = read_configs(&options.config_file)?;
+    launch_master_workers(configs)
 }
 
 pub fn read_config(config_file_path: &Path) -> Result<ServerConfig, Error> {
@@ -103,7 +122,31 @@ pub fn read_config(config_file_path: &Path) -> Result<ServerConfig, Error> {
     Ok(config)
 }
 
+/// Reads the config of all instances to be served.
+///
+/// The config file either describes a single instance,
+/// or declares several instances in `[[instances]]` tables.
+pub fn read_configs(config_file_path: &Path) -> Result<Vec<ServerConfig>, Error> {
+    info!("Reading config from file {:?}", &config_file_path);
+    let content = fs::read_to_string(&config_file_path)
+        .with_context(|| format!("Error opening config file {:?}", &config_file_path))?;
+    let value: toml::Value = toml::from_str(&content)?;
+    if value.get("instances").is_none() {
+        let config: ServerConfig = toml::from_str(&content)?;
+        debug!("Launching with config : {:#?}", config);
+        return Ok(vec![config]);
+    }
+    let instances_config: InstancesConfig = toml::from_str(&content)?;
+    instances_config.check()?;
+    debug!("Launching with config : {:#?}", instances_config);
+    Ok(instances_config.instances)
+}
+
 pub fn launch_master_worker(config: ServerConfig) -> Result<(), Error> {
     let master_worker = master_worker::MasterWorker::new(config)?;
     master_worker.run_blocking()
 }
+
+pub fn launch_master_workers(configs: Vec<ServerConfig>) -> Result<(), Error> {
+    master_worker::MasterWorker::run_all_blocking(configs)
+}
@@ -35,23 +35,26 @@
 // www.navitia.io
 
 use anyhow::{format_err, Context, Error};
-use launch::{
-    config,
-    loki::{
-        models::{base_model::BaseModel, real_time_model::RealTimeModel},
-        tracing::{error, info, log::trace},
-        TransitData,
-    },
-};
+use launch::loki::tracing::{error, info, log::trace, warn};
 use std::{
-    sync::{Arc, RwLock},
+    collections::VecDeque,
+    sync::Arc,
     thread::{self},
+    time::{Duration, Instant},
+};
+use tokio::{
+    runtime::{Builder, Handle},
+    sync::mpsc,
 };
-use tokio::{runtime::Builder, sync::mpsc};
 
 use crate::{
     compute_worker::ComputeWorker,
-    zmq_worker::{LoadBalancerToZmqChannels, RequestMessage, ResponseMessage},
+    metrics::{Metrics, RejectionReason},
+    navitia_proto,
+    response_cache::ResponseCache,
+    server_config::{LoadBalancerParams, ServerConfig},
+    shared_data::SharedData,
+    zmq_worker::{is_deadline_expired, LoadBalancerToZmqChannels, RequestMessage, ResponseMessage},
 };
 
 #[derive(Debug, PartialEq, Eq)]
@@ -72,11 +75,53 @@ pub struct WorkerId {
     pub id: usize,
 }
 
+// Requests waiting for an available worker are queued by class.
+// Cheap requests should not wait behind a burst of journey requests,
+// so the queues are emptied by priority, in the order of `RequestClass::BY_PRIORITY`.
+// But a request that has waited more than `max_priority_delay` goes first,
+// otherwise a steady flow of cheap requests would starve the journeys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RequestClass {
+    PlacesNearby,
+    Schedules,
+    Journeys,
+}
+
+impl RequestClass {
+    const BY_PRIORITY: [RequestClass; 3] = [
+        RequestClass::PlacesNearby,
+        RequestClass::Schedules,
+        RequestClass::Journeys,
+    ];
+
+    fn of(api: navitia_proto::Api) -> Self {
+        use navitia_proto::Api;
+        match api {
+            Api::PlacesNearby => RequestClass::PlacesNearby,
+            Api::NextDepartures | Api::NextArrivals => RequestClass::Schedules,
+            // journeys, and requests that workers will answer with an error
+            _ => RequestClass::Journeys,
+        }
+    }
+}
+
+struct RequestQueue {
+    class: RequestClass,
+    capacity: usize,
+    // along with the instant they were queued at
+    requests: VecDeque<(Instant, RequestMessage)>,
+}
+
 pub struct LoadBalancer {
     worker_request_senders: Vec<mpsc::Sender<RequestMessage>>,
     workers_response_receiver: mpsc::Receiver<(WorkerId, ResponseMessage)>,
     worker_states: Vec<WorkerState>,
 
+    // requests received, waiting for an available worker
+    // one queue per RequestClass, ordered by decreasing priority
+    pending_requests: Vec<RequestQueue>,
+    max_priority_delay: Duration,
+
     order_receiver: mpsc::Receiver<LoadBalancerOrder>,
     stopped_sender: mpsc::Sender<()>,
 
@@ -85,6 +130,8 @@ pub struct LoadBalancer {
     state: LoadBalancerState,
 
     zmq_channels: LoadBalancerToZmqChannels,
+
+    metrics: Arc<Metrics>,
 }
 
 pub struct LoadBalancerChannels {
@@ -100,10 +147,11 @@ pub enum LoadBalancerOrder {
 
 impl LoadBalancer {
     pub fn new(
-        data_and_models: Arc<RwLock<(TransitData, BaseModel, RealTimeModel)>>,
-        nb_workers: u16,
-        default_request_params: &config::RequestParams,
+        data_and_models: Arc<SharedData>,
+        config: &ServerConfig,
+        response_cache: Option<Arc<ResponseCache>>,
         zmq_channels: LoadBalancerToZmqChannels,
+        metrics: Arc<Metrics>,
         shutdown_sender: mpsc::Sender<()>,
     ) -> Result<(Self, LoadBalancerChannels), Error> {
         let mut worker_request_senders = Vec::new();
@@ -111,7 +159,7 @@ impl LoadBalancer {
 
         // Compute workers
         let (workers_response_sender, workers_response_receiver) = mpsc::channel(1);
-        for id in 0..nb_workers {
+        for id in 0..config.nb_workers {
             let builder = thread::Builder::new().name(format!("loki_worker_{}", id));
 
             let worker_id = WorkerId {
@@ -121,8 +169,11 @@ impl LoadBalancer {
             let (worker, request_channel) = ComputeWorker::new(
                 worker_id,
                 data_and_models.clone(),
-                default_request_params.clone(),
+                config.default_request_params.clone(),
+                config.default_comparator_type.clone(),
                 workers_response_sender.clone(),
+                metrics.clone(),
+                response_cache.clone(),
             );
             let _thread_handle = builder.spawn(move || worker.run())?;
             worker_request_senders.push(request_channel);
@@ -145,24 +196,23 @@ impl LoadBalancer {
             worker_request_senders,
             workers_response_receiver,
             worker_states,
+            pending_requests: make_request_queues(&config.load_balancer),
+            max_priority_delay: Duration::from_secs(
+                config.load_balancer.max_priority_delay.total_seconds(),
+            ),
             order_receiver,
             stopped_sender,
             shutdown_sender,
             state: LoadBalancerState::Online,
             zmq_channels,
+            metrics,
         };
         Ok((result, load_balancer_handle))
     }
 
-    // run in a spawned thread
-    pub fn run_in_a_thread(self) -> Result<std::thread::JoinHandle<()>, Error> {
-        // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
-
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
-
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
         let thread_builder = thread::Builder::new().name("loki_load_balancer".to_string());
         let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
         Ok(handle)
@@ -190,21 +240,11 @@ impl LoadBalancer {
     async fn main_loop(&mut self) -> Result<(), Error> {
         info!("Starting LoadBalancer worker");
         loop {
-            let has_available_worker =
-                self.worker_states
-                    .iter()
-                    .enumerate()
-                    .find_map(|(id, state)| {
-                        if *state == WorkerState::Available {
-                            Some(id)
-                        } else {
-                            None
-                        }
-                    });
+            self.dispatch_pending_requests().await?;
 
             trace!(
-                "LoadBalancer worker is waiting. Available worker : {:?}",
-                has_available_worker
+                "LoadBalancer worker is waiting. {} pending requests.",
+                self.nb_of_pending_requests()
             );
             tokio::select! {
                 // this indicates to tokio to poll the futures in the order they appears below
@@ -251,24 +291,144 @@ impl LoadBalancer {
 
                     }
                 }
-                //receive requests from the zmq socket, and dispatch them to an available worker
-                has_request = self.zmq_channels.requests_receiver.recv(),
-                if has_available_worker.is_some() && self.state == LoadBalancerState::Online => {
+                //receive requests from the zmq socket, they will be dispatched to an available worker
+                has_request = self.zmq_channels.requests_receiver.recv() => {
                     let request = has_request.ok_or_else(|| format_err!("Channel to receive zmq requests has closed."))?;
                     trace!("Load Balancer received a request.");
-                    // unwrap is safe here, because we enter this block only if has_available_worker.is_some()
-                    let worker_id = has_available_worker.unwrap();
-                    trace!("LoadBalancer is sending request to worker {:?}", worker_id);
-                    let sender = &self.worker_request_senders[worker_id];
-                    sender.send(request).await
-                                .with_context(||format!("Channel to forward request to worker {} has closed", worker_id))?;
-
-                    self.worker_states[worker_id] = WorkerState::Busy;
+                    self.enqueue_request(request)?;
+                }
+            }
+        }
+    }
+
+    // Put the request in the queue of its class.
+    // The request is rejected if its deadline has already passed, or if the queue is full.
+    fn enqueue_request(&mut self, request: RequestMessage) -> Result<(), Error> {
+        if is_deadline_expired(&request.payload) {
+            return self.reject_request(request, RejectionReason::DeadlineExpired);
+        }
+        let class = RequestClass::of(request.payload.requested_api());
+        // unwrap is safe here, because there is a queue for each RequestClass
+        let queue = self
+            .pending_requests
+            .iter_mut()
+            .find(|queue| queue.class == class)
+            .unwrap();
+        if queue.requests.len() >= queue.capacity {
+            warn!(
+                "LoadBalancer queue for {:?} requests is full ({} requests). \
+                I'll reject the new request.",
+                class, queue.capacity
+            );
+            return self.reject_request(request, RejectionReason::Overloaded);
+        }
+        queue.requests.push_back((Instant::now(), request));
+        Ok(())
+    }
+
+    // Pop the oldest request of the highest priority non-empty queue,
+    // unless some requests have waited more than `max_priority_delay` :
+    // then the one that waited the longest is popped, whatever its priority.
+    // Requests whose deadline expired while they were waiting are rejected.
+    fn pop_pending_request(&mut self) -> Result<Option<RequestMessage>, Error> {
+        loop {
+            let now = Instant::now();
+            let has_delayed_queue = self
+                .pending_requests
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, queue)| {
+                    queue
+                        .requests
+                        .front()
+                        .map(|(queued_at, _)| (idx, *queued_at))
+                })
+                .filter(|(_, queued_at)| now.duration_since(*queued_at) > self.max_priority_delay)
+                .min_by_key(|(_, queued_at)| *queued_at)
+                .map(|(idx, _)| idx);
+            let has_request = match has_delayed_queue {
+                Some(idx) => self.pending_requests[idx].requests.pop_front(),
+                None => self
+                    .pending_requests
+                    .iter_mut()
+                    .find_map(|queue| queue.requests.pop_front()),
+            }
+            .map(|(_, request)| request);
+            match has_request {
+                Some(request) if is_deadline_expired(&request.payload) => {
+                    self.reject_request(request, RejectionReason::DeadlineExpired)?;
                 }
+                _ => return Ok(has_request),
             }
         }
     }
 
+    fn nb_of_pending_requests(&self) -> usize {
+        self.pending_requests
+            .iter()
+            .map(|queue| queue.requests.len())
+            .sum()
+    }
+
+    // send pending requests to available workers, by priority
+    async fn dispatch_pending_requests(&mut self) -> Result<(), Error> {
+        while self.state == LoadBalancerState::Online {
+            let has_available_worker = self
+                .worker_states
+                .iter()
+                .position(|state| *state == WorkerState::Available);
+            let worker_id = match has_available_worker {
+                Some(worker_id) => worker_id,
+                None => break,
+            };
+            let request = match self.pop_pending_request()? {
+                Some(request) => request,
+                None => break,
+            };
+            trace!("LoadBalancer is sending request to worker {:?}", worker_id);
+            let sender = &self.worker_request_senders[worker_id];
+            sender.send(request).await.with_context(|| {
+                format!(
+                    "Channel to forward request to worker {} has closed",
+                    worker_id
+                )
+            })?;
+
+            self.worker_states[worker_id] = WorkerState::Busy;
+        }
+
+        let nb_busy_workers = self
+            .worker_states
+            .iter()
+            .filter(|state| **state == WorkerState::Busy)
+            .count();
+        self.metrics
+            .set_load_balancer_state(self.nb_of_pending_requests(), nb_busy_workers);
+        Ok(())
+    }
+
+    // answer the request with an error, without sending it to a worker
+    fn reject_request(
+        &mut self,
+        request: RequestMessage,
+        reason: RejectionReason,
+    ) -> Result<(), Error> {
+        let requested_api = request.payload.requested_api();
+        info!(
+            "LoadBalancer rejects a {:?} request : {:?}",
+            requested_api, reason
+        );
+        self.metrics.request_rejected(requested_api, reason);
+        let response = ResponseMessage {
+            payload: make_rejection_response(reason),
+            client_id: request.client_id,
+        };
+        self.zmq_channels
+            .responses_sender
+            .send(response)
+            .context("Channel to send responses to zmq worker has closed")
+    }
+
     async fn stop_if_all_workers_available(&mut self) -> Result<(), Error> {
         let all_workers_available = self
             .worker_states
@@ -328,3 +488,250 @@ impl LoadBalancer {
         Ok(())
     }
 }
+
+// one empty queue per RequestClass, ordered by decreasing priority
+fn make_request_queues(params: &LoadBalancerParams) -> Vec<RequestQueue> {
+    RequestClass::BY_PRIORITY
+        .iter()
+        .map(|class| {
+            let capacity = match class {
+                RequestClass::PlacesNearby => params.places_nearby_queue_size,
+                RequestClass::Schedules => params.schedules_queue_size,
+                RequestClass::Journeys => params.journeys_queue_size,
+            };
+            RequestQueue {
+                class: *class,
+                capacity,
+                requests: VecDeque::with_capacity(capacity),
+            }
+        })
+        .collect()
+}
+
+fn make_rejection_response(reason: RejectionReason) -> navitia_proto::Response {
+    let (error_id, message) = match reason {
+        RejectionReason::Overloaded => (
+            navitia_proto::error::ErrorId::ServiceUnavailable,
+            "Server is overloaded, the request was not handled.",
+        ),
+        RejectionReason::DeadlineExpired => (
+            navitia_proto::error::ErrorId::InternalError,
+            "Deadline reached.",
+        ),
+    };
+    let mut proto_response = navitia_proto::Response::default();
+    proto_response.set_response_type(navitia_proto::ResponseType::NoSolution);
+    let mut proto_error = navitia_proto::Error::default();
+    proto_error.set_id(error_id);
+    proto_error.message = Some(message.to_string());
+    proto_response.error = Some(proto_error);
+    proto_response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::new_registry;
+    use crate::navitia_proto::{error::ErrorId, Api};
+    use crate::zmq_worker::ClientId;
+    use tokio::sync::oneshot;
+
+    const PAST_DEADLINE: &str = "20000101T000000,000000";
+    const FUTURE_DEADLINE: &str = "30000101T000000,000000";
+
+    // a load balancer without workers,
+    // along with the receiver of the responses it sends to the zmq worker
+    fn make_load_balancer(
+        params: &LoadBalancerParams,
+    ) -> (LoadBalancer, mpsc::UnboundedReceiver<ResponseMessage>) {
+        let (_, requests_receiver) = mpsc::unbounded_channel();
+        let (responses_sender, responses_receiver) = mpsc::unbounded_channel();
+        let (_, workers_response_receiver) = mpsc::channel(1);
+        let (_, order_receiver) = mpsc::channel(1);
+        let (stopped_sender, _) = mpsc::channel(1);
+        let (shutdown_sender, _) = mpsc::channel(1);
+        let load_balancer = LoadBalancer {
+            worker_request_senders: Vec::new(),
+            workers_response_receiver,
+            worker_states: Vec::new(),
+            pending_requests: make_request_queues(params),
+            max_priority_delay: Duration::from_secs(params.max_priority_delay.total_seconds()),
+            order_receiver,
+            stopped_sender,
+            shutdown_sender,
+            state: LoadBalancerState::Online,
+            zmq_channels: LoadBalancerToZmqChannels {
+                requests_receiver,
+                responses_sender,
+            },
+            metrics: Arc::new(Metrics::new(&new_registry().unwrap(), "test").unwrap()),
+        };
+        (load_balancer, responses_receiver)
+    }
+
+    fn make_request(api: Api, request_id: &str, deadline: Option<&str>) -> RequestMessage {
+        let mut payload = navitia_proto::Request {
+            request_id: Some(request_id.to_string()),
+            deadline: deadline.map(str::to_string),
+            ..Default::default()
+        };
+        payload.set_requested_api(api);
+        let (response_sender, _) = oneshot::channel();
+        RequestMessage {
+            payload,
+            client_id: ClientId::Http(response_sender),
+        }
+    }
+
+    fn pop_request_id(load_balancer: &mut LoadBalancer) -> Option<String> {
+        load_balancer
+            .pop_pending_request()
+            .unwrap()
+            .map(|request| request.payload.request_id.unwrap())
+    }
+
+    fn rejection_error_id(
+        responses_receiver: &mut mpsc::UnboundedReceiver<ResponseMessage>,
+    ) -> ErrorId {
+        let response = responses_receiver.try_recv().unwrap();
+        response.payload.error.unwrap().id()
+    }
+
+    #[test]
+    fn requests_are_popped_by_priority() {
+        let (mut load_balancer, mut responses_receiver) =
+            make_load_balancer(&LoadBalancerParams::default());
+        let requests = [
+            make_request(Api::PtPlanner, "journeys_1", None),
+            make_request(Api::NextDepartures, "schedules_1", Some(FUTURE_DEADLINE)),
+            make_request(Api::PlacesNearby, "places_nearby_1", None),
+            make_request(Api::PtPlanner, "journeys_2", None),
+            make_request(Api::NextArrivals, "schedules_2", None),
+        ];
+        for request in requests {
+            load_balancer.enqueue_request(request).unwrap();
+        }
+        assert_eq!(load_balancer.nb_of_pending_requests(), 5);
+
+        let popped: Vec<_> = std::iter::from_fn(|| pop_request_id(&mut load_balancer)).collect();
+        assert_eq!(
+            popped,
+            vec![
+                "places_nearby_1",
+                "schedules_1",
+                "schedules_2",
+                "journeys_1",
+                "journeys_2"
+            ]
+        );
+        assert_eq!(load_balancer.nb_of_pending_requests(), 0);
+        assert!(responses_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn delayed_requests_are_popped_first() {
+        let (mut load_balancer, _) = make_load_balancer(&LoadBalancerParams::default());
+        let long_ago = Instant::now() - Duration::from_secs(10);
+        let a_while_ago = Instant::now() - Duration::from_secs(5);
+        // the queues are ordered as RequestClass::BY_PRIORITY
+        let (places_nearby_queue, schedules_queue, journeys_queue) =
+            match load_balancer.pending_requests.as_mut_slice() {
+                [places_nearby, schedules, journeys] => (places_nearby, schedules, journeys),
+                _ => unreachable!(),
+            };
+        journeys_queue
+            .requests
+            .push_back((long_ago, make_request(Api::PtPlanner, "journeys_1", None)));
+        schedules_queue.requests.push_back((
+            a_while_ago,
+            make_request(Api::NextDepartures, "schedules_1", None),
+        ));
+        places_nearby_queue.requests.push_back((
+            Instant::now(),
+            make_request(Api::PlacesNearby, "places_nearby_1", None),
+        ));
+        journeys_queue.requests.push_back((
+            Instant::now(),
+            make_request(Api::PtPlanner, "journeys_2", None),
+        ));
+
+        let popped: Vec<_> = std::iter::from_fn(|| pop_request_id(&mut load_balancer)).collect();
+        assert_eq!(
+            popped,
+            vec!["journeys_1", "schedules_1", "places_nearby_1", "journeys_2"]
+        );
+    }
+
+    #[test]
+    fn requests_are_rejected_when_their_queue_is_full() {
+        let params = LoadBalancerParams {
+            journeys_queue_size: 1,
+            ..LoadBalancerParams::default()
+        };
+        let (mut load_balancer, mut responses_receiver) = make_load_balancer(&params);
+
+        load_balancer
+            .enqueue_request(make_request(Api::PtPlanner, "journeys_1", None))
+            .unwrap();
+        assert!(responses_receiver.try_recv().is_err());
+
+        load_balancer
+            .enqueue_request(make_request(Api::PtPlanner, "journeys_2", None))
+            .unwrap();
+        assert_eq!(
+            rejection_error_id(&mut responses_receiver),
+            ErrorId::ServiceUnavailable
+        );
+
+        // the queues of other classes are not full
+        load_balancer
+            .enqueue_request(make_request(Api::PlacesNearby, "places_nearby_1", None))
+            .unwrap();
+        assert!(responses_receiver.try_recv().is_err());
+
+        assert_eq!(load_balancer.nb_of_pending_requests(), 2);
+        assert_eq!(
+            pop_request_id(&mut load_balancer).as_deref(),
+            Some("places_nearby_1")
+        );
+        assert_eq!(
+            pop_request_id(&mut load_balancer).as_deref(),
+            Some("journeys_1")
+        );
+    }
+
+    #[test]
+    fn requests_are_rejected_when_their_deadline_expired() {
+        let (mut load_balancer, mut responses_receiver) =
+            make_load_balancer(&LoadBalancerParams::default());
+
+        // the deadline has expired before the request is queued
+        load_balancer
+            .enqueue_request(make_request(Api::PtPlanner, "expired", Some(PAST_DEADLINE)))
+            .unwrap();
+        assert_eq!(load_balancer.nb_of_pending_requests(), 0);
+        assert_eq!(
+            rejection_error_id(&mut responses_receiver),
+            ErrorId::InternalError
+        );
+
+        // the deadline expires while the request waits in a queue
+        load_balancer
+            .enqueue_request(make_request(Api::PtPlanner, "journeys_1", None))
+            .unwrap();
+        load_balancer.pending_requests[0].requests.push_back((
+            Instant::now(),
+            make_request(Api::PlacesNearby, "expired", Some(PAST_DEADLINE)),
+        ));
+        assert_eq!(
+            pop_request_id(&mut load_balancer).as_deref(),
+            Some("journeys_1")
+        );
+        assert_eq!(
+            rejection_error_id(&mut responses_receiver),
+            ErrorId::InternalError
+        );
+        assert!(pop_request_id(&mut load_balancer).is_none());
+        assert!(responses_receiver.try_recv().is_err());
+    }
+}
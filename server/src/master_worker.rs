@@ -40,89 +40,221 @@ use launch::loki::{
     tracing::{error, info},
     TransitData,
 };
-use std::sync::{Arc, RwLock};
-use tokio::{runtime::Builder, signal, sync::mpsc};
+use prometheus::Registry;
+use std::{collections::HashMap, sync::Arc};
+use tokio::{
+    runtime::{Builder, Handle, Runtime},
+    signal,
+    sync::mpsc,
+};
 
 use crate::{
-    data_worker::DataWorker, load_balancer::LoadBalancer, status_worker::StatusWorker,
-    zmq_worker::ZmqWorker, ServerConfig,
+    admin_worker::AdminWorker,
+    data_worker::DataWorker,
+    http_worker::HttpWorker,
+    load_balancer::{LoadBalancer, LoadBalancerChannels},
+    metrics::{self, Metrics, MetricsWorker},
+    rabbitmq_connection::RabbitMqConnection,
+    response_cache::ResponseCache,
+    shared_data::SharedData,
+    status_worker::StatusWorker,
+    zmq_worker::ZmqWorker,
+    ServerConfig,
 };
 
-pub type DataAndModels = (TransitData, BaseModel, RealTimeModel);
+// The base model is never modified once loaded, so it is shared between
+// the versions of the data and models created by real time updates.
+pub type DataAndModels = (TransitData, Arc<BaseModel>, RealTimeModel);
 
 pub struct MasterWorker {
     shutdown_receiver: mpsc::Receiver<()>,
+    // keeps the channel of orders to the load balancer open
+    _load_balancer_channels: LoadBalancerChannels,
+    // the runtime of the workers, when it is not shared with other master workers
+    runtime: Option<Runtime>,
 }
 
 impl MasterWorker {
     pub fn new(config: ServerConfig) -> Result<Self, Error> {
+        let runtime = make_runtime()?;
+        let rabbitmq_connection = Arc::new(RabbitMqConnection::new(&config.rabbitmq.endpoint));
+        let metrics_registry = metrics::new_registry()?;
+        let mut master_worker = Self::new_on_runtime(
+            config,
+            runtime.handle(),
+            rabbitmq_connection,
+            &metrics_registry,
+            true,
+        )?;
+        master_worker.runtime = Some(runtime);
+        Ok(master_worker)
+    }
+
+    /// Creates the workers of an instance, which run on `runtime`,
+    /// receive real time messages through `rabbitmq_connection`
+    /// and register their metrics in `metrics_registry`.
+    /// All three may be shared with the workers of other instances.
+    /// When `serve_metrics` is true, the metrics of `metrics_registry` are served
+    /// on the metrics endpoint of `config`, if any.
+    pub fn new_on_runtime(
+        config: ServerConfig,
+        runtime: &Handle,
+        rabbitmq_connection: Arc<RabbitMqConnection>,
+        metrics_registry: &Registry,
+        serve_metrics: bool,
+    ) -> Result<Self, Error> {
+        let instance_name = config.instance_name.clone();
         // Initialize models and data.
         // We init everything with empty data.
         // DataWorker will take care of reading data from disk
         let base_model = BaseModel::empty();
         let data = TransitData::new(&base_model);
         let real_time_model = RealTimeModel::new();
-        let data_and_models = Arc::new(RwLock::new((data, base_model, real_time_model)));
+        let data_and_models = Arc::new(SharedData::new((
+            data,
+            Arc::new(base_model),
+            real_time_model,
+        )));
 
         let (shutdown_sender, shutdown_receiver) = mpsc::channel(1);
 
+        let metrics = Arc::new(Metrics::new(metrics_registry, &instance_name)?);
+        if let (Some(metrics_params), true) = (&config.metrics, serve_metrics) {
+            let metrics_worker = MetricsWorker::new(
+                metrics_params.endpoint,
+                metrics_registry.clone(),
+                shutdown_sender.clone(),
+            );
+            let _metrics_handle = metrics_worker.run_in_a_thread(runtime)?;
+        }
+
+        // shared between the compute workers, and invalidated by the data worker
+        let response_cache = config
+            .response_cache
+            .as_ref()
+            .map(|params| Arc::new(ResponseCache::new(params)));
+
         // Zmq worker
         let (zmq_worker, load_balancer_to_zmq_channels, status_worker_to_zmq_channels) =
             ZmqWorker::new(&config.requests_socket, shutdown_sender.clone());
 
-        let _zmq_handle = zmq_worker.run_in_a_thread()?;
+        // Http worker
+        if let Some(http_params) = &config.http {
+            let http_worker = HttpWorker::new(
+                http_params.endpoint,
+                zmq_worker.http_channels(),
+                shutdown_sender.clone(),
+            );
+            let _http_handle = http_worker.run_in_a_thread(runtime)?;
+        }
+
+        let _zmq_handle = zmq_worker.run_in_a_thread(runtime)?;
 
         // LoadBalancer
         let (load_balancer, load_balancer_channels) = LoadBalancer::new(
             data_and_models.clone(),
-            config.nb_workers,
-            &config.default_request_params,
+            &config,
+            response_cache.clone(),
             load_balancer_to_zmq_channels,
+            metrics.clone(),
             shutdown_sender.clone(),
         )?;
-        let _load_balancer_handle = load_balancer.run_in_a_thread()?;
+        let _load_balancer_handle = load_balancer.run_in_a_thread(runtime)?;
 
         // Status worker
         let (status_worker, status_update_sender) = StatusWorker::new(
             status_worker_to_zmq_channels,
+            metrics.clone(),
             shutdown_sender.clone(),
             &config,
         );
-        let _status_worker_handle = status_worker.run_in_a_thread()?;
+        let _status_worker_handle = status_worker.run_in_a_thread(runtime)?;
 
         // Data worker
+        let admin_params = config.admin.clone();
         let data_worker = DataWorker::new(
             config,
             data_and_models,
-            load_balancer_channels,
+            response_cache,
             status_update_sender,
-            shutdown_sender,
+            rabbitmq_connection,
+            metrics,
+            shutdown_sender.clone(),
         )?;
-        let _data_worker_handle = data_worker.run_in_a_thread()?;
+
+        // Admin worker
+        if let Some(admin_params) = admin_params {
+            let admin_worker = AdminWorker::new(
+                admin_params.endpoint,
+                data_worker.admin_requests_sender(),
+                shutdown_sender,
+            );
+            let _admin_handle = admin_worker.run_in_a_thread(runtime)?;
+        }
+
+        let _data_worker_handle = data_worker.run_in_a_thread(runtime)?;
 
         // Master worker
-        let result = Self { shutdown_receiver };
-        info!("Master worker created.");
+        let result = Self {
+            shutdown_receiver,
+            _load_balancer_channels: load_balancer_channels,
+            runtime: None,
+        };
+        info!("Master worker created for instance {}.", instance_name);
         Ok(result)
     }
 
-    pub fn run_blocking(self) -> Result<(), Error> {
-        // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
+    pub fn run_blocking(mut self) -> Result<(), Error> {
+        match self.runtime.take() {
+            Some(runtime) => runtime.block_on(self.run()),
+            None => make_runtime()?.block_on(self.run()),
+        }
+    }
 
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
+    /// Runs the instances described by `configs` on the same tokio runtime,
+    /// until one of them stops.
+    /// Instances with the same rabbitmq endpoint share their connection to rabbitmq.
+    pub fn run_all_blocking(configs: Vec<ServerConfig>) -> Result<(), Error> {
+        if configs.is_empty() {
+            bail!("No instance to run.");
+        }
+        let runtime = make_runtime()?;
 
-        runtime.block_on(self.run())
-    }
+        let mut rabbitmq_connections: HashMap<String, Arc<RabbitMqConnection>> = HashMap::new();
+        // the metrics of all instances are served on a single endpoint
+        let metrics_registry = metrics::new_registry()?;
+        let mut metrics_served = false;
+        let mut master_workers = Vec::with_capacity(configs.len());
+        for config in configs {
+            let endpoint = &config.rabbitmq.endpoint;
+            let rabbitmq_connection = rabbitmq_connections
+                .entry(endpoint.clone())
+                .or_insert_with(|| Arc::new(RabbitMqConnection::new(endpoint)))
+                .clone();
+            let serve_metrics = !metrics_served && config.metrics.is_some();
+            metrics_served |= serve_metrics;
+            let master_worker = Self::new_on_runtime(
+                config,
+                runtime.handle(),
+                rabbitmq_connection,
+                &metrics_registry,
+                serve_metrics,
+            )?;
+            master_workers.push(master_worker);
+        }
 
-    pub fn run_in_a_thread(self) -> Result<(), Error> {
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
+        let runs = master_workers
+            .into_iter()
+            .map(|master_worker| Box::pin(master_worker.run()));
+        let (result, _, _) = runtime.block_on(futures::future::select_all(runs));
+        result
+    }
 
+    pub fn run_in_a_thread(mut self) -> Result<(), Error> {
+        let runtime = match self.runtime.take() {
+            Some(runtime) => runtime,
+            None => make_runtime()?,
+        };
         let thread_builder = std::thread::Builder::new().name("loki_master_worker".to_string());
         let _handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
         Ok(())
@@ -142,3 +274,23 @@ impl MasterWorker {
         }
     }
 }
+
+impl Drop for MasterWorker {
+    fn drop(&mut self) {
+        // dropping a runtime blocks until its tasks are stopped,
+        // which is not allowed when the master worker is dropped in an async context
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+// a multi threaded runtime, since each worker drives its own future on it
+// from its own thread, which requires the io and time drivers to be driven
+// by the runtime
+fn make_runtime() -> Result<Runtime, Error> {
+    Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to build tokio runtime.")
+}
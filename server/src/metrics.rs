@@ -0,0 +1,421 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::{collections::HashMap, convert::Infallible, net::SocketAddr, thread, time::Duration};
+
+use anyhow::{Context, Error};
+use hyper::{
+    header,
+    service::{make_service_fn, service_fn},
+    Body, Method, Server, StatusCode,
+};
+use launch::loki::{
+    tracing::{error, info},
+    NaiveDateTime,
+};
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use tokio::{runtime::Handle, sync::mpsc};
+
+use crate::navitia_proto;
+
+/// Metrics of an instance, exposed in the prometheus text format
+/// by the `MetricsWorker`.
+/// The metrics of all instances are registered in the same registry,
+/// and labelled with the name of their instance.
+pub struct Metrics {
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    rejected_requests: IntCounterVec,
+    response_cache: IntCounterVec,
+
+    queue_length: IntGauge,
+    busy_workers: IntGauge,
+
+    real_time_messages: IntCounterVec,
+
+    data_loaded_at: IntGauge,
+    last_real_time_update: IntGauge,
+    data_reload_duration: Histogram,
+    data_reloads_rejected: IntCounter,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RealTimeSource {
+    Kirin,
+    Chaos,
+}
+
+impl RealTimeSource {
+    fn label(self) -> &'static str {
+        match self {
+            RealTimeSource::Kirin => "kirin",
+            RealTimeSource::Chaos => "chaos",
+        }
+    }
+}
+
+/// Why the load balancer rejected a request without solving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectionReason {
+    Overloaded,
+    DeadlineExpired,
+}
+
+impl RejectionReason {
+    fn label(self) -> &'static str {
+        match self {
+            RejectionReason::Overloaded => "overloaded",
+            RejectionReason::DeadlineExpired => "deadline_expired",
+        }
+    }
+}
+
+/// Creates the registry shared by the metrics of all instances.
+pub fn new_registry() -> Result<Registry, Error> {
+    Registry::new_custom(Some("loki".to_string()), None)
+        .context("Could not create metrics registry.")
+}
+
+impl Metrics {
+    /// Creates the metrics of the instance `instance_name`, and registers them in `registry`.
+    pub fn new(registry: &Registry, instance_name: &str) -> Result<Self, Error> {
+        let instance_label: HashMap<String, String> =
+            HashMap::from([("instance".to_string(), instance_name.to_string())]);
+        let opts =
+            |name: &str, help: &str| Opts::new(name, help).const_labels(instance_label.clone());
+        let histogram_opts = |name: &str, help: &str| {
+            HistogramOpts::new(name, help).const_labels(instance_label.clone())
+        };
+
+        let requests = IntCounterVec::new(
+            opts("requests_total", "Number of requests handled, by api."),
+            &["api"],
+        )?;
+        let request_duration = HistogramVec::new(
+            histogram_opts(
+                "request_duration_seconds",
+                "Time spent solving requests, by api.",
+            )
+            .buckets(vec![
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+            ]),
+            &["api"],
+        )?;
+        let rejected_requests = IntCounterVec::new(
+            opts(
+                "rejected_requests_total",
+                "Number of requests rejected by the load balancer, by api and by reason.",
+            ),
+            &["api", "reason"],
+        )?;
+        let response_cache = IntCounterVec::new(
+            opts(
+                "response_cache_requests_total",
+                "Number of lookups in the response cache, by result (hit or miss).",
+            ),
+            &["result"],
+        )?;
+        let queue_length = IntGauge::with_opts(opts(
+            "load_balancer_queue_length",
+            "Number of requests waiting for an available worker.",
+        ))?;
+        let busy_workers = IntGauge::with_opts(opts(
+            "load_balancer_busy_workers",
+            "Number of workers currently solving a request.",
+        ))?;
+        let real_time_messages = IntCounterVec::new(
+            opts(
+                "real_time_messages_total",
+                "Number of real time messages received, by source and by status (applied or failed).",
+            ),
+            &["source", "status"],
+        )?;
+        let data_loaded_at = IntGauge::with_opts(opts(
+            "data_loaded_at_timestamp_seconds",
+            "Utc timestamp of the last successful load of base data.",
+        ))?;
+        let last_real_time_update = IntGauge::with_opts(opts(
+            "last_real_time_update_timestamp_seconds",
+            "Utc timestamp of the last real time update applied on the data.",
+        ))?;
+        let data_reload_duration = Histogram::with_opts(
+            histogram_opts(
+                "data_reload_duration_seconds",
+                "Time spent (re)loading base data.",
+            )
+            .buckets(vec![
+                1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0,
+            ]),
+        )?;
+
+        let data_reloads_rejected = IntCounter::with_opts(opts(
+            "data_reloads_rejected_total",
+            "Number of new base data rejected by the data checks.",
+        ))?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(rejected_requests.clone()))?;
+        registry.register(Box::new(response_cache.clone()))?;
+        registry.register(Box::new(queue_length.clone()))?;
+        registry.register(Box::new(busy_workers.clone()))?;
+        registry.register(Box::new(real_time_messages.clone()))?;
+        registry.register(Box::new(data_loaded_at.clone()))?;
+        registry.register(Box::new(last_real_time_update.clone()))?;
+        registry.register(Box::new(data_reload_duration.clone()))?;
+        registry.register(Box::new(data_reloads_rejected.clone()))?;
+
+        Ok(Self {
+            requests,
+            request_duration,
+            rejected_requests,
+            response_cache,
+            queue_length,
+            busy_workers,
+            real_time_messages,
+            data_loaded_at,
+            last_real_time_update,
+            data_reload_duration,
+            data_reloads_rejected,
+        })
+    }
+
+    pub fn observe_request(&self, api: navitia_proto::Api, duration: Duration) {
+        let api = format!("{:?}", api);
+        self.requests.with_label_values(&[&api]).inc();
+        self.request_duration
+            .with_label_values(&[&api])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn request_rejected(&self, api: navitia_proto::Api, reason: RejectionReason) {
+        let api = format!("{:?}", api);
+        self.rejected_requests
+            .with_label_values(&[&api, reason.label()])
+            .inc();
+    }
+
+    pub fn response_cache_lookup(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.response_cache.with_label_values(&[result]).inc();
+    }
+
+    pub fn set_load_balancer_state(&self, queue_length: usize, busy_workers: usize) {
+        self.queue_length
+            .set(i64::try_from(queue_length).unwrap_or(i64::MAX));
+        self.busy_workers
+            .set(i64::try_from(busy_workers).unwrap_or(i64::MAX));
+    }
+
+    pub fn real_time_message_applied(&self, source: RealTimeSource) {
+        self.real_time_messages
+            .with_label_values(&[source.label(), "applied"])
+            .inc();
+    }
+
+    pub fn real_time_message_failed(&self, source: RealTimeSource) {
+        self.real_time_messages
+            .with_label_values(&[source.label(), "failed"])
+            .inc();
+    }
+
+    pub fn set_data_loaded_at(&self, datetime: &NaiveDateTime) {
+        self.data_loaded_at.set(datetime.timestamp());
+    }
+
+    pub fn set_last_real_time_update(&self, datetime: &NaiveDateTime) {
+        self.last_real_time_update.set(datetime.timestamp());
+    }
+
+    pub fn observe_data_reload(&self, duration: Duration) {
+        self.data_reload_duration.observe(duration.as_secs_f64());
+    }
+
+    pub fn data_reload_rejected(&self) {
+        self.data_reloads_rejected.inc();
+    }
+}
+
+pub fn encode(registry: &Registry) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&registry.gather(), &mut buffer)
+        .context("Could not encode metrics.")?;
+    Ok(buffer)
+}
+
+/// Serves the metrics of all instances on `/metrics`, to be scraped by prometheus.
+pub struct MetricsWorker {
+    endpoint: SocketAddr,
+    registry: Registry,
+    // to send shutdown signal to Master when an error occurs insider MetricsWorker
+    shutdown_sender: mpsc::Sender<()>,
+}
+
+impl MetricsWorker {
+    pub fn new(
+        endpoint: SocketAddr,
+        registry: Registry,
+        shutdown_sender: mpsc::Sender<()>,
+    ) -> Self {
+        Self {
+            endpoint,
+            registry,
+            shutdown_sender,
+        }
+    }
+
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
+        let thread_builder = thread::Builder::new().name("loki_metrics_worker".to_string());
+        let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
+        Ok(handle)
+    }
+
+    async fn run(self) {
+        let run_err = self.run_server().await;
+        error!("MetricsWorker stopped : {:?}", run_err);
+        // send shutdown signal
+        let _ = self.shutdown_sender.send(()).await;
+    }
+
+    async fn run_server(&self) -> Result<(), Error> {
+        let registry = self.registry.clone();
+        let make_service = make_service_fn(move |_connection| {
+            let registry = registry.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    handle_metrics_request(request, registry.clone())
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&self.endpoint)
+            .with_context(|| format!("Could not bind metrics endpoint {}", self.endpoint))?;
+        info!("Metrics worker bound to endpoint {}", self.endpoint);
+        server
+            .serve(make_service)
+            .await
+            .context("Metrics server failed.")
+    }
+}
+
+async fn handle_metrics_request(
+    request: hyper::Request<Body>,
+    registry: Registry,
+) -> Result<hyper::Response<Body>, Infallible> {
+    let mut response = hyper::Response::new(Body::empty());
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return Ok(response);
+    }
+    match encode(&registry) {
+        Ok(buffer) => {
+            // unwrap is safe since the format type is a valid header value
+            let content_type =
+                header::HeaderValue::from_str(TextEncoder::new().format_type()).unwrap();
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, content_type);
+            *response.body_mut() = Body::from(buffer);
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_metrics() {
+        let registry = new_registry().unwrap();
+        let metrics = Metrics::new(&registry, "my_coverage").unwrap();
+        metrics.observe_request(navitia_proto::Api::PtPlanner, Duration::from_millis(20));
+        metrics.request_rejected(navitia_proto::Api::PtPlanner, RejectionReason::Overloaded);
+        metrics.response_cache_lookup(true);
+        metrics.set_load_balancer_state(3, 2);
+        metrics.real_time_message_applied(RealTimeSource::Kirin);
+        metrics.real_time_message_failed(RealTimeSource::Chaos);
+        metrics.data_reload_rejected();
+
+        let text = String::from_utf8(encode(&registry).unwrap()).unwrap();
+        assert!(text.contains("loki_requests_total{api=\"PtPlanner\",instance=\"my_coverage\"} 1"));
+        assert!(text.contains(
+            "loki_request_duration_seconds_bucket{api=\"PtPlanner\",instance=\"my_coverage\",le=\"0.025\"} 1"
+        ));
+        assert!(text.contains(
+            "loki_rejected_requests_total{api=\"PtPlanner\",instance=\"my_coverage\",reason=\"overloaded\"} 1"
+        ));
+        assert!(text.contains(
+            "loki_response_cache_requests_total{instance=\"my_coverage\",result=\"hit\"} 1"
+        ));
+        assert!(text.contains("loki_load_balancer_queue_length{instance=\"my_coverage\"} 3"));
+        assert!(text.contains("loki_load_balancer_busy_workers{instance=\"my_coverage\"} 2"));
+        assert!(text.contains(
+            "loki_real_time_messages_total{instance=\"my_coverage\",source=\"kirin\",status=\"applied\"} 1"
+        ));
+        assert!(text.contains(
+            "loki_real_time_messages_total{instance=\"my_coverage\",source=\"chaos\",status=\"failed\"} 1"
+        ));
+        assert!(text.contains("loki_data_reloads_rejected_total{instance=\"my_coverage\"} 1"));
+    }
+
+    #[test]
+    fn instances_share_the_registry() {
+        let registry = new_registry().unwrap();
+        let metrics = Metrics::new(&registry, "my_coverage").unwrap();
+        let other_metrics = Metrics::new(&registry, "my_other_coverage").unwrap();
+        metrics.data_reload_rejected();
+        other_metrics.data_reload_rejected();
+        other_metrics.data_reload_rejected();
+
+        let text = String::from_utf8(encode(&registry).unwrap()).unwrap();
+        assert!(text.contains("loki_data_reloads_rejected_total{instance=\"my_coverage\"} 1"));
+        assert!(text.contains("loki_data_reloads_rejected_total{instance=\"my_other_coverage\"} 2"));
+
+        // an instance cannot be registered twice
+        assert!(Metrics::new(&registry, "my_coverage").is_err());
+    }
+}
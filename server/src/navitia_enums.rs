@@ -0,0 +1,144 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+// Prost stores the enum fields of navitia protobufs as i32.
+// When a response is sent as json, these fields are written with the name
+// of their value instead, as written in the .proto in lowercase.
+//
+// The build script generates, in `navitia_proto::enum_names`, a module for each enum
+// with the names of its values, and tells serde to serialize the fields of this enum with it.
+
+use serde::{ser::Serializer, Serialize};
+
+/// The types of the enum fields : required, optional or repeated.
+pub trait EnumValues {
+    fn serialize_names<S: Serializer>(
+        &self,
+        names: &[(i32, &'static str)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>;
+}
+
+// A value unknown to the proto we were built with is written as a number
+struct EnumName<'a> {
+    value: i32,
+    names: &'a [(i32, &'static str)],
+}
+
+impl<'a> Serialize for EnumName<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let has_name = self
+            .names
+            .iter()
+            .find(|(value, _)| *value == self.value)
+            .map(|(_, name)| *name);
+        match has_name {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_i32(self.value),
+        }
+    }
+}
+
+impl EnumValues for i32 {
+    fn serialize_names<S: Serializer>(
+        &self,
+        names: &[(i32, &'static str)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        EnumName {
+            value: *self,
+            names,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl EnumValues for Option<i32> {
+    fn serialize_names<S: Serializer>(
+        &self,
+        names: &[(i32, &'static str)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(value) => serializer.serialize_some(&EnumName {
+                value: *value,
+                names,
+            }),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl EnumValues for Vec<i32> {
+    fn serialize_names<S: Serializer>(
+        &self,
+        names: &[(i32, &'static str)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(|value| EnumName {
+            value: *value,
+            names,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: &[(i32, &str)] = &[(1, "public_transport"), (2, "street_network")];
+
+    fn serialize<V: EnumValues>(value: &V, serializer: serde_json::value::Serializer) -> String {
+        value
+            .serialize_names(NAMES, serializer)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn enum_values_are_written_with_their_names() {
+        let serializer = || serde_json::value::Serializer;
+        assert_eq!(serialize(&1, serializer()), r#""public_transport""#);
+        assert_eq!(serialize(&Some(2), serializer()), r#""street_network""#);
+        assert_eq!(serialize(&None::<i32>, serializer()), "null");
+        assert_eq!(
+            serialize(&vec![2, 1], serializer()),
+            r#"["street_network","public_transport"]"#
+        );
+        // unknown values are kept as numbers
+        assert_eq!(serialize(&7, serializer()), "7");
+    }
+}
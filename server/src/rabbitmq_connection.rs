@@ -0,0 +1,89 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::{Context, Error};
+use launch::loki::tracing::info;
+use tokio::sync::Mutex;
+
+/// A connection to a rabbitmq endpoint, shared by the data workers
+/// of all the instances that use this endpoint.
+/// Each data worker opens its own channel on it.
+pub struct RabbitMqConnection {
+    endpoint: String,
+    // None until the first connection
+    connection: Mutex<Option<lapin::Connection>>,
+}
+
+impl RabbitMqConnection {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    /// Opens a new channel, after (re)connecting to rabbitmq
+    /// if the connection is not established.
+    pub async fn create_channel(&self) -> Result<lapin::Channel, Error> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection
+            .as_ref()
+            .filter(|connection| connection.status().connected())
+        {
+            return connection
+                .create_channel()
+                .await
+                .context("Could not create a rabbitmq channel.");
+        }
+
+        let new_connection =
+            lapin::Connection::connect(&self.endpoint, lapin::ConnectionProperties::default())
+                .await
+                .with_context(|| {
+                    format!("Could not connect to rabbitmq endpoint {}", self.endpoint)
+                })?;
+        info!(
+            "Successfully connected to rabbitmq at endpoint {}",
+            self.endpoint
+        );
+        let channel = new_connection
+            .create_channel()
+            .await
+            .context("Could not create a rabbitmq channel.")?;
+        *connection = Some(new_connection);
+        Ok(channel)
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::{
+    chaos_proto::{
+        chaos::{exts, Disruption},
+        gtfs_realtime::{FeedEntity, FeedMessage},
+    },
+    server_config::RealTimeJournalParams,
+};
+use anyhow::{Context, Error};
+use launch::loki::{
+    chrono::NaiveDate,
+    models::base_model::BaseModel,
+    tracing::{error, info},
+    NaiveDateTime,
+};
+use protobuf::{well_known_types::StringValue, Message as ProtobufMessage, RepeatedField};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+const JOURNAL_FILE_NAME: &str = "real_time_journal.pb";
+const SIRI_JOURNAL_FILE_NAME: &str = "siri_journal.pb";
+const FINGERPRINT_FILE_NAME: &str = "base_data_fingerprint";
+
+/// An append-only journal of the real time messages applied on top of some base data.
+///
+/// Messages are stored as length delimited gtfs-rt FeedMessages,
+/// and SIRI deliveries as their xml in a separate file, alongside a fingerprint of the base data they were applied to.
+/// At startup, the journal is only replayed on top of base data with the same fingerprint.
+/// When new base data is loaded while running, its gtfs-rt messages are also applied
+/// on the new data, and the journal starts over with the fingerprint of the new data.
+pub struct RealTimeJournal {
+    journal_path: PathBuf,
+    siri_journal_path: PathBuf,
+    fingerprint_path: PathBuf,
+}
+
+impl RealTimeJournal {
+    pub fn new(params: &RealTimeJournalParams) -> Result<Self, Error> {
+        std::fs::create_dir_all(&params.directory).with_context(|| {
+            format!(
+                "Could not create real time journal directory {:?}",
+                params.directory
+            )
+        })?;
+        Ok(Self {
+            journal_path: params.directory.join(JOURNAL_FILE_NAME),
+            siri_journal_path: params.directory.join(SIRI_JOURNAL_FILE_NAME),
+            fingerprint_path: params.directory.join(FINGERPRINT_FILE_NAME),
+        })
+    }
+
+    /// Returns the messages stored in the journal, if they were applied
+    /// on top of base data with the given `fingerprint`.
+    /// Otherwise, returns an empty Vec.
+    /// A partially written last message is removed from the journal.
+    pub fn read(&self, fingerprint: &str) -> Result<Vec<FeedMessage>, Error> {
+        if !self.is_built_on(fingerprint, &self.journal_path)? {
+            return Ok(Vec::new());
+        }
+        let messages = read_messages(&self.journal_path)?;
+        info!("Read {} messages from real time journal.", messages.len());
+        Ok(messages)
+    }
+
+    /// Returns the xml of the SIRI deliveries stored in the journal, if they were applied
+    /// on top of base data with the given `fingerprint`.
+    /// Otherwise, returns an empty Vec.
+    pub fn read_siri_deliveries(&self, fingerprint: &str) -> Result<Vec<String>, Error> {
+        if !self.is_built_on(fingerprint, &self.siri_journal_path)? {
+            return Ok(Vec::new());
+        }
+        let deliveries: Vec<StringValue> = read_messages(&self.siri_journal_path)?;
+        info!(
+            "Read {} SIRI deliveries from real time journal.",
+            deliveries.len()
+        );
+        Ok(deliveries
+            .into_iter()
+            .map(|mut delivery| delivery.take_value())
+            .collect())
+    }
+
+    /// Returns the messages stored in the journal, whatever the base data
+    /// they were applied on.
+    /// Used to carry the real time updates over to new base data.
+    pub fn read_all(&self) -> Result<Vec<FeedMessage>, Error> {
+        if !self.journal_path.exists() {
+            return Ok(Vec::new());
+        }
+        read_messages(&self.journal_path)
+    }
+
+    fn is_built_on(&self, fingerprint: &str, path: &Path) -> Result<bool, Error> {
+        if !self.fingerprint_path.exists() || !path.exists() {
+            info!("No real time journal found in {:?}.", path);
+            return Ok(false);
+        }
+        let journal_fingerprint = std::fs::read_to_string(&self.fingerprint_path)
+            .with_context(|| format!("Could not read {:?}", self.fingerprint_path))?;
+        if journal_fingerprint != fingerprint {
+            info!("Real time journal was built on other base data. I'll not replay it.");
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Empties the journal, to store messages applied
+    /// on base data with the given `fingerprint`.
+    pub fn reset(&mut self, fingerprint: &str) -> Result<(), Error> {
+        for path in [&self.journal_path, &self.siri_journal_path] {
+            File::create(path).with_context(|| format!("Could not truncate {:?}", path))?;
+        }
+        std::fs::write(&self.fingerprint_path, fingerprint)
+            .with_context(|| format!("Could not write {:?}", self.fingerprint_path))?;
+        Ok(())
+    }
+
+    pub fn append(&mut self, messages: &[FeedMessage]) -> Result<(), Error> {
+        append_messages(&self.journal_path, messages)
+    }
+
+    /// Appends the xml of SIRI deliveries
+    pub fn append_siri_deliveries(&mut self, deliveries: &[String]) -> Result<(), Error> {
+        let messages: Vec<StringValue> = deliveries
+            .iter()
+            .map(|delivery| {
+                let mut message = StringValue::new();
+                message.set_value(delivery.clone());
+                message
+            })
+            .collect();
+        append_messages(&self.siri_journal_path, &messages)
+    }
+
+    /// Appends chaos disruptions read from the chaos database,
+    /// as a FeedMessage in the same format as the ones sent by chaos on rabbitmq.
+    pub fn append_chaos_disruptions(
+        &mut self,
+        disruptions: &[Disruption],
+        datetime: NaiveDateTime,
+    ) -> Result<(), Error> {
+        let mut entities = Vec::with_capacity(disruptions.len());
+        for disruption in disruptions {
+            let mut entity = FeedEntity::new();
+            entity.set_id(disruption.get_id().to_string());
+            entity
+                .mut_unknown_fields()
+                .add_length_delimited(exts::disruption.field_number, disruption.write_to_bytes()?);
+            entities.push(entity);
+        }
+        self.append_entities(entities, datetime)
+    }
+
+    /// Appends the cancellation of chaos disruptions
+    pub fn append_chaos_cancellations(
+        &mut self,
+        disruption_ids: &[String],
+        datetime: NaiveDateTime,
+    ) -> Result<(), Error> {
+        let entities = disruption_ids
+            .iter()
+            .map(|disruption_id| {
+                let mut entity = FeedEntity::new();
+                entity.set_id(disruption_id.clone());
+                entity.set_is_deleted(true);
+                entity
+            })
+            .collect();
+        self.append_entities(entities, datetime)
+    }
+
+    /// Appends the cancellation of a kirin disruption, made through the admin api.
+    /// Unlike a chaos cancellation, it carries the trip that the disruption updated.
+    pub fn append_kirin_cancellation(
+        &mut self,
+        disruption_id: &str,
+        vehicle_journey_id: &str,
+        date: NaiveDate,
+        datetime: NaiveDateTime,
+    ) -> Result<(), Error> {
+        let mut entity = FeedEntity::new();
+        entity.set_id(disruption_id.to_string());
+        entity.set_is_deleted(true);
+        let trip = entity.mut_trip_update().mut_trip();
+        trip.set_trip_id(vehicle_journey_id.to_string());
+        trip.set_start_date(date.format("%Y%m%d").to_string());
+        self.append_entities(vec![entity], datetime)
+    }
+
+    fn append_entities(
+        &mut self,
+        entities: Vec<FeedEntity>,
+        datetime: NaiveDateTime,
+    ) -> Result<(), Error> {
+        if entities.is_empty() {
+            return Ok(());
+        }
+        let mut message = FeedMessage::new();
+        let header = message.mut_header();
+        header.set_gtfs_realtime_version("1.0".to_string());
+        header.set_timestamp(u64::try_from(datetime.timestamp())?);
+        message.set_entity(RepeatedField::from_vec(entities));
+        self.append(&[message])
+    }
+}
+
+fn read_messages<M: ProtobufMessage>(path: &Path) -> Result<Vec<M>, Error> {
+    let file = File::open(path).with_context(|| format!("Could not open {:?}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut input = protobuf::CodedInputStream::from_buffered_reader(&mut reader);
+    let mut messages = Vec::new();
+    let mut last_good_offset = 0;
+    while !input.eof()? {
+        match input.read_message() {
+            Ok(message) => {
+                messages.push(message);
+                last_good_offset = input.pos();
+            }
+            Err(err) => {
+                // the last message may have been partially written
+                // if we stopped while writing it.
+                // We remove it, so that messages appended afterwards can be read.
+                error!(
+                    "Could not read message {} of {:?}. \
+                    I'll truncate it to the previous messages. {:?}",
+                    messages.len(),
+                    path,
+                    err
+                );
+                truncate(path, last_good_offset)?;
+                break;
+            }
+        }
+    }
+    Ok(messages)
+}
+
+fn append_messages<M: ProtobufMessage>(path: &Path, messages: &[M]) -> Result<(), Error> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let mut bytes = Vec::new();
+    for message in messages {
+        message.write_length_delimited_to_vec(&mut bytes)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Could not open {:?}", path))?;
+    let previous_len = file.metadata()?.len();
+    let write_result = file.write_all(&bytes).and_then(|()| file.sync_data());
+    if let Err(err) = write_result {
+        // do not leave a partial message at the end of the journal,
+        // since it would hide the messages appended afterwards
+        file.set_len(previous_len)?;
+        return Err(Error::from(err).context(format!("Could not append messages to {:?}", path)));
+    }
+    Ok(())
+}
+
+fn truncate(path: &Path, len: u64) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Could not open {:?}", path))?;
+    file.set_len(len)
+        .with_context(|| format!("Could not truncate {:?}", path))?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Identifies the base data on which real time messages are applied
+pub fn base_data_fingerprint(base_model: &BaseModel) -> String {
+    let mut contributors: Vec<String> = base_model.contributors().map(|c| c.id).collect();
+    contributors.sort();
+    format!(
+        "dataset_created_at={:?}\nvalidity_period={:?}\ncontributors={:?}\nnb_of_vehicle_journeys={}\n",
+        base_model.dataset_created_at(),
+        base_model.validity_period(),
+        contributors,
+        base_model.nb_of_vehicle_journeys(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime() -> NaiveDateTime {
+        NaiveDate::from_ymd(2022, 1, 10).and_hms(8, 0, 0)
+    }
+
+    #[test]
+    fn journal_is_replayed_only_on_same_base_data() {
+        let directory = tempfile::tempdir().unwrap();
+        let params = RealTimeJournalParams {
+            directory: directory.path().join("journal"),
+        };
+        let mut journal = RealTimeJournal::new(&params).unwrap();
+        assert!(journal.read("base_1").unwrap().is_empty());
+
+        journal.reset("base_1").unwrap();
+        let mut disruption = Disruption::new();
+        disruption.set_id("disruption_1".to_string());
+        journal
+            .append_chaos_disruptions(&[disruption], datetime())
+            .unwrap();
+        journal
+            .append_chaos_cancellations(&["disruption_1".to_string()], datetime())
+            .unwrap();
+        let siri_delivery = "<Siri><ServiceDelivery/></Siri>".to_string();
+        journal
+            .append_siri_deliveries(&[siri_delivery.clone()])
+            .unwrap();
+
+        // a journal opened later on the same directory reads the same messages
+        let journal = RealTimeJournal::new(&params).unwrap();
+        let messages = journal.read("base_1").unwrap();
+        assert_eq!(messages.len(), 2);
+        let entity = &messages[0].get_entity()[0];
+        assert_eq!(entity.get_id(), "disruption_1");
+        let disruption = exts::disruption.get(entity).unwrap();
+        assert_eq!(disruption.get_id(), "disruption_1");
+        assert!(messages[1].get_entity()[0].get_is_deleted());
+        assert_eq!(
+            journal.read_siri_deliveries("base_1").unwrap(),
+            vec![siri_delivery]
+        );
+
+        assert!(journal.read("base_2").unwrap().is_empty());
+        assert!(journal.read_siri_deliveries("base_2").unwrap().is_empty());
+    }
+
+    #[test]
+    fn kirin_cancellation_carries_its_trip() {
+        let directory = tempfile::tempdir().unwrap();
+        let params = RealTimeJournalParams {
+            directory: directory.path().to_path_buf(),
+        };
+        let mut journal = RealTimeJournal::new(&params).unwrap();
+        journal.reset("base").unwrap();
+        let date = NaiveDate::from_ymd(2022, 1, 10);
+        journal
+            .append_kirin_cancellation("kirin_1", "vj_1", date, datetime())
+            .unwrap();
+
+        let messages = journal.read("base").unwrap();
+        let entity = &messages[0].get_entity()[0];
+        assert_eq!(entity.get_id(), "kirin_1");
+        assert!(entity.get_is_deleted());
+        let trip = entity.get_trip_update().get_trip();
+        assert_eq!(trip.get_trip_id(), "vj_1");
+        assert_eq!(trip.get_start_date(), "20220110");
+    }
+
+    #[test]
+    fn partially_written_message_is_ignored() {
+        let directory = tempfile::tempdir().unwrap();
+        let params = RealTimeJournalParams {
+            directory: directory.path().to_path_buf(),
+        };
+        let mut journal = RealTimeJournal::new(&params).unwrap();
+        journal.reset("base").unwrap();
+        journal
+            .append_chaos_cancellations(&["disruption_1".to_string()], datetime())
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&journal.journal_path)
+            .unwrap();
+        // a length of 100 bytes, followed by only 2 bytes
+        file.write_all(&[100, 1, 2]).unwrap();
+
+        drop(file);
+
+        let mut journal = RealTimeJournal::new(&params).unwrap();
+        let messages = journal.read("base").unwrap();
+        assert_eq!(messages.len(), 1);
+
+        // messages appended after the broken one are replayed on the next restart
+        journal
+            .append_chaos_cancellations(&["disruption_2".to_string()], datetime())
+            .unwrap();
+        let journal = RealTimeJournal::new(&params).unwrap();
+        let messages = journal.read("base").unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].get_entity()[0].get_id(), "disruption_2");
+    }
+}
@@ -0,0 +1,360 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::{
+    chaos_proto::gtfs_realtime::FeedMessage,
+    navitia_proto,
+    server_config::{DirectorySourceParams, RealTimeSourceParams, ZmqSourceParams},
+};
+use anyhow::{bail, format_err, Context, Error};
+use futures::StreamExt;
+use launch::loki::tracing::{debug, error, info};
+use prost::Message as ProstMessage;
+use protobuf::Message as ProtobufMessage;
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    thread,
+    time::SystemTime,
+};
+use tokio::{sync::mpsc, time::Duration};
+
+/// A message received from a local source
+#[derive(Debug)]
+pub enum LocalMessage {
+    /// real time updates, sent by chaos or kirin
+    FeedMessage(FeedMessage),
+    /// a navitia Task asking for a reload of the base data,
+    /// as the ones received on the rabbitmq reload queue
+    Reload,
+}
+
+/// Launch all local sources of real time messages.
+/// Each message received by a source is sent to `sender`.
+///
+/// Directory and zmq sources are spawned on the current tokio runtime,
+/// so this must be called from within a tokio runtime.
+/// The stdin source is read in its own thread.
+pub fn launch_real_time_sources(
+    sources: &[RealTimeSourceParams],
+    sender: &mpsc::UnboundedSender<LocalMessage>,
+) -> Result<(), Error> {
+    for source in sources {
+        match source {
+            RealTimeSourceParams::Directory(params) => {
+                tokio::spawn(watch_directory(params.clone(), sender.clone()));
+            }
+            RealTimeSourceParams::Zmq(params) => {
+                tokio::spawn(listen_zmq(params.clone(), sender.clone()));
+            }
+            RealTimeSourceParams::Stdin => {
+                let sender = sender.clone();
+                thread::Builder::new()
+                    .name("loki_stdin_real_time".to_string())
+                    .spawn(move || {
+                        let result = read_stdin(&sender);
+                        error!("Real time source on stdin stopped. {:?}", result);
+                    })
+                    .context("Could not spawn thread to read real time messages from stdin.")?;
+            }
+        }
+        info!("Real time source launched : {:?}", source);
+    }
+    Ok(())
+}
+
+async fn watch_directory(
+    params: DirectorySourceParams,
+    sender: mpsc::UnboundedSender<LocalMessage>,
+) {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(params.poll_interval.total_seconds()));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    // the last modification time of each file already handled
+    let mut handled_files: HashMap<PathBuf, SystemTime> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+        let result = scan_directory(&params.path, &mut handled_files, &sender);
+        if let Err(err) = result {
+            error!(
+                "Error while scanning real time directory {:?}. {:?}",
+                params.path, err
+            );
+        }
+        if sender.is_closed() {
+            return;
+        }
+    }
+}
+
+fn scan_directory(
+    directory: &Path,
+    handled_files: &mut HashMap<PathBuf, SystemTime>,
+    sender: &mpsc::UnboundedSender<LocalMessage>,
+) -> Result<(), Error> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(directory)
+        .with_context(|| format!("Could not read directory {:?}", directory))?
+    {
+        let path = entry?.path();
+        if path.is_file() && path.extension() == Some(OsStr::new("pb")) {
+            files.push(path);
+        }
+    }
+    // files are handled in the order of their names
+    files.sort();
+    // forget the files that were removed from the directory,
+    // so that `handled_files` does not grow forever
+    handled_files.retain(|path, _| files.binary_search(path).is_ok());
+
+    for path in files {
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .with_context(|| format!("Could not read modification time of {:?}", path))?;
+        if handled_files.get(&path) == Some(&modified) {
+            continue;
+        }
+        handled_files.insert(path.clone(), modified);
+
+        debug!("Reading real time file {:?}", path);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                error!("Could not read real time file {:?}. {:?}", path, err);
+                continue;
+            }
+        };
+        match decode_local_message(&bytes) {
+            Ok(message) => {
+                sender
+                    .send(message)
+                    .map_err(|_| format_err!("Channel to send real time messages has closed."))?;
+            }
+            Err(err) => {
+                error!("Could not decode real time file {:?}. {:?}", path, err);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn listen_zmq(params: ZmqSourceParams, sender: mpsc::UnboundedSender<LocalMessage>) {
+    let result = listen_zmq_loop(&params, &sender).await;
+    error!(
+        "Real time source on zmq endpoint {} stopped. {:?}",
+        params.endpoint, result
+    );
+}
+
+async fn listen_zmq_loop(
+    params: &ZmqSourceParams,
+    sender: &mpsc::UnboundedSender<LocalMessage>,
+) -> Result<(), Error> {
+    let context = tmq::Context::new();
+    let subscriber = tmq::subscribe(&context)
+        .connect(&params.endpoint)
+        .with_context(|| format!("Could not connect zmq to endpoint {}", params.endpoint))?;
+
+    // an empty topic means that we subscribe to all messages
+    let mut topics = params.topics.iter().map(String::as_bytes);
+    let mut socket = subscriber.subscribe(topics.next().unwrap_or(b""))?;
+    for topic in topics {
+        socket.subscribe(topic)?;
+    }
+    info!(
+        "Listening for real time messages on zmq endpoint {}",
+        params.endpoint
+    );
+
+    while let Some(zmq_message_result) = socket.next().await {
+        let zmq_message = match zmq_message_result {
+            Ok(zmq_message) => zmq_message,
+            Err(err) => {
+                error!("Error while reading zmq socket. {:?}", err);
+                continue;
+            }
+        };
+        // the first frames may contain the topic, the protobuf is in the last one
+        let payload = match zmq_message.iter().last() {
+            Some(payload) => payload,
+            None => {
+                error!("Received an empty zmq message.");
+                continue;
+            }
+        };
+        match decode_local_message(payload) {
+            Ok(message) => {
+                sender
+                    .send(message)
+                    .map_err(|_| format_err!("Channel to send real time messages has closed."))?;
+            }
+            Err(err) => {
+                error!("Could not decode zmq message. {:?}", err);
+            }
+        }
+    }
+    Err(format_err!("The zmq socket is closed."))
+}
+
+fn read_stdin(sender: &mpsc::UnboundedSender<LocalMessage>) -> Result<(), Error> {
+    let stdin = std::io::stdin();
+    let mut stdin_lock = stdin.lock();
+    let mut input = protobuf::CodedInputStream::from_buffered_reader(&mut stdin_lock);
+    info!("Listening for real time messages on stdin");
+    while !input.eof()? {
+        // messages are length delimited, so an error here means
+        // that we lost track of the message boundaries, and we cannot continue
+        let bytes = input
+            .read_bytes()
+            .context("Could not read message from stdin.")?;
+        match decode_local_message(&bytes) {
+            Ok(message) => {
+                sender
+                    .send(message)
+                    .map_err(|_| format_err!("Channel to send real time messages has closed."))?;
+            }
+            Err(err) => {
+                error!("Could not decode message from stdin. {:?}", err);
+            }
+        }
+    }
+    info!("Reached end of stdin.");
+    Ok(())
+}
+
+// A local message is either a gtfs-rt FeedMessage or a navitia Task.
+// They cannot be mistaken for each other, since the first field of a FeedMessage
+// is its header, whereas the first field of a Task is its action, encoded as a varint.
+fn decode_local_message(bytes: &[u8]) -> Result<LocalMessage, Error> {
+    let feed_message_error = match FeedMessage::parse_from_bytes(bytes) {
+        Ok(message) => return Ok(LocalMessage::FeedMessage(message)),
+        Err(err) => err,
+    };
+    // an empty protobuf would be decoded as a Task with the default action
+    if bytes.is_empty() {
+        bail!("Received an empty message.");
+    }
+    let task = navitia_proto::Task::decode(bytes).map_err(|task_error| {
+        format_err!(
+            "Message is neither a FeedMessage ({}) nor a Task ({}).",
+            feed_message_error,
+            task_error
+        )
+    })?;
+    match task.action() {
+        navitia_proto::Action::Reload => Ok(LocalMessage::Reload),
+        action => bail!("Received a Task with unhandled action {:?}.", action),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaos_proto::gtfs_realtime::FeedEntity;
+    use protobuf::RepeatedField;
+
+    fn received_entity_id(receiver: &mut mpsc::UnboundedReceiver<LocalMessage>) -> String {
+        match receiver.try_recv() {
+            Ok(LocalMessage::FeedMessage(message)) => message.get_entity()[0].get_id().to_string(),
+            other => panic!("expected a FeedMessage, received {:?}", other),
+        }
+    }
+
+    fn write_message(path: &Path, entity_id: &str) {
+        let mut message = FeedMessage::new();
+        message
+            .mut_header()
+            .set_gtfs_realtime_version("1.0".to_string());
+        let mut entity = FeedEntity::new();
+        entity.set_id(entity_id.to_string());
+        message.set_entity(RepeatedField::from_vec(vec![entity]));
+        std::fs::write(path, message.write_to_bytes().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn files_are_forwarded_once() {
+        let directory = tempfile::tempdir().unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut handled_files = HashMap::new();
+
+        let file_path = directory.path().join("message.pb");
+        write_message(&file_path, "entity_1");
+        // files without the pb extension are ignored
+        std::fs::write(directory.path().join("message.tmp"), b"not a message").unwrap();
+
+        scan_directory(directory.path(), &mut handled_files, &sender).unwrap();
+        assert_eq!(received_entity_id(&mut receiver), "entity_1");
+        assert!(receiver.try_recv().is_err());
+
+        // the file is not forwarded again
+        scan_directory(directory.path(), &mut handled_files, &sender).unwrap();
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(handled_files.len(), 1);
+
+        // a removed file is forgotten
+        std::fs::remove_file(&file_path).unwrap();
+        scan_directory(directory.path(), &mut handled_files, &sender).unwrap();
+        assert!(handled_files.is_empty());
+
+        // so a new file with the same name is forwarded
+        write_message(&file_path, "entity_2");
+        scan_directory(directory.path(), &mut handled_files, &sender).unwrap();
+        assert_eq!(received_entity_id(&mut receiver), "entity_2");
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn reload_tasks_are_forwarded() {
+        let directory = tempfile::tempdir().unwrap();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut handled_files = HashMap::new();
+
+        let mut task = navitia_proto::Task::default();
+        task.set_action(navitia_proto::Action::Reload);
+        std::fs::write(directory.path().join("reload.pb"), task.encode_to_vec()).unwrap();
+
+        scan_directory(directory.path(), &mut handled_files, &sender).unwrap();
+        assert!(matches!(receiver.try_recv(), Ok(LocalMessage::Reload)));
+
+        // other actions are not forwarded
+        task.set_action(navitia_proto::Action::LoadRealtime);
+        assert!(decode_local_message(&task.encode_to_vec()).is_err());
+        assert!(decode_local_message(&[]).is_err());
+    }
+}
@@ -90,17 +90,19 @@ use std::convert::TryFrom;
 
 pub fn make_response(
     request_input: &RequestInput,
-    journeys: Vec<loki::Response>,
+    journeys: Vec<(NaiveDateTime, loki::Response)>,
     model: &ModelRefs<'_>,
 ) -> Result<navitia_proto::Response, Error> {
     let mut proto = navitia_proto::Response {
         journeys: journeys
             .iter()
             .enumerate()
-            .map(|(idx, journey)| make_journey(request_input, journey, idx, model))
+            .map(|(idx, (requested_datetime, journey))| {
+                make_journey(request_input, requested_datetime, journey, idx, model)
+            })
             .collect::<Result<Vec<_>, _>>()?,
         feed_publishers: make_feed_publishers(model),
-        impacts: make_impacts(&journeys, model),
+        impacts: make_impacts(journeys.iter().map(|(_, journey)| journey), model),
         ..Default::default()
     };
 
@@ -109,7 +111,10 @@ pub fn make_response(
     Ok(proto)
 }
 
-fn make_impacts(journeys: &[loki::Response], model: &ModelRefs<'_>) -> Vec<navitia_proto::Impact> {
+fn make_impacts<'a>(
+    journeys: impl Iterator<Item = &'a loki::Response>,
+    model: &ModelRefs<'_>,
+) -> Vec<navitia_proto::Impact> {
     let mut chaos_impacts = HashSet::new();
     let mut kirin_disruptions = HashSet::new();
     for journey in journeys {
@@ -261,6 +266,7 @@ fn effect_to_string(effect: Effect) -> String {
 
 fn make_journey(
     request_input: &RequestInput,
+    requested_datetime: &NaiveDateTime,
     journey: &loki::Response,
     journey_id: usize,
     model: &ModelRefs<'_>,
@@ -287,7 +293,7 @@ fn make_journey(
             ridesharing: Some(0),
             taxi: Some(0),
         }),
-        requested_date_time: Some(to_u64_timestamp(&request_input.datetime)?),
+        requested_date_time: Some(to_u64_timestamp(requested_datetime)?),
         most_serious_disruption_effect: worst_effect_on_journey(journey, model)
             .map(effect_to_string),
         ..Default::default()
@@ -0,0 +1,386 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{format_err, Context, Error};
+use launch::loki::models::VehicleJourneyIdx;
+use lru::LruCache;
+use prost::Message;
+
+use crate::{navitia_proto, server_config::ResponseCacheParams};
+
+/// A cache of the responses computed by the workers, shared between them.
+///
+/// Requests are identified by their protobuf encoding, once normalized :
+/// the fields that identify the client (request id, deadline, current datetime)
+/// are cleared. Datetimes are kept as is, since the workers solve each
+/// request on its exact datetimes.
+///
+/// Each response remembers the vehicle journeys it uses, so that a real time update
+/// only invalidates the responses that use the vehicle journeys it modified.
+/// A real time update can also make a journey possible that was not before. Such a
+/// change is not seen by the cached responses, so they are discarded after `max_age`.
+pub struct ResponseCache {
+    max_age: Duration,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // incremented on each invalidation, so that responses computed on data
+    // that was modified in the meantime are not inserted
+    generation: u64,
+    entries: LruCache<Vec<u8>, Entry>,
+    keys_by_vehicle_journey: HashMap<VehicleJourneyIdx, HashSet<Vec<u8>>>,
+}
+
+struct Entry {
+    response: Vec<u8>,
+    vehicle_journeys: Vec<VehicleJourneyIdx>,
+    inserted_at: Instant,
+}
+
+impl ResponseCache {
+    pub fn new(params: &ResponseCacheParams) -> Self {
+        let inner = Inner {
+            generation: 0,
+            entries: LruCache::new(params.capacity),
+            keys_by_vehicle_journey: HashMap::new(),
+        };
+        Self {
+            max_age: Duration::from_secs(params.max_age.total_seconds()),
+            inner: Mutex::new(inner),
+        }
+    }
+
+    /// Returns the key of `proto_request` in the cache,
+    /// or None if responses to this request should not be cached.
+    pub fn key(&self, proto_request: &navitia_proto::Request) -> Option<Vec<u8>> {
+        use navitia_proto::Api;
+        match proto_request.requested_api() {
+            Api::PtPlanner | Api::NextDepartures | Api::NextArrivals | Api::PlacesNearby => (),
+            _ => return None,
+        }
+        let mut normalized = proto_request.clone();
+        normalized.request_id = None;
+        normalized.deadline = None;
+        normalized.current_datetime = None;
+        Some(normalized.encode_to_vec())
+    }
+
+    /// To be called before accessing the data used to compute
+    /// a response, and given back to `insert()`.
+    pub fn generation(&self) -> Result<u64, Error> {
+        Ok(self.lock()?.generation)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<navitia_proto::Response>, Error> {
+        let mut inner = self.lock()?;
+        let is_expired = match inner.entries.get(key) {
+            None => return Ok(None),
+            Some(entry) => entry.inserted_at.elapsed() > self.max_age,
+        };
+        if is_expired {
+            inner.remove(key);
+            return Ok(None);
+        }
+        // unwrap is safe since we just checked that the entry is present
+        let entry = inner.entries.peek(key).unwrap();
+        let response = navitia_proto::Response::decode(entry.response.as_slice())
+            .context("Could not decode cached response.")?;
+        Ok(Some(response))
+    }
+
+    /// Inserts the `response` to the request identified by `key`.
+    ///
+    /// `generation` must be obtained before accessing the data used to compute the response.
+    /// The response is not inserted if the cache was invalidated since,
+    /// or if the response is an error.
+    pub fn insert(
+        &self,
+        key: Vec<u8>,
+        generation: u64,
+        response: &navitia_proto::Response,
+        vehicle_journeys: Vec<VehicleJourneyIdx>,
+    ) -> Result<(), Error> {
+        if response.error.is_some() {
+            return Ok(());
+        }
+        let mut inner = self.lock()?;
+        if inner.generation != generation {
+            return Ok(());
+        }
+        inner.remove(&key);
+        for vehicle_journey in &vehicle_journeys {
+            inner
+                .keys_by_vehicle_journey
+                .entry(vehicle_journey.clone())
+                .or_default()
+                .insert(key.clone());
+        }
+        let entry = Entry {
+            response: response.encode_to_vec(),
+            vehicle_journeys,
+            inserted_at: Instant::now(),
+        };
+        if let Some((evicted_key, evicted_entry)) = inner.entries.push(key, entry) {
+            inner.unlink(&evicted_key, &evicted_entry);
+        }
+        Ok(())
+    }
+
+    /// Removes all responses. To be called when new base data is loaded.
+    pub fn invalidate_all(&self) -> Result<(), Error> {
+        let mut inner = self.lock()?;
+        inner.generation += 1;
+        inner.entries.clear();
+        inner.keys_by_vehicle_journey.clear();
+        Ok(())
+    }
+
+    /// Removes the responses that use one of `vehicle_journeys`.
+    pub fn invalidate_vehicle_journeys(
+        &self,
+        vehicle_journeys: &[VehicleJourneyIdx],
+    ) -> Result<(), Error> {
+        let mut inner = self.lock()?;
+        inner.generation += 1;
+        for vehicle_journey in vehicle_journeys {
+            if let Some(keys) = inner.keys_by_vehicle_journey.remove(vehicle_journey) {
+                for key in keys {
+                    inner.remove(&key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Inner>, Error> {
+        self.inner
+            .lock()
+            .map_err(|err| format_err!("Failed to acquire lock on response cache. {}", err))
+    }
+}
+
+impl Inner {
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.unlink(key, &entry);
+        }
+    }
+
+    fn unlink(&mut self, key: &[u8], entry: &Entry) {
+        for vehicle_journey in &entry.vehicle_journeys {
+            if let Some(keys) = self.keys_by_vehicle_journey.get_mut(vehicle_journey) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.keys_by_vehicle_journey.remove(vehicle_journey);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch::loki::models::real_time_model::NewVehicleJourneyIdx;
+
+    fn journeys_request(datetime: u64, deadline: &str) -> navitia_proto::Request {
+        let mut request = navitia_proto::Request {
+            journeys: Some(navitia_proto::JourneysRequest {
+                datetimes: vec![datetime],
+                ..Default::default()
+            }),
+            deadline: Some(deadline.to_string()),
+            ..Default::default()
+        };
+        request.set_requested_api(navitia_proto::Api::PtPlanner);
+        request
+    }
+
+    fn response(nb_journeys: usize) -> navitia_proto::Response {
+        navitia_proto::Response {
+            journeys: vec![navitia_proto::Journey::default(); nb_journeys],
+            ..Default::default()
+        }
+    }
+
+    fn new_vehicle_journey(idx: usize) -> VehicleJourneyIdx {
+        VehicleJourneyIdx::New(NewVehicleJourneyIdx { idx })
+    }
+
+    #[test]
+    fn requests_differing_by_their_client_fields_share_their_key() {
+        let cache = ResponseCache::new(&ResponseCacheParams::default());
+        let key = cache.key(&journeys_request(60, "20210101T080000,000000"));
+        assert!(key.is_some());
+        assert_eq!(
+            key,
+            cache.key(&journeys_request(60, "20210101T090000,000000"))
+        );
+        assert_ne!(
+            key,
+            cache.key(&journeys_request(61, "20210101T080000,000000"))
+        );
+
+        let mut status_request = navitia_proto::Request::default();
+        status_request.set_requested_api(navitia_proto::Api::Status);
+        assert!(cache.key(&status_request).is_none());
+    }
+
+    #[test]
+    fn response_is_not_shared_with_a_later_request_of_the_same_minute() {
+        let cache = ResponseCache::new(&ResponseCacheParams::default());
+        let first_key = cache.key(&journeys_request(60, "")).unwrap();
+        // departs after the first request, but before the second one
+        let response = navitia_proto::Response {
+            journeys: vec![navitia_proto::Journey {
+                departure_date_time: Some(90),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let generation = cache.generation().unwrap();
+        cache
+            .insert(
+                first_key.clone(),
+                generation,
+                &response,
+                vec![new_vehicle_journey(0)],
+            )
+            .unwrap();
+        assert_eq!(cache.get(&first_key).unwrap(), Some(response));
+
+        let second_key = cache.key(&journeys_request(119, "")).unwrap();
+        assert_eq!(cache.get(&second_key).unwrap(), None);
+    }
+
+    #[test]
+    fn real_time_update_invalidates_responses_using_modified_vehicle_journeys() {
+        let cache = ResponseCache::new(&ResponseCacheParams::default());
+        let first_key = cache.key(&journeys_request(60, "")).unwrap();
+        let second_key = cache.key(&journeys_request(120, "")).unwrap();
+
+        let generation = cache.generation().unwrap();
+        cache
+            .insert(
+                first_key.clone(),
+                generation,
+                &response(1),
+                vec![new_vehicle_journey(0)],
+            )
+            .unwrap();
+        cache
+            .insert(
+                second_key.clone(),
+                generation,
+                &response(2),
+                vec![new_vehicle_journey(1)],
+            )
+            .unwrap();
+        assert_eq!(cache.get(&first_key).unwrap(), Some(response(1)));
+        assert_eq!(cache.get(&second_key).unwrap(), Some(response(2)));
+
+        cache
+            .invalidate_vehicle_journeys(&[new_vehicle_journey(0)])
+            .unwrap();
+        assert_eq!(cache.get(&first_key).unwrap(), None);
+        assert_eq!(cache.get(&second_key).unwrap(), Some(response(2)));
+
+        cache.invalidate_all().unwrap();
+        assert_eq!(cache.get(&second_key).unwrap(), None);
+    }
+
+    #[test]
+    fn response_computed_before_an_invalidation_is_not_inserted() {
+        let cache = ResponseCache::new(&ResponseCacheParams::default());
+        let key = cache.key(&journeys_request(60, "")).unwrap();
+
+        let generation = cache.generation().unwrap();
+        cache
+            .invalidate_vehicle_journeys(&[new_vehicle_journey(0)])
+            .unwrap();
+        cache
+            .insert(
+                key.clone(),
+                generation,
+                &response(1),
+                vec![new_vehicle_journey(0)],
+            )
+            .unwrap();
+        assert_eq!(cache.get(&key).unwrap(), None);
+    }
+
+    #[test]
+    fn evicted_responses_are_unlinked() {
+        let params = ResponseCacheParams {
+            capacity: 1,
+            ..Default::default()
+        };
+        let cache = ResponseCache::new(&params);
+        let first_key = cache.key(&journeys_request(60, "")).unwrap();
+        let second_key = cache.key(&journeys_request(120, "")).unwrap();
+
+        let generation = cache.generation().unwrap();
+        cache
+            .insert(
+                first_key.clone(),
+                generation,
+                &response(1),
+                vec![new_vehicle_journey(0)],
+            )
+            .unwrap();
+        cache
+            .insert(
+                second_key.clone(),
+                generation,
+                &response(2),
+                vec![new_vehicle_journey(1)],
+            )
+            .unwrap();
+        assert_eq!(cache.get(&first_key).unwrap(), None);
+        assert_eq!(cache.get(&second_key).unwrap(), Some(response(2)));
+        let inner = cache.lock().unwrap();
+        assert!(!inner
+            .keys_by_vehicle_journey
+            .contains_key(&new_vehicle_journey(0)));
+    }
+}
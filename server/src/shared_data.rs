@@ -0,0 +1,202 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use anyhow::{format_err, Error};
+use launch::loki::models::VehicleJourneyIdx;
+use std::sync::{Arc, RwLock};
+
+use crate::master_worker::DataAndModels;
+
+/// The data and models used to answer requests.
+///
+/// Compute workers take a [`Snapshot`] at the start of each request,
+/// which only clones an `Arc` under a read lock.
+/// Hence new data and models can be built off to the side while
+/// the current ones keep serving requests, and then be swapped in
+/// without waiting for the requests in progress to complete.
+pub struct SharedData {
+    current: RwLock<Snapshot>,
+}
+
+#[derive(Clone)]
+pub struct Snapshot {
+    /// incremented each time new data and models are swapped in
+    pub epoch: u64,
+    pub data_and_models: Arc<DataAndModels>,
+}
+
+impl SharedData {
+    pub fn new(data_and_models: DataAndModels) -> Self {
+        let snapshot = Snapshot {
+            epoch: 0,
+            data_and_models: Arc::new(data_and_models),
+        };
+        Self {
+            current: RwLock::new(snapshot),
+        }
+    }
+
+    pub fn snapshot(&self) -> Result<Snapshot, Error> {
+        let lock_guard = self
+            .current
+            .read()
+            .map_err(|err| format_err!("Failed to acquire read lock on shared data. {}", err))?;
+        Ok(lock_guard.clone())
+    }
+
+    /// Replaces the current data and models by `data_and_models`.
+    ///
+    /// Returns the new epoch, along with the previous data and models,
+    /// so that the caller can drop them outside of the lock.
+    pub fn swap(&self, data_and_models: DataAndModels) -> Result<(u64, Arc<DataAndModels>), Error> {
+        let new_data_and_models = Arc::new(data_and_models);
+        let mut lock_guard = self
+            .current
+            .write()
+            .map_err(|err| format_err!("Failed to acquire write lock on shared data. {}", err))?;
+        let epoch = lock_guard.epoch + 1;
+        let previous = std::mem::replace(
+            &mut *lock_guard,
+            Snapshot {
+                epoch,
+                data_and_models: new_data_and_models,
+            },
+        );
+        Ok((epoch, previous.data_and_models))
+    }
+
+    /// Applies `updater` on a copy of the current data and models,
+    /// and then swaps this copy in, without changing the epoch.
+    ///
+    /// Requests keep using the current data and models while `updater` runs.
+    /// The copy only clones pointers to the chunks of the collections in the data
+    /// and models (see `loki::shared_collections`). A chunk is copied when `updater`
+    /// modifies one of its elements, so the cost of an update grows with the number
+    /// of chunks it modifies, plus the number of chunks of each collection.
+    /// If `updater` fails, the current data and models are left untouched.
+    ///
+    /// Since the copy is made before `updater` runs, `update()` and `swap()`
+    /// must not be called concurrently, otherwise some modifications would be lost.
+    ///
+    /// Returns the result of `updater`, along with the vehicle journeys
+    /// whose real time history was modified by `updater`.
+    pub fn update<Updater, T>(&self, updater: Updater) -> Result<(T, Vec<VehicleJourneyIdx>), Error>
+    where
+        Updater: FnOnce(&mut DataAndModels) -> Result<T, Error>,
+    {
+        let snapshot = self.snapshot()?;
+        let mut new_data_and_models = DataAndModels::clone(&snapshot.data_and_models);
+        let result = updater(&mut new_data_and_models)?;
+        let modified_vehicle_journeys = new_data_and_models
+            .2
+            .modified_vehicle_journeys(&snapshot.data_and_models.2);
+        drop(snapshot);
+        let new_data_and_models = Arc::new(new_data_and_models);
+        let previous_data_and_models = {
+            let mut lock_guard = self.current.write().map_err(|err| {
+                format_err!("Failed to acquire write lock on shared data. {}", err)
+            })?;
+            std::mem::replace(&mut lock_guard.data_and_models, new_data_and_models)
+        }; // lock is released
+           // the previous version is freed once the last request using it completes
+        drop(previous_data_and_models);
+        Ok((result, modified_vehicle_journeys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use launch::loki::{
+        models::{base_model::BaseModel, real_time_model::RealTimeModel},
+        TransitData,
+    };
+    use std::ops::Deref;
+
+    fn empty_data_and_models() -> DataAndModels {
+        let base_model = BaseModel::empty();
+        let data = TransitData::new(&base_model);
+        (data, Arc::new(base_model), RealTimeModel::new())
+    }
+
+    #[test]
+    fn snapshot_survives_swap() {
+        let shared_data = SharedData::new(empty_data_and_models());
+        let snapshot = shared_data.snapshot().unwrap();
+        assert_eq!(snapshot.epoch, 0);
+
+        let (epoch, previous) = shared_data.swap(empty_data_and_models()).unwrap();
+        assert_eq!(epoch, 1);
+        assert!(Arc::ptr_eq(&previous, &snapshot.data_and_models));
+        assert_eq!(shared_data.snapshot().unwrap().epoch, 1);
+    }
+
+    #[test]
+    fn update_does_not_modify_previous_snapshot() {
+        let shared_data = SharedData::new(empty_data_and_models());
+        let snapshot = shared_data.snapshot().unwrap();
+
+        let (_, modified_vehicle_journeys) = shared_data
+            .update(|data_and_models| {
+                data_and_models.2.insert_new_vehicle_journey("new_vj");
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(modified_vehicle_journeys.len(), 1);
+
+        let (_, _, real_time_model) = snapshot.data_and_models.deref();
+        assert_eq!(real_time_model.nb_of_new_vehicle_journeys(), 0);
+
+        let new_snapshot = shared_data.snapshot().unwrap();
+        assert_eq!(new_snapshot.epoch, 0);
+        let (_, _, real_time_model) = new_snapshot.data_and_models.deref();
+        assert_eq!(real_time_model.nb_of_new_vehicle_journeys(), 1);
+    }
+
+    #[test]
+    fn failed_update_keeps_current_data() {
+        let shared_data = SharedData::new(empty_data_and_models());
+        let result = shared_data.update(|data_and_models| -> Result<(), Error> {
+            data_and_models.2.insert_new_vehicle_journey("new_vj");
+            Err(format_err!("failure"))
+        });
+        assert!(result.is_err());
+
+        let snapshot = shared_data.snapshot().unwrap();
+        let (_, _, real_time_model) = snapshot.data_and_models.deref();
+        assert_eq!(real_time_model.nb_of_new_vehicle_journeys(), 0);
+    }
+}
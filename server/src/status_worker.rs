@@ -34,23 +34,28 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use crate::zmq_worker::{RequestMessage, ResponseMessage, StatusWorkerToZmqChannels};
+use crate::{
+    metrics::Metrics,
+    zmq_worker::{RequestMessage, ResponseMessage, StatusWorkerToZmqChannels},
+};
 
 use super::navitia_proto;
 
-use anyhow::{format_err, Context, Error};
+use anyhow::{format_err, Error};
 
 use launch::loki::{
     chrono::NaiveDate,
     chrono_tz,
+    memory_usage::MemoryUsage,
     tracing::{error, log::warn},
     NaiveDateTime,
 };
 
-use std::thread;
+use serde::{Serialize, Serializer};
+use std::{sync::Arc, thread, time::Instant};
 
 use crate::ServerConfig;
-use tokio::{runtime::Builder, sync::mpsc};
+use tokio::{runtime::Handle, sync::mpsc};
 
 pub const DATE_FORMAT: &str = "%Y%m%d";
 pub const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S.%f";
@@ -59,8 +64,9 @@ pub const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub struct StatusWorker {
     base_data_info: Option<BaseDataInfo>,
     config_info: ConfigInfo,
-    last_load_succeeded: bool, // last reload was successful
-    is_realtime_loaded: bool,  // is_realtime_loaded for the last reload
+    last_load_succeeded: bool,         // last reload was successful
+    last_load_failure: Option<String>, // why the last reload failed
+    is_realtime_loaded: bool,          // is_realtime_loaded for the last reload
     is_connected_to_rabbitmq: bool,
     last_kirin_reload: Option<NaiveDateTime>,
     last_chaos_reload: Option<NaiveDateTime>,
@@ -70,17 +76,30 @@ pub struct StatusWorker {
 
     status_update_receiver: mpsc::UnboundedReceiver<StatusUpdate>,
 
+    metrics: Arc<Metrics>,
+
     shutdown_sender: mpsc::Sender<()>,
 }
 
+#[derive(Debug, Clone, Serialize)]
 pub struct BaseDataInfo {
     pub start_date: NaiveDate,
     pub end_date: NaiveDate,
     pub last_load_at: NaiveDateTime,
     pub dataset_created_at: Option<NaiveDateTime>,
+    #[serde(serialize_with = "serialize_timezone")]
     pub timezone: chrono_tz::Tz,
     pub contributors: Vec<String>,
     pub publisher_name: Option<String>,
+    /// memory used by the data just after it was loaded
+    pub memory_usage: MemoryUsage,
+}
+
+fn serialize_timezone<S: Serializer>(
+    timezone: &chrono_tz::Tz,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(timezone.name())
 }
 
 pub struct ConfigInfo {
@@ -90,7 +109,8 @@ pub struct ConfigInfo {
 }
 
 pub enum StatusUpdate {
-    BaseDataLoadFailed,
+    // the new base data was rejected, for the given reason
+    BaseDataLoadFailed(String),
     BaseDataLoad(BaseDataInfo),
     RabbitMqConnected,
     RabbitMqDisconnected,
@@ -102,6 +122,7 @@ pub enum StatusUpdate {
 impl StatusWorker {
     pub fn new(
         zmq_channels: StatusWorkerToZmqChannels,
+        metrics: Arc<Metrics>,
         shutdown_sender: mpsc::Sender<()>,
         server_config: &ServerConfig,
     ) -> (Self, mpsc::UnboundedSender<StatusUpdate>) {
@@ -114,6 +135,7 @@ impl StatusWorker {
                 nb_workers: server_config.nb_workers,
             },
             last_load_succeeded: false,
+            last_load_failure: None,
             is_realtime_loaded: false,
             is_connected_to_rabbitmq: false,
             last_chaos_reload: None,
@@ -121,21 +143,16 @@ impl StatusWorker {
             last_real_time_update: None,
             zmq_channels,
             status_update_receiver,
+            metrics,
             shutdown_sender,
         };
 
         (worker, status_update_sender)
     }
 
-    // run in a spawned thread
-    pub fn run_in_a_thread(self) -> Result<std::thread::JoinHandle<()>, Error> {
-        // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
-
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
-
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
         let thread_builder = thread::Builder::new().name("loki_status_worker".to_string());
         let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
         Ok(handle)
@@ -173,6 +190,7 @@ impl StatusWorker {
     }
 
     fn handle_request(&self, request_message: RequestMessage) -> Result<(), Error> {
+        let start = Instant::now();
         let requested_api = request_message.payload.requested_api();
         let response_payload = match requested_api {
             navitia_proto::Api::Status => navitia_proto::Response {
@@ -194,6 +212,7 @@ impl StatusWorker {
             client_id: request_message.client_id,
             payload: response_payload,
         };
+        self.metrics.observe_request(requested_api, start.elapsed());
 
         self.zmq_channels
             .status_responses_sender
@@ -225,6 +244,9 @@ impl StatusWorker {
             status.status = Some("no_data".to_string());
         }
         status.last_load_status = Some(self.last_load_succeeded);
+        if let Some(reason) = &self.last_load_failure {
+            status.status = Some(format!("last_load_failed : {}", reason));
+        }
 
         status.is_connected_to_rabbitmq = Some(self.is_connected_to_rabbitmq);
 
@@ -268,12 +290,16 @@ impl StatusWorker {
 
     fn handle_status_update(&mut self, status_update: StatusUpdate) {
         match status_update {
-            StatusUpdate::BaseDataLoadFailed => {
+            StatusUpdate::BaseDataLoadFailed(reason) => {
                 self.last_load_succeeded = false;
+                self.last_load_failure = Some(reason);
             }
             StatusUpdate::BaseDataLoad(base_data_info) => {
+                self.metrics
+                    .set_data_loaded_at(&base_data_info.last_load_at);
                 self.base_data_info = Some(base_data_info);
                 self.last_load_succeeded = true;
+                self.last_load_failure = None;
             }
             StatusUpdate::RabbitMqConnected => {
                 if self.is_connected_to_rabbitmq {
@@ -288,15 +314,18 @@ impl StatusWorker {
                 self.is_connected_to_rabbitmq = false;
             }
             StatusUpdate::ChaosReload(datetime) => {
+                self.metrics.set_last_real_time_update(&datetime);
                 self.last_chaos_reload = Some(datetime);
                 self.last_real_time_update = Some(datetime);
             }
             StatusUpdate::KirinReload(datetime) => {
+                self.metrics.set_last_real_time_update(&datetime);
                 self.last_kirin_reload = Some(datetime);
                 self.last_real_time_update = Some(datetime);
                 self.is_realtime_loaded = true;
             }
             StatusUpdate::RealTimeUpdate(datetime) => {
+                self.metrics.set_last_real_time_update(&datetime);
                 self.last_real_time_update = Some(datetime);
             }
         }
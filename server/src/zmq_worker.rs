@@ -46,7 +46,10 @@ use launch::loki::{
 use prost::Message;
 use tmq;
 
-use tokio::{runtime::Builder, sync::mpsc};
+use tokio::{
+    runtime::{Builder, Handle},
+    sync::{mpsc, oneshot},
+};
 
 use futures::SinkExt;
 use std::ops::Deref;
@@ -55,14 +58,23 @@ use crate::navitia_proto;
 
 #[derive(Debug)]
 pub struct RequestMessage {
-    pub payload: navitia_proto::Request, // the actual data received from zmq
-    pub client_id: tmq::Message,         // the identifer of the client in the zmq socket
+    pub payload: navitia_proto::Request, // the actual data received from zmq or http
+    pub client_id: ClientId,
 }
 
 #[derive(Debug)]
 pub struct ResponseMessage {
     pub payload: navitia_proto::Response,
-    pub client_id: tmq::Message, // the identifer of the client in the zmq socket
+    pub client_id: ClientId,
+}
+
+/// Where the response to a request should be sent back.
+#[derive(Debug)]
+pub enum ClientId {
+    // the identifer of the client in the zmq socket
+    Zmq(tmq::Message),
+    // the http worker waits for the response on this channel
+    Http(oneshot::Sender<navitia_proto::Response>),
 }
 
 pub struct ZmqWorker {
@@ -89,6 +101,16 @@ pub struct StatusWorkerToZmqChannels {
     pub status_responses_sender: mpsc::UnboundedSender<ResponseMessage>,
 }
 
+// Requests received by the http worker are sent to the same load balancer
+// and status worker as the ones received on the zmq socket.
+// Their responses come back through the zmq worker, which forwards them
+// to the http worker.
+#[derive(Clone)]
+pub struct HttpWorkerToZmqChannels {
+    pub requests_sender: mpsc::UnboundedSender<RequestMessage>,
+    pub status_requests_sender: mpsc::UnboundedSender<RequestMessage>,
+}
+
 impl ZmqWorker {
     pub fn new(
         endpoint: &str,
@@ -121,6 +143,13 @@ impl ZmqWorker {
         (worker, load_balancer_channels, status_channels)
     }
 
+    pub fn http_channels(&self) -> HttpWorkerToZmqChannels {
+        HttpWorkerToZmqChannels {
+            requests_sender: self.requests_sender.clone(),
+            status_requests_sender: self.status_requests_sender.clone(),
+        }
+    }
+
     // run by blocking the current thread
     pub fn run_blocking(self) -> Result<(), Error> {
         // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
@@ -135,15 +164,9 @@ impl ZmqWorker {
         Ok(())
     }
 
-    // run in a spawned thread
-    pub fn run_in_a_thread(self) -> Result<std::thread::JoinHandle<()>, Error> {
-        // copied from https://tokio.rs/tokio/topics/bridging#sending-messages
-
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build()
-            .context("Failed to build tokio runtime.")?;
-
+    // run in a spawned thread, on the given runtime
+    pub fn run_in_a_thread(self, runtime: &Handle) -> Result<std::thread::JoinHandle<()>, Error> {
+        let runtime = runtime.clone();
         let thread_builder = thread::Builder::new().name("loki_zmq_worker".to_string());
         let handle = thread_builder.spawn(move || runtime.block_on(self.run()))?;
         Ok(handle)
@@ -190,7 +213,7 @@ impl ZmqWorker {
                         format_err!("ZmqWorker : channel to receive responses is closed.")
                     )?;
                     trace!("ZmqWorker received a response.");
-                    send_response_to_zmq(& mut zmq_socket, response).await?;
+                    send_response(& mut zmq_socket, response).await?;
 
                 }
                 has_status_response = self.status_responses_receiver.recv() => {
@@ -198,7 +221,7 @@ impl ZmqWorker {
                         format_err!("ZmqWorker : channel to receive status responses is closed.")
                     )?;
                     trace!("ZmqWorker received a status response.");
-                    send_response_to_zmq(& mut zmq_socket, response).await?;
+                    send_response(& mut zmq_socket, response).await?;
                 }
                 // receive requests from the zmq socket, and send them to the main thread for dispatch to workers
                 has_zmq_message = zmq_socket.next() => {
@@ -229,11 +252,31 @@ impl ZmqWorker {
     }
 }
 
-async fn send_response_to_zmq(
+async fn send_response(
     zmq_socket: &mut tmq::router::Router,
     response: ResponseMessage,
 ) -> Result<(), Error> {
-    let response_bytes = response.payload.encode_to_vec();
+    match response.client_id {
+        ClientId::Zmq(client_id_message) => {
+            send_response_to_zmq(zmq_socket, client_id_message, response.payload).await
+        }
+        ClientId::Http(response_sender) => {
+            // the http client may have gone away in the meantime,
+            // so there is nobody left to answer to
+            if response_sender.send(response.payload).is_err() {
+                warn!("ZmqWorker could not forward a response to the http worker, the http client is gone.");
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn send_response_to_zmq(
+    zmq_socket: &mut tmq::router::Router,
+    client_id_message: tmq::Message,
+    payload: navitia_proto::Response,
+) -> Result<(), Error> {
+    let response_bytes = payload.encode_to_vec();
     let payload_message = tmq::Message::from(response_bytes);
 
     // The Router socket requires sending 3 parts messages as responses, where :
@@ -241,7 +284,6 @@ async fn send_response_to_zmq(
     //  - the second part is empty
     //  - the third part is the actual message
     // see https://zguide.zeromq.org/docs/chapter3/#The-Extended-Reply-Envelope
-    let client_id_message = response.client_id;
     let empty_message = tmq::Message::new();
     let iter = std::iter::once(client_id_message)
         .chain(std::iter::once(empty_message))
@@ -290,7 +332,7 @@ async fn handle_incoming_request(
             let requested_api = proto_request.requested_api();
 
             let request_message = RequestMessage {
-                client_id: client_id_message,
+                client_id: ClientId::Zmq(client_id_message),
                 payload: proto_request,
             };
             use navitia_proto::Api;
@@ -320,11 +362,7 @@ async fn handle_incoming_request(
 
             // let's send back a response to our zmq client that we received an invalid protobuf
             let response_proto = make_error_response(&format_err!("{}", err_str));
-            let response_message = ResponseMessage {
-                client_id: client_id_message,
-                payload: response_proto,
-            };
-            send_response_to_zmq(zmq_socket, response_message).await
+            send_response_to_zmq(zmq_socket, client_id_message, response_proto).await
         }
     }
 }
@@ -339,7 +377,7 @@ fn make_error_response(error: &Error) -> navitia_proto::Response {
     proto_response
 }
 
-fn is_deadline_expired(proto_request: &navitia_proto::Request) -> bool {
+pub(crate) fn is_deadline_expired(proto_request: &navitia_proto::Request) -> bool {
     if let Some(deadline_str) = &proto_request.deadline {
         let datetime_result = NaiveDateTime::parse_from_str(deadline_str, "%Y%m%dT%H%M%S,%f");
         match datetime_result {
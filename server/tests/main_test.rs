@@ -40,7 +40,7 @@ use loki_server::{
     chaos_proto,
     master_worker::MasterWorker,
     navitia_proto,
-    server_config::{self, ChaosParams, ServerConfig},
+    server_config::{self, ChaosDatabaseParams, ChaosParams, ServerConfig},
 };
 use prost::Message;
 use protobuf::Message as ProtobufMessage;
@@ -85,11 +85,13 @@ async fn run() {
     let container_rabbitmq_id = start_rabbitmq_docker().await;
 
     let mut config = ServerConfig::new(input_data_path, zmq_endpoint, instance_name);
-    let chaos_params = ChaosParams {
+    let chaos_params = ChaosDatabaseParams {
         database: chaos_endpoint.to_string(),
         batch_size: server_config::default_batch_size(),
+        // so that the database updates made by chaos_test::sync_database_test are read quickly
+        sync_interval: Some(PositiveDuration::from_hms(0, 0, 1)),
     };
-    config.chaos = Some(chaos_params.clone());
+    config.chaos = Some(ChaosParams::Database(chaos_params.clone()));
     config.rabbitmq.endpoint = rabbitmq_endpoint.to_string();
     config.rabbitmq.reload_kirin_timeout = PositiveDuration::from_hms(0, 0, 1);
     config.rabbitmq.connect_retry_interval = PositiveDuration::from_hms(0, 0, 2);
@@ -138,6 +140,9 @@ async fn run() {
     subtests::schedule_test::simple_next_departure_test(&config).await;
     subtests::schedule_test::simple_next_arrival_test(&config).await;
 
+    // modifies the chaos database, so it comes last
+    subtests::chaos_test::sync_database_test(&config).await;
+
     info!("Everything went Ok ! Now stopping.");
 
     stop_docker(&container_postgres_id).await;
@@ -28,7 +28,10 @@
 // www.navitia.io
 
 pub use loki_server;
-use loki_server::{chaos_proto, navitia_proto, server_config::ServerConfig};
+use loki_server::{
+    chaos_proto, navitia_proto,
+    server_config::{ChaosParams, ServerConfig},
+};
 
 use chaos_proto::{chaos::exts, gtfs_realtime as gtfs_proto};
 use launch::loki::{
@@ -1017,6 +1020,108 @@ pub async fn delete_stop_area_test(config: &ServerConfig) {
     }
 }
 
+// Update the disruption of the chaos database, and check that the incremental sync
+// cancels its previous version and applies its new version.
+// Then archive it, and check that the sync only cancels it.
+pub async fn sync_database_test(config: &ServerConfig) {
+    let database = match &config.chaos {
+        Some(ChaosParams::Database(database_params)) => database_params.database.as_str(),
+        _ => panic!("The chaos database is not configured."),
+    };
+    let datetime =
+        NaiveDateTime::parse_from_str("2021-01-01 18:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let base_request =
+        crate::make_journeys_request("stop_point:pontoise", "stop_point:dourdan", datetime);
+    let realtime_request = {
+        let mut request = base_request.clone();
+        request
+            .journeys
+            .as_mut()
+            .unwrap()
+            .set_realtime_level(navitia_proto::RtLevel::Realtime);
+        request
+    };
+
+    // change the message of the disruption
+    let update_datetime = Utc::now().naive_utc();
+    execute_on_chaos_database(
+        database,
+        "UPDATE public.message SET text = 'Updated Message', updated_at = now()
+            WHERE id = '8498f3a6-5682-11eb-b8c6-005056a40962';
+        UPDATE public.disruption SET updated_at = now()
+            WHERE id = 'dddddddd-dddd-dddd-dddd-dddddddddddd';",
+    );
+    crate::wait_until_realtime_updated_after(&config.requests_socket, &update_datetime).await;
+
+    // the previous version of the disruption was cancelled,
+    // so we get only the impact of the new version
+    {
+        let journeys_response = crate::send_request_and_wait_for_response(
+            &config.requests_socket,
+            base_request.clone(),
+        )
+        .await;
+        assert_eq!(
+            first_section_vj_name(&journeys_response.journeys[0]),
+            "vehicle_journey:rer_c_soir"
+        );
+        assert_eq!(journeys_response.impacts.len(), 1);
+        let impact = &journeys_response.impacts[0];
+        assert_eq!(
+            impact.disruption_uri.as_ref().unwrap(),
+            "dddddddd-dddd-dddd-dddd-dddddddddddd"
+        );
+        assert_eq!(impact.messages.len(), 1);
+        assert_eq!(impact.messages[0].text.as_ref().unwrap(), "Updated Message");
+    }
+    // and the new version still deletes the trip
+    {
+        let journeys_response = crate::send_request_and_wait_for_response(
+            &config.requests_socket,
+            realtime_request.clone(),
+        )
+        .await;
+        assert_eq!(journeys_response.journeys.len(), 0);
+    }
+
+    // archive the disruption
+    let archive_datetime = Utc::now().naive_utc();
+    execute_on_chaos_database(
+        database,
+        "UPDATE public.disruption SET status = 'archived', updated_at = now()
+            WHERE id = 'dddddddd-dddd-dddd-dddd-dddddddddddd';",
+    );
+    crate::wait_until_realtime_updated_after(&config.requests_socket, &archive_datetime).await;
+
+    // the disruption was cancelled and not applied again
+    {
+        let journeys_response = crate::send_request_and_wait_for_response(
+            &config.requests_socket,
+            base_request.clone(),
+        )
+        .await;
+        assert_eq!(journeys_response.impacts.len(), 0);
+    }
+    {
+        let journeys_response = crate::send_request_and_wait_for_response(
+            &config.requests_socket,
+            realtime_request.clone(),
+        )
+        .await;
+        assert_eq!(journeys_response.journeys.len(), 1);
+        assert_eq!(
+            first_section_vj_name(&journeys_response.journeys[0]),
+            "vehicle_journey:rer_c_soir"
+        );
+    }
+}
+
+fn execute_on_chaos_database(database: &str, sql: &str) {
+    use diesel::{connection::SimpleConnection, prelude::*};
+    let connection = PgConnection::establish(database).unwrap();
+    connection.batch_execute(sql).unwrap();
+}
+
 fn create_no_service_disruption(
     pt_object: &PtObject,
     application_period: &TimePeriod,
@@ -110,6 +110,10 @@ pub struct Filters<'a> {
 }
 
 impl<'a> Filters<'a> {
+    pub fn must_be_wheelchair_accessible(&self) -> bool {
+        self.must_be_wheelchair_accessible
+    }
+
     pub fn is_vehicle_journey_valid(&self, idx: &VehicleJourneyIdx, model: &ModelRefs<'_>) -> bool {
         // if *one* forbidden filter applies, then the vehicle_journey is invalid
         for forbid_filter in self.forbidden_vehicles.iter() {
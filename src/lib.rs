@@ -40,10 +40,14 @@ mod engine;
 pub mod filters;
 pub mod geometry;
 pub mod loads_data;
+pub mod memory_usage;
 pub mod models;
+mod parallel;
 pub mod places_nearby;
 pub mod request;
 pub mod schedule;
+pub mod shared_collections;
+pub mod snapshot;
 pub mod time;
 pub mod timetables;
 pub mod transit_data;
@@ -35,8 +35,9 @@
 // www.navitia.io
 
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::{error::Error, fmt::Display, io};
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Load {
     Unknown,
 }
@@ -117,6 +118,10 @@ impl LoadsData {
         LoadsData {}
     }
 
+    pub fn heap_bytes(&self) -> usize {
+        0
+    }
+
     pub fn new<R: io::Read>(
         _reader: R,
         _collections: &base_model::Collections,
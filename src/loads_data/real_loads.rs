@@ -35,18 +35,22 @@
 // www.navitia.io
 
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, error::Error, fmt::Display, io};
 use tracing::{debug, trace};
 
 type StopSequence = u32;
 type Occupancy = u8;
 
-use crate::models::{
-    base_model::{self, BaseModel, BaseVehicleJourneyIdx},
-    VehicleJourneyIdx,
+use crate::{
+    memory_usage::{btree_map_bytes, vec_bytes},
+    models::{
+        base_model::{self, BaseModel, BaseVehicleJourneyIdx},
+        VehicleJourneyIdx,
+    },
 };
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Load {
     Low,
     Medium,
@@ -246,6 +250,25 @@ impl LoadsData {
         }
     }
 
+    /// Memory allocated by the loads, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        let vehicle_journeys_bytes: usize = self
+            .per_vehicle_journey
+            .values()
+            .map(|vehicle_journey_loads| {
+                let trips_bytes: usize = vehicle_journey_loads
+                    .per_date
+                    .values()
+                    .map(|trip_loads| vec_bytes(&trip_loads.per_stop))
+                    .sum();
+                btree_map_bytes(&vehicle_journey_loads.stop_sequence_to_idx)
+                    + btree_map_bytes(&vehicle_journey_loads.per_date)
+                    + trips_bytes
+            })
+            .sum();
+        btree_map_bytes(&self.per_vehicle_journey) + vehicle_journeys_bytes
+    }
+
     pub fn new<R: io::Read>(
         csv_occupancys_reader: R,
         model: &base_model::Model,
@@ -0,0 +1,163 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use crate::shared_collections::{SharedMap, SharedVec};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{self, Display},
+    hash::Hash,
+    mem::size_of,
+    sync::Arc,
+};
+
+/// Estimation of the memory used by a data structure, with the details of its components.
+///
+/// Only the memory allocated on the heap is counted. Some of it may be
+/// shared with a clone of the data structure (for example the timetables shared
+/// between the base data and the data modified by real time updates).
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryUsage {
+    pub name: String,
+    /// bytes used by the data structure, including those of its components
+    pub bytes: usize,
+    pub components: Vec<MemoryUsage>,
+}
+
+impl MemoryUsage {
+    pub fn new(name: &str, bytes: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            bytes,
+            components: Vec::new(),
+        }
+    }
+
+    pub fn with_components(name: &str, components: Vec<MemoryUsage>) -> Self {
+        Self {
+            name: name.to_string(),
+            bytes: components.iter().map(|component| component.bytes).sum(),
+            components,
+        }
+    }
+
+    /// Returns the component with `name`, if any.
+    pub fn component(&self, name: &str) -> Option<&MemoryUsage> {
+        self.components
+            .iter()
+            .find(|component| component.name == name)
+    }
+
+    fn fmt_with_indent(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{:indent$}{} : {}",
+            "",
+            self.name,
+            HumanReadableBytes(self.bytes),
+            indent = indent
+        )?;
+        for component in &self.components {
+            component.fmt_with_indent(f, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_indent(f, 0)
+    }
+}
+
+struct HumanReadableBytes(usize);
+
+impl Display for HumanReadableBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64 / 1024.;
+        let mut unit = 0;
+        while value >= 1024. && unit + 1 < UNITS.len() {
+            value /= 1024.;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", value, UNITS[unit])
+    }
+}
+
+// The functions below estimate the memory allocated by a collection for its elements.
+// The memory allocated by the elements themselves has to be added by the caller.
+
+pub(crate) fn vec_bytes<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+pub(crate) fn nested_vec_bytes<T>(vecs: &Vec<Vec<T>>) -> usize {
+    vec_bytes(vecs) + vecs.iter().map(vec_bytes).sum::<usize>()
+}
+
+// hashbrown stores the entries in a single allocation, with one control byte per entry
+pub(crate) fn hash_map_bytes<K, V>(map: &HashMap<K, V>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
+
+pub(crate) fn hash_set_bytes<T>(set: &HashSet<T>) -> usize {
+    set.capacity() * (size_of::<T>() + 1)
+}
+
+pub(crate) fn shared_vec_bytes<T: Clone>(vec: &SharedVec<T>) -> usize {
+    vec.chunks()
+        .map(|chunk| size_of::<Arc<Vec<T>>>() + vec_bytes(chunk))
+        .sum()
+}
+
+pub(crate) fn shared_map_bytes<K, V>(map: &SharedMap<K, V>) -> usize
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    map.shards()
+        .map(|shard| size_of::<Arc<HashMap<K, V>>>() + hash_map_bytes(shard))
+        .sum()
+}
+
+// a BTreeSet node holds up to 11 elements, we count them as if the nodes were full
+pub(crate) fn btree_set_bytes<T>(set: &BTreeSet<T>) -> usize {
+    set.len() * size_of::<T>()
+}
@@ -36,6 +36,7 @@
 
 pub mod base_model;
 pub mod model_refs;
+pub mod pathways;
 pub mod real_time_disruption;
 pub mod real_time_model;
 
@@ -37,25 +37,28 @@
 use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
+    mem::size_of,
     ops::Index,
 };
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 use transit_model::objects::{
     Availability, CommercialMode, Equipment, Line, Network, Pathway, PhysicalMode, Properties,
     Route, StopArea, StopType, VehicleJourney,
 };
 
-use typed_index_collection::Idx;
+use typed_index_collection::{CollectionWithId, Id, Idx};
 
 use crate::{
+    memory_usage::{btree_set_bytes, hash_map_bytes, hash_set_bytes, vec_bytes, MemoryUsage},
     time::{calendar, SecondsSinceTimezonedDayStart},
     timetables::FlowDirection,
     LoadsData, PositiveDuration,
 };
 
 use super::{
-    real_time_disruption::time_periods::TimePeriod, Contributor, Coord, Rgb, StopPointIdx,
-    StopTime, StopTimeIdx,
+    pathways::{compute_pathway_durations, PathwayDurations, StopPointsPathwayDurations},
+    real_time_disruption::time_periods::TimePeriod,
+    Contributor, Coord, Rgb, StopPointIdx, StopTime, StopTimeIdx,
 };
 
 pub const PREFIX_ID_NETWORK: &str = "network:";
@@ -91,6 +94,7 @@ pub struct BaseModel {
     validity_period: (NaiveDate, NaiveDate),
     default_transfer_duration: PositiveDuration,
     stop_point_to_pathways: StopPointToPathWays,
+    pathway_durations: StopPointsPathwayDurations,
 }
 
 pub type BaseVehicleJourneyIdx = Idx<transit_model::objects::VehicleJourney>;
@@ -152,6 +156,7 @@ impl BaseModel {
             validity_period: (day, day),
             default_transfer_duration: PositiveDuration::zero(),
             stop_point_to_pathways: StopPointToPathWays::new(),
+            pathway_durations: StopPointsPathwayDurations::new(),
         }
     }
 
@@ -178,24 +183,30 @@ impl BaseModel {
         let mut association = StopPointToPathWays::new();
 
         for (pathway_idx, pathway) in &model.pathways {
-            let (stop_point_id, access_point_id) =
-                if (&pathway.from_stop_type, &pathway.to_stop_type)
-                    == (&StopType::Point, &StopType::StopEntrance)
-                {
-                    (&pathway.from_stop_id, &pathway.to_stop_id)
-                } else if (&pathway.to_stop_type, &pathway.from_stop_type)
-                    == (&StopType::Point, &StopType::StopEntrance)
-                {
-                    (&pathway.to_stop_id, &pathway.from_stop_id)
-                } else {
-                    warn!(
-                        "Pathway {} has incorrect stop_type from:{} to:{}",
-                        &pathway_idx.get(),
+            let (stop_point_id, access_point_id) = if (
+                &pathway.from_stop_type,
+                &pathway.to_stop_type,
+            ) == (
+                &StopType::Point,
+                &StopType::StopEntrance,
+            ) {
+                (&pathway.from_stop_id, &pathway.to_stop_id)
+            } else if (&pathway.to_stop_type, &pathway.from_stop_type)
+                == (&StopType::Point, &StopType::StopEntrance)
+            {
+                (&pathway.to_stop_id, &pathway.from_stop_id)
+            } else {
+                // a pathway inside the station, only used for the durations of transfers
+                debug!(
+                        "Pathway {} does not link a stop point to an entrance, from:{} ({:?}) to:{} ({:?})",
+                        pathway.id,
                         pathway.from_stop_id,
-                        pathway.to_stop_id
+                        pathway.from_stop_type,
+                        pathway.to_stop_id,
+                        pathway.to_stop_type
                     );
-                    continue;
-                };
+                continue;
+            };
             match Self::insert_pathway_into_association(
                 model,
                 stop_point_id,
@@ -229,6 +240,23 @@ impl BaseModel {
         model: transit_model::model::Model,
         loads_data: LoadsData,
         default_transfer_duration: PositiveDuration,
+    ) -> Result<Self, BadModel> {
+        let pathway_durations = compute_pathway_durations(&model);
+        Self::with_pathway_durations(
+            model,
+            loads_data,
+            default_transfer_duration,
+            pathway_durations,
+        )
+    }
+
+    /// Same as [`BaseModel::new()`], with `pathway_durations` previously computed
+    /// on the same model.
+    pub fn with_pathway_durations(
+        model: transit_model::model::Model,
+        loads_data: LoadsData,
+        default_transfer_duration: PositiveDuration,
+        pathway_durations: StopPointsPathwayDurations,
     ) -> Result<Self, BadModel> {
         let validity_period = model
             .calculate_validity_period()
@@ -246,13 +274,22 @@ impl BaseModel {
             validity_period,
             default_transfer_duration,
             stop_point_to_pathways,
+            pathway_durations,
         })
     }
 
+    pub fn pathway_durations(&self) -> &StopPointsPathwayDurations {
+        &self.pathway_durations
+    }
+
     pub fn loads_data(&self) -> &LoadsData {
         &self.loads_data
     }
 
+    pub fn transit_model(&self) -> &Model {
+        &self.model
+    }
+
     pub fn validity_period(&self) -> (NaiveDate, NaiveDate) {
         self.validity_period
     }
@@ -263,6 +300,83 @@ impl BaseModel {
         TimePeriod::new(start_datetime, end_datetime).unwrap() // unwrap is safe here, because we check in new()
                                                                // that validity_period.0 <= validity_period.1
     }
+
+    /// Memory used by the main collections of the model.
+    /// Strings are counted only for the identifiers of the objects.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let model = &self.model;
+        let stop_times_bytes: usize = model
+            .vehicle_journeys
+            .values()
+            .map(|vehicle_journey| vec_bytes(&vehicle_journey.stop_times))
+            .sum();
+        let dates_bytes: usize = model
+            .calendars
+            .values()
+            .map(|calendar| btree_set_bytes(&calendar.dates))
+            .sum();
+        let stop_times_properties_bytes: usize = [
+            &model.stop_time_headsigns,
+            &model.stop_time_ids,
+            &model.stop_time_comments,
+        ]
+        .into_iter()
+        .map(|properties| {
+            let strings_bytes: usize = properties
+                .iter()
+                .map(|((vehicle_journey_id, _), value)| vehicle_journey_id.len() + value.len())
+                .sum();
+            hash_map_bytes(properties) + strings_bytes
+        })
+        .sum();
+        let pathways_bytes: usize = self
+            .stop_point_to_pathways
+            .values()
+            .map(hash_set_bytes)
+            .sum();
+
+        MemoryUsage::with_components(
+            "base_model",
+            vec![
+                MemoryUsage::new(
+                    "vehicle_journeys",
+                    collection_with_id_bytes(&model.vehicle_journeys),
+                ),
+                MemoryUsage::new("stop_times", stop_times_bytes),
+                MemoryUsage::new("stop_times_properties", stop_times_properties_bytes),
+                MemoryUsage::new("stop_points", collection_with_id_bytes(&model.stop_points)),
+                MemoryUsage::new("stop_areas", collection_with_id_bytes(&model.stop_areas)),
+                MemoryUsage::new(
+                    "stop_locations",
+                    collection_with_id_bytes(&model.stop_locations),
+                ),
+                MemoryUsage::new("routes", collection_with_id_bytes(&model.routes)),
+                MemoryUsage::new("lines", collection_with_id_bytes(&model.lines)),
+                MemoryUsage::new(
+                    "calendars",
+                    collection_with_id_bytes(&model.calendars) + dates_bytes,
+                ),
+                MemoryUsage::new(
+                    "transfers",
+                    model.transfers.len() * size_of::<transit_model::objects::Transfer>(),
+                ),
+                MemoryUsage::new(
+                    "pathways",
+                    collection_with_id_bytes(&model.pathways)
+                        + hash_map_bytes(&self.stop_point_to_pathways)
+                        + pathways_bytes
+                        + hash_map_bytes(&self.pathway_durations),
+                ),
+                MemoryUsage::new("loads_data", self.loads_data.heap_bytes()),
+            ],
+        )
+    }
+}
+
+// the objects, their identifiers, and the map from identifiers to objects
+fn collection_with_id_bytes<T: Id<T>>(collection: &CollectionWithId<T>) -> usize {
+    let ids_bytes: usize = collection.values().map(|object| object.id().len()).sum();
+    collection.len() * (size_of::<T>() + size_of::<(String, Idx<T>)>() + 1) + 2 * ids_bytes
 }
 
 pub struct PathwayByIter<'model> {
@@ -834,18 +948,55 @@ impl BaseModel {
         self.model.transfers[transfer_idx].to_stop_id.as_str()
     }
 
+    // durations of the walk through pathways between the stop points of the transfer, if any
+    fn transfer_pathway_durations(
+        &self,
+        transfer_idx: BaseTransferIdx,
+    ) -> Option<&PathwayDurations> {
+        let from_stop = self.from_stop(transfer_idx)?;
+        let to_stop = self.to_stop(transfer_idx)?;
+        self.pathway_durations.get(&(from_stop, to_stop))
+    }
+
+    /// The duration of the transfer given in the input data.
+    /// When the input data gives none, the duration of the walk through pathways,
+    /// or `default_transfer_duration` when the stop points are not linked by pathways.
     pub fn transfer_duration(&self, transfer_idx: BaseTransferIdx) -> PositiveDuration {
-        let seconds = self.model.transfers[transfer_idx]
-            .real_min_transfer_time
-            .unwrap_or(self.default_transfer_duration.seconds);
-        PositiveDuration { seconds }
+        let transfer = &self.model.transfers[transfer_idx];
+        if let Some(seconds) = transfer.real_min_transfer_time {
+            return PositiveDuration { seconds };
+        }
+        self.transfer_pathway_durations(transfer_idx)
+            .map(|pathway_durations| pathway_durations.duration)
+            .unwrap_or(self.default_transfer_duration)
     }
 
     pub fn transfer_walking_duration(&self, transfer_idx: BaseTransferIdx) -> PositiveDuration {
-        let seconds = self.model.transfers[transfer_idx]
-            .min_transfer_time
-            .unwrap_or(0u32);
-        PositiveDuration { seconds }
+        let transfer = &self.model.transfers[transfer_idx];
+        if let Some(seconds) = transfer.min_transfer_time {
+            return PositiveDuration { seconds };
+        }
+        self.transfer_pathway_durations(transfer_idx)
+            .map(|pathway_durations| pathway_durations.duration)
+            .unwrap_or_else(PositiveDuration::zero)
+    }
+
+    /// The duration of the transfer for a wheelchair user.
+    /// When the stop points are linked by pathways, it is at least the duration
+    /// of the walk through the pathways that have neither stairs nor escalators,
+    /// and None when there is no such walk.
+    /// Otherwise, it is the `transfer_duration()`.
+    pub fn transfer_wheelchair_duration(
+        &self,
+        transfer_idx: BaseTransferIdx,
+    ) -> Option<PositiveDuration> {
+        let duration = self.transfer_duration(transfer_idx);
+        match self.transfer_pathway_durations(transfer_idx) {
+            Some(pathway_durations) => pathway_durations
+                .wheelchair_duration
+                .map(|wheelchair_duration| wheelchair_duration.max(duration)),
+            None => Some(duration),
+        }
     }
 
     pub fn transfer_property(
@@ -913,6 +1064,10 @@ impl BaseModel {
         self.model.lines.get(id)
     }
 
+    pub fn lines(&self) -> impl Iterator<Item = &Line> {
+        self.model.lines.iter().map(|(_, line)| line)
+    }
+
     pub fn route(&self, id: &str) -> Option<&Route> {
         self.model.routes.get(id)
     }
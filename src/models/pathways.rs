@@ -0,0 +1,276 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! Durations of the walks between the stop points of a station, through its pathways.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    convert::TryFrom,
+};
+use tracing::{debug, info};
+use transit_model::objects::{Pathway, PathwayMode, StopType};
+
+use crate::PositiveDuration;
+
+use super::base_model::{BaseStopPointIdx, Model};
+
+/// Walking speed, in meters per second, along a pathway
+/// that provides a length but no traversal time.
+pub const PATHWAY_WALKING_SPEED: f64 = 1.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathwayDurations {
+    /// duration of the shortest walk through pathways
+    pub duration: PositiveDuration,
+    /// duration of the shortest walk through pathways that uses neither stairs nor escalators,
+    /// or None if there is no such walk
+    pub wheelchair_duration: Option<PositiveDuration>,
+}
+
+pub type StopPointsPathwayDurations =
+    HashMap<(BaseStopPointIdx, BaseStopPointIdx), PathwayDurations>;
+
+struct Edge {
+    to: usize,
+    seconds: u32,
+    is_wheelchair_accessible: bool,
+}
+
+// nodes are the stop points and stop locations linked by pathways
+#[derive(Default)]
+struct PathwayGraph<'model> {
+    node_ids: Vec<&'model str>,
+    node_types: Vec<&'model StopType>,
+    node_idx: HashMap<&'model str, usize>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl<'model> PathwayGraph<'model> {
+    fn node(&mut self, id: &'model str, stop_type: &'model StopType) -> usize {
+        if let Some(idx) = self.node_idx.get(id) {
+            return *idx;
+        }
+        let idx = self.node_ids.len();
+        self.node_ids.push(id);
+        self.node_types.push(stop_type);
+        self.node_idx.insert(id, idx);
+        self.edges.push(Vec::new());
+        idx
+    }
+
+    fn add_pathway(&mut self, pathway: &'model Pathway, seconds: u32) {
+        let from = self.node(&pathway.from_stop_id, &pathway.from_stop_type);
+        let to = self.node(&pathway.to_stop_id, &pathway.to_stop_type);
+        let is_wheelchair_accessible = is_wheelchair_accessible(pathway);
+        self.edges[from].push(Edge {
+            to,
+            seconds,
+            is_wheelchair_accessible,
+        });
+        if pathway.is_bidirectional {
+            self.edges[to].push(Edge {
+                to: from,
+                seconds,
+                is_wheelchair_accessible,
+            });
+        }
+    }
+
+    // The connected components of the graph, regardless of the direction of the pathways.
+    // Each one holds the nodes of a station.
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut neighbours = vec![Vec::new(); self.node_ids.len()];
+        for (from, edges) in self.edges.iter().enumerate() {
+            for edge in edges {
+                neighbours[from].push(edge.to);
+                neighbours[edge.to].push(from);
+            }
+        }
+        let mut is_visited = vec![false; self.node_ids.len()];
+        let mut components = Vec::new();
+        for start in 0..self.node_ids.len() {
+            if is_visited[start] {
+                continue;
+            }
+            is_visited[start] = true;
+            let mut component = vec![start];
+            let mut next = 0;
+            while let Some(node) = component.get(next).copied() {
+                next += 1;
+                for neighbour in &neighbours[node] {
+                    if !is_visited[*neighbour] {
+                        is_visited[*neighbour] = true;
+                        component.push(*neighbour);
+                    }
+                }
+            }
+            components.push(component);
+        }
+        components
+    }
+
+    // Duration, in seconds, of the shortest walk from `source` to each node of its component,
+    // indexed by `local_idx`, u32::MAX for the nodes that cannot be reached.
+    // Also returns the nodes reached.
+    fn shortest_durations(
+        &self,
+        source: usize,
+        wheelchair: bool,
+        local_idx: &[usize],
+        component_len: usize,
+    ) -> (Vec<u32>, Vec<usize>) {
+        let mut durations = vec![u32::MAX; component_len];
+        let mut reached = Vec::new();
+        let mut queue = BinaryHeap::new();
+        durations[local_idx[source]] = 0;
+        queue.push(Reverse((0u32, source)));
+        while let Some(Reverse((seconds, node))) = queue.pop() {
+            if seconds > durations[local_idx[node]] {
+                continue;
+            }
+            reached.push(node);
+            for edge in &self.edges[node] {
+                if wheelchair && !edge.is_wheelchair_accessible {
+                    continue;
+                }
+                let to_seconds = seconds.saturating_add(edge.seconds);
+                if to_seconds < durations[local_idx[edge.to]] {
+                    durations[local_idx[edge.to]] = to_seconds;
+                    queue.push(Reverse((to_seconds, edge.to)));
+                }
+            }
+        }
+        (durations, reached)
+    }
+}
+
+fn is_wheelchair_accessible(pathway: &Pathway) -> bool {
+    let has_stairs = matches!(pathway.stair_count, Some(stair_count) if stair_count != 0);
+    !has_stairs
+        && !matches!(
+            pathway.pathway_mode,
+            PathwayMode::Stairs | PathwayMode::Escalator
+        )
+}
+
+// The traversal time of the pathway if given, and otherwise the time to walk its length.
+// None when the pathway provides neither.
+fn pathway_seconds(pathway: &Pathway) -> Option<u32> {
+    if let Some(traversal_time) = pathway.traversal_time {
+        return Some(traversal_time);
+    }
+    let length = f64::try_from(pathway.length?).ok()?;
+    Some((length / PATHWAY_WALKING_SPEED).ceil() as u32)
+}
+
+/// Computes, for each pair of distinct stop points linked by pathways,
+/// the durations of the shortest walk between them.
+pub fn compute_pathway_durations(model: &Model) -> StopPointsPathwayDurations {
+    let mut graph = PathwayGraph::default();
+    let mut nb_of_pathways_without_duration = 0;
+    for pathway in model.pathways.values() {
+        match pathway_seconds(pathway) {
+            Some(seconds) => graph.add_pathway(pathway, seconds),
+            None => {
+                debug!(
+                    "Pathway {} has neither a traversal time nor a length. I ignore it.",
+                    pathway.id
+                );
+                nb_of_pathways_without_duration += 1;
+            }
+        }
+    }
+
+    // the stop point of each node, if any
+    let stop_points: Vec<Option<BaseStopPointIdx>> = graph
+        .node_ids
+        .iter()
+        .zip(graph.node_types.iter())
+        .map(|(id, stop_type)| match stop_type {
+            StopType::Point => model.stop_points.get_idx(id),
+            _ => None,
+        })
+        .collect();
+
+    let mut result = StopPointsPathwayDurations::new();
+    // index of each node in its component
+    let mut local_idx = vec![0; graph.node_ids.len()];
+    for component in graph.components() {
+        let nb_of_stop_points = component
+            .iter()
+            .filter(|node| stop_points[**node].is_some())
+            .count();
+        if nb_of_stop_points < 2 {
+            continue;
+        }
+        for (idx, node) in component.iter().enumerate() {
+            local_idx[*node] = idx;
+        }
+        for from_node in &component {
+            let from_idx = match stop_points[*from_node] {
+                Some(from_idx) => from_idx,
+                None => continue,
+            };
+            let (durations, reached) =
+                graph.shortest_durations(*from_node, false, &local_idx, component.len());
+            let (wheelchair_durations, _) =
+                graph.shortest_durations(*from_node, true, &local_idx, component.len());
+            for to_node in reached {
+                let to_idx = match stop_points[to_node] {
+                    Some(to_idx) if to_node != *from_node => to_idx,
+                    _ => continue,
+                };
+                let seconds = durations[local_idx[to_node]];
+                let wheelchair_seconds = wheelchair_durations[local_idx[to_node]];
+                let pathway_durations = PathwayDurations {
+                    duration: PositiveDuration::from_hms(0, 0, seconds),
+                    wheelchair_duration: (wheelchair_seconds != u32::MAX)
+                        .then(|| PositiveDuration::from_hms(0, 0, wheelchair_seconds)),
+                };
+                result.insert((from_idx, to_idx), pathway_durations);
+            }
+        }
+    }
+    info!(
+        "Pathways give the walking durations between {} pairs of stop points. \
+        {} pathways without duration are ignored.",
+        result.len(),
+        nb_of_pathways_without_duration
+    );
+    result
+}
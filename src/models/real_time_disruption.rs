@@ -39,12 +39,15 @@ pub mod chaos_disruption;
 pub mod kirin_disruption;
 pub mod time_periods;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Debug, Clone)]
 pub struct VehicleJourneyId {
     pub id: String,
 }
 
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+#[derive(Deserialize, Serialize, Eq, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
 pub enum Effect {
     // DO NOT change the order of effects !!
     // Effects are ordered from the least to the worst impact
@@ -46,6 +46,7 @@ use crate::{
 
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
 use serde::Deserialize;
+use std::sync::Arc;
 use tracing::{debug, error, warn};
 
 use super::{
@@ -54,31 +55,41 @@ use super::{
     Effect, VehicleJourneyId,
 };
 
-#[derive(Debug, Clone)]
+// Chaos disruptions can be deserialized (e.g. from json)
+// in addition to being read from chaos protobuf messages
+#[derive(Deserialize, Debug, Clone)]
 pub struct ChaosDisruption {
     pub id: String,
     pub reference: Option<String>,
     pub contributor: Option<String>,
     pub publication_period: TimePeriod,
+    #[serde(default)]
     pub cause: Cause,
+    #[serde(default)]
     pub tags: Vec<Tag>,
+    #[serde(default)]
     pub properties: Vec<DisruptionProperty>,
     pub impacts: Vec<ChaosImpact>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ChaosImpact {
     pub id: String,
     pub updated_at: NaiveDateTime,
     pub application_periods: Vec<TimePeriod>,
+    #[serde(default)]
     pub application_patterns: Vec<ApplicationPattern>,
     pub severity: Severity,
+    #[serde(default)]
     pub messages: Vec<Message>,
+    #[serde(default)]
     pub impacted_pt_objects: Vec<Impacted>,
+    #[serde(default)]
     pub informed_pt_objects: Vec<Informed>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum Impacted {
     NetworkDeleted(NetworkId),
     LineDeleted(LineId),
@@ -91,7 +102,8 @@ pub enum Impacted {
     BaseTripDeleted(VehicleJourneyId),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum Informed {
     Network(NetworkId),
     Line(LineId),
@@ -101,45 +113,48 @@ pub enum Informed {
     StopPoint(StopPointId),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct NetworkId {
     pub id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct LineId {
     pub id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct RouteId {
     pub id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct StopPointId {
     pub id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct StopAreaId {
     pub id: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct LineSection {
     pub line: LineId,
     pub start: StopAreaId,
     pub end: StopAreaId,
+    #[serde(default)]
     pub routes: Vec<RouteId>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct RailSection {
     pub line: LineId,
     pub start: StopAreaId,
     pub end: StopAreaId,
+    #[serde(default)]
     pub routes: Vec<RouteId>,
+    #[serde(default)]
     pub blocked_stop_area: Vec<BlockedStopArea>,
 }
 
@@ -149,7 +164,8 @@ pub struct BlockedStopArea {
     pub order: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
 pub enum ChannelType {
     Web,
     Sms,
@@ -163,25 +179,25 @@ pub enum ChannelType {
     Beacon,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Deserialize, Debug, Clone)]
 pub struct Cause {
     pub wording: String,
     pub category: String,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Deserialize, Debug, Clone)]
 pub struct DisruptionProperty {
     pub key: String,
     pub type_: String,
     pub value: String,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Deserialize, Debug, Clone)]
 pub struct Tag {
     pub name: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Severity {
     pub wording: Option<String>,
     pub color: Option<String>,
@@ -189,16 +205,17 @@ pub struct Severity {
     pub effect: Effect,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Message {
     pub text: String,
     pub channel_id: Option<String>,
     pub channel_name: String,
     pub channel_content_type: Option<String>,
+    #[serde(default)]
     pub channel_types: Vec<ChannelType>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct ApplicationPattern {
     pub begin_date: NaiveDate,
     pub end_date: NaiveDate,
@@ -206,7 +223,7 @@ pub struct ApplicationPattern {
     pub week_pattern: [bool; 7],
 }
 
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct TimeSlot {
     //TODO : determine in which timezone are these ?
     // can we use SecondsTimezoneDayStart/SecondsSinceUtcDayStart ?
@@ -241,6 +258,7 @@ pub fn store_and_apply_chaos_disruption(
 ) {
     debug!("Apply chaos disruption {}", disruption.id);
     let disruption_idx = real_time_model.chaos_disruptions.len();
+    let disruption = Arc::new(disruption);
     real_time_model.chaos_disruptions.push(disruption.clone());
 
     for (idx, impact) in disruption.impacts.iter().enumerate() {
@@ -267,12 +285,17 @@ pub fn cancel_chaos_disruption(
 ) {
     debug!("Cancel chaos disruption {disruption_id}");
 
+    // several versions of a disruption may have been stored,
+    // the one currently applied is the last one
     let has_disruption_idx = real_time_model
         .chaos_disruptions
         .iter()
-        .position(|disruption| disruption.id == disruption_id);
+        .enumerate()
+        .filter(|(_, disruption)| disruption.id == disruption_id)
+        .map(|(idx, _)| idx)
+        .last();
     if let Some(disruption_idx) = has_disruption_idx {
-        let disruption = &real_time_model.chaos_disruptions[disruption_idx].clone();
+        let disruption = real_time_model.chaos_disruptions[disruption_idx].clone();
         for (idx, impact) in disruption.impacts.iter().enumerate() {
             let chaos_impact_idx = ChaosImpactIdx {
                 disruption_idx,
@@ -287,6 +310,9 @@ pub fn cancel_chaos_disruption(
                 true,
             );
         }
+        real_time_model
+            .cancelled_chaos_disruptions
+            .insert(disruption_idx);
     } else {
         error!("Cannot cancel chaos disruption {disruption_id} since it was not found in present disruptions.");
     }
@@ -1117,6 +1143,24 @@ fn cancel_impact(
         }
     }
 
+    restore_base_trip(
+        real_time_model,
+        base_model,
+        data,
+        base_vehicle_journey_idx,
+        date,
+    );
+}
+
+// Restores the vehicle journey on `date` to its base schedule,
+// and applies again the chaos impacts still linked to it.
+pub(super) fn restore_base_trip(
+    real_time_model: &mut RealTimeModel,
+    base_model: &BaseModel,
+    data: &mut TransitData,
+    base_vehicle_journey_idx: BaseVehicleJourneyIdx,
+    date: NaiveDate,
+) {
     let vehicle_journey_idx = VehicleJourneyIdx::Base(base_vehicle_journey_idx);
 
     let base_stop_times: Vec<_> = match base_model.stop_times(base_vehicle_journey_idx) {
@@ -49,9 +49,12 @@ use crate::{
 use chrono::{NaiveDate, NaiveDateTime};
 use tracing::{debug, error};
 
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
-use super::{apply_disruption, time_periods::TimePeriod, Effect, VehicleJourneyId};
+use super::{
+    apply_disruption, chaos_disruption::restore_base_trip, time_periods::TimePeriod, Effect,
+    VehicleJourneyId,
+};
 
 #[derive(Debug, Clone)]
 pub struct KirinDisruption {
@@ -148,7 +151,84 @@ pub fn store_and_apply_kirin_disruption(
             disruption.id, err
         );
     }
-    real_time_model.kirin_disruptions.push(disruption);
+    real_time_model.kirin_disruptions.push(Arc::new(disruption));
+}
+
+/// Cancels the kirin disruption `disruption_id`, if it is the one currently applied
+/// on its (vehicle_journey, date).
+/// A base trip is restored to its base schedule, with the chaos impacts linked to it applied again,
+/// whereas a new trip is deleted.
+pub fn cancel_kirin_disruption(
+    real_time_model: &mut RealTimeModel,
+    disruption_id: &str,
+    base_model: &BaseModel,
+    data: &mut TransitData,
+) {
+    debug!("Cancel kirin disruption {disruption_id}");
+
+    // several disruptions with the same id may have been stored,
+    // the one that may be currently applied is the last one
+    let has_disruption = real_time_model
+        .kirin_disruptions
+        .iter()
+        .filter(|disruption| disruption.id == disruption_id)
+        .last()
+        .map(|disruption| (disruption.trip_id.id.clone(), disruption.trip_date));
+    let (vehicle_journey_id, date) = match has_disruption {
+        Some(trip) => trip,
+        None => {
+            error!("Cannot cancel kirin disruption {disruption_id} since it was not found in present disruptions.");
+            return;
+        }
+    };
+
+    // the disruption may have been applied on a base trip or on a new trip with the same id
+    let base_vj_idx = base_model
+        .vehicle_journey_idx(&vehicle_journey_id)
+        .map(VehicleJourneyIdx::Base);
+    let new_vj_idx = real_time_model
+        .new_vehicle_journey_idx(&vehicle_journey_id)
+        .map(VehicleJourneyIdx::New);
+    let has_applied_vj_idx = [base_vj_idx, new_vj_idx]
+        .into_iter()
+        .flatten()
+        .find(|vj_idx| {
+            real_time_model
+                .get_linked_kirin_disruption(vj_idx, date)
+                .map(|kirin_disruption_idx| {
+                    real_time_model
+                        .get_kirin_disruption(*kirin_disruption_idx)
+                        .id
+                        == disruption_id
+                })
+                .unwrap_or(false)
+        });
+    let vehicle_journey_idx = match has_applied_vj_idx {
+        Some(vj_idx) => vj_idx,
+        None => {
+            error!("Cannot cancel kirin disruption {disruption_id} since it is no longer applied on vehicle journey {vehicle_journey_id} on {date}.");
+            return;
+        }
+    };
+
+    real_time_model.unset_linked_kirin_disruption(&vehicle_journey_idx, date);
+
+    match vehicle_journey_idx {
+        VehicleJourneyIdx::Base(base_vj_idx) => {
+            restore_base_trip(real_time_model, base_model, data, base_vj_idx, date);
+        }
+        VehicleJourneyIdx::New(new_vj_idx) => {
+            if real_time_model.new_vehicle_journey_is_present(new_vj_idx, date) {
+                apply_disruption::delete_trip(
+                    real_time_model,
+                    base_model,
+                    data,
+                    &vehicle_journey_idx,
+                    date,
+                );
+            }
+        }
+    }
 }
 
 fn update_new_trip(
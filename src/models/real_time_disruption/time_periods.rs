@@ -36,6 +36,7 @@
 
 use crate::time::MAX_SECONDS_IN_UTC_DAY;
 use chrono::{Duration, NaiveDate, NaiveDateTime};
+use serde::Deserialize;
 use std::{
     cmp::{max, min},
     fmt::{Debug, Display},
@@ -46,12 +47,28 @@ use std::{
 /// if and only if
 ///  `start <= t < end`
 ///
-#[derive(Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "TimePeriodBounds")]
 pub struct TimePeriod {
     start: NaiveDateTime,
     end: NaiveDateTime,
 }
 
+// Used to deserialize a TimePeriod, while checking that start < end
+#[derive(Deserialize)]
+struct TimePeriodBounds {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl TryFrom<TimePeriodBounds> for TimePeriod {
+    type Error = TimePeriodError;
+
+    fn try_from(bounds: TimePeriodBounds) -> Result<Self, Self::Error> {
+        TimePeriod::new(bounds.start, bounds.end)
+    }
+}
+
 impl TimePeriod {
     pub fn new(start: NaiveDateTime, end: NaiveDateTime) -> Result<TimePeriod, TimePeriodError> {
         if start < end {
@@ -34,10 +34,21 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    mem::size_of,
+    sync::Arc,
+};
 use tracing::warn;
 
-use crate::chrono::NaiveDate;
+use crate::{
+    chrono::NaiveDate,
+    memory_usage::{
+        hash_map_bytes, hash_set_bytes, shared_map_bytes, shared_vec_bytes, vec_bytes, MemoryUsage,
+    },
+    shared_collections::{SharedMap, SharedVec},
+};
 
 use super::{
     base_model::{BaseModel, BaseVehicleJourneyIdx},
@@ -48,20 +59,26 @@ use super::{
     StopPointIdx, StopTime, StopTimeIdx, VehicleJourneyIdx,
 };
 
+// Histories and disruptions are behind an Arc, and stored in shared collections,
+// so that cloning a RealTimeModel only clones pointers to chunks of them.
+// A history is copied only when a clone modifies it.
+#[derive(Clone)]
 pub struct RealTimeModel {
-    pub(super) new_vehicle_journeys_id_to_idx: HashMap<String, NewVehicleJourneyIdx>,
+    pub(super) new_vehicle_journeys_id_to_idx: SharedMap<String, NewVehicleJourneyIdx>,
     // indexed by NewVehicleJourney.idx
-    pub(super) new_vehicle_journeys_history: Vec<(String, VehicleJourneyHistory)>,
+    pub(super) new_vehicle_journeys_history: SharedVec<Arc<(String, VehicleJourneyHistory)>>,
 
     pub(super) base_vehicle_journeys_idx_to_history:
-        HashMap<BaseVehicleJourneyIdx, VehicleJourneyHistory>,
+        SharedMap<BaseVehicleJourneyIdx, Arc<VehicleJourneyHistory>>,
 
-    pub(super) new_stop_id_to_idx: HashMap<String, NewStopPointIdx>,
-    pub(super) new_stops: Vec<StopData>,
+    pub(super) new_stop_id_to_idx: SharedMap<String, NewStopPointIdx>,
+    pub(super) new_stops: SharedVec<StopData>,
 
-    pub(super) chaos_disruptions: Vec<ChaosDisruption>,
+    pub(super) chaos_disruptions: SharedVec<Arc<ChaosDisruption>>,
+    // positions in chaos_disruptions of the disruptions that were cancelled
+    pub(super) cancelled_chaos_disruptions: HashSet<usize>,
 
-    pub(super) kirin_disruptions: Vec<KirinDisruption>,
+    pub(super) kirin_disruptions: SharedVec<Arc<KirinDisruption>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
@@ -109,6 +126,7 @@ pub struct NewStopPointIdx {
     pub idx: usize, // position in new_stops
 }
 
+#[derive(Clone)]
 pub struct StopData {
     pub(super) name: String,
 }
@@ -133,10 +151,11 @@ impl RealTimeModel {
     ) -> Option<TripVersion> {
         let history = self
             .base_vehicle_journeys_idx_to_history
-            .entry(vehicle_journey_idx)
-            .or_insert_with(VehicleJourneyHistory::new);
+            .get_or_insert_with(vehicle_journey_idx, Arc::default);
 
-        history.by_reference_date.insert(*date, trip_version)
+        Arc::make_mut(history)
+            .by_reference_date
+            .insert(*date, trip_version)
     }
 
     pub fn set_new_trip_version(
@@ -145,23 +164,26 @@ impl RealTimeModel {
         date: &NaiveDate,
         trip_version: TripVersion,
     ) -> Option<TripVersion> {
-        let history = &mut self.new_vehicle_journeys_history[vehicle_journey_idx.idx].1;
+        let history =
+            &mut Arc::make_mut(&mut self.new_vehicle_journeys_history[vehicle_journey_idx.idx]).1;
 
         history.by_reference_date.insert(*date, trip_version)
     }
 
     pub fn insert_new_vehicle_journey(&mut self, vehicle_journey_id: &str) -> NewVehicleJourneyIdx {
-        let histories = &mut self.new_vehicle_journeys_history;
-        let idx = self
-            .new_vehicle_journeys_id_to_idx
-            .entry(vehicle_journey_id.to_string())
-            .or_insert_with(|| {
-                let idx = histories.len();
-                histories.push((vehicle_journey_id.to_string(), VehicleJourneyHistory::new()));
-                NewVehicleJourneyIdx { idx }
-            });
-
-        *idx
+        if let Some(idx) = self.new_vehicle_journeys_id_to_idx.get(vehicle_journey_id) {
+            return *idx;
+        }
+        let idx = NewVehicleJourneyIdx {
+            idx: self.new_vehicle_journeys_history.len(),
+        };
+        self.new_vehicle_journeys_history.push(Arc::new((
+            vehicle_journey_id.to_string(),
+            VehicleJourneyHistory::new(),
+        )));
+        self.new_vehicle_journeys_id_to_idx
+            .insert(vehicle_journey_id.to_string(), idx);
+        idx
     }
 
     pub fn set_linked_kirin_disruption(
@@ -171,12 +193,12 @@ impl RealTimeModel {
         kirin_disruption_idx: KirinDisruptionIdx,
     ) -> Option<KirinDisruptionIdx> {
         let history = match vehicle_journey_idx {
-            VehicleJourneyIdx::Base(base_idx) => self
-                .base_vehicle_journeys_idx_to_history
-                .entry(*base_idx)
-                .or_insert_with(VehicleJourneyHistory::new),
+            VehicleJourneyIdx::Base(base_idx) => Arc::make_mut(
+                self.base_vehicle_journeys_idx_to_history
+                    .get_or_insert_with(*base_idx, Arc::default),
+            ),
             VehicleJourneyIdx::New(new_idx) => {
-                &mut self.new_vehicle_journeys_history[new_idx.idx].1
+                &mut Arc::make_mut(&mut self.new_vehicle_journeys_history[new_idx.idx]).1
             }
         };
         history
@@ -190,12 +212,12 @@ impl RealTimeModel {
         date: NaiveDate,
     ) {
         let history = match vehicle_journey_idx {
-            VehicleJourneyIdx::Base(base_idx) => self
-                .base_vehicle_journeys_idx_to_history
-                .entry(*base_idx)
-                .or_insert_with(VehicleJourneyHistory::new),
+            VehicleJourneyIdx::Base(base_idx) => Arc::make_mut(
+                self.base_vehicle_journeys_idx_to_history
+                    .get_or_insert_with(*base_idx, Arc::default),
+            ),
             VehicleJourneyIdx::New(new_idx) => {
-                &mut self.new_vehicle_journeys_history[new_idx.idx].1
+                &mut Arc::make_mut(&mut self.new_vehicle_journeys_history[new_idx.idx]).1
             }
         };
         history.linked_kirin_disruption.remove(&date);
@@ -223,10 +245,10 @@ impl RealTimeModel {
         chaos_impact_idx: &ChaosImpactIdx,
         impact_object_idx: &ChaosImpactObjectIdx,
     ) {
-        let history = self
-            .base_vehicle_journeys_idx_to_history
-            .entry(base_vehicle_journey_idx)
-            .or_insert_with(VehicleJourneyHistory::new);
+        let history = Arc::make_mut(
+            self.base_vehicle_journeys_idx_to_history
+                .get_or_insert_with(base_vehicle_journey_idx, Arc::default),
+        );
 
         let linked_impacts = history
             .linked_chaos_impacts
@@ -259,10 +281,10 @@ impl RealTimeModel {
         chaos_impact_idx: &ChaosImpactIdx,
         impact_object_idx: &ChaosImpactObjectIdx,
     ) {
-        let history = self
-            .base_vehicle_journeys_idx_to_history
-            .entry(base_vehicle_journey_idx)
-            .or_insert_with(VehicleJourneyHistory::new);
+        let history = Arc::make_mut(
+            self.base_vehicle_journeys_idx_to_history
+                .get_or_insert_with(base_vehicle_journey_idx, Arc::default),
+        );
 
         let linked_impacts = history
             .linked_chaos_impacts
@@ -312,6 +334,12 @@ impl RealTimeModel {
         Some((disruption, impact))
     }
 
+    pub fn contains_chaos_disruption(&self, disruption_id: &str) -> bool {
+        self.chaos_disruptions
+            .iter()
+            .any(|disruption| disruption.id == disruption_id)
+    }
+
     pub fn get_kirin_disruption(
         &self,
         kirin_disruption_idx: KirinDisruptionIdx,
@@ -466,6 +494,82 @@ impl RealTimeModel {
         range.map(|idx| NewVehicleJourneyIdx { idx })
     }
 
+    /// Returns the vehicle journeys whose history differs between `previous` and `self`,
+    /// where `self` was obtained by modifying a clone of `previous`.
+    ///
+    /// Since histories are shared between clones until modified, this only
+    /// compares pointers, and skips the shards of histories that are shared.
+    /// A vehicle journey may be returned even if its history
+    /// was modified back to its previous state.
+    pub fn modified_vehicle_journeys(&self, previous: &RealTimeModel) -> Vec<VehicleJourneyIdx> {
+        let modified_base_vehicle_journeys = self
+            .base_vehicle_journeys_idx_to_history
+            .iter_not_shared_with(&previous.base_vehicle_journeys_idx_to_history)
+            .filter(
+                |(idx, history)| match previous.base_vehicle_journeys_idx_to_history.get(idx) {
+                    Some(previous_history) => !Arc::ptr_eq(history, previous_history),
+                    None => true,
+                },
+            )
+            .map(|(idx, _)| VehicleJourneyIdx::Base(*idx));
+
+        let modified_new_vehicle_journeys = self
+            .new_vehicle_journeys_history
+            .iter_not_shared_with(&previous.new_vehicle_journeys_history)
+            .filter(
+                |(idx, history)| match previous.new_vehicle_journeys_history.get(*idx) {
+                    Some(previous_history) => !Arc::ptr_eq(history, previous_history),
+                    None => true,
+                },
+            )
+            .map(|(idx, _)| VehicleJourneyIdx::New(NewVehicleJourneyIdx { idx }));
+
+        modified_base_vehicle_journeys
+            .chain(modified_new_vehicle_journeys)
+            .collect()
+    }
+
+    /// The chaos disruptions currently applied : for each disruption id,
+    /// the last version stored, unless it was cancelled.
+    pub fn applied_chaos_disruptions(&self) -> Vec<&ChaosDisruption> {
+        let mut seen_ids = HashSet::new();
+        let disruptions: Vec<&Arc<ChaosDisruption>> = self.chaos_disruptions.iter().collect();
+        let mut applied: Vec<&ChaosDisruption> = disruptions
+            .into_iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, disruption)| seen_ids.insert(disruption.id.as_str()))
+            .filter(|(idx, _)| !self.cancelled_chaos_disruptions.contains(idx))
+            .map(|(_, disruption)| disruption.as_ref())
+            .collect();
+        applied.reverse();
+        applied
+    }
+
+    /// The kirin disruptions currently applied : the last one received
+    /// for each (vehicle_journey, date).
+    pub fn applied_kirin_disruptions(&self) -> Vec<&KirinDisruption> {
+        let base_histories = self
+            .base_vehicle_journeys_idx_to_history
+            .values()
+            .map(|history| history.as_ref());
+        let new_histories = self
+            .new_vehicle_journeys_history
+            .iter()
+            .map(|history| &history.1);
+        let mut kirin_disruption_idxs: Vec<usize> = base_histories
+            .chain(new_histories)
+            .flat_map(|history| history.linked_kirin_disruption.values())
+            .map(|kirin_disruption_idx| kirin_disruption_idx.idx)
+            .collect();
+        kirin_disruption_idxs.sort_unstable();
+        kirin_disruption_idxs.dedup();
+        kirin_disruption_idxs
+            .into_iter()
+            .map(|idx| self.kirin_disruptions[idx].as_ref())
+            .collect()
+    }
+
     pub fn get_chaos_disruption_and_impact(
         &self,
         chaos_impact_idx: &ChaosImpactIdx,
@@ -477,15 +581,112 @@ impl RealTimeModel {
 
     pub fn new() -> Self {
         Self {
-            new_vehicle_journeys_id_to_idx: HashMap::new(),
-            new_vehicle_journeys_history: Vec::new(),
-            base_vehicle_journeys_idx_to_history: HashMap::new(),
-            new_stop_id_to_idx: HashMap::new(),
-            new_stops: Vec::new(),
-            chaos_disruptions: Vec::new(),
-            kirin_disruptions: Vec::new(),
+            new_vehicle_journeys_id_to_idx: SharedMap::new(),
+            new_vehicle_journeys_history: SharedVec::new(),
+            base_vehicle_journeys_idx_to_history: SharedMap::new(),
+            new_stop_id_to_idx: SharedMap::new(),
+            new_stops: SharedVec::new(),
+            chaos_disruptions: SharedVec::new(),
+            cancelled_chaos_disruptions: HashSet::new(),
+            kirin_disruptions: SharedVec::new(),
         }
     }
+
+    /// Memory used by each kind of addition made by the real time updates.
+    /// Disruptions are counted without their messages and properties.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let new_vehicle_journeys_bytes: usize = self
+            .new_vehicle_journeys_history
+            .iter()
+            .map(|history| {
+                let (id, history) = history.as_ref();
+                size_of::<(String, VehicleJourneyHistory)>() + 2 * id.len() + history.heap_bytes()
+            })
+            .sum();
+        let base_vehicle_journeys_bytes: usize = self
+            .base_vehicle_journeys_idx_to_history
+            .values()
+            .map(|history| size_of::<VehicleJourneyHistory>() + history.heap_bytes())
+            .sum();
+        let new_stops_bytes: usize = self
+            .new_stop_id_to_idx
+            .keys()
+            .map(String::len)
+            .chain(self.new_stops.iter().map(|stop_data| stop_data.name.len()))
+            .sum();
+        let chaos_disruptions_bytes: usize = self
+            .chaos_disruptions
+            .iter()
+            .map(|disruption| {
+                let impacts_bytes: usize = disruption
+                    .impacts
+                    .iter()
+                    .map(|impact| {
+                        impact.id.len()
+                            + vec_bytes(&impact.application_periods)
+                            + vec_bytes(&impact.impacted_pt_objects)
+                            + vec_bytes(&impact.informed_pt_objects)
+                    })
+                    .sum();
+                size_of::<ChaosDisruption>()
+                    + disruption.id.len()
+                    + vec_bytes(&disruption.impacts)
+                    + impacts_bytes
+            })
+            .sum();
+        let kirin_disruptions_bytes: usize = self
+            .kirin_disruptions
+            .iter()
+            .map(|disruption| {
+                let update_bytes = match &disruption.update {
+                    kirin_disruption::UpdateType::TripDeleted() => 0,
+                    kirin_disruption::UpdateType::BaseTripUpdated(update_data)
+                    | kirin_disruption::UpdateType::NewTripUpdated(update_data) => {
+                        let stop_ids_bytes: usize = update_data
+                            .stop_times
+                            .iter()
+                            .map(|stop_time| stop_time.stop_id.len())
+                            .sum();
+                        vec_bytes(&update_data.stop_times) + stop_ids_bytes
+                    }
+                };
+                size_of::<KirinDisruption>() + disruption.id.len() + update_bytes
+            })
+            .sum();
+
+        MemoryUsage::with_components(
+            "real_time_model",
+            vec![
+                MemoryUsage::new(
+                    "new_vehicle_journeys",
+                    shared_map_bytes(&self.new_vehicle_journeys_id_to_idx)
+                        + shared_vec_bytes(&self.new_vehicle_journeys_history)
+                        + new_vehicle_journeys_bytes,
+                ),
+                MemoryUsage::new(
+                    "modified_base_vehicle_journeys",
+                    shared_map_bytes(&self.base_vehicle_journeys_idx_to_history)
+                        + base_vehicle_journeys_bytes,
+                ),
+                MemoryUsage::new(
+                    "new_stop_points",
+                    shared_map_bytes(&self.new_stop_id_to_idx)
+                        + shared_vec_bytes(&self.new_stops)
+                        + new_stops_bytes,
+                ),
+                MemoryUsage::new(
+                    "chaos_disruptions",
+                    shared_vec_bytes(&self.chaos_disruptions)
+                        + hash_set_bytes(&self.cancelled_chaos_disruptions)
+                        + chaos_disruptions_bytes,
+                ),
+                MemoryUsage::new(
+                    "kirin_disruptions",
+                    shared_vec_bytes(&self.kirin_disruptions) + kirin_disruptions_bytes,
+                ),
+            ],
+        )
+    }
 }
 
 impl Default for RealTimeModel {
@@ -508,6 +709,23 @@ impl VehicleJourneyHistory {
             linked_kirin_disruption: HashMap::new(),
         }
     }
+
+    fn heap_bytes(&self) -> usize {
+        let trip_versions_bytes: usize = self
+            .by_reference_date
+            .values()
+            .map(|trip_version| match trip_version {
+                TripVersion::Deleted() => 0,
+                TripVersion::Present(stop_times) => vec_bytes(stop_times),
+            })
+            .sum();
+        let chaos_impacts_bytes: usize = self.linked_chaos_impacts.values().map(vec_bytes).sum();
+        hash_map_bytes(&self.by_reference_date)
+            + trip_versions_bytes
+            + hash_map_bytes(&self.linked_chaos_impacts)
+            + chaos_impacts_bytes
+            + hash_map_bytes(&self.linked_kirin_disruption)
+    }
 }
 
 impl<'a> Iterator for RealTimeStopTimes<'a> {
@@ -0,0 +1,121 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+use std::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of threads to use when nothing else is specified :
+/// the parallelism available on this machine.
+pub(crate) fn default_nb_of_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// Returns `inputs.iter().map(f).collect()`, where `f` is called on `nb_of_threads` threads.
+///
+/// Inputs are handed out one at a time to the first available thread,
+/// so that a few costly inputs do not leave the other threads idle.
+/// The outputs are in the same order as the inputs, whatever the number of threads.
+pub(crate) fn map_on_threads<Input, Output, F>(
+    inputs: &[Input],
+    nb_of_threads: usize,
+    f: F,
+) -> Vec<Output>
+where
+    Input: Sync,
+    Output: Send,
+    F: Fn(&Input) -> Output + Sync,
+{
+    let nb_of_threads = nb_of_threads.min(inputs.len());
+    if nb_of_threads <= 1 {
+        return inputs.iter().map(f).collect();
+    }
+
+    let next_input = AtomicUsize::new(0);
+    let mut outputs: Vec<Option<Output>> = Vec::with_capacity(inputs.len());
+    outputs.resize_with(inputs.len(), || None);
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..nb_of_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut thread_outputs = Vec::new();
+                    loop {
+                        let idx = next_input.fetch_add(1, Ordering::Relaxed);
+                        match inputs.get(idx) {
+                            Some(input) => thread_outputs.push((idx, f(input))),
+                            None => break,
+                        }
+                    }
+                    thread_outputs
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let thread_outputs = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            for (idx, output) in thread_outputs {
+                outputs[idx] = Some(output);
+            }
+        }
+    });
+
+    outputs
+        .into_iter()
+        // unwrap is safe since each input was handed out to exactly one thread
+        .map(Option::unwrap)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::map_on_threads;
+
+    #[test]
+    fn outputs_keep_the_order_of_inputs() {
+        let inputs: Vec<usize> = (0..1000).collect();
+        let expected: Vec<usize> = inputs.iter().map(|input| input * 2).collect();
+        for nb_of_threads in [0, 1, 3, 8, 2000] {
+            let outputs = map_on_threads(&inputs, nb_of_threads, |input| input * 2);
+            assert_eq!(outputs, expected);
+        }
+    }
+}
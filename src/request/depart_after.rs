@@ -515,7 +515,7 @@ where
                 transfers_duration: self.criteria.transfers_duration + durations.walking_duration,
                 loads_count: self.criteria.loads_count.clone(),
             };
-            (stop.clone(), new_criteria, transfer.clone())
+            (stop, new_criteria, transfer)
         })
     }
 }
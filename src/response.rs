@@ -745,6 +745,28 @@ impl DepartureSection {
 }
 
 impl Response {
+    /// Returns true if both journeys board and debark the same vehicles at the same stops.
+    pub fn has_same_vehicle_sections(&self, other: &Self) -> bool {
+        self.connections.len() == other.connections.len()
+            && self.vehicle_sections().zip(other.vehicle_sections()).all(
+                |(section, other_section)| {
+                    section.vehicle_journey == other_section.vehicle_journey
+                        && section.day_for_vehicle_journey == other_section.day_for_vehicle_journey
+                        && section.from_stoptime_idx == other_section.from_stoptime_idx
+                        && section.to_stoptime_idx == other_section.to_stoptime_idx
+                },
+            )
+    }
+
+    /// The vehicle sections of the journey, in order.
+    pub fn vehicle_sections(&self) -> impl Iterator<Item = &VehicleSection> {
+        std::iter::once(&self.first_vehicle).chain(
+            self.connections
+                .iter()
+                .map(|(_, _, vehicle_section)| vehicle_section),
+        )
+    }
+
     pub fn nb_of_sections(&self) -> usize {
         1 + 3 * self.connections.len()
     }
@@ -0,0 +1,441 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! Collections whose clones share their content.
+//!
+//! The elements are stored in chunks (resp. shards) behind an `Arc`, so cloning
+//! a collection only clones one pointer per chunk. Modifying an element copies
+//! the chunk that contains it, if this chunk is shared with a clone.
+//! This allows to apply real time updates on a copy of the data without copying
+//! all of it, while the previous version is still used to answer requests.
+
+use serde::{
+    ser::{SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::{Index, IndexMut},
+    sync::Arc,
+};
+
+const CHUNK_LEN: usize = 256;
+
+const NB_OF_SHARDS: usize = 256;
+
+/// A Vec whose clones share chunks of `CHUNK_LEN` elements.
+#[derive(Debug, Clone)]
+pub struct SharedVec<T> {
+    chunks: Vec<Arc<Vec<T>>>,
+    len: usize,
+}
+
+impl<T> Default for SharedVec<T> {
+    fn default() -> Self {
+        Self {
+            chunks: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T: Clone> SharedVec<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.chunks
+            .get(idx / CHUNK_LEN)
+            .and_then(|chunk| chunk.get(idx % CHUNK_LEN))
+    }
+
+    /// The chunk containing `idx` is copied if it is shared with a clone of self.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        if idx >= self.len {
+            return None;
+        }
+        let chunk = &mut self.chunks[idx / CHUNK_LEN];
+        Arc::make_mut(chunk).get_mut(idx % CHUNK_LEN)
+    }
+
+    pub fn push(&mut self, value: T) {
+        match self.chunks.last() {
+            Some(last_chunk) if last_chunk.len() < CHUNK_LEN => (),
+            _ => self.chunks.push(Arc::new(Vec::with_capacity(CHUNK_LEN))),
+        }
+        // there is at least one chunk, since we just pushed one if needed
+        let last_chunk = self.chunks.last_mut().unwrap();
+        Arc::make_mut(last_chunk).push(value);
+        self.len += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+
+    /// Iterates over the positions and values of the elements of `self`
+    /// that are not shared with `other`, where `self` was obtained by
+    /// modifying a clone of `other`.
+    /// Elements that are in a chunk modified in `self` are all returned,
+    /// even if some of them are equal to those of `other`.
+    pub fn iter_not_shared_with<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = (usize, &'a T)> + 'a {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(
+                move |(chunk_idx, chunk)| match other.chunks.get(*chunk_idx) {
+                    Some(other_chunk) => !Arc::ptr_eq(chunk, other_chunk),
+                    None => true,
+                },
+            )
+            .flat_map(|(chunk_idx, chunk)| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .map(move |(idx, value)| (chunk_idx * CHUNK_LEN + idx, value))
+            })
+    }
+
+    pub(crate) fn chunks(&self) -> impl Iterator<Item = &Vec<T>> + '_ {
+        self.chunks.iter().map(|chunk| chunk.as_ref())
+    }
+}
+
+impl<T: Clone> Index<usize> for SharedVec<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &T {
+        &self.chunks[idx / CHUNK_LEN][idx % CHUNK_LEN]
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for SharedVec<T> {
+    fn index_mut(&mut self, idx: usize) -> &mut T {
+        assert!(
+            idx < self.len,
+            "index {} out of bounds of SharedVec of len {}",
+            idx,
+            self.len
+        );
+        self.get_mut(idx).unwrap()
+    }
+}
+
+impl<T: Clone> FromIterator<T> for SharedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut result = Self::new();
+        for value in iter {
+            result.push(value);
+        }
+        result
+    }
+}
+
+impl<T: Clone + Serialize> Serialize for SharedVec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // the length is given, since some formats (e.g. bincode) need it upfront
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        for value in self.iter() {
+            seq.serialize_element(value)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de, T: Clone + Deserialize<'de>> Deserialize<'de> for SharedVec<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Vec::<T>::deserialize(deserializer)?;
+        Ok(values.into_iter().collect())
+    }
+}
+
+/// A HashMap whose clones share `NB_OF_SHARDS` shards of entries.
+#[derive(Debug, Clone)]
+pub struct SharedMap<K, V> {
+    // empty until the first insertion, so that an empty map does not allocate
+    shards: Vec<Arc<HashMap<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> Default for SharedMap<K, V> {
+    fn default() -> Self {
+        Self {
+            shards: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<K, V> SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shards.get(shard_idx(key))?.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// The shard containing `key` is copied if it is shared with a clone of self.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shards.get_mut(shard_idx(key))?;
+        if !shard.contains_key(key) {
+            return None;
+        }
+        Arc::make_mut(shard).get_mut(key)
+    }
+
+    /// Returns the value of `key`, after inserting `default()` if `key` is absent.
+    pub fn get_or_insert_with<F>(&mut self, key: K, default: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        let shard = Arc::make_mut(shard_for_insertion(&mut self.shards, &key));
+        let len = &mut self.len;
+        shard.entry(key).or_insert_with(|| {
+            *len += 1;
+            default()
+        })
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let shard = Arc::make_mut(shard_for_insertion(&mut self.shards, &key));
+        let previous = shard.insert(key, value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let shard = self.shards.get_mut(shard_idx(key))?;
+        if !shard.contains_key(key) {
+            return None;
+        }
+        let removed = Arc::make_mut(shard).remove(key);
+        self.len -= 1;
+        removed
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Iterates over the entries of `self` that are not shared with `other`,
+    /// where `self` was obtained by modifying a clone of `other`.
+    /// Entries that are in a shard modified in `self` are all returned,
+    /// even if some of them are equal to those of `other`.
+    pub fn iter_not_shared_with<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = (&'a K, &'a V)> + 'a {
+        self.shards
+            .iter()
+            .enumerate()
+            .filter(
+                move |(shard_idx, shard)| match other.shards.get(*shard_idx) {
+                    Some(other_shard) => !Arc::ptr_eq(shard, other_shard),
+                    None => true,
+                },
+            )
+            .flat_map(|(_, shard)| shard.iter())
+    }
+
+    pub(crate) fn shards(&self) -> impl Iterator<Item = &HashMap<K, V>> + '_ {
+        self.shards.iter().map(|shard| shard.as_ref())
+    }
+}
+
+fn shard_for_insertion<'a, K: Hash, V>(
+    shards: &'a mut Vec<Arc<HashMap<K, V>>>,
+    key: &K,
+) -> &'a mut Arc<HashMap<K, V>> {
+    if shards.is_empty() {
+        *shards = (0..NB_OF_SHARDS).map(|_| Arc::default()).collect();
+    }
+    &mut shards[shard_idx(key)]
+}
+
+fn shard_idx<Q: Hash + ?Sized>(key: &Q) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % (NB_OF_SHARDS as u64)) as usize
+}
+
+impl<K, V> FromIterator<(K, V)> for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut result = Self::new();
+        for (key, value) in iter {
+            result.insert(key, value);
+        }
+        result
+    }
+}
+
+impl<K, V> Serialize for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone + Serialize,
+    V: Clone + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for SharedMap<K, V>
+where
+    K: Hash + Eq + Clone + Deserialize<'de>,
+    V: Clone + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = HashMap::<K, V>::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifying_a_clone_of_a_vec_copies_only_one_chunk() {
+        let vec: SharedVec<usize> = (0..3 * CHUNK_LEN).collect();
+        let mut clone = vec.clone();
+        clone[CHUNK_LEN + 1] = 0;
+        clone.push(3 * CHUNK_LEN);
+
+        assert_eq!(vec[CHUNK_LEN + 1], CHUNK_LEN + 1);
+        assert_eq!(vec.len(), 3 * CHUNK_LEN);
+        assert_eq!(clone[CHUNK_LEN + 1], 0);
+        assert_eq!(clone.len(), 3 * CHUNK_LEN + 1);
+        assert_eq!(clone.get(3 * CHUNK_LEN), Some(&(3 * CHUNK_LEN)));
+
+        let not_shared: Vec<usize> = clone
+            .iter_not_shared_with(&vec)
+            .map(|(idx, _)| idx)
+            .collect();
+        let expected: Vec<usize> = (CHUNK_LEN..2 * CHUNK_LEN)
+            .chain(std::iter::once(3 * CHUNK_LEN))
+            .collect();
+        assert_eq!(not_shared, expected);
+    }
+
+    #[test]
+    fn modifying_a_clone_of_a_map_copies_only_one_shard() {
+        let map: SharedMap<String, usize> = (0..1000).map(|idx| (idx.to_string(), idx)).collect();
+        let mut clone = map.clone();
+        *clone.get_mut("10").unwrap() = 0;
+        assert_eq!(clone.remove("11"), Some(11));
+        *clone.get_or_insert_with("new".to_string(), || 1) += 1;
+
+        assert_eq!(map.get("10"), Some(&10));
+        assert_eq!(map.get("11"), Some(&11));
+        assert_eq!(map.len(), 1000);
+        assert_eq!(clone.get("10"), Some(&0));
+        assert!(!clone.contains_key("11"));
+        assert_eq!(clone.get("new"), Some(&2));
+        assert_eq!(clone.len(), 1000);
+
+        let not_shared: Vec<&String> = clone
+            .iter_not_shared_with(&map)
+            .map(|(key, _)| key)
+            .collect();
+        assert!(not_shared.contains(&&"10".to_string()));
+        assert!(not_shared.contains(&&"new".to_string()));
+        assert!(not_shared.len() < 100);
+    }
+}
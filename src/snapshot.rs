@@ -0,0 +1,180 @@
+// Copyright  (C) 2020, Kisio Digital and/or its affiliates. All rights reserved.
+//
+// This file is part of Navitia,
+// the software to build cool stuff with public transport.
+//
+// Hope you'll enjoy and contribute to this project,
+// powered by Kisio Digital (www.kisio.com).
+// Help us simplify mobility and open public transport:
+// a non ending quest to the responsive locomotion way of traveling!
+//
+// This contribution is a part of the research and development work of the
+// IVA Project which aims to enhance traveler information and is carried out
+// under the leadership of the Technological Research Institute SystemX,
+// with the partnership and support of the transport organization authority
+// Ile-De-France Mobilités (IDFM), SNCF, and public funds
+// under the scope of the French Program "Investissements d’Avenir".
+//
+// LICENCE: This program is free software; you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+//
+// Stay tuned using
+// twitter @navitia
+// channel `#navitia` on riot https://riot.im/app/#/room/#navitia:matrix.org
+// https://groups.google.com/d/forum/navitia
+// www.navitia.io
+
+//! Serialization of a [`TransitData`](crate::TransitData) so that it can be stored
+//! once built, and loaded later without being rebuilt.
+//!
+//! A `TransitData` refers to the objects of the [`BaseModel`] it was built from
+//! through their indexes. These are serialized as plain integers, and are turned
+//! back into indexes of a `BaseModel` when deserialized. Hence the deserialization
+//! of a `TransitData` must happen inside [`with_base_indexes_of()`], with
+//! the same `BaseModel` as the one used to build the serialized `TransitData`.
+
+use std::cell::RefCell;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::models::{
+    base_model::{BaseModel, BaseStopPointIdx, BaseTransferIdx, BaseVehicleJourneyIdx},
+    real_time_model::{NewStopPointIdx, NewVehicleJourneyIdx},
+    StopPointIdx, TransferIdx, VehicleJourneyIdx,
+};
+
+/// Version of loki, written in snapshots to refuse the ones
+/// written by another version.
+pub const LOKI_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+struct BaseIndexes {
+    vehicle_journeys: Vec<BaseVehicleJourneyIdx>,
+    stop_points: Vec<BaseStopPointIdx>,
+    transfers: Vec<BaseTransferIdx>,
+}
+
+thread_local! {
+    static BASE_INDEXES: RefCell<Option<BaseIndexes>> = const { RefCell::new(None) };
+}
+
+/// Calls `deserialize`, during which the indexes of base objects
+/// are resolved in `base_model`.
+pub fn with_base_indexes_of<T>(base_model: &BaseModel, deserialize: impl FnOnce() -> T) -> T {
+    // removes the indexes once `deserialize` is done, even if it panics
+    struct ClearOnDrop;
+    impl Drop for ClearOnDrop {
+        fn drop(&mut self) {
+            BASE_INDEXES.with(|base_indexes| base_indexes.borrow_mut().take());
+        }
+    }
+
+    let base_indexes = BaseIndexes {
+        vehicle_journeys: base_model.vehicle_journeys().collect(),
+        stop_points: base_model.stop_points().collect(),
+        transfers: base_model.transfers().collect(),
+    };
+    BASE_INDEXES.with(|cell| *cell.borrow_mut() = Some(base_indexes));
+    let _clear_on_drop = ClearOnDrop;
+    deserialize()
+}
+
+fn base_idx<Idx: Copy, E: serde::de::Error>(
+    idx: usize,
+    object_name: &str,
+    select: impl FnOnce(&BaseIndexes) -> &[Idx],
+) -> Result<Idx, E> {
+    BASE_INDEXES.with(|cell| {
+        let base_indexes = cell.borrow();
+        let base_indexes = base_indexes.as_ref().ok_or_else(|| {
+            E::custom("base indexes can only be deserialized inside with_base_indexes_of()")
+        })?;
+        select(base_indexes).get(idx).copied().ok_or_else(|| {
+            E::custom(format!(
+                "there is no {} with index {} in the base model",
+                object_name, idx
+            ))
+        })
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+enum SerializedIdx {
+    Base(usize),
+    New(usize),
+}
+
+impl Serialize for VehicleJourneyIdx {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            VehicleJourneyIdx::Base(idx) => SerializedIdx::Base(idx.get()),
+            VehicleJourneyIdx::New(idx) => SerializedIdx::New(idx.idx),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for VehicleJourneyIdx {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SerializedIdx::deserialize(deserializer)? {
+            SerializedIdx::Base(idx) => base_idx(idx, "vehicle journey", |base_indexes| {
+                &base_indexes.vehicle_journeys
+            })
+            .map(VehicleJourneyIdx::Base),
+            SerializedIdx::New(idx) => Ok(VehicleJourneyIdx::New(NewVehicleJourneyIdx { idx })),
+        }
+    }
+}
+
+impl Serialize for StopPointIdx {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            StopPointIdx::Base(idx) => SerializedIdx::Base(idx.get()),
+            StopPointIdx::New(idx) => SerializedIdx::New(idx.idx),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StopPointIdx {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SerializedIdx::deserialize(deserializer)? {
+            SerializedIdx::Base(idx) => {
+                base_idx(idx, "stop point", |base_indexes| &base_indexes.stop_points)
+                    .map(StopPointIdx::Base)
+            }
+            SerializedIdx::New(idx) => Ok(StopPointIdx::New(NewStopPointIdx { idx })),
+        }
+    }
+}
+
+impl Serialize for TransferIdx {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            TransferIdx::Base(idx) => SerializedIdx::Base(idx.get()),
+            TransferIdx::New(idx) => SerializedIdx::New(*idx),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TransferIdx {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SerializedIdx::deserialize(deserializer)? {
+            SerializedIdx::Base(idx) => {
+                base_idx(idx, "transfer", |base_indexes| &base_indexes.transfers)
+                    .map(TransferIdx::Base)
+            }
+            SerializedIdx::New(idx) => Ok(TransferIdx::New(idx)),
+        }
+    }
+}
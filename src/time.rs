@@ -35,6 +35,7 @@
 // www.navitia.io
 
 use chrono::{FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 pub mod calendar;
@@ -52,7 +53,7 @@ pub struct SecondsSinceTimezonedDayStart {
     seconds: i32,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SecondsSinceUTCDayStart {
     seconds: i32,
 }
@@ -81,7 +82,7 @@ pub struct DaysSinceDatasetStart {
 // we allow 36_600 days which is more than 100 years, and less than u16::MAX = 65_535 days
 const MAX_DAYS_IN_CALENDAR: u16 = 100 * 366;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Calendar {
     first_date: NaiveDate, //first date which may be allowed
     last_date: NaiveDate,  //last date (included) which may be allowed
@@ -27,11 +27,12 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use crate::time::DaysSinceDatasetStart;
+use crate::{memory_usage::vec_bytes, time::DaysSinceDatasetStart};
 
 use super::days_patterns::{DaysPattern, DaysPatterns};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaysMap<T> {
     // invariants :
     //  1. a day is set in at most one DaysPattern of the Vec
@@ -48,6 +49,11 @@ impl<T> DaysMap<T> {
         self.data.is_empty()
     }
 
+    /// Memory allocated by this map, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        vec_bytes(&self.data)
+    }
+
     pub fn get(&self, day: DaysSinceDatasetStart, days_patterns: &DaysPatterns) -> Option<&T> {
         self.data.iter().find_map(|(days_pattern, value)| {
             if days_patterns.is_allowed(days_pattern, day) {
@@ -34,24 +34,31 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use std::{borrow::Borrow, iter::Enumerate, ops::Not};
+use std::{borrow::Borrow, iter::Enumerate, mem::size_of, ops::Not, sync::Arc};
 
-use crate::time::{Calendar, DaysSinceDatasetStart};
+use crate::{
+    memory_usage::{shared_vec_bytes, vec_bytes, MemoryUsage},
+    shared_collections::SharedVec,
+    time::{Calendar, DaysSinceDatasetStart},
+};
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaysPatterns {
-    days_patterns: Vec<DaysPatternData>,
+    // patterns are never modified once inserted,
+    // so they can be shared between clones
+    days_patterns: SharedVec<Arc<DaysPatternData>>,
 
     buffer: Vec<bool>,
 }
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct DaysPatternData {
     allowed_dates: Vec<bool>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct DaysPattern {
     idx: usize,
 }
@@ -59,7 +66,7 @@ pub struct DaysPattern {
 impl DaysPatterns {
     pub fn new(nb_of_days: usize) -> Self {
         let mut result = Self {
-            days_patterns: Vec::new(),
+            days_patterns: SharedVec::new(),
             buffer: vec![false; nb_of_days],
         };
         let empty_pattern = result.get_from_days(std::iter::empty());
@@ -103,7 +110,7 @@ impl DaysPatterns {
             let days_pattern_data = DaysPatternData {
                 allowed_dates: self.buffer.clone(),
             };
-            self.days_patterns.push(days_pattern_data);
+            self.days_patterns.push(Arc::new(days_pattern_data));
             idx
         };
 
@@ -282,6 +289,18 @@ impl DaysPatterns {
                 days: day_idx as u16,
             })
     }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let patterns_bytes: usize = self
+            .days_patterns
+            .iter()
+            .map(|pattern| size_of::<DaysPatternData>() + vec_bytes(&pattern.allowed_dates))
+            .sum();
+        MemoryUsage::new(
+            "days_patterns",
+            shared_vec_bytes(&self.days_patterns) + patterns_bytes + vec_bytes(&self.buffer),
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -37,12 +37,17 @@
 use std::collections::HashMap;
 
 use super::days_patterns::{DaysPattern, DaysPatterns};
-use crate::time::Calendar;
+use crate::{
+    memory_usage::{hash_map_bytes, vec_bytes},
+    time::Calendar,
+};
 
 use chrono::{FixedOffset, NaiveDate, Offset, TimeZone as TimeZoneTrait};
 use chrono_tz::Tz as TimeZone;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use std::str::FromStr;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct TimezonesPatterns {
     timezones_patterns: HashMap<TimeZone, Vec<(FixedOffset, DaysPattern)>>,
     buffer: HashMap<FixedOffset, Vec<NaiveDate>>,
@@ -53,6 +58,16 @@ impl TimezonesPatterns {
         Self::default()
     }
 
+    /// Memory allocated by this data structure, in bytes.
+    pub fn heap_bytes(&self) -> usize {
+        let timezones_bytes: usize = self.timezones_patterns.values().map(vec_bytes).sum();
+        let buffer_bytes: usize = self.buffer.values().map(vec_bytes).sum();
+        hash_map_bytes(&self.timezones_patterns)
+            + timezones_bytes
+            + hash_map_bytes(&self.buffer)
+            + buffer_bytes
+    }
+
     pub fn fetch_or_insert(
         &mut self,
         timezone: TimeZone,
@@ -87,3 +102,50 @@ impl TimezonesPatterns {
         self.timezones_patterns.get(&timezone).unwrap().as_slice()
     }
 }
+
+// Timezones are serialized by name, and offsets by their number of seconds.
+// The buffer is only used while inserting a timezone, so it is not serialized.
+impl Serialize for TimezonesPatterns {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let timezones_patterns: Vec<(&str, Vec<(i32, DaysPattern)>)> = self
+            .timezones_patterns
+            .iter()
+            .map(|(timezone, patterns)| {
+                let patterns = patterns
+                    .iter()
+                    .map(|(offset, days_pattern)| (offset.local_minus_utc(), *days_pattern))
+                    .collect();
+                (timezone.name(), patterns)
+            })
+            .collect();
+        timezones_patterns.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TimezonesPatterns {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let serialized: Vec<(String, Vec<(i32, DaysPattern)>)> = Vec::deserialize(deserializer)?;
+        let mut timezones_patterns = HashMap::new();
+        for (timezone_name, serialized_patterns) in serialized {
+            let timezone = TimeZone::from_str(&timezone_name).map_err(D::Error::custom)?;
+            let mut patterns = Vec::with_capacity(serialized_patterns.len());
+            for (seconds, days_pattern) in serialized_patterns {
+                let offset = FixedOffset::east_opt(seconds).ok_or_else(|| {
+                    D::Error::custom(format!("Invalid timezone offset {}", seconds))
+                })?;
+                patterns.push((offset, days_pattern));
+            }
+            timezones_patterns.insert(timezone, patterns);
+        }
+        Ok(Self {
+            timezones_patterns,
+            buffer: HashMap::new(),
+        })
+    }
+}
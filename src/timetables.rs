@@ -50,11 +50,12 @@ use crate::{
 };
 
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
 use self::generic_timetables::VehicleTimesError;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum FlowDirection {
     BoardOnly,
     DebarkOnly,
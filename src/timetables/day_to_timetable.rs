@@ -34,10 +34,13 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, mem::size_of, sync::Arc};
 
 use crate::{
+    memory_usage::{hash_map_bytes, shared_map_bytes, MemoryUsage},
     models::VehicleJourneyIdx,
+    shared_collections::SharedMap,
     time::{
         days_map::{DaysMap, InsertError},
         days_patterns::{DaysPattern, DaysPatterns},
@@ -48,10 +51,14 @@ use crate::{
 
 pub type LocalZone = Option<u16>;
 
+// Cloning a VehicleJourneyToTimetable only clones pointers to shards of the data
+// of the vehicle journeys, which are copied when modified.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct VehicleJourneyToTimetable<Timetable> {
-    data: HashMap<VehicleJourneyIdx, HashMap<LocalZone, DayToTimetable<Timetable>>>,
+    data: SharedMap<VehicleJourneyIdx, Arc<HashMap<LocalZone, DayToTimetable<Timetable>>>>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 struct DayToTimetable<Timetable> {
     base: DaysMap<Timetable>,
     real_time: DaysMap<Timetable>,
@@ -72,7 +79,7 @@ where
 {
     pub fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            data: SharedMap::new(),
         }
     }
 
@@ -84,10 +91,10 @@ where
         timetable_to_insert: &Timetable,
         days_patterns: &mut DaysPatterns,
     ) -> Result<(), InsertionError> {
-        let day_to_timetable = self
+        let local_zone_to_day_to_timetable = self
             .data
-            .entry(vehicle_journey_idx.clone())
-            .or_insert_with(HashMap::new)
+            .get_or_insert_with(vehicle_journey_idx.clone(), Arc::default);
+        let day_to_timetable = Arc::make_mut(local_zone_to_day_to_timetable)
             .entry(local_zone)
             .or_insert_with(DayToTimetable::new);
 
@@ -129,10 +136,10 @@ where
         timetable_to_insert: &Timetable,
         days_patterns: &mut DaysPatterns,
     ) -> Result<(), InsertionError> {
-        let day_to_timetable = self
+        let local_zone_to_day_to_timetable = self
             .data
-            .entry(vehicle_journey_idx.clone())
-            .or_insert_with(HashMap::new)
+            .get_or_insert_with(vehicle_journey_idx.clone(), Arc::default);
+        let day_to_timetable = Arc::make_mut(local_zone_to_day_to_timetable)
             .entry(local_zone)
             .or_insert_with(DayToTimetable::new);
 
@@ -159,7 +166,7 @@ where
         local_zone: LocalZone,
     ) -> Option<&mut DayToTimetable<Timetable>> {
         let has_day_to_timetable = self.data.get_mut(vehicle_journey_idx)?;
-        has_day_to_timetable.get_mut(&local_zone)
+        Arc::make_mut(has_day_to_timetable).get_mut(&local_zone)
     }
 
     fn get_day_to_timetable(
@@ -237,6 +244,28 @@ where
             None => Vec::new(),
         }
     }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let vehicle_journeys_bytes: usize = self
+            .data
+            .values()
+            .map(|local_zones| {
+                let days_maps_bytes: usize = local_zones
+                    .values()
+                    .map(|day_to_timetable| {
+                        day_to_timetable.base.heap_bytes() + day_to_timetable.real_time.heap_bytes()
+                    })
+                    .sum();
+                size_of::<HashMap<LocalZone, DayToTimetable<Timetable>>>()
+                    + hash_map_bytes(local_zones)
+                    + days_maps_bytes
+            })
+            .sum();
+        MemoryUsage::new(
+            "vehicle_journey_to_timetable",
+            shared_map_bytes(&self.data) + vehicle_journeys_bytes,
+        )
+    }
 }
 #[derive(Debug)]
 pub enum InsertionError {
@@ -34,24 +34,30 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
-use std::{collections::BTreeMap, fmt::Debug};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Debug, mem::size_of, sync::Arc};
 use tracing::debug;
 use FlowDirection::{BoardAndDebark, BoardOnly, DebarkOnly, NoBoardDebark};
 
 use crate::{
+    memory_usage::{nested_vec_bytes, shared_map_bytes, shared_vec_bytes, vec_bytes, MemoryUsage},
     models::StopTimeIdx,
+    parallel::map_on_threads,
+    shared_collections::{SharedMap, SharedVec},
     time::DaysSinceDatasetStart,
     timetables::{FlowDirection, StopFlows},
     transit_data::Stop,
 };
 
-#[derive(Debug)]
+// Cloning a GenericTimetables only clones pointers to chunks of timetables,
+// which are shared between the clones until one of them modifies a timetable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(super) struct GenericTimetables<Time, Load, VehicleData> {
-    pub(super) stop_flows_to_timetables: BTreeMap<StopFlows, Vec<Timetable>>,
-    pub(super) timetable_datas: Vec<TimetableData<Time, Load, VehicleData>>,
+    pub(super) stop_flows_to_timetables: SharedMap<Arc<StopFlows>, Vec<Timetable>>,
+    pub(super) timetable_datas: SharedVec<Arc<TimetableData<Time, Load, VehicleData>>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(super) struct TimetableData<Time, Load, VehicleData> {
     pub(super) stop_flows: StopFlows,
 
@@ -82,12 +88,32 @@ pub(super) struct TimetableData<Time, Load, VehicleData> {
     pub(super) debark_times_by_position: Vec<Vec<Time>>,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+/// A vehicle to insert with [`GenericTimetables::insert_batch()`].
+pub(super) struct VehicleToInsert<Time, Load, VehicleData> {
+    pub(super) stop_flows: StopFlows,
+    pub(super) board_times: Vec<Time>,
+    pub(super) debark_times: Vec<Time>,
+    pub(super) loads: Vec<Load>,
+    pub(super) vehicle_data: VehicleData,
+}
+
+// Where a timetable built by `insert_batch()` comes from
+enum TimetableOrigin {
+    // the timetable existed before the batch
+    Existing(Timetable),
+    // the timetable was created to receive the vehicle at this position in the batch
+    CreatedBy(usize),
+}
+
+type TimetableToFill<Time, Load, VehicleData> =
+    (TimetableOrigin, Arc<TimetableData<Time, Load, VehicleData>>);
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
 pub struct Timetable {
     pub(super) idx: usize,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct Position {
     pub(super) timetable: Timetable,
     pub(super) idx: usize,
@@ -112,8 +138,8 @@ where
 {
     pub(super) fn new() -> Self {
         Self {
-            stop_flows_to_timetables: BTreeMap::new(),
-            timetable_datas: Vec::new(),
+            stop_flows_to_timetables: SharedMap::new(),
+            timetable_datas: SharedVec::new(),
         }
     }
 
@@ -128,11 +154,15 @@ where
         &self.timetable_datas[timetable.idx]
     }
 
+    // The timetable is copied if it is shared with a clone of self
     pub(super) fn timetable_data_mut(
         &mut self,
         timetable: &Timetable,
-    ) -> &mut TimetableData<Time, Load, VehicleData> {
-        &mut self.timetable_datas[timetable.idx]
+    ) -> &mut TimetableData<Time, Load, VehicleData>
+    where
+        VehicleData: Clone,
+    {
+        Arc::make_mut(&mut self.timetable_datas[timetable.idx])
     }
 
     pub(super) fn vehicle_data(&self, vehicle: &Vehicle) -> &VehicleData {
@@ -301,10 +331,61 @@ where
     pub fn nb_of_trips(&self) -> usize {
         self.timetable_datas
             .iter()
-            .map(TimetableData::nb_of_vehicle)
+            .map(|timetable_data| timetable_data.nb_of_vehicle())
             .sum()
     }
 
+    pub(super) fn memory_usage(&self) -> MemoryUsage {
+        let datas = || self.timetable_datas.iter();
+        let stop_flows_to_timetables_bytes: usize = self
+            .stop_flows_to_timetables
+            .iter()
+            .map(|(stop_flows, timetables)| vec_bytes(stop_flows) + vec_bytes(timetables))
+            .sum();
+        MemoryUsage::with_components(
+            "timetables",
+            vec![
+                MemoryUsage::new(
+                    "stop_flows",
+                    shared_vec_bytes(&self.timetable_datas)
+                        + datas()
+                            .map(|data| {
+                                size_of::<TimetableData<Time, Load, VehicleData>>()
+                                    + vec_bytes(&data.stop_flows)
+                            })
+                            .sum::<usize>(),
+                ),
+                MemoryUsage::new(
+                    "vehicle_datas",
+                    datas().map(|data| vec_bytes(&data.vehicle_datas)).sum(),
+                ),
+                MemoryUsage::new(
+                    "loads",
+                    datas()
+                        .map(|data| nested_vec_bytes(&data.vehicle_loads))
+                        .sum(),
+                ),
+                MemoryUsage::new(
+                    "board_times",
+                    datas()
+                        .map(|data| nested_vec_bytes(&data.board_times_by_position))
+                        .sum(),
+                ),
+                MemoryUsage::new(
+                    "debark_times",
+                    datas()
+                        .map(|data| nested_vec_bytes(&data.debark_times_by_position))
+                        .sum(),
+                ),
+                MemoryUsage::new(
+                    "stop_flows_to_timetables",
+                    shared_map_bytes(&self.stop_flows_to_timetables)
+                        + stop_flows_to_timetables_bytes,
+                ),
+            ],
+        )
+    }
+
     // Insert in the trip in a timetable if
     // the given debark_times, board_times and loads are coherent.
     // Returns a VehicleTimesError otherwise.
@@ -333,35 +414,39 @@ where
         assert!(nb_of_positions == loads.len() + 1);
         inspect(flows.clone(), board_times.clone(), debark_times.clone())?;
 
-        let corrected_board_debark_times = board_times.zip(debark_times).zip(flows.clone()).map(
-            |((board_time, debark_time), flow_direction)| match flow_direction {
-                BoardOnly => (board_time.clone(), board_time),
-                DebarkOnly => (debark_time.clone(), debark_time),
-                BoardAndDebark | NoBoardDebark => (board_time, debark_time),
-            },
-        );
+        let corrected_board_debark_times =
+            corrected_board_debark_times(board_times, debark_times, flows.clone());
         let corrected_board_times = corrected_board_debark_times.clone().map(|(board, _)| board);
         let corrected_debark_times = corrected_board_debark_times.map(|(_, debark)| debark);
         let stop_flows: Vec<(Stop, FlowDirection)> = stops.zip(flows).collect();
         let stop_flows_timetables = self
             .stop_flows_to_timetables
-            .entry(stop_flows.clone())
-            .or_insert_with(Vec::new);
+            .get(&stop_flows)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
 
         for timetable in stop_flows_timetables.iter() {
-            let timetable_data = &mut self.timetable_datas[timetable.idx];
-            let is_inserted = timetable_data.try_insert(
+            let has_insert_idx = self.timetable_datas[timetable.idx].find_insert_idx(
                 corrected_board_times.clone(),
                 corrected_debark_times.clone(),
                 loads.clone(),
-                vehicle_data.clone(),
             );
-            if is_inserted {
+            if let Some(insert_idx) = has_insert_idx {
+                // we look for the insertion position before calling make_mut()
+                // so that we copy only the timetable that will actually be modified
+                let timetable_data = Arc::make_mut(&mut self.timetable_datas[timetable.idx]);
+                timetable_data.do_insert(
+                    corrected_board_times,
+                    corrected_debark_times,
+                    loads,
+                    vehicle_data,
+                    insert_idx,
+                );
                 return Ok(timetable.clone());
             }
         }
         let new_timetable_data = TimetableData::new(
-            stop_flows,
+            stop_flows.clone(),
             corrected_board_times,
             corrected_debark_times,
             loads,
@@ -370,10 +455,217 @@ where
         let timetable = Timetable {
             idx: self.timetable_datas.len(),
         };
-        self.timetable_datas.push(new_timetable_data);
-        stop_flows_timetables.push(timetable.clone());
+        self.timetable_datas.push(Arc::new(new_timetable_data));
+        self.stop_flows_to_timetables
+            .get_or_insert_with(Arc::new(stop_flows), Vec::new)
+            .push(timetable.clone());
         Ok(timetable)
     }
+
+    // Inserts the vehicles as `insert()` would if it were called on each of them in order,
+    // and returns the timetable of each vehicle.
+    // A vehicle is only inserted in a timetable with the same stop_flows, so the vehicles
+    // of each stop_flows are inserted independently from the others, on `nb_of_threads` threads.
+    // New timetables are then numbered in the order in which `insert()` would have created them,
+    // so that the result does not depend on `nb_of_threads`.
+    pub(super) fn insert_batch(
+        &mut self,
+        vehicles: Vec<VehicleToInsert<Time, Load, VehicleData>>,
+        nb_of_threads: usize,
+    ) -> Vec<Result<Timetable, VehicleTimesError>>
+    where
+        Time: Send + Sync,
+        Load: Send + Sync,
+        VehicleData: Clone + Send + Sync,
+    {
+        let mut vehicles_by_stop_flows: BTreeMap<&StopFlows, Vec<usize>> = BTreeMap::new();
+        for (vehicle_idx, vehicle) in vehicles.iter().enumerate() {
+            vehicles_by_stop_flows
+                .entry(&vehicle.stop_flows)
+                .or_insert_with(Vec::new)
+                .push(vehicle_idx);
+        }
+        let vehicles_by_stop_flows: Vec<(&StopFlows, Vec<usize>)> =
+            vehicles_by_stop_flows.into_iter().collect();
+
+        let inserted_by_stop_flows = map_on_threads(
+            &vehicles_by_stop_flows,
+            nb_of_threads,
+            |(stop_flows, vehicle_idxs)| {
+                // an existing timetable is copied only if a vehicle is inserted in it
+                let mut timetables: Vec<_> = self
+                    .stop_flows_to_timetables
+                    .get(*stop_flows)
+                    .map(Vec::as_slice)
+                    .unwrap_or(&[])
+                    .iter()
+                    .map(|timetable| {
+                        (
+                            TimetableOrigin::Existing(timetable.clone()),
+                            self.timetable_datas[timetable.idx].clone(),
+                        )
+                    })
+                    .collect();
+                let positions =
+                    insert_with_same_stop_flows(&mut timetables, vehicle_idxs, &vehicles);
+                (timetables, positions)
+            },
+        );
+
+        // timetables of each stop_flows, by their position in the vector built above
+        let mut stop_flows_timetables: Vec<Vec<Option<Timetable>>> = Vec::new();
+        let mut created_timetables = Vec::new();
+        for (stop_flows_idx, (timetables, _)) in inserted_by_stop_flows.iter().enumerate() {
+            let mut positions = Vec::with_capacity(timetables.len());
+            for (position, (origin, timetable_data)) in timetables.iter().enumerate() {
+                match origin {
+                    TimetableOrigin::Existing(timetable) => {
+                        self.timetable_datas[timetable.idx] = timetable_data.clone();
+                        positions.push(Some(timetable.clone()));
+                    }
+                    TimetableOrigin::CreatedBy(vehicle_idx) => {
+                        created_timetables.push((*vehicle_idx, stop_flows_idx, position));
+                        positions.push(None);
+                    }
+                }
+            }
+            stop_flows_timetables.push(positions);
+        }
+
+        created_timetables.sort_unstable();
+        for (_, stop_flows_idx, position) in created_timetables {
+            let (timetables, _) = &inserted_by_stop_flows[stop_flows_idx];
+            let timetable_data = timetables[position].1.clone();
+            let timetable = Timetable {
+                idx: self.timetable_datas.len(),
+            };
+            self.stop_flows_to_timetables
+                .get_or_insert_with(Arc::new(timetable_data.stop_flows.clone()), Vec::new)
+                .push(timetable.clone());
+            self.timetable_datas.push(timetable_data);
+            stop_flows_timetables[stop_flows_idx][position] = Some(timetable);
+        }
+
+        let mut result: Vec<Option<Result<Timetable, VehicleTimesError>>> =
+            Vec::with_capacity(vehicles.len());
+        result.resize_with(vehicles.len(), || None);
+        for (stop_flows_idx, (_, positions)) in inserted_by_stop_flows.into_iter().enumerate() {
+            let vehicle_idxs = &vehicles_by_stop_flows[stop_flows_idx].1;
+            for (vehicle_idx, position) in vehicle_idxs.iter().zip(positions) {
+                result[*vehicle_idx] = Some(position.map(|position| {
+                    // unwrap is safe since all timetables were numbered above
+                    stop_flows_timetables[stop_flows_idx][position]
+                        .clone()
+                        .unwrap()
+                }));
+            }
+        }
+        result
+            .into_iter()
+            // unwrap is safe since each vehicle belongs to exactly one stop_flows
+            .map(Option::unwrap)
+            .collect()
+    }
+}
+
+// At a position where a vehicle can only board (resp. debark),
+// its debark (resp. board) time is replaced by its board (resp. debark) time.
+fn corrected_board_debark_times<BoardTimes, DebarkTimes, Flows, Time>(
+    board_times: BoardTimes,
+    debark_times: DebarkTimes,
+    flows: Flows,
+) -> impl ExactSizeIterator<Item = (Time, Time)> + Clone
+where
+    BoardTimes: Iterator<Item = Time> + ExactSizeIterator + Clone,
+    DebarkTimes: Iterator<Item = Time> + ExactSizeIterator + Clone,
+    Flows: Iterator<Item = FlowDirection> + ExactSizeIterator + Clone,
+    Time: Clone,
+{
+    board_times
+        .zip(debark_times)
+        .zip(flows)
+        .map(
+            |((board_time, debark_time), flow_direction)| match flow_direction {
+                BoardOnly => (board_time.clone(), board_time),
+                DebarkOnly => (debark_time.clone(), debark_time),
+                BoardAndDebark | NoBoardDebark => (board_time, debark_time),
+            },
+        )
+}
+
+// Inserts the vehicles at `vehicle_idxs` in `timetables`, which all have the same stop_flows,
+// in the same way as `GenericTimetables::insert()`.
+// Returns, for each vehicle, the position in `timetables` of the timetable it was inserted in.
+fn insert_with_same_stop_flows<Time, Load, VehicleData>(
+    timetables: &mut Vec<TimetableToFill<Time, Load, VehicleData>>,
+    vehicle_idxs: &[usize],
+    vehicles: &[VehicleToInsert<Time, Load, VehicleData>],
+) -> Vec<Result<usize, VehicleTimesError>>
+where
+    Time: Ord + Clone + Debug,
+    Load: Ord + Clone + Debug,
+    VehicleData: Clone,
+{
+    let mut result = Vec::with_capacity(vehicle_idxs.len());
+    for vehicle_idx in vehicle_idxs {
+        let vehicle = &vehicles[*vehicle_idx];
+        let flows = vehicle.stop_flows.iter().map(|(_, flow)| *flow);
+        let board_times = vehicle.board_times.iter().cloned();
+        let debark_times = vehicle.debark_times.iter().cloned();
+        let loads = vehicle.loads.iter().cloned();
+        if let Err(err) = inspect(flows.clone(), board_times.clone(), debark_times.clone()) {
+            result.push(Err(err));
+            continue;
+        }
+
+        let corrected_board_debark_times =
+            corrected_board_debark_times(board_times, debark_times, flows);
+        let corrected_board_times = corrected_board_debark_times.clone().map(|(board, _)| board);
+        let corrected_debark_times = corrected_board_debark_times.map(|(_, debark)| debark);
+
+        let has_insert_idx =
+            timetables
+                .iter()
+                .enumerate()
+                .find_map(|(position, (_, timetable_data))| {
+                    timetable_data
+                        .find_insert_idx(
+                            corrected_board_times.clone(),
+                            corrected_debark_times.clone(),
+                            loads.clone(),
+                        )
+                        .map(|insert_idx| (position, insert_idx))
+                });
+        let position = match has_insert_idx {
+            Some((position, insert_idx)) => {
+                let timetable_data = Arc::make_mut(&mut timetables[position].1);
+                timetable_data.do_insert(
+                    corrected_board_times,
+                    corrected_debark_times,
+                    loads,
+                    vehicle.vehicle_data.clone(),
+                    insert_idx,
+                );
+                position
+            }
+            None => {
+                let new_timetable_data = TimetableData::new(
+                    vehicle.stop_flows.clone(),
+                    corrected_board_times,
+                    corrected_debark_times,
+                    loads,
+                    vehicle.vehicle_data.clone(),
+                );
+                timetables.push((
+                    TimetableOrigin::CreatedBy(*vehicle_idx),
+                    Arc::new(new_timetable_data),
+                ));
+                timetables.len() - 1
+            }
+        };
+        result.push(Ok(position));
+    }
+    result
 }
 
 fn is_increasing<EnumeratedValues, Value>(
@@ -323,35 +323,9 @@ where
         result
     }
 
-    // Try to insert the trip in this timetable
-    // Returns `true` if insertion was succesfull, `false` otherwise
-    pub(super) fn try_insert<BoardTimes, DebarkTimes, Loads>(
-        &mut self,
-        board_times: BoardTimes,
-        debark_times: DebarkTimes,
-        loads: Loads,
-        vehicle_data: VehicleData,
-    ) -> bool
-    where
-        BoardTimes: Iterator<Item = Time> + ExactSizeIterator + Clone,
-        DebarkTimes: Iterator<Item = Time> + ExactSizeIterator + Clone,
-        Loads: Iterator<Item = Load> + ExactSizeIterator + Clone,
-        Time: Clone,
-    {
-        assert!(board_times.len() == self.nb_of_positions());
-        assert!(debark_times.len() == self.nb_of_positions());
-        assert!(loads.len() + 1 == self.nb_of_positions());
-        let has_insert_idx =
-            self.find_insert_idx(board_times.clone(), debark_times.clone(), loads.clone());
-        if let Some(insert_idx) = has_insert_idx {
-            self.do_insert(board_times, debark_times, loads, vehicle_data, insert_idx);
-            true
-        } else {
-            false
-        }
-    }
-
-    fn find_insert_idx<BoardTimes, DebarkTimes, Loads>(
+    // Returns the position at which the trip can be inserted in this timetable,
+    // or None if the trip cannot be inserted.
+    pub(super) fn find_insert_idx<BoardTimes, DebarkTimes, Loads>(
         &self,
         board_times: BoardTimes,
         debark_times: DebarkTimes,
@@ -364,6 +338,9 @@ where
         Time: Debug,
         Load: Debug,
     {
+        assert!(board_times.len() == self.nb_of_positions());
+        assert!(debark_times.len() == self.nb_of_positions());
+        assert!(loads.len() + 1 == self.nb_of_positions());
         let nb_of_vehicle = self.nb_of_vehicle();
         if nb_of_vehicle == 0 {
             return Some(0);
@@ -36,6 +36,7 @@
 
 use crate::{
     loads_data::{Load, LoadsData},
+    memory_usage::MemoryUsage,
     models::VehicleJourneyIdx,
     time::{
         calendar::DecomposeUTCResult,
@@ -48,7 +49,7 @@ use crate::{
 
 use super::{
     day_to_timetable::LocalZone,
-    generic_timetables::{GenericTimetables, Vehicle},
+    generic_timetables::{self, GenericTimetables, Vehicle},
     timetable_iters::{PositionsIter, TimetableIter},
 };
 use crate::time::{
@@ -56,19 +57,47 @@ use crate::time::{
     SecondsSinceUTCDayStart, TimezonesPatterns,
 };
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
 use tracing::log::error;
 
-use crate::timetables::FlowDirection;
+use crate::timetables::{FlowDirection, StopFlows};
 
 pub use super::generic_timetables::{Position, Timetable as Mission, Trip};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct UTCTimetables {
     timetables: GenericTimetables<SecondsSinceUTCDayStart, Load, VehicleData>,
     timezones_patterns: TimezonesPatterns,
 }
 
-#[derive(Debug, Clone)]
+type GenericVehicleToInsert =
+    generic_timetables::VehicleToInsert<SecondsSinceUTCDayStart, Load, VehicleData>;
+
+/// A vehicle to be inserted : its stops and times, the days on which it is valid,
+/// and the vehicle journey it comes from.
+///
+/// In the timetables, `Stops` yields [`Stop`]s and `Days` is a [`DaysPattern`],
+/// while [`TransitData`](crate::TransitData) takes stop points and dates.
+pub struct VehicleInput<'a, Stops, Flows, BoardTimes, DebarkTimes, Days> {
+    pub stops: Stops,
+    pub flows: Flows,
+    pub board_times: BoardTimes,
+    pub debark_times: DebarkTimes,
+    pub days: Days,
+    pub timezone: chrono_tz::Tz,
+    pub vehicle_journey_idx: &'a VehicleJourneyIdx,
+    pub local_zone: LocalZone,
+    pub real_time_level: RealTimeLevel,
+}
+
+/// A vehicle whose times have been checked, ready to be inserted in the timetables.
+pub struct VehicleToInsert {
+    vehicle: GenericVehicleToInsert,
+    days_pattern: DaysPattern,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VehicleData {
     vehicle_journey_idx: VehicleJourneyIdx,
     base_days_pattern: DaysPattern,
@@ -119,6 +148,16 @@ impl UTCTimetables {
         self.timetables.nb_of_trips()
     }
 
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage::with_components(
+            "utc_timetables",
+            vec![
+                self.timetables.memory_usage(),
+                MemoryUsage::new("timezones_patterns", self.timezones_patterns.heap_bytes()),
+            ],
+        )
+    }
+
     pub fn is_upstream_in_mission(
         &self,
         upstream: &Position,
@@ -367,18 +406,10 @@ impl UTCTimetables {
 
     pub fn insert<Stops, Flows, BoardTimes, DebarkTimes>(
         &mut self,
-        stops: Stops,
-        flows: Flows,
-        board_times: BoardTimes,
-        debark_times: DebarkTimes,
+        vehicle: VehicleInput<'_, Stops, Flows, BoardTimes, DebarkTimes, &DaysPattern>,
         loads_data: &LoadsData,
-        days: &DaysPattern,
         calendar: &Calendar,
         days_patterns: &mut DaysPatterns,
-        timezone: chrono_tz::Tz,
-        vehicle_journey_idx: &VehicleJourneyIdx,
-        local_zone: LocalZone,
-        real_time_level: RealTimeLevel,
     ) -> Result<HashMap<Mission, DaysPattern>, (VehicleTimesError, Vec<NaiveDate>)>
     where
         Stops: Iterator<Item = Stop> + ExactSizeIterator + Clone,
@@ -386,6 +417,37 @@ impl UTCTimetables {
         BoardTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
         DebarkTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
     {
+        let vehicles = self.prepare_insert(vehicle, loads_data, calendar, days_patterns)?;
+        Ok(self.insert_prepared(vehicles, days_patterns))
+    }
+
+    // Checks the times of the vehicle, and returns the vehicles to be inserted
+    // in the timetables : one for each set of loads and timezone offset.
+    // The timetables are not modified.
+    pub fn prepare_insert<Stops, Flows, BoardTimes, DebarkTimes>(
+        &mut self,
+        vehicle: VehicleInput<'_, Stops, Flows, BoardTimes, DebarkTimes, &DaysPattern>,
+        loads_data: &LoadsData,
+        calendar: &Calendar,
+        days_patterns: &mut DaysPatterns,
+    ) -> Result<Vec<VehicleToInsert>, (VehicleTimesError, Vec<NaiveDate>)>
+    where
+        Stops: Iterator<Item = Stop> + ExactSizeIterator + Clone,
+        Flows: Iterator<Item = FlowDirection> + ExactSizeIterator + Clone,
+        BoardTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
+        DebarkTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
+    {
+        let VehicleInput {
+            stops,
+            flows,
+            board_times,
+            debark_times,
+            days,
+            timezone,
+            vehicle_journey_idx,
+            local_zone,
+            real_time_level,
+        } = vehicle;
         let mut load_patterns_dates: BTreeMap<&[Load], Vec<NaiveDate>> = BTreeMap::new();
 
         let nb_of_positions = stops.len();
@@ -434,7 +496,8 @@ impl UTCTimetables {
             }
         }
 
-        let mut result = HashMap::new();
+        let stop_flows: StopFlows = stops.zip(flows).collect();
+        let mut result = Vec::new();
 
         for (loads, dates) in load_patterns_dates.into_iter() {
             let all_days_pattern = days_patterns.get_from_dates(dates.iter(), calendar);
@@ -466,36 +529,90 @@ impl UTCTimetables {
                     time_in_timezoned_day.to_utc(offset)
                 };
 
-                let insert_result = self.timetables.insert(
-                    stops.clone(),
-                    flows.clone(),
-                    board_times.clone().map(apply_offset),
-                    debark_times.clone().map(apply_offset),
-                    loads.iter().copied(),
+                let vehicle = GenericVehicleToInsert {
+                    stop_flows: stop_flows.clone(),
+                    board_times: board_times.clone().map(apply_offset).collect(),
+                    debark_times: debark_times.clone().map(apply_offset).collect(),
+                    loads: loads.to_vec(),
                     vehicle_data,
-                );
-                match insert_result {
-                    Ok(mission) => {
-                        let pattern = result
-                            .entry(mission)
-                            .or_insert_with(|| days_patterns.empty_pattern());
-                        *pattern = days_patterns.get_union(*pattern, days_pattern);
-                    }
-                    Err(times_error) => {
-                        // this should not happen, since we inspect the times above
-                        // an returns early with an error if insertion should fail.
-                        // Let's log an error if this happens anyway
-                        error!(
-                            "An error occured while inserting a vehicle. {:?}",
-                            times_error
-                        );
-                    }
-                }
+                };
+                result.push(VehicleToInsert {
+                    vehicle,
+                    days_pattern,
+                });
             }
         }
         Ok(result)
     }
 
+    // Inserts vehicles obtained from `prepare_insert()`,
+    // and returns the missions they were inserted in, along with the days
+    // on which they are valid in each mission.
+    pub fn insert_prepared(
+        &mut self,
+        vehicles: Vec<VehicleToInsert>,
+        days_patterns: &mut DaysPatterns,
+    ) -> HashMap<Mission, DaysPattern> {
+        let mut result = HashMap::new();
+        for VehicleToInsert {
+            vehicle,
+            days_pattern,
+        } in vehicles
+        {
+            let insert_result = self.timetables.insert(
+                vehicle.stop_flows.iter().map(|(stop, _)| *stop),
+                vehicle.stop_flows.iter().map(|(_, flow)| *flow),
+                vehicle.board_times.into_iter(),
+                vehicle.debark_times.into_iter(),
+                vehicle.loads.into_iter(),
+                vehicle.vehicle_data,
+            );
+            add_mission(&mut result, insert_result, days_pattern, days_patterns);
+        }
+        result
+    }
+
+    // Same as calling `insert_prepared()` on each element of `vehicles` in order,
+    // with the insertion in timetables made on `nb_of_threads` threads.
+    pub fn insert_prepared_batch(
+        &mut self,
+        vehicles: Vec<Vec<VehicleToInsert>>,
+        days_patterns: &mut DaysPatterns,
+        nb_of_threads: usize,
+    ) -> Vec<HashMap<Mission, DaysPattern>> {
+        let mut days_pattern_of_vehicle = Vec::new();
+        let mut nb_of_vehicles = Vec::with_capacity(vehicles.len());
+        let mut generic_vehicles = Vec::new();
+        for prepared_vehicles in vehicles {
+            nb_of_vehicles.push(prepared_vehicles.len());
+            for VehicleToInsert {
+                vehicle,
+                days_pattern,
+            } in prepared_vehicles
+            {
+                generic_vehicles.push(vehicle);
+                days_pattern_of_vehicle.push(days_pattern);
+            }
+        }
+
+        let mut insert_results = self
+            .timetables
+            .insert_batch(generic_vehicles, nb_of_threads)
+            .into_iter()
+            .zip(days_pattern_of_vehicle);
+
+        nb_of_vehicles
+            .into_iter()
+            .map(|nb_of_vehicles| {
+                let mut result = HashMap::new();
+                for (insert_result, days_pattern) in insert_results.by_ref().take(nb_of_vehicles) {
+                    add_mission(&mut result, insert_result, days_pattern, days_patterns);
+                }
+                result
+            })
+            .collect()
+    }
+
     pub fn find_trip(
         &self,
         timetable: &Mission,
@@ -637,6 +754,32 @@ impl UTCTimetables {
     }
 }
 
+// Adds the days of a vehicle to the days of the mission it was inserted in.
+fn add_mission(
+    missions: &mut HashMap<Mission, DaysPattern>,
+    insert_result: Result<Mission, VehicleTimesError>,
+    days_pattern: DaysPattern,
+    days_patterns: &mut DaysPatterns,
+) {
+    match insert_result {
+        Ok(mission) => {
+            let pattern = missions
+                .entry(mission)
+                .or_insert_with(|| days_patterns.empty_pattern());
+            *pattern = days_patterns.get_union(*pattern, days_pattern);
+        }
+        Err(times_error) => {
+            // this should not happen, since the times are inspected in prepare_insert()
+            // which returns early with an error if insertion should fail.
+            // Let's log an error if this happens anyway
+            error!(
+                "An error occured while inserting a vehicle. {:?}",
+                times_error
+            );
+        }
+    }
+}
+
 pub struct TripsIter<'a> {
     utc_timetables: &'a UTCTimetables,
     current_vehicle_days: Option<(Vehicle, DaysInPatternIter<'a>)>,
@@ -42,7 +42,9 @@ use chrono::NaiveDate;
 
 use crate::{
     loads_data::Load,
+    memory_usage::{hash_map_bytes, shared_map_bytes, shared_vec_bytes, vec_bytes, MemoryUsage},
     models::{ModelRefs, StopPointIdx, StopTimeIdx, TransferIdx, VehicleJourneyIdx},
+    shared_collections::{SharedMap, SharedVec},
     time::{days_patterns::DaysPatterns, Calendar, PositiveDuration, SecondsSinceDatasetUTCStart},
     timetables::{
         day_to_timetable::VehicleJourneyToTimetable,
@@ -53,7 +55,8 @@ use crate::{
     RealTimeLevel,
 };
 
-use std::{collections::HashMap, fmt::Debug};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Debug, mem::size_of, sync::Arc};
 
 use crate::timetables::RemovalError;
 
@@ -65,23 +68,35 @@ pub type Timetables = utc_timetables::UTCTimetables;
 
 pub use utc_timetables::{Mission, Position, Trip};
 
+// Cloning a TransitData is cheap compared to building it :
+// the stops and timetables data are shared between the clones,
+// by chunks that are copied only when a clone modifies them.
+// This allows to apply real time updates on a new version of the data
+// while the previous version is still used to answer requests.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TransitData {
-    pub(super) stop_point_idx_to_stop: HashMap<StopPointIdx, Stop>,
+    pub(super) stop_point_idx_to_stop: SharedMap<StopPointIdx, Stop>,
 
-    pub(super) stops_data: Vec<StopData>,
+    pub(super) stops_data: SharedVec<Arc<StopData>>,
     pub(super) timetables: Timetables,
 
-    pub(super) transfers_data: Vec<TransferData>,
+    // transfers are not modified by real time updates
+    pub(super) transfers_data: Arc<Vec<TransferData>>,
 
     pub(super) vehicle_journey_to_timetable: VehicleJourneyToTimetable<Mission>,
 
     pub(super) calendar: Calendar,
     pub(super) days_patterns: DaysPatterns,
 
-    pub(super) vehicle_journey_to_next_stay_in: HashMap<VehicleJourneyIdx, VehicleJourneyIdx>,
-    pub(super) vehicle_journey_to_prev_stay_in: HashMap<VehicleJourneyIdx, VehicleJourneyIdx>,
+    // stay-ins are not modified by real time updates
+    pub(super) vehicle_journey_to_next_stay_in: Arc<HashMap<VehicleJourneyIdx, VehicleJourneyIdx>>,
+    pub(super) vehicle_journey_to_prev_stay_in: Arc<HashMap<VehicleJourneyIdx, VehicleJourneyIdx>>,
+
+    // number of base vehicle journeys that could not be inserted, on some or all of their dates
+    pub(super) nb_of_base_insertion_errors: usize,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StopData {
     pub(super) stop_point_idx: StopPointIdx,
     pub(super) position_in_timetables: Vec<(Mission, Position)>,
@@ -89,12 +104,15 @@ pub struct StopData {
     pub(super) incoming_transfers: Vec<(Stop, TransferDurations, Transfer)>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct TransferDurations {
     pub walking_duration: PositiveDuration,
     pub total_duration: PositiveDuration, // = walking_duration + some waiting time
+    /// total duration for a wheelchair user, None if the transfer is not wheelchair accessible
+    pub wheelchair_duration: Option<PositiveDuration>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TransferData {
     pub from_stop: Stop,
     pub to_stop: Stop,
@@ -102,12 +120,12 @@ pub struct TransferData {
     pub transit_model_transfer_idx: TransferIdx,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Ord, PartialOrd)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct Stop {
     pub(super) idx: usize,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub struct Transfer {
     pub(super) idx: usize,
 }
@@ -120,6 +138,65 @@ impl TransitData {
     pub fn stop_point_idx_to_stop(&self, stop_point_idx: &StopPointIdx) -> Option<&Stop> {
         self.stop_point_idx_to_stop.get(stop_point_idx)
     }
+
+    pub fn transfer_durations(&self, transfer: &Transfer) -> &TransferDurations {
+        &self.transfers_data[transfer.idx].durations
+    }
+
+    /// Number of vehicle journeys of the base model that were skipped,
+    /// or could not be inserted on some of their dates, when this data was built.
+    pub fn nb_of_base_insertion_errors(&self) -> usize {
+        self.nb_of_base_insertion_errors
+    }
+
+    /// Memory used by each component, where stops added by real time updates
+    /// are counted apart from the base stops.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let stop_data_bytes = |stop_data: &Arc<StopData>| {
+            size_of::<StopData>()
+                + vec_bytes(&stop_data.position_in_timetables)
+                + vec_bytes(&stop_data.outgoing_transfers)
+                + vec_bytes(&stop_data.incoming_transfers)
+        };
+        let is_base_stop = |stop_data: &&Arc<StopData>| match stop_data.stop_point_idx {
+            StopPointIdx::Base(_) => true,
+            StopPointIdx::New(_) => false,
+        };
+        let base_stops_bytes: usize = self
+            .stops_data
+            .iter()
+            .filter(is_base_stop)
+            .map(stop_data_bytes)
+            .sum();
+        let real_time_stops_bytes: usize = self
+            .stops_data
+            .iter()
+            .filter(|stop_data| !is_base_stop(stop_data))
+            .map(stop_data_bytes)
+            .sum();
+
+        MemoryUsage::with_components(
+            "transit_data",
+            vec![
+                MemoryUsage::new(
+                    "stops",
+                    shared_vec_bytes(&self.stops_data)
+                        + shared_map_bytes(&self.stop_point_idx_to_stop)
+                        + base_stops_bytes,
+                ),
+                MemoryUsage::new("real_time_stops", real_time_stops_bytes),
+                self.timetables.memory_usage(),
+                MemoryUsage::new("transfers", vec_bytes(&self.transfers_data)),
+                self.vehicle_journey_to_timetable.memory_usage(),
+                self.days_patterns.memory_usage(),
+                MemoryUsage::new(
+                    "stay_ins",
+                    hash_map_bytes(&self.vehicle_journey_to_next_stay_in)
+                        + hash_map_bytes(&self.vehicle_journey_to_prev_stay_in),
+                ),
+            ],
+        )
+    }
 }
 
 impl data_interface::TransitTypes for TransitData {
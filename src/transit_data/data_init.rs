@@ -35,22 +35,24 @@
 // www.navitia.io
 
 use crate::{
-    loads_data::LoadsData,
     models::{
         base_model::{BaseModel, BaseTransferIdx},
         real_time_model::RealTimeModel,
         ModelRefs, StopPointIdx, TransferIdx, VehicleJourneyIdx,
     },
-    time::{days_patterns::DaysPatterns, Calendar},
-    timetables::{day_to_timetable::VehicleJourneyToTimetable, FlowDirection::*},
+    parallel::{default_nb_of_threads, map_on_threads},
+    shared_collections::{SharedMap, SharedVec},
+    time::{days_patterns::DaysPatterns, Calendar, SecondsSinceTimezonedDayStart},
+    timetables::{
+        day_to_timetable::{LocalZone, VehicleJourneyToTimetable},
+        utc_timetables::VehicleInput,
+        FlowDirection::{self, *},
+    },
     transit_data::{data_interface::Data as DataInterface, Stop, TransitData},
     RealTimeLevel,
 };
-use std::collections::HashMap;
-
-use crate::time::PositiveDuration;
-use transit_model::objects::VehicleJourney;
-use typed_index_collection::Idx;
+use chrono::NaiveDate;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::models::base_model::BaseVehicleJourneyIdx;
 use tracing::{info, warn};
@@ -166,9 +168,175 @@ impl VJGroupedByStayIn {
     }
 }
 
+// What is read from the base model to insert a vehicle journey
+struct BaseVehicleJourney {
+    stops: Vec<StopPointIdx>,
+    board_times: Vec<SecondsSinceTimezonedDayStart>,
+    debark_times: Vec<SecondsSinceTimezonedDayStart>,
+    dates: Vec<NaiveDate>,
+    timezone: chrono_tz::Tz,
+    // the vehicle journey is inserted once for each of its local zones,
+    // with the flows to use on this local zone
+    flows_by_local_zone: Vec<(LocalZone, Vec<FlowDirection>)>,
+}
+
+fn read_base_vehicle_journey(
+    vehicle_journey_idx: BaseVehicleJourneyIdx,
+    vehicle_journey_to_prev_stay_in: &HashMap<BaseVehicleJourneyIdx, StayInType>,
+    vehicle_journey_to_next_stay_in: &HashMap<BaseVehicleJourneyIdx, StayInType>,
+    base_model: &BaseModel,
+) -> Result<BaseVehicleJourney, ()> {
+    let stop_times =
+        base_model
+            .stop_times(vehicle_journey_idx)
+            .map_err(|(err, stop_time_idx)| {
+                warn!(
+                    "Skipping vehicle journey {} because its {}-th stop time is ill formed {:?}.",
+                    base_model.vehicle_journey_name(vehicle_journey_idx),
+                    stop_time_idx.idx,
+                    err
+                );
+            })?;
+
+    if stop_times.len() < 2 {
+        warn!(
+            "Skipping vehicle journey {} because it has less than 2 stop times.",
+            base_model.vehicle_journey_name(vehicle_journey_idx),
+        );
+        return Err(());
+    }
+
+    let dates = base_model
+        .vehicle_journey_dates(vehicle_journey_idx)
+        .ok_or_else(|| {
+            warn!(
+                "Skipping vehicle journey {} because it has no dates.",
+                base_model.vehicle_journey_name(vehicle_journey_idx)
+            );
+        })?;
+
+    let timezone = base_model.timezone(vehicle_journey_idx).ok_or_else(|| {
+        warn!(
+            "Skipping vehicle journey {} because it has no timezone.",
+            base_model.vehicle_journey_name(vehicle_journey_idx)
+        );
+    })?;
+
+    /*
+     * Flow correction with stay-in
+     *
+     * The stay-in section is allowed with 2 configurations:
+     *  - when two VJ share the same stop point with similar stop times (example 1)
+     *  - when two VJ are joined on 2 different stop points with consecutive stop times (example 2)
+     *
+     *   Example 1:
+     *   ----------
+     *         out          in   out         in
+     *          X    SP1    |    ▲    SP2    X
+     *          X           ▼    |           X
+     *    VJ:1   08:00-09:00      10:00-11:00
+     *    VJ:2                    10:00-11:00      14:00-15:00
+     *                           X           ▲    |           X
+     *                           X           |    ▼   SP3     X
+     *                           out         in   out         in
+     *                           |- Stay-In -|
+     *
+     *   Example 2:
+     *   ----------
+     *                                       (1)  (2)
+     *         out          in   out         in   out         in   out         in
+     *          X    SP1    |    ▲    SP2    |    ▲    SP3    |    ▲   SP4     X
+     *          X           ▼    |           ▼    |           |    |           X
+     *    VJ:1   08:00-09:00      10:00-11:00     |           ▼    |           X
+     *    VJ:2                                     12:00-13:00      14:00-15:00
+     *                           |---------- Stay In ---------|
+     *
+     *  Example 2 is the only case were we allow specific pick-up and drop-off
+     */
+    let has_prev_stay_in_on_same_stop =
+        match vehicle_journey_to_prev_stay_in.get(&vehicle_journey_idx) {
+            Some(StayInType::SameStopPoint(_)) => true,
+            Some(StayInType::DifferentStopPoint(_)) | None => false,
+        };
+    let has_next_stay_in_on_same_stop =
+        match vehicle_journey_to_next_stay_in.get(&vehicle_journey_idx) {
+            Some(StayInType::SameStopPoint(_)) => true,
+            Some(StayInType::DifferentStopPoint(_)) | None => false,
+        };
+
+    let nb_of_positions = stop_times.len();
+    let corrected_flows = stop_times
+        .clone()
+        .enumerate()
+        .map(|(position_idx, stop_time)| {
+            let flow = stop_time.flow_direction;
+            if position_idx == 0 && has_prev_stay_in_on_same_stop {
+                match flow {
+                    BoardAndDebark | BoardOnly => BoardOnly,
+                    DebarkOnly | NoBoardDebark => NoBoardDebark,
+                }
+            } else if position_idx == nb_of_positions - 1 && has_next_stay_in_on_same_stop {
+                match flow {
+                    BoardAndDebark | DebarkOnly => DebarkOnly,
+                    BoardOnly | NoBoardDebark => NoBoardDebark,
+                }
+            } else {
+                flow
+            }
+        });
+
+    let mut local_zones: Vec<_> = stop_times.clone().map(|s| s.local_zone_id).collect();
+    local_zones.sort_unstable();
+    local_zones.dedup();
+
+    let flows_by_local_zone = if local_zones.len() == 1 {
+        vec![(local_zones[0], corrected_flows.collect())]
+    } else {
+        local_zones
+            .into_iter()
+            .map(|local_zone| {
+                // we change the flows regarding the `local_zone` so that:
+                // - we can only board on stops that belong to `local_zone`
+                // - we can only debark on stops that don't belong to `local_zone`
+                let local_flows = stop_times
+                    .clone()
+                    .map(|stop_time| {
+                        if stop_time.local_zone_id == local_zone {
+                            match stop_time.flow_direction {
+                                BoardOnly | BoardAndDebark => BoardOnly,
+                                DebarkOnly | NoBoardDebark => NoBoardDebark,
+                            }
+                        } else {
+                            match stop_time.flow_direction {
+                                BoardOnly | NoBoardDebark => NoBoardDebark,
+                                DebarkOnly | BoardAndDebark => DebarkOnly,
+                            }
+                        }
+                    })
+                    .collect();
+                (local_zone, local_flows)
+            })
+            .collect()
+    };
+
+    Ok(BaseVehicleJourney {
+        stops: stop_times.clone().map(|s| s.stop).collect(),
+        board_times: stop_times.clone().map(|s| s.board_time).collect(),
+        debark_times: stop_times.map(|s| s.debark_time).collect(),
+        dates: dates.collect(),
+        timezone,
+        flows_by_local_zone,
+    })
+}
+
 impl TransitData {
     pub fn new(base_model: &BaseModel) -> Self {
-        let nb_of_stop_points = base_model.nb_of_stop_points();
+        Self::new_with_nb_of_threads(base_model, default_nb_of_threads())
+    }
+
+    /// Builds the data using `nb_of_threads` threads.
+    /// The data obtained is the same whatever the value of `nb_of_threads`.
+    pub fn new_with_nb_of_threads(base_model: &BaseModel, nb_of_threads: usize) -> Self {
         let nb_transfers = base_model.nb_of_transfers();
 
         let (start_date, end_date) = base_model.validity_period();
@@ -176,62 +344,146 @@ impl TransitData {
         let nb_of_days = calendar.nb_of_days();
 
         let mut data = Self {
-            stop_point_idx_to_stop: std::collections::HashMap::new(),
-            stops_data: Vec::with_capacity(nb_of_stop_points),
+            stop_point_idx_to_stop: SharedMap::new(),
+            stops_data: SharedVec::new(),
             timetables: Timetables::new(),
-            transfers_data: Vec::with_capacity(nb_transfers),
+            transfers_data: Arc::new(Vec::with_capacity(nb_transfers)),
             vehicle_journey_to_timetable: VehicleJourneyToTimetable::new(),
             calendar,
             days_patterns: DaysPatterns::new(usize::from(nb_of_days)),
-            vehicle_journey_to_next_stay_in: std::collections::HashMap::new(),
-            vehicle_journey_to_prev_stay_in: std::collections::HashMap::new(),
+            vehicle_journey_to_next_stay_in: Arc::default(),
+            vehicle_journey_to_prev_stay_in: Arc::default(),
+            nb_of_base_insertion_errors: 0,
         };
 
-        data.init(base_model);
+        data.init(base_model, nb_of_threads);
 
         data
     }
 
-    fn init(&mut self, base_model: &BaseModel) {
+    fn init(&mut self, base_model: &BaseModel, nb_of_threads: usize) {
         let loads_data = base_model.loads_data();
-        info!("Inserting vehicle journeys");
+        info!("Reading vehicle journeys");
 
         let vehicle_stay_in = VJGroupedByStayIn::new(base_model);
 
-        for vehicle_journey_idx in base_model.vehicle_journeys() {
-            let _ = self.insert_base_vehicle_journey(
+        let vehicle_journey_idxs: Vec<BaseVehicleJourneyIdx> =
+            base_model.vehicle_journeys().collect();
+        let base_vehicle_journeys = map_on_threads(
+            &vehicle_journey_idxs,
+            nb_of_threads,
+            |vehicle_journey_idx| {
+                read_base_vehicle_journey(
+                    *vehicle_journey_idx,
+                    &vehicle_stay_in.vehicle_journey_to_prev_stay_in,
+                    &vehicle_stay_in.vehicle_journey_to_next_stay_in,
+                    base_model,
+                )
+            },
+        );
+
+        info!("Inserting vehicle journeys");
+        let real_time_model = RealTimeModel::new();
+        let model = ModelRefs {
+            base: base_model,
+            real_time: &real_time_model,
+        };
+        // The vehicles of all vehicle journeys are inserted in the timetables at once,
+        // so that timetables can be built on several threads.
+        let mut vehicles_to_insert = Vec::new();
+        // the vehicle journey and local zone of each element of `vehicles_to_insert`
+        let mut vehicle_journeys_to_insert = Vec::new();
+        for (vehicle_journey_idx, base_vehicle_journey) in
+            vehicle_journey_idxs.into_iter().zip(base_vehicle_journeys)
+        {
+            let base_vehicle_journey = match base_vehicle_journey {
+                Ok(base_vehicle_journey) => base_vehicle_journey,
+                Err(()) => {
+                    self.nb_of_base_insertion_errors += 1;
+                    continue;
+                }
+            };
+            let vehicle_journey_idx = VehicleJourneyIdx::Base(vehicle_journey_idx);
+            let mut has_insertion_error = false;
+            for (local_zone, flows) in base_vehicle_journey.flows_by_local_zone.iter() {
+                let vehicle = VehicleInput {
+                    stops: base_vehicle_journey.stops.iter().cloned(),
+                    flows: flows.iter().copied(),
+                    board_times: base_vehicle_journey.board_times.iter().copied(),
+                    debark_times: base_vehicle_journey.debark_times.iter().copied(),
+                    days: base_vehicle_journey.dates.iter().copied(),
+                    timezone: base_vehicle_journey.timezone,
+                    vehicle_journey_idx: &vehicle_journey_idx,
+                    local_zone: *local_zone,
+                    real_time_level: RealTimeLevel::Base,
+                };
+                let prepare_result = self.prepare_insert_inner(vehicle, loads_data);
+                match prepare_result {
+                    Ok(vehicles) => {
+                        vehicles_to_insert.push(vehicles);
+                        vehicle_journeys_to_insert.push((vehicle_journey_idx.clone(), *local_zone));
+                    }
+                    Err(err) => {
+                        has_insertion_error = true;
+                        handle_insertion_error(
+                            &model,
+                            self.calendar().first_date(),
+                            self.calendar().last_date(),
+                            &err,
+                        );
+                    }
+                }
+            }
+            if has_insertion_error {
+                self.nb_of_base_insertion_errors += 1;
+            }
+        }
+
+        let missions = self.timetables.insert_prepared_batch(
+            vehicles_to_insert,
+            &mut self.days_patterns,
+            nb_of_threads,
+        );
+        for ((vehicle_journey_idx, local_zone), missions) in
+            vehicle_journeys_to_insert.iter().zip(missions)
+        {
+            self.add_vehicle_missions(
                 vehicle_journey_idx,
-                &vehicle_stay_in.vehicle_journey_to_prev_stay_in,
-                &vehicle_stay_in.vehicle_journey_to_next_stay_in,
-                base_model,
-                loads_data,
+                *local_zone,
+                RealTimeLevel::Base,
+                &missions,
             );
         }
-        self.vehicle_journey_to_prev_stay_in = vehicle_stay_in
-            .vehicle_journey_to_prev_stay_in
-            .into_iter()
-            .map(|(vehicle_idx, prev_vehicle_idx)| {
-                let vehicle_idx = VehicleJourneyIdx::Base(vehicle_idx);
-                let prev_vehicle_idx = match prev_vehicle_idx {
-                    StayInType::SameStopPoint(idx) => VehicleJourneyIdx::Base(idx),
-                    StayInType::DifferentStopPoint(idx) => VehicleJourneyIdx::Base(idx),
-                };
-                (vehicle_idx, prev_vehicle_idx)
-            })
-            .collect();
 
-        self.vehicle_journey_to_next_stay_in = vehicle_stay_in
-            .vehicle_journey_to_next_stay_in
-            .into_iter()
-            .map(|(vehicle_idx, next_vehicle_idx)| {
-                let vehicle_idx = VehicleJourneyIdx::Base(vehicle_idx);
-                let next_vehicle_idx = match next_vehicle_idx {
-                    StayInType::SameStopPoint(idx) => VehicleJourneyIdx::Base(idx),
-                    StayInType::DifferentStopPoint(idx) => VehicleJourneyIdx::Base(idx),
-                };
-                (vehicle_idx, next_vehicle_idx)
-            })
-            .collect();
+        self.vehicle_journey_to_prev_stay_in = Arc::new(
+            vehicle_stay_in
+                .vehicle_journey_to_prev_stay_in
+                .into_iter()
+                .map(|(vehicle_idx, prev_vehicle_idx)| {
+                    let vehicle_idx = VehicleJourneyIdx::Base(vehicle_idx);
+                    let prev_vehicle_idx = match prev_vehicle_idx {
+                        StayInType::SameStopPoint(idx) => VehicleJourneyIdx::Base(idx),
+                        StayInType::DifferentStopPoint(idx) => VehicleJourneyIdx::Base(idx),
+                    };
+                    (vehicle_idx, prev_vehicle_idx)
+                })
+                .collect(),
+        );
+
+        self.vehicle_journey_to_next_stay_in = Arc::new(
+            vehicle_stay_in
+                .vehicle_journey_to_next_stay_in
+                .into_iter()
+                .map(|(vehicle_idx, next_vehicle_idx)| {
+                    let vehicle_idx = VehicleJourneyIdx::Base(vehicle_idx);
+                    let next_vehicle_idx = match next_vehicle_idx {
+                        StayInType::SameStopPoint(idx) => VehicleJourneyIdx::Base(idx),
+                        StayInType::DifferentStopPoint(idx) => VehicleJourneyIdx::Base(idx),
+                    };
+                    (vehicle_idx, next_vehicle_idx)
+                })
+                .collect(),
+        );
 
         info!("Inserting transfers");
         for transfer_idx in base_model.transfers() {
@@ -263,10 +515,16 @@ impl TransitData {
 
         let duration = base_model.transfer_duration(transfer_idx);
         let walking_duration = base_model.transfer_walking_duration(transfer_idx);
+        let wheelchair_duration = base_model.transfer_wheelchair_duration(transfer_idx);
+        let durations = TransferDurations {
+            total_duration: duration,
+            walking_duration,
+            wheelchair_duration,
+        };
 
         let transfer_idx = TransferIdx::Base(transfer_idx);
 
-        self.insert_transfer_inner(from_stop, to_stop, transfer_idx, duration, walking_duration);
+        self.insert_transfer_inner(from_stop, to_stop, transfer_idx, durations);
 
         Ok(())
     }
@@ -276,226 +534,28 @@ impl TransitData {
         from_stop: Stop,
         to_stop: Stop,
         transfer_idx: TransferIdx,
-        duration: PositiveDuration,
-        walking_duration: PositiveDuration,
+        durations: TransferDurations,
     ) {
         let transfer = Transfer {
             idx: self.transfers_data.len(),
         };
-        let durations = TransferDurations {
-            total_duration: duration,
-            walking_duration,
-        };
         let transfer_data = TransferData {
             from_stop,
             to_stop,
-            durations: durations.clone(),
+            durations,
             transit_model_transfer_idx: transfer_idx,
         };
-        self.transfers_data.push(transfer_data);
-        let from_stop_data = &mut self.stops_data[from_stop.idx];
+        Arc::make_mut(&mut self.transfers_data).push(transfer_data);
+        let from_stop_data = Arc::make_mut(&mut self.stops_data[from_stop.idx]);
         from_stop_data
             .outgoing_transfers
-            .push((to_stop, durations.clone(), transfer));
-        let to_stop_data = &mut self.stops_data[to_stop.idx];
+            .push((to_stop, durations, transfer));
+        let to_stop_data = Arc::make_mut(&mut self.stops_data[to_stop.idx]);
         to_stop_data
             .incoming_transfers
             .push((from_stop, durations, transfer));
     }
 
-    fn insert_base_vehicle_journey(
-        &mut self,
-        vehicle_journey_idx: Idx<VehicleJourney>,
-        vehicle_journey_to_prev_stay_in: &HashMap<BaseVehicleJourneyIdx, StayInType>,
-        vehicle_journey_to_next_stay_in: &HashMap<BaseVehicleJourneyIdx, StayInType>,
-        base_model: &BaseModel,
-        loads_data: &LoadsData,
-    ) -> Result<(), ()> {
-        let stop_times =
-            base_model
-                .stop_times(vehicle_journey_idx)
-                .map_err(|(err, stop_time_idx)| {
-                    warn!(
-                    "Skipping vehicle journey {} because its {}-th stop time is ill formed {:?}.",
-                    base_model.vehicle_journey_name(vehicle_journey_idx),
-                    stop_time_idx.idx,
-                    err
-                );
-                })?;
-
-        if stop_times.len() < 2 {
-            warn!(
-                "Skipping vehicle journey {} because it has less than 2 stop times.",
-                base_model.vehicle_journey_name(vehicle_journey_idx),
-            );
-            return Err(());
-        }
-
-        let dates = base_model
-            .vehicle_journey_dates(vehicle_journey_idx)
-            .ok_or_else(|| {
-                warn!(
-                    "Skipping vehicle journey {} because it has no dates.",
-                    base_model.vehicle_journey_name(vehicle_journey_idx)
-                );
-            })?;
-
-        let timezone = base_model.timezone(vehicle_journey_idx).ok_or_else(|| {
-            warn!(
-                "Skipping vehicle journey {} because it has no timezone.",
-                base_model.vehicle_journey_name(vehicle_journey_idx)
-            );
-        })?;
-
-        let stops = stop_times.clone().map(|s| s.stop);
-        let flows = stop_times.clone().map(|s| s.flow_direction);
-        let board_times = stop_times.clone().map(|s| s.board_time);
-        let debark_times = stop_times.clone().map(|s| s.debark_time);
-
-        /*
-         * Flow correction with stay-in
-         *
-         * The stay-in section is allowed with 2 configurations:
-         *  - when two VJ share the same stop point with similar stop times (example 1)
-         *  - when two VJ are joined on 2 different stop points with consecutive stop times (example 2)
-         *
-         *   Example 1:
-         *   ----------
-         *         out          in   out         in
-         *          X    SP1    |    ▲    SP2    X
-         *          X           ▼    |           X
-         *    VJ:1   08:00-09:00      10:00-11:00
-         *    VJ:2                    10:00-11:00      14:00-15:00
-         *                           X           ▲    |           X
-         *                           X           |    ▼   SP3     X
-         *                           out         in   out         in
-         *                           |- Stay-In -|
-         *
-         *   Example 2:
-         *   ----------
-         *                                       (1)  (2)
-         *         out          in   out         in   out         in   out         in
-         *          X    SP1    |    ▲    SP2    |    ▲    SP3    |    ▲   SP4     X
-         *          X           ▼    |           ▼    |           |    |           X
-         *    VJ:1   08:00-09:00      10:00-11:00     |           ▼    |           X
-         *    VJ:2                                     12:00-13:00      14:00-15:00
-         *                           |---------- Stay In ---------|
-         *
-         *  Example 2 is the only case were we allow specific pick-up and drop-off
-         */
-        let has_prev_stay_in_on_same_stop =
-            match vehicle_journey_to_prev_stay_in.get(&vehicle_journey_idx) {
-                Some(StayInType::SameStopPoint(_)) => true,
-                Some(StayInType::DifferentStopPoint(_)) | None => false,
-            };
-        let has_next_stay_in_on_same_stop =
-            match vehicle_journey_to_next_stay_in.get(&vehicle_journey_idx) {
-                Some(StayInType::SameStopPoint(_)) => true,
-                Some(StayInType::DifferentStopPoint(_)) | None => false,
-            };
-
-        let nb_of_positions = flows.len();
-        let corrected_flows = flows.enumerate().map(|(position_idx, flow)| {
-            if position_idx == 0 && has_prev_stay_in_on_same_stop {
-                match flow {
-                    BoardAndDebark | BoardOnly => BoardOnly,
-                    DebarkOnly | NoBoardDebark => NoBoardDebark,
-                }
-            } else if position_idx == nb_of_positions - 1 && has_next_stay_in_on_same_stop {
-                match flow {
-                    BoardAndDebark | DebarkOnly => DebarkOnly,
-                    BoardOnly | NoBoardDebark => NoBoardDebark,
-                }
-            } else {
-                flow
-            }
-        });
-
-        let vehicle_journey_idx = VehicleJourneyIdx::Base(vehicle_journey_idx);
-
-        let mut local_zones: Vec<_> = stop_times.clone().map(|s| s.local_zone_id).collect();
-        local_zones.sort_unstable();
-        local_zones.dedup();
-        let nb_of_local_zones = local_zones.len();
-
-        if nb_of_local_zones == 1 {
-            let insert_result = self.insert_inner(
-                stops,
-                corrected_flows,
-                board_times,
-                debark_times,
-                loads_data,
-                dates,
-                timezone,
-                vehicle_journey_idx,
-                local_zones[0],
-                RealTimeLevel::Base,
-            );
-            let real_time_model = RealTimeModel::new();
-            let model = ModelRefs {
-                base: base_model,
-                real_time: &real_time_model,
-            };
-
-            if let Err(err) = insert_result {
-                handle_insertion_error(
-                    &model,
-                    self.calendar().first_date(),
-                    self.calendar().last_date(),
-                    &err,
-                );
-            }
-        } else {
-            for local_zone in local_zones {
-                // we change the flows regarding the `local_zone` so that:
-                // - we can only board on stops that belong to `local_zone`
-                // - we can only debark on stops that don't belong to `local_zone`
-                let local_flows = stop_times.clone().map(|stop_time| {
-                    if stop_time.local_zone_id == local_zone {
-                        match stop_time.flow_direction {
-                            BoardOnly | BoardAndDebark => BoardOnly,
-                            DebarkOnly | NoBoardDebark => NoBoardDebark,
-                        }
-                    } else {
-                        match stop_time.flow_direction {
-                            BoardOnly | NoBoardDebark => NoBoardDebark,
-                            DebarkOnly | BoardAndDebark => DebarkOnly,
-                        }
-                    }
-                });
-                let insert_result = self.insert_inner(
-                    stops.clone(),
-                    local_flows,
-                    board_times.clone(),
-                    debark_times.clone(),
-                    loads_data,
-                    dates.clone(),
-                    timezone,
-                    vehicle_journey_idx.clone(),
-                    local_zone,
-                    RealTimeLevel::Base,
-                );
-
-                let real_time_model = RealTimeModel::new();
-                let model = ModelRefs {
-                    base: base_model,
-                    real_time: &real_time_model,
-                };
-
-                if let Err(err) = insert_result {
-                    handle_insertion_error(
-                        &model,
-                        self.calendar().first_date(),
-                        self.calendar().last_date(),
-                        &err,
-                    );
-                }
-            }
-        }
-
-        Ok(())
-    }
-
     fn add_new_stop_point(&mut self, stop_point_idx: StopPointIdx) -> Stop {
         use super::StopData;
 
@@ -510,7 +570,7 @@ impl TransitData {
         let stop = Stop {
             idx: self.stops_data.len(),
         };
-        self.stops_data.push(stop_data);
+        self.stops_data.push(Arc::new(stop_data));
         self.stop_point_idx_to_stop.insert(stop_point_idx, stop);
         stop
     }
@@ -210,18 +210,14 @@ where
     fn missions_at(&'a self, stop: &Self::Stop) -> Self::MissionsAtStop;
 
     /// Iterator for all `Transfer`s that can be taken at a `Stop`
-    type OutgoingTransfersAtStop: Iterator<
-        Item = &'a (Self::Stop, TransferDurations, Self::Transfer),
-    >;
+    type OutgoingTransfersAtStop: Iterator<Item = (Self::Stop, TransferDurations, Self::Transfer)>;
     /// Returns all `Transfer`s that can be taken at `from_stop`
     ///
     /// Should not return twice the same `Transfer`.
     fn outgoing_transfers_at(&'a self, from_stop: &Self::Stop) -> Self::OutgoingTransfersAtStop;
 
     /// Iterator for all `Transfer`s that can debark at a `Stop`
-    type IncomingTransfersAtStop: Iterator<
-        Item = &'a (Self::Stop, TransferDurations, Self::Transfer),
-    >;
+    type IncomingTransfersAtStop: Iterator<Item = (Self::Stop, TransferDurations, Self::Transfer)>;
     /// Returns all `Transfer`s that can debark at `stop`
     ///
     /// Should not return twice the same `Transfer`.
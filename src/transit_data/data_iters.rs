@@ -47,9 +47,9 @@ use crate::{
 use super::{Stop, Transfer, TransferDurations, TransitData};
 
 pub type OutgoingTransfersAtStop<'data> =
-    std::slice::Iter<'data, (Stop, TransferDurations, Transfer)>;
+    std::iter::Copied<std::slice::Iter<'data, (Stop, TransferDurations, Transfer)>>;
 pub type IncomingTransfersAtStop<'data> =
-    std::slice::Iter<'data, (Stop, TransferDurations, Transfer)>;
+    std::iter::Copied<std::slice::Iter<'data, (Stop, TransferDurations, Transfer)>>;
 
 impl TransitData {
     pub fn missions_of(&self, stop: &Stop) -> MissionsOfStop {
@@ -61,12 +61,12 @@ impl TransitData {
 
     pub fn outgoing_transfers_at(&self, stop: &Stop) -> OutgoingTransfersAtStop {
         let stop_data = self.stop_data(stop);
-        stop_data.outgoing_transfers.iter()
+        stop_data.outgoing_transfers.iter().copied()
     }
 
     pub fn incoming_transfers_at(&self, stop: &Stop) -> IncomingTransfersAtStop {
         let stop_data = self.stop_data(stop);
-        stop_data.incoming_transfers.iter()
+        stop_data.incoming_transfers.iter().copied()
     }
 
     pub fn trips_boardable_between<'a>(
@@ -34,12 +34,18 @@
 // https://groups.google.com/d/forum/navitia
 // www.navitia.io
 
+use std::{collections::HashMap, sync::Arc};
 use tracing::log::error;
 
 use crate::{
     loads_data::LoadsData,
     models::{StopPointIdx, VehicleJourneyIdx},
-    timetables::{day_to_timetable::LocalZone, InsertionError, ModifyError, RemovalError},
+    time::days_patterns::DaysPattern,
+    timetables::{
+        day_to_timetable::LocalZone,
+        utc_timetables::{VehicleInput, VehicleToInsert},
+        InsertionError, ModifyError, RemovalError,
+    },
     transit_data::TransitData,
 };
 
@@ -109,18 +115,18 @@ impl TransitData {
         BoardTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
         DebarkTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
     {
-        self.insert_inner(
-            stop_points,
+        let vehicle = VehicleInput {
+            stops: stop_points,
             flows,
             board_times,
             debark_times,
-            loads_data,
-            valid_dates,
+            days: valid_dates,
             timezone,
-            vehicle_journey_idx,
-            None,
-            RealTimeLevel::RealTime,
-        )
+            vehicle_journey_idx: &vehicle_journey_idx,
+            local_zone: None,
+            real_time_level: RealTimeLevel::RealTime,
+        };
+        self.insert_inner(vehicle, loads_data)
     }
 
     pub fn modify_real_time_vehicle<Stops, Flows, Dates, BoardTimes, DebarkTimes>(
@@ -194,19 +200,22 @@ impl TransitData {
                 .days_patterns
                 .get_from_dates(valid_dates.clone(), &self.calendar);
 
-            let timetables = self.timetables.insert(
+            let vehicle = VehicleInput {
                 stops,
-                flows.clone(),
-                board_times.clone(),
-                debark_times.clone(),
-                loads_data,
-                &days,
-                &self.calendar,
-                &mut self.days_patterns,
+                flows: flows.clone(),
+                board_times: board_times.clone(),
+                debark_times: debark_times.clone(),
+                days: &days,
                 timezone,
                 vehicle_journey_idx,
                 local_zone,
-                RealTimeLevel::RealTime,
+                real_time_level: RealTimeLevel::RealTime,
+            };
+            let timetables = self.timetables.insert(
+                vehicle,
+                loads_data,
+                &self.calendar,
+                &mut self.days_patterns,
             );
             let timetables = match timetables {
                 Err(err) => {
@@ -246,16 +255,8 @@ impl TransitData {
 impl TransitData {
     pub(super) fn insert_inner<Stops, Flows, Dates, BoardTimes, DebarkTimes>(
         &mut self,
-        stop_points: Stops,
-        flows: Flows,
-        board_times: BoardTimes,
-        debark_times: DebarkTimes,
+        vehicle: VehicleInput<'_, Stops, Flows, BoardTimes, DebarkTimes, Dates>,
         loads_data: &LoadsData,
-        valid_dates: Dates,
-        timezone: chrono_tz::Tz,
-        vehicle_journey_idx: VehicleJourneyIdx,
-        local_zone: LocalZone,
-        real_time_level: RealTimeLevel,
     ) -> Result<(), InsertionError>
     where
         Stops: Iterator<Item = StopPointIdx> + ExactSizeIterator + Clone,
@@ -264,12 +265,48 @@ impl TransitData {
         BoardTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
         DebarkTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
     {
+        let vehicle_journey_idx = vehicle.vehicle_journey_idx;
+        let local_zone = vehicle.local_zone;
+        let real_time_level = vehicle.real_time_level;
+        let vehicles = self.prepare_insert_inner(vehicle, loads_data)?;
+        let missions = self
+            .timetables
+            .insert_prepared(vehicles, &mut self.days_patterns);
+        self.add_vehicle_missions(vehicle_journey_idx, local_zone, real_time_level, &missions);
+        Ok(())
+    }
+
+    // Checks that the vehicle can be inserted, and returns the vehicles
+    // to insert in the timetables.
+    pub(super) fn prepare_insert_inner<Stops, Flows, Dates, BoardTimes, DebarkTimes>(
+        &mut self,
+        vehicle: VehicleInput<'_, Stops, Flows, BoardTimes, DebarkTimes, Dates>,
+        loads_data: &LoadsData,
+    ) -> Result<Vec<VehicleToInsert>, InsertionError>
+    where
+        Stops: Iterator<Item = StopPointIdx> + ExactSizeIterator + Clone,
+        Flows: Iterator<Item = FlowDirection> + ExactSizeIterator + Clone,
+        Dates: Iterator<Item = chrono::NaiveDate> + Clone,
+        BoardTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
+        DebarkTimes: Iterator<Item = SecondsSinceTimezonedDayStart> + ExactSizeIterator + Clone,
+    {
+        let VehicleInput {
+            stops: stop_points,
+            flows,
+            board_times,
+            debark_times,
+            days: valid_dates,
+            timezone,
+            vehicle_journey_idx,
+            local_zone,
+            real_time_level,
+        } = vehicle;
         // if we add on the base level, let's check that
         // the vehicle does not exists in base vehicles
         if real_time_level == RealTimeLevel::Base
             && self
                 .vehicle_journey_to_timetable
-                .base_vehicle_exists(&vehicle_journey_idx, local_zone)
+                .base_vehicle_exists(vehicle_journey_idx, local_zone)
         {
             return Err(InsertionError::BaseVehicleJourneyAlreadyExists(
                 vehicle_journey_idx.clone(),
@@ -284,7 +321,7 @@ impl TransitData {
                 .ok_or_else(|| InsertionError::InvalidDate(date, vehicle_journey_idx.clone()))?;
 
             if self.vehicle_journey_to_timetable.real_time_vehicle_exists(
-                &vehicle_journey_idx,
+                vehicle_journey_idx,
                 local_zone,
                 day,
                 &self.days_patterns,
@@ -297,7 +334,7 @@ impl TransitData {
         }
 
         if valid_dates.clone().next().is_none() {
-            return Err(InsertionError::NoValidDates(vehicle_journey_idx));
+            return Err(InsertionError::NoValidDates(vehicle_journey_idx.clone()));
         }
 
         let stops = self.create_stops(stop_points).into_iter();
@@ -305,32 +342,38 @@ impl TransitData {
             .days_patterns
             .get_from_dates(valid_dates, &self.calendar);
 
-        let timetables = self
-            .timetables
-            .insert(
-                stops,
-                flows,
-                board_times,
-                debark_times,
-                loads_data,
-                &days,
-                &self.calendar,
-                &mut self.days_patterns,
-                timezone,
-                &vehicle_journey_idx,
-                local_zone,
-                real_time_level,
-            )
+        let vehicle = VehicleInput {
+            stops,
+            flows,
+            board_times,
+            debark_times,
+            days: &days,
+            timezone,
+            vehicle_journey_idx,
+            local_zone,
+            real_time_level,
+        };
+        self.timetables
+            .prepare_insert(vehicle, loads_data, &self.calendar, &mut self.days_patterns)
             .map_err(|(err, dates)| {
                 InsertionError::Times(vehicle_journey_idx.clone(), real_time_level, err, dates)
-            })?;
+            })
+    }
 
-        for (timetable, days_pattern) in timetables.iter() {
+    // Registers the missions in which a vehicle was inserted.
+    pub(super) fn add_vehicle_missions(
+        &mut self,
+        vehicle_journey_idx: &VehicleJourneyIdx,
+        local_zone: LocalZone,
+        real_time_level: RealTimeLevel,
+        missions: &HashMap<Mission, DaysPattern>,
+    ) {
+        for (timetable, days_pattern) in missions.iter() {
             let result = match real_time_level {
                 RealTimeLevel::Base => self
                     .vehicle_journey_to_timetable
                     .insert_base_and_realtime_vehicle(
-                        &vehicle_journey_idx,
+                        vehicle_journey_idx,
                         local_zone,
                         days_pattern,
                         timetable,
@@ -339,7 +382,7 @@ impl TransitData {
                 RealTimeLevel::RealTime => self
                     .vehicle_journey_to_timetable
                     .insert_real_time_only_vehicle(
-                        &vehicle_journey_idx,
+                        vehicle_journey_idx,
                         local_zone,
                         days_pattern,
                         timetable,
@@ -348,7 +391,7 @@ impl TransitData {
             };
 
             if let Err(err) = result {
-                // we checked at the beginning of this function that this vehicle_journey_idx has no base/real_time vehicle
+                // we checked in prepare_insert_inner() that this vehicle_journey_idx has no base/real_time vehicle
                 // in vehicle_journey_to_timetable.
                 // So we should not obtain any error while inserting.
                 // If this happens, let's just log an error and keep going.
@@ -356,19 +399,15 @@ impl TransitData {
             }
         }
 
-        let missions = timetables.keys();
-
-        for mission in missions {
+        for mission in missions.keys() {
             self.add_mission_to_stops(mission);
         }
-
-        Ok(())
     }
 
     pub(super) fn add_mission_to_stops(&mut self, mission: &Mission) {
         for position in self.timetables.positions(mission) {
             let stop = self.timetables.stop_at(&position, mission);
-            let stop_data = &mut self.stops_data[stop.idx];
+            let stop_data = Arc::make_mut(&mut self.stops_data[stop.idx]);
             let position_in_timetables = &mut stop_data.position_in_timetables;
             if !position_in_timetables.contains(&(mission.clone(), position.clone())) {
                 position_in_timetables.push((mission.clone(), position));
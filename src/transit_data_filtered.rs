@@ -40,7 +40,7 @@ use crate::{
     models::{ModelRefs, StopPointIdx, StopTimeIdx, TransferIdx, VehicleJourneyIdx},
     time::{Calendar, PositiveDuration, SecondsSinceDatasetUTCStart},
     timetables::utc_timetables,
-    transit_data::{self, data_interface, data_iters},
+    transit_data::{self, data_interface, data_iters, Stop, Transfer, TransferDurations},
     RealTimeLevel, TransitData,
 };
 pub use transit_model::objects::{
@@ -59,6 +59,7 @@ pub struct FilterMemory {
     allowed_new_stop_points: Vec<bool>,
     allowed_base_vehicle_journeys: Vec<bool>,
     allowed_new_vehicle_journeys: Vec<bool>,
+    must_be_wheelchair_accessible: bool,
 }
 
 impl Default for FilterMemory {
@@ -74,10 +75,13 @@ impl FilterMemory {
             allowed_new_stop_points: Vec::new(),
             allowed_base_vehicle_journeys: Vec::new(),
             allowed_new_vehicle_journeys: Vec::new(),
+            must_be_wheelchair_accessible: false,
         }
     }
 
     pub fn fill_allowed_stops_and_vehicles(&mut self, filters: &Filters, model: &ModelRefs<'_>) {
+        self.must_be_wheelchair_accessible = filters.must_be_wheelchair_accessible();
+
         self.allowed_base_vehicle_journeys
             .resize(model.nb_of_base_vehicle_journeys(), true);
         for idx in model.base_vehicle_journeys() {
@@ -244,7 +248,16 @@ impl data_interface::Data for TransitDataFiltered<'_, '_> {
     }
 
     fn transfer_duration(&self, transfer: &Self::Transfer) -> PositiveDuration {
-        self.transit_data.transfer_duration(transfer)
+        let durations = self.transit_data.transfer_durations(transfer);
+        if self.memory.must_be_wheelchair_accessible {
+            // transfers without a wheelchair duration are never given to the engine
+            // by the transfers iterators below
+            durations
+                .wheelchair_duration
+                .unwrap_or(durations.total_duration)
+        } else {
+            durations.total_duration
+        }
     }
 
     fn transfer_idx(&self, transfer: &Self::Transfer) -> TransferIdx {
@@ -434,14 +447,20 @@ impl<'data> data_interface::DataIters<'data> for TransitDataFiltered<'_, '_> {
         self.transit_data.missions_at(stop)
     }
 
-    type OutgoingTransfersAtStop = data_iters::OutgoingTransfersAtStop<'data>;
+    type OutgoingTransfersAtStop = TransfersAtStop<data_iters::OutgoingTransfersAtStop<'data>>;
     fn outgoing_transfers_at(&'data self, from_stop: &Self::Stop) -> Self::OutgoingTransfersAtStop {
-        self.transit_data.outgoing_transfers_at(from_stop)
+        TransfersAtStop {
+            inner: self.transit_data.outgoing_transfers_at(from_stop),
+            must_be_wheelchair_accessible: self.memory.must_be_wheelchair_accessible,
+        }
     }
 
-    type IncomingTransfersAtStop = data_iters::IncomingTransfersAtStop<'data>;
+    type IncomingTransfersAtStop = TransfersAtStop<data_iters::IncomingTransfersAtStop<'data>>;
     fn incoming_transfers_at(&'data self, stop: &Self::Stop) -> Self::IncomingTransfersAtStop {
-        self.transit_data.incoming_transfers_at(stop)
+        TransfersAtStop {
+            inner: self.transit_data.incoming_transfers_at(stop),
+            must_be_wheelchair_accessible: self.memory.must_be_wheelchair_accessible,
+        }
     }
 
     type TripsOfMission = utc_timetables::TripsIter<'data>;
@@ -456,3 +475,35 @@ impl<'data> data_interface::DataIters<'data> for TransitDataFiltered<'_, '_> {
 }
 
 impl data_interface::DataWithIters for TransitDataFiltered<'_, '_> {}
+
+/// Transfers at a stop, as seen by a request.
+///
+/// When the request must be wheelchair accessible, each transfer is given with its
+/// wheelchair duration, and the transfers that have none are skipped.
+pub struct TransfersAtStop<Inner> {
+    inner: Inner,
+    must_be_wheelchair_accessible: bool,
+}
+
+impl<Inner> Iterator for TransfersAtStop<Inner>
+where
+    Inner: Iterator<Item = (Stop, TransferDurations, Transfer)>,
+{
+    type Item = (Stop, TransferDurations, Transfer);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.must_be_wheelchair_accessible {
+            return self.inner.next();
+        }
+        for (stop, durations, transfer) in self.inner.by_ref() {
+            if let Some(wheelchair_duration) = durations.wheelchair_duration {
+                let durations = TransferDurations {
+                    total_duration: wheelchair_duration,
+                    ..durations
+                };
+                return Some((stop, durations, transfer));
+            }
+        }
+        None
+    }
+}